@@ -8,10 +8,24 @@
 //! updating `memory.x` ensures a rebuild of the application with the
 //! new memory settings.
 
+//!
+//! It also converts any BDF fonts in the `fonts` directory into const glyph
+//! tables that are included by `src/fonts.rs`.
+
 use std::env;
-use std::fs::File;
+use std::fmt::Write as _;
+use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Directory custom BDF fonts are read from.
+const FONTS_DIR: &str = "fonts";
+
+/// The widest glyph that can be embedded, as rows are stored in a `u32`.
+const MAX_GLYPH_WIDTH: i32 = 32;
+
+/// The highest character embedded from a font, covering ASCII and Latin-1.
+const MAX_ENCODING: u32 = 0xFF;
 
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
@@ -33,4 +47,164 @@ fn main() {
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
+
+    // Convert custom fonts into glyph tables.
+    println!("cargo:rerun-if-changed={FONTS_DIR}");
+    File::create(out.join("custom_fonts.rs"))
+        .unwrap()
+        .write_all(embed_fonts(Path::new(FONTS_DIR)).as_bytes())
+        .unwrap();
+}
+
+/// A glyph parsed from a BDF font.
+struct BdfGlyph {
+    /// The character the glyph is for.
+    character: char,
+
+    /// Horizontal distance to the next glyph.
+    advance: i32,
+
+    /// The pixel rows of the glyph, from the top of the font bounding box.
+    /// The leftmost pixel is in the highest bit.
+    rows: Vec<u32>,
+}
+
+/// Generate the Rust source for every `.bdf` file in `dir`.
+/// A missing directory generates an empty font list.
+fn embed_fonts(dir: &Path) -> String {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("bdf"))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+
+    let mut source =
+        String::from("/// Fonts converted from the `fonts` directory at build time.\n");
+    source.push_str("pub const FONTS: &[CustomFont] = &[\n");
+
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+
+        let name = path.file_stem().unwrap().to_string_lossy().to_lowercase();
+        let bdf = fs::read_to_string(&path).unwrap();
+        let (height, glyphs) =
+            parse_bdf(&bdf).unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+
+        writeln!(source, "    CustomFont {{").unwrap();
+        writeln!(source, "        name: {name:?},").unwrap();
+        writeln!(source, "        height: {height},").unwrap();
+        writeln!(source, "        glyphs: &[").unwrap();
+        for glyph in glyphs {
+            let rows: Vec<String> = glyph.rows.iter().map(|row| format!("{row:#x}")).collect();
+            writeln!(
+                source,
+                "            CustomGlyph {{ character: {:?}, advance: {}, rows: &[{}] }},",
+                glyph.character,
+                glyph.advance,
+                rows.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(source, "        ],").unwrap();
+        writeln!(source, "    }},").unwrap();
+    }
+
+    source.push_str("];\n");
+    source
+}
+
+/// Parse a BDF font into its height and glyphs, sorted by character.
+fn parse_bdf(bdf: &str) -> Result<(usize, Vec<BdfGlyph>), String> {
+    let mut bounding_box = None;
+    let mut glyphs = Vec::new();
+    let mut lines = bdf.lines().map(str::trim);
+
+    while let Some(line) = lines.next() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("FONTBOUNDINGBOX") => bounding_box = Some(parse_numbers::<4>(words)?),
+            Some("STARTCHAR") => {
+                let [_, font_height, _, font_y_offset] =
+                    bounding_box.ok_or("STARTCHAR before FONTBOUNDINGBOX")?;
+                let ascent = font_height + font_y_offset;
+
+                let mut encoding = None;
+                let mut advance = None;
+                let mut glyph_box = None;
+                let mut rows = vec![0u32; font_height as usize];
+
+                while let Some(line) = lines.next() {
+                    let mut words = line.split_whitespace();
+                    match words.next() {
+                        Some("ENCODING") => encoding = Some(parse_numbers::<1>(words)?[0]),
+                        Some("DWIDTH") => advance = Some(parse_numbers::<1>(words)?[0]),
+                        Some("BBX") => glyph_box = Some(parse_numbers::<4>(words)?),
+                        Some("BITMAP") => {
+                            let [width, height, x_offset, y_offset] =
+                                glyph_box.ok_or("BITMAP before BBX")?;
+                            if x_offset < 0 || x_offset + width > MAX_GLYPH_WIDTH {
+                                return Err(format!("glyph wider than {MAX_GLYPH_WIDTH} pixels"));
+                            }
+
+                            let top = ascent - (y_offset + height);
+                            for y in 0..height {
+                                let hex = lines.next().ok_or("unexpected end of BITMAP")?;
+                                let bytes = hex.len() as i32 / 2;
+                                if !(1..=4).contains(&bytes) {
+                                    return Err(format!("bad BITMAP row {hex}"));
+                                }
+                                let value = u32::from_str_radix(hex, 16)
+                                    .map_err(|err| format!("bad BITMAP row {hex}: {err}"))?;
+
+                                let row = top + y;
+                                if (0..font_height).contains(&row) {
+                                    rows[row as usize] = (value << (32 - 8 * bytes)) >> x_offset;
+                                }
+                            }
+                        }
+                        Some("ENDCHAR") => break,
+                        _ => {}
+                    }
+                }
+
+                let character = encoding
+                    .filter(|encoding| (32..=MAX_ENCODING as i32).contains(encoding))
+                    .and_then(|encoding| char::from_u32(encoding as u32));
+                if let Some(character) = character {
+                    glyphs.push(BdfGlyph {
+                        character,
+                        advance: advance.ok_or("glyph without DWIDTH")?,
+                        rows,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let [_, height, _, _] = bounding_box.ok_or("missing FONTBOUNDINGBOX")?;
+    glyphs.sort_by_key(|glyph| glyph.character);
+
+    Ok((height as usize, glyphs))
+}
+
+/// Parse the first `N` whitespace separated numbers of a BDF property.
+fn parse_numbers<'a, const N: usize>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<[i32; N], String> {
+    let mut numbers = [0; N];
+    for number in numbers.iter_mut() {
+        let word = words.next().ok_or("missing number")?;
+        *number = word
+            .parse()
+            .map_err(|err| format!("bad number {word}: {err}"))?;
+    }
+
+    Ok(numbers)
 }
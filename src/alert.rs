@@ -0,0 +1,198 @@
+//! High priority alerts, such as a doorbell.
+//!
+//! Alerts always use the interrupt channel, so they show over quiet hours and a display
+//! turned off, and flash an icon with their text at full brightness.
+
+use core::str::FromStr;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, signal::Signal};
+use embassy_time::{Duration, Instant};
+use embedded_graphics::{
+    geometry::Point,
+    pixelcolor::{Rgb888, RgbColor},
+};
+use heapless::String;
+use portable_atomic::{AtomicBool, Ordering};
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    board::{HEIGHT, WIDTH},
+    display::{messages::DisplayGraphicsMessage, Display, FrameTicker},
+    fonts::Font,
+    graphics::colors::Rgb888Str,
+    icons::{self, DrawIcon, Icon},
+    json,
+    mqtt::MqttMessage,
+};
+
+/// How long an alert is shown for when it does not repeat.
+const ALERT_SECS: u64 = 10;
+
+/// The longest a repeating alert is shown for without being acknowledged.
+const MAX_REPEAT_SECS: u64 = 600;
+
+/// How long the icon is shown, then hidden, when flashing.
+const FLASH_MILLIS: u64 = 500;
+
+/// Scroll speed of long alert text in pixels per second.
+const SCROLL_SPEED: f32 = 20.0;
+
+/// Where the text starts, leaving room for the icon.
+const TEXT_X: i32 = 9;
+
+/// An alert waiting to be shown.
+struct Alert {
+    /// The text shown next to the icon.
+    text: String<64>,
+
+    /// The icon to flash.
+    icon: &'static Icon,
+
+    /// The color of the icon and text.
+    color: Rgb888,
+
+    /// Keep showing the alert until it is acknowledged by a button press.
+    repeat: bool,
+}
+
+/// Alerts waiting to be shown.
+static ALERTS: Channel<ThreadModeRawMutex, Alert, 1> = Channel::new();
+
+/// Signal for when an alert is acknowledged by a button press.
+static ACKNOWLEDGED: Signal<ThreadModeRawMutex, bool> = Signal::new();
+
+/// Is an alert being shown.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Check if an alert is on the display.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Acknowledge the alert on the display, stopping it.
+pub fn acknowledge() {
+    ACKNOWLEDGED.signal(true);
+}
+
+/// Queue an alert, replacing any alert waiting to be shown.
+///
+/// `body` is either the text on its own or JSON such as
+/// `{"text": "Doorbell", "icon": "bell", "color": "#ff0000", "repeat": true}`.
+pub async fn request(body: &str) {
+    let text = json::extract(body, "text").unwrap_or(body.trim());
+    let mut alert_text = String::new();
+    for c in text.chars() {
+        if alert_text.push(c).is_err() {
+            break;
+        }
+    }
+
+    let icon = match json::extract(body, "icon") {
+        Some(name) => match icons::from_name(name) {
+            Some(icon) => icon,
+            None => {
+                MqttMessage::enqueue_debug("Invalid alert icon").await;
+                return;
+            }
+        },
+        None => &icons::BELL,
+    };
+
+    let color = match json::extract(body, "color").map(Rgb888Str::from_str) {
+        Some(Ok(Rgb888Str(color))) => color,
+        Some(Err(_)) => {
+            MqttMessage::enqueue_debug("Invalid alert color").await;
+            return;
+        }
+        None => Rgb888::RED,
+    };
+
+    let alert = Alert {
+        text: alert_text,
+        icon,
+        color,
+        repeat: json::extract(body, "repeat") == Some("true"),
+    };
+
+    // a newer alert is more important than one still waiting
+    let _ = ALERTS.try_receive();
+    let _ = ALERTS.try_send(alert);
+
+    // stop any alert on the display so the new one is shown
+    if is_active() {
+        acknowledge();
+    }
+}
+
+/// Show queued alerts.
+#[embassy_executor::task]
+pub async fn alert_task(display: &'static Display<'static>) {
+    loop {
+        let alert = ALERTS.receive().await;
+
+        ACKNOWLEDGED.reset();
+        ACTIVE.store(true, Ordering::Relaxed);
+        display.set_full_brightness(true).await;
+
+        let secs = if alert.repeat {
+            MAX_REPEAT_SECS
+        } else {
+            ALERT_SECS
+        };
+        let show = show_alert(&alert, Duration::from_secs(secs));
+        if let Either::Second(_) = select(show, ACKNOWLEDGED.wait()).await {
+            MqttMessage::enqueue_debug("Alert acknowledged").await;
+        }
+
+        ACTIVE.store(false, Ordering::Relaxed);
+        display.set_full_brightness(false).await;
+    }
+}
+
+/// Flash the alert icon, with the text beside it scrolling if it is too long, for `duration`.
+async fn show_alert(alert: &Alert, duration: Duration) {
+    let font = Font::default();
+    let text_width = font.text_width(&alert.text) as i32;
+    let scrolls = text_width > WIDTH as i32 - TEXT_X;
+
+    let started = Instant::now();
+    let mut offset: f32 = 0.0;
+    let mut ticker = FrameTicker::new();
+    let mut gr = UnicornGraphics::<WIDTH, HEIGHT>::new();
+
+    while started.elapsed() < duration {
+        gr.clear_all();
+
+        let x = if scrolls {
+            // scroll in from the right edge and off past the icon, then start again
+            let distance = (WIDTH as i32 - TEXT_X + text_width) as f32;
+            if offset > distance {
+                offset = 0.0;
+            }
+            WIDTH as i32 - offset as i32
+        } else {
+            TEXT_X
+        };
+        font.draw(&mut gr, &alert.text, x, (HEIGHT / 2) as i32, alert.color);
+
+        // keep the icon clear of text scrolling behind it
+        for y in 0..HEIGHT as i32 {
+            for x in 0..TEXT_X - 1 {
+                gr.set_pixel(Point::new(x, y), Rgb888::BLACK);
+            }
+        }
+
+        if started.elapsed().as_millis() / FLASH_MILLIS % 2 == 0 {
+            let top = (HEIGHT as i32 - alert.icon.height() as i32) / 2;
+            gr.draw_icon(alert.icon, Point::new(0, top), alert.color);
+        }
+
+        DisplayGraphicsMessage::from_app(gr.get_pixels(), Duration::from_millis(200))
+            .send_and_show_now()
+            .await;
+
+        let elapsed = ticker.next().await;
+        offset += SCROLL_SPEED * elapsed.as_micros() as f32 / 1_000_000.0;
+    }
+}
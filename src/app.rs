@@ -1,37 +1,77 @@
+use core::fmt::Write;
 use core::str::FromStr;
 
 use embassy_executor::Spawner;
-use embassy_futures::select::{select, select3, Either3};
+use embassy_futures::select::{select, select4, Either4};
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_sync::pubsub::Subscriber;
 use embassy_sync::signal::Signal;
 use embassy_time::Duration;
-
-use galactic_unicorn_embassy::{HEIGHT, WIDTH};
 use static_cell::make_static;
 use strum_macros::{EnumString, IntoStaticStr};
 use unicorn_graphics::UnicornGraphics;
 
-use crate::buttons::{ButtonPress, SWITCH_A_PRESS, SWITCH_B_PRESS, SWITCH_C_PRESS};
-use crate::clock_app::ClockApp;
-use crate::display::messages::{DisplayGraphicsMessage, DisplayTextMessage};
-use crate::display::STOP_CURRENT_DISPLAY;
-use crate::effects_app::EffectsApp;
+use crate::alert;
+#[cfg(feature = "badge-app")]
+use crate::badge_app::BadgeApp;
+use crate::board::{HEIGHT, WIDTH};
+use crate::buttons::{ButtonPress, SWITCH_A_PRESS, SWITCH_B_PRESS, SWITCH_C_PRESS, SWITCH_D_PRESS};
+#[cfg(feature = "clock-app")]
+use crate::clock_app::{self, ClockApp};
+use crate::display::messages::DisplayGraphicsMessage;
+use crate::display::{self, STOP_CURRENT_DISPLAY};
+#[cfg(feature = "effects-app")]
+use crate::effects_app::{self, EffectsApp};
+#[cfg(feature = "energy-app")]
+use crate::energy_app::EnergyApp;
+#[cfg(feature = "gallery-app")]
+use crate::gallery_app::GalleryApp;
+#[cfg(feature = "effects-app")]
+use crate::json;
 use crate::mqtt::topics::APP_STATE_TOPIC;
+#[cfg(feature = "badge-app")]
+use crate::mqtt::topics::BADGE_SET_TOPIC;
+#[cfg(feature = "energy-app")]
+use crate::mqtt::topics::ENERGY_SET_TOPIC;
+#[cfg(feature = "clock-app")]
+use crate::mqtt::topics::{
+    CLOCK_APP_SET_TOPIC, CLOCK_CHIME_HOURS_SET_TOPIC, CLOCK_CHIME_SET_TOPIC,
+    CLOCK_FORMAT_SET_TOPIC, CLOCK_LAYOUT_SET_TOPIC, CLOCK_TICKER_SET_TOPIC,
+};
+#[cfg(feature = "effects-app")]
+use crate::mqtt::topics::{EFFECT_SET_TOPIC, LIGHT_SET_TOPIC};
+#[cfg(feature = "gallery-app")]
+use crate::mqtt::topics::{
+    GALLERY_DELETE_TOPIC, GALLERY_LIST_TOPIC, GALLERY_SHOW_TOPIC, GALLERY_UPLOAD_TOPIC,
+};
+#[cfg(feature = "mqtt-app")]
+use crate::mqtt::topics::{PERSISTENT_TEXT_SET_TOPIC, TEXT_SET_TOPIC, TEXT_TEMPLATE_SET_TOPIC};
+#[cfg(feature = "quotes-app")]
+use crate::mqtt::topics::{QUOTES_ADD_TOPIC, QUOTES_INTERVAL_SET_TOPIC, QUOTES_REMOVE_TOPIC};
 use crate::mqtt::{
-    topics::{APP_SET_TOPIC, CLOCK_APP_SET_TOPIC, TEXT_SET_TOPIC},
+    topics::{APP_SET_TOPIC, SEQUENCE_SET_TOPIC, SEQUENCE_STOP_TOPIC, TEXT_CLEAR_TOPIC},
     MqttMessage, MqttReceiveMessage,
 };
+#[cfg(feature = "mqtt-app")]
 use crate::mqtt_app::MqttApp;
 use crate::network::NetworkState;
+#[cfg(feature = "pong-app")]
+use crate::pong_app::PongApp;
+#[cfg(feature = "quotes-app")]
+use crate::quotes_app::QuotesApp;
+use crate::sequence;
 use crate::system::{StateUpdates, SystemState, STATE_CHANGED};
 use crate::system_app::SystemApp;
+#[cfg(feature = "timer-app")]
+use crate::timer_app::{self, TimerApp};
+#[cfg(feature = "weather-app")]
+use crate::weather_app::WeatherApp;
 
 /// Signal for an app change for the display task.
 static CHANGE_APP: Signal<ThreadModeRawMutex, Apps> = Signal::new();
 
-/// All apps that can be switched to.
+/// All apps that can be switched to. Apps are only built when their cargo feature is enabled.
 #[derive(Copy, Clone, PartialEq, Eq, EnumString, IntoStaticStr)]
 #[strum(ascii_case_insensitive)]
 enum Apps {
@@ -39,13 +79,112 @@ enum Apps {
     System,
 
     /// The clock app.
+    #[cfg(feature = "clock-app")]
     Clock,
 
     /// The effects app.
+    #[cfg(feature = "effects-app")]
     Effects,
 
     /// The MQTT app.
+    #[cfg(feature = "mqtt-app")]
     Mqtt,
+
+    /// The weather app.
+    #[cfg(feature = "weather-app")]
+    Weather,
+
+    /// The timer app.
+    #[cfg(feature = "timer-app")]
+    Timer,
+
+    /// The gallery app.
+    #[cfg(feature = "gallery-app")]
+    Gallery,
+
+    /// The pong game.
+    #[cfg(feature = "pong-app")]
+    Pong,
+
+    /// The quotes app.
+    #[cfg(feature = "quotes-app")]
+    Quotes,
+
+    /// The name badge.
+    #[cfg(feature = "badge-app")]
+    Badge,
+
+    /// The energy price chart.
+    #[cfg(feature = "energy-app")]
+    Energy,
+}
+
+impl Apps {
+    /// Apps the user can switch to, in switch order.
+    const SELECTABLE: &'static [Apps] = &[
+        #[cfg(feature = "clock-app")]
+        Apps::Clock,
+        #[cfg(feature = "effects-app")]
+        Apps::Effects,
+        #[cfg(feature = "mqtt-app")]
+        Apps::Mqtt,
+        #[cfg(feature = "weather-app")]
+        Apps::Weather,
+        #[cfg(feature = "timer-app")]
+        Apps::Timer,
+        #[cfg(feature = "gallery-app")]
+        Apps::Gallery,
+        #[cfg(feature = "pong-app")]
+        Apps::Pong,
+        #[cfg(feature = "quotes-app")]
+        Apps::Quotes,
+        #[cfg(feature = "badge-app")]
+        Apps::Badge,
+        #[cfg(feature = "energy-app")]
+        Apps::Energy,
+    ];
+
+    /// The app to show once the system app is done, until another is chosen.
+    const DEFAULT: Apps = match Self::SELECTABLE.first() {
+        Some(app) => *app,
+        None => Apps::System,
+    };
+
+    /// Get the app switch A, B or C changes to, or `None` if its app is not enabled.
+    fn for_switch(switch: usize) -> Option<Apps> {
+        match switch {
+            #[cfg(feature = "clock-app")]
+            0 => Some(Apps::Clock),
+            #[cfg(feature = "effects-app")]
+            1 => Some(Apps::Effects),
+            #[cfg(feature = "mqtt-app")]
+            2 => Some(Apps::Mqtt),
+            _ => None,
+        }
+    }
+}
+
+/// Length of the longest app name, such as `Effects`.
+const MAX_APP_NAME: usize = 7;
+
+/// Size of the JSON array written by `write_app_options`, with room for every app in the build.
+pub const APP_OPTIONS_SIZE: usize = 128;
+
+// each name is quoted and followed by a comma, inside the brackets
+const _: () = assert!(Apps::SELECTABLE.len() * (MAX_APP_NAME + 3) + 2 <= APP_OPTIONS_SIZE);
+
+/// Write the names of the apps the user can switch to as a JSON array.
+pub fn write_app_options(out: &mut impl Write) -> core::fmt::Result {
+    out.write_char('[')?;
+    for (i, app) in Apps::SELECTABLE.iter().enumerate() {
+        if i > 0 {
+            out.write_char(',')?;
+        }
+
+        let name: &'static str = (*app).into();
+        write!(out, r#""{name}""#)?;
+    }
+    out.write_char(']')
 }
 
 pub trait UnicornApp {
@@ -83,14 +222,45 @@ pub struct AppController {
     system_app: &'static SystemApp,
 
     /// Clock app.
+    #[cfg(feature = "clock-app")]
     clock_app: &'static ClockApp,
 
     /// Effects app.
+    #[cfg(feature = "effects-app")]
     effects_app: &'static EffectsApp,
 
     /// MQTT app.
+    #[cfg(feature = "mqtt-app")]
     mqtt_app: &'static MqttApp,
 
+    /// Weather app.
+    #[cfg(feature = "weather-app")]
+    weather_app: &'static WeatherApp,
+
+    /// Timer app.
+    #[cfg(feature = "timer-app")]
+    timer_app: &'static TimerApp,
+
+    /// Gallery app.
+    #[cfg(feature = "gallery-app")]
+    gallery_app: &'static GalleryApp,
+
+    /// Pong app.
+    #[cfg(feature = "pong-app")]
+    pong_app: &'static PongApp,
+
+    /// Quotes app.
+    #[cfg(feature = "quotes-app")]
+    quotes_app: &'static QuotesApp,
+
+    /// Badge app.
+    #[cfg(feature = "badge-app")]
+    badge_app: &'static BadgeApp,
+
+    /// Energy app.
+    #[cfg(feature = "energy-app")]
+    energy_app: &'static EnergyApp,
+
     /// System state.
     system_state: &'static SystemState,
 
@@ -103,19 +273,43 @@ impl AppController {
     /// Must only be called once or will panic.
     pub fn new(
         system_app: &'static SystemApp,
-        clock_app: &'static ClockApp,
-        effects_app: &'static EffectsApp,
-        mqtt_app: &'static MqttApp,
+        #[cfg(feature = "clock-app")] clock_app: &'static ClockApp,
+        #[cfg(feature = "effects-app")] effects_app: &'static EffectsApp,
+        #[cfg(feature = "mqtt-app")] mqtt_app: &'static MqttApp,
+        #[cfg(feature = "weather-app")] weather_app: &'static WeatherApp,
+        #[cfg(feature = "timer-app")] timer_app: &'static TimerApp,
+        #[cfg(feature = "gallery-app")] gallery_app: &'static GalleryApp,
+        #[cfg(feature = "pong-app")] pong_app: &'static PongApp,
+        #[cfg(feature = "quotes-app")] quotes_app: &'static QuotesApp,
+        #[cfg(feature = "badge-app")] badge_app: &'static BadgeApp,
+        #[cfg(feature = "energy-app")] energy_app: &'static EnergyApp,
         system_state: &'static SystemState,
         spawner: Spawner,
     ) -> &'static Self {
         let controller = make_static!(Self {
             active_app: Mutex::new(Apps::System),
-            previous_app: Mutex::new(Apps::Clock),
+            previous_app: Mutex::new(Apps::DEFAULT),
             system_app,
+            #[cfg(feature = "clock-app")]
             clock_app,
+            #[cfg(feature = "effects-app")]
             effects_app,
+            #[cfg(feature = "mqtt-app")]
             mqtt_app,
+            #[cfg(feature = "weather-app")]
+            weather_app,
+            #[cfg(feature = "timer-app")]
+            timer_app,
+            #[cfg(feature = "gallery-app")]
+            gallery_app,
+            #[cfg(feature = "pong-app")]
+            pong_app,
+            #[cfg(feature = "quotes-app")]
+            quotes_app,
+            #[cfg(feature = "badge-app")]
+            badge_app,
+            #[cfg(feature = "energy-app")]
+            energy_app,
             system_state,
             spawner,
         });
@@ -129,33 +323,79 @@ impl AppController {
     fn init(&'static self) {
         self.spawner.spawn(display_task(self)).unwrap();
         self.spawner.spawn(process_state_change_task(self)).unwrap();
+        self.spawner.spawn(sequence::sequence_task(self)).unwrap();
     }
 
     /// The main program loop.
     pub async fn run_forever(&'static self) -> ! {
         loop {
-            let (app, press): (Apps, ButtonPress) = match select3(
+            let (switch, press): (usize, ButtonPress) = match select4(
                 SWITCH_A_PRESS.wait(),
                 SWITCH_B_PRESS.wait(),
                 SWITCH_C_PRESS.wait(),
+                SWITCH_D_PRESS.wait(),
             )
             .await
             {
-                Either3::First(press) => (Apps::Clock, press),
-                Either3::Second(press) => (Apps::Effects, press),
-                Either3::Third(press) => (Apps::Mqtt, press),
+                Either4::First(press) => (0, press),
+                Either4::Second(press) => (1, press),
+                Either4::Third(press) => (2, press),
+                Either4::Fourth(press) => (3, press),
             };
+            let app = Apps::for_switch(switch);
+
+            // any press acknowledges an alert instead of being handled by an app
+            if alert::is_active() {
+                alert::acknowledge();
+                continue;
+            }
 
-            if app == *self.active_app.lock().await {
-                let current_app = *self.active_app.lock().await;
+            // any press dismisses a sticky message instead of being handled by an app
+            if display::is_sticky_shown() {
+                display::clear_sticky();
+                continue;
+            }
+
+            let current_app = *self.active_app.lock().await;
+
+            // switch D does not change app, it is a second button for the active app
+            if switch == 3 {
+                self.switch_d_press(current_app).await;
+                self.send_mqtt_states().await;
+                continue;
+            }
+
+            // whilst showing a network error, any press is for the system app to retry
+            let is_network_error = matches!(
+                self.system_state.get_network_state().await,
+                NetworkState::Error(_)
+            );
 
+            if app == Some(current_app) || (current_app == Apps::System && is_network_error) {
                 match current_app {
                     Apps::System => self.system_app.button_press(press).await,
+                    #[cfg(feature = "clock-app")]
                     Apps::Clock => self.clock_app.button_press(press).await,
+                    #[cfg(feature = "effects-app")]
                     Apps::Effects => self.effects_app.button_press(press).await,
+                    #[cfg(feature = "mqtt-app")]
                     Apps::Mqtt => self.mqtt_app.button_press(press).await,
+                    #[cfg(feature = "weather-app")]
+                    Apps::Weather => self.weather_app.button_press(press).await,
+                    #[cfg(feature = "timer-app")]
+                    Apps::Timer => self.timer_app.button_press(press).await,
+                    #[cfg(feature = "gallery-app")]
+                    Apps::Gallery => self.gallery_app.button_press(press).await,
+                    #[cfg(feature = "pong-app")]
+                    Apps::Pong => self.pong_app.button_press(press).await,
+                    #[cfg(feature = "quotes-app")]
+                    Apps::Quotes => self.quotes_app.button_press(press).await,
+                    #[cfg(feature = "badge-app")]
+                    Apps::Badge => self.badge_app.button_press(press).await,
+                    #[cfg(feature = "energy-app")]
+                    Apps::Energy => self.energy_app.button_press(press).await,
                 }
-            } else {
+            } else if let Some(app) = app {
                 self.change_app(app).await;
             }
 
@@ -163,15 +403,124 @@ impl AppController {
         }
     }
 
+    /// Pass a press of switch D to `app`, for the apps that use it.
+    async fn switch_d_press(&self, app: Apps) {
+        match app {
+            #[cfg(feature = "mqtt-app")]
+            Apps::Mqtt => self.mqtt_app.toggle_source().await,
+            _ => {}
+        }
+    }
+
     /// Send MQTT states from each app.
     pub async fn send_mqtt_states(&self) {
         let active_app = *self.active_app.lock().await;
         let app_text = active_app.into();
         MqttMessage::enqueue_state(APP_STATE_TOPIC, app_text).await;
 
+        #[cfg(feature = "clock-app")]
         self.clock_app.send_mqtt_state().await;
+        #[cfg(feature = "effects-app")]
         self.effects_app.send_mqtt_state().await;
+        #[cfg(feature = "mqtt-app")]
         self.mqtt_app.send_mqtt_state().await;
+        #[cfg(feature = "weather-app")]
+        self.weather_app.send_mqtt_state().await;
+        #[cfg(feature = "timer-app")]
+        self.timer_app.send_mqtt_state().await;
+        #[cfg(feature = "gallery-app")]
+        self.gallery_app.send_mqtt_state().await;
+        #[cfg(feature = "pong-app")]
+        self.pong_app.send_mqtt_state().await;
+        #[cfg(feature = "quotes-app")]
+        self.quotes_app.send_mqtt_state().await;
+        #[cfg(feature = "badge-app")]
+        self.badge_app.send_mqtt_state().await;
+        #[cfg(feature = "energy-app")]
+        self.energy_app.send_mqtt_state().await;
+    }
+
+    /// Get the name of the active app.
+    pub async fn active_app_name(&self) -> &'static str {
+        (*self.active_app.lock().await).into()
+    }
+
+    /// Change to the app the user can switch to called `name`.
+    /// Returns `false` if there is no such app.
+    pub async fn select_app(&self, name: &str) -> bool {
+        match Apps::from_str(name) {
+            Ok(app) if Apps::SELECTABLE.contains(&app) => {
+                self.change_app(app).await;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Change to the effects app showing the effect called `name`.
+    /// Returns `false` if there is no such effect.
+    pub async fn select_effect(&self, name: &str) -> bool {
+        #[cfg(feature = "effects-app")]
+        {
+            if !self.effects_app.set_effect(name).await {
+                return false;
+            }
+
+            self.change_app(Apps::Effects).await;
+            true
+        }
+
+        #[cfg(not(feature = "effects-app"))]
+        {
+            let _ = name;
+            false
+        }
+    }
+
+    /// Show the gallery image called `name` ahead of the active app for `duration`.
+    /// Returns `false` if there is no such image.
+    pub async fn show_gallery_image(&self, name: &str, duration: Duration) -> bool {
+        #[cfg(feature = "gallery-app")]
+        {
+            self.gallery_app.show_image(name, duration).await
+        }
+
+        #[cfg(not(feature = "gallery-app"))]
+        {
+            let _ = (name, duration);
+            false
+        }
+    }
+
+    /// Show the name badge if it was shown when the device was turned off, without waiting for
+    /// the network.
+    pub async fn restore_badge(&self) {
+        #[cfg(feature = "badge-app")]
+        if self.badge_app.is_enabled() {
+            self.change_app(Apps::Badge).await;
+        }
+    }
+
+    /// Scroll the latest text message beside the clock in its ticker layout.
+    pub async fn update_clock_ticker(&self) {
+        #[cfg(all(feature = "clock-app", feature = "mqtt-app"))]
+        if let Some(message) = self.mqtt_app.messages.lock().await.last() {
+            self.clock_app
+                .set_ticker(&message.display_text(false), message.color())
+                .await;
+        }
+    }
+
+    /// Show the name badge, or go back to the app shown before it if it is already shown.
+    #[cfg(feature = "badge-app")]
+    pub async fn toggle_badge(&self) {
+        if *self.active_app.lock().await == Apps::Badge {
+            let previous_app = *self.previous_app.lock().await;
+            self.change_app(previous_app).await;
+        } else {
+            self.change_app(Apps::Badge).await;
+        }
+        self.send_mqtt_states().await;
     }
 
     /// Change the current app by stopping the current and starting the new chosen app.
@@ -185,25 +534,81 @@ impl AppController {
         match current_app {
             Apps::System => {
                 self.system_app.stop().await;
-                current_app = Apps::Clock
+                current_app = Apps::DEFAULT
             }
+            #[cfg(feature = "clock-app")]
             Apps::Clock => self.clock_app.stop().await,
+            #[cfg(feature = "effects-app")]
             Apps::Effects => self.effects_app.stop().await,
+            #[cfg(feature = "mqtt-app")]
             Apps::Mqtt => self.mqtt_app.stop().await,
+            #[cfg(feature = "weather-app")]
+            Apps::Weather => self.weather_app.stop().await,
+            #[cfg(feature = "timer-app")]
+            Apps::Timer => self.timer_app.stop().await,
+            #[cfg(feature = "gallery-app")]
+            Apps::Gallery => self.gallery_app.stop().await,
+            #[cfg(feature = "pong-app")]
+            Apps::Pong => self.pong_app.stop().await,
+            #[cfg(feature = "quotes-app")]
+            Apps::Quotes => self.quotes_app.stop().await,
+            #[cfg(feature = "badge-app")]
+            Apps::Badge => self.badge_app.stop().await,
+            #[cfg(feature = "energy-app")]
+            Apps::Energy => self.energy_app.stop().await,
         };
 
         *self.previous_app.lock().await = current_app;
         *self.active_app.lock().await = new_app;
         match new_app {
             Apps::System => self.system_app.start().await,
+            #[cfg(feature = "clock-app")]
             Apps::Clock => self.clock_app.start().await,
+            #[cfg(feature = "effects-app")]
             Apps::Effects => self.effects_app.start().await,
+            #[cfg(feature = "mqtt-app")]
             Apps::Mqtt => self.mqtt_app.start().await,
+            #[cfg(feature = "weather-app")]
+            Apps::Weather => self.weather_app.start().await,
+            #[cfg(feature = "timer-app")]
+            Apps::Timer => self.timer_app.start().await,
+            #[cfg(feature = "gallery-app")]
+            Apps::Gallery => self.gallery_app.start().await,
+            #[cfg(feature = "pong-app")]
+            Apps::Pong => self.pong_app.start().await,
+            #[cfg(feature = "quotes-app")]
+            Apps::Quotes => self.quotes_app.start().await,
+            #[cfg(feature = "badge-app")]
+            Apps::Badge => self.badge_app.start().await,
+            #[cfg(feature = "energy-app")]
+            Apps::Energy => self.energy_app.start().await,
         };
         CHANGE_APP.signal(new_app);
     }
 }
 
+/// Check if `topic` is the text topic, or a text topic with a source.
+#[cfg(feature = "mqtt-app")]
+fn is_text_topic(topic: &str) -> bool {
+    topic
+        .strip_prefix(TEXT_SET_TOPIC)
+        .is_some_and(|suffix| suffix.is_empty() || suffix.starts_with('/'))
+}
+
+/// Show the effect called `effect`, or leave the effects app for `NO_EFFECT`.
+#[cfg(feature = "effects-app")]
+async fn process_effect(app_controller: &AppController, effect: &str) {
+    if effect == effects_app::NO_EFFECT {
+        // leave the effects app for the app shown before it
+        if *app_controller.active_app.lock().await == Apps::Effects {
+            let previous_app = *app_controller.previous_app.lock().await;
+            app_controller.change_app(previous_app).await;
+        }
+    } else {
+        app_controller.select_effect(effect).await;
+    }
+}
+
 /// Process MQTT messages related to app functionality.
 #[embassy_executor::task]
 pub async fn process_mqtt_messages_task(
@@ -213,17 +618,79 @@ pub async fn process_mqtt_messages_task(
     loop {
         let message = subscriber.next_message_pure().await;
 
-        if message.topic == TEXT_SET_TOPIC {
-            DisplayTextMessage::from_mqtt(&message.body, None, None)
-                .send()
-                .await;
-            app_controller.mqtt_app.set_last_message(message.body).await;
-        } else if message.topic == CLOCK_APP_SET_TOPIC {
-            app_controller.clock_app.process_mqtt_message(message).await;
-        } else if message.topic == APP_SET_TOPIC {
-            if let Ok(new_app) = Apps::from_str(&message.body) {
-                app_controller.change_app(new_app).await;
+        let topic = message.topic.clone();
+        match topic.as_str() {
+            #[cfg(feature = "mqtt-app")]
+            topic if is_text_topic(topic) => {
+                app_controller
+                    .mqtt_app
+                    .receive_text(topic, &message.body)
+                    .await;
+                app_controller.update_clock_ticker().await;
+            }
+            TEXT_CLEAR_TOPIC => display::clear_sticky(),
+            #[cfg(feature = "mqtt-app")]
+            TEXT_TEMPLATE_SET_TOPIC | PERSISTENT_TEXT_SET_TOPIC => {
+                app_controller.mqtt_app.process_mqtt_message(message).await;
+            }
+            #[cfg(feature = "clock-app")]
+            topic
+                if matches!(
+                    topic,
+                    CLOCK_APP_SET_TOPIC
+                        | CLOCK_CHIME_SET_TOPIC
+                        | CLOCK_CHIME_HOURS_SET_TOPIC
+                        | CLOCK_FORMAT_SET_TOPIC
+                        | CLOCK_LAYOUT_SET_TOPIC
+                        | CLOCK_TICKER_SET_TOPIC
+                ) || clock_app::is_temperature_topic(topic) =>
+            {
+                app_controller.clock_app.process_mqtt_message(message).await;
+            }
+            #[cfg(feature = "effects-app")]
+            EFFECT_SET_TOPIC => process_effect(app_controller, &message.body).await,
+            #[cfg(feature = "effects-app")]
+            LIGHT_SET_TOPIC => {
+                if let Some(effect) = json::extract(&message.body, "effect") {
+                    process_effect(app_controller, effect).await;
+                }
+            }
+            #[cfg(feature = "timer-app")]
+            topic if timer_app::is_timer_topic(topic) => {
+                app_controller.timer_app.process_mqtt_message(message).await;
+            }
+            #[cfg(feature = "gallery-app")]
+            GALLERY_UPLOAD_TOPIC | GALLERY_DELETE_TOPIC | GALLERY_SHOW_TOPIC
+            | GALLERY_LIST_TOPIC => {
+                app_controller
+                    .gallery_app
+                    .process_mqtt_message(message)
+                    .await;
+            }
+            #[cfg(feature = "quotes-app")]
+            QUOTES_ADD_TOPIC | QUOTES_REMOVE_TOPIC | QUOTES_INTERVAL_SET_TOPIC => {
+                app_controller
+                    .quotes_app
+                    .process_mqtt_message(message)
+                    .await;
+            }
+            #[cfg(feature = "badge-app")]
+            BADGE_SET_TOPIC => app_controller.badge_app.process_mqtt_message(message).await,
+            #[cfg(feature = "energy-app")]
+            ENERGY_SET_TOPIC => {
+                app_controller
+                    .energy_app
+                    .process_mqtt_message(message)
+                    .await
+            }
+            SEQUENCE_SET_TOPIC => sequence::start(&message.body).await,
+            SEQUENCE_STOP_TOPIC => sequence::stop(),
+            APP_SET_TOPIC => {
+                if let Ok(new_app) = Apps::from_str(&message.body) {
+                    app_controller.change_app(new_app).await;
+                }
             }
+            _ => {}
         }
 
         app_controller.send_mqtt_states().await;
@@ -238,15 +705,33 @@ async fn process_state_change_task(app_controller: &'static AppController) {
 
         MqttMessage::enqueue_debug("State changed").await;
 
+        // the badge stays shown whatever the network does, so it can be worn away from it
+        #[cfg(feature = "badge-app")]
+        if *app_controller.active_app.lock().await == Apps::Badge {
+            continue;
+        }
+
+        let network_state = app_controller.system_state.get_network_state().await;
+        let active_app = *app_controller.active_app.lock().await;
+
         match state_update {
-            StateUpdates::Network => {
-                match app_controller.system_state.get_network_state().await {
+            // a boot stage can replace the network update in the signal, so both check the network
+            StateUpdates::Network | StateUpdates::Boot => {
+                match network_state {
                     NetworkState::NotInitialised => {}
                     NetworkState::Connected => {
+                        // NTP syncs in the background, the clock runs from the saved time until then
+                        if active_app == Apps::System {
+                            let previous_app = *app_controller.previous_app.lock().await;
+                            app_controller.change_app(previous_app).await;
+                        }
+                    }
+                    NetworkState::Offline => {
+                        // carry on without a network, the clock runs from the restored time
                         let previous_app = *app_controller.previous_app.lock().await;
                         app_controller.change_app(previous_app).await;
                     }
-                    NetworkState::Error => app_controller.change_app(Apps::System).await,
+                    NetworkState::Error(_) => app_controller.change_app(Apps::System).await,
                 };
             }
         }
@@ -264,15 +749,46 @@ async fn display_task(app_controller: &'static AppController) {
             Apps::System => {
                 select(app_controller.system_app.display(), CHANGE_APP.wait()).await;
             }
+            #[cfg(feature = "clock-app")]
             Apps::Clock => {
                 select(app_controller.clock_app.display(), CHANGE_APP.wait()).await;
             }
+            #[cfg(feature = "effects-app")]
             Apps::Effects => {
                 select(app_controller.effects_app.display(), CHANGE_APP.wait()).await;
             }
+            #[cfg(feature = "mqtt-app")]
             Apps::Mqtt => {
                 select(app_controller.mqtt_app.display(), CHANGE_APP.wait()).await;
             }
+            #[cfg(feature = "weather-app")]
+            Apps::Weather => {
+                select(app_controller.weather_app.display(), CHANGE_APP.wait()).await;
+            }
+            #[cfg(feature = "timer-app")]
+            Apps::Timer => {
+                select(app_controller.timer_app.display(), CHANGE_APP.wait()).await;
+            }
+            #[cfg(feature = "gallery-app")]
+            Apps::Gallery => {
+                select(app_controller.gallery_app.display(), CHANGE_APP.wait()).await;
+            }
+            #[cfg(feature = "pong-app")]
+            Apps::Pong => {
+                select(app_controller.pong_app.display(), CHANGE_APP.wait()).await;
+            }
+            #[cfg(feature = "quotes-app")]
+            Apps::Quotes => {
+                select(app_controller.quotes_app.display(), CHANGE_APP.wait()).await;
+            }
+            #[cfg(feature = "badge-app")]
+            Apps::Badge => {
+                select(app_controller.badge_app.display(), CHANGE_APP.wait()).await;
+            }
+            #[cfg(feature = "energy-app")]
+            Apps::Energy => {
+                select(app_controller.energy_app.display(), CHANGE_APP.wait()).await;
+            }
         };
 
         STOP_CURRENT_DISPLAY.signal(true);
@@ -0,0 +1,318 @@
+use core::str::FromStr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::Instant;
+use embedded_graphics::{geometry::Point, pixelcolor::Rgb888, Drawable, Pixel};
+use heapless::{String, Vec};
+use micromath::F32Ext;
+use static_cell::make_static;
+use strum_macros::EnumString;
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    app::UnicornApp,
+    board::{HEIGHT, WIDTH},
+    buttons::ButtonPress,
+    display::{self, messages::FrameBuilder, Display, FrameTicker},
+    fonts::Font,
+    graphics::{
+        colors::{self, Rgb888Str},
+        Rng,
+    },
+    json, logging,
+    mqtt::{
+        topics::{BADGE_SET_TOPIC, BADGE_STATE_TOPIC},
+        MqttMessage, MqttReceiveMessage,
+    },
+    storage::{Record, Storage},
+};
+
+/// Size of the badge JSON saved to flash, after the byte marking if the badge is shown.
+const JSON_SIZE: usize = Record::Badge.max_size() - 1;
+
+/// Longest name the badge can show.
+const NAME_SIZE: usize = 32;
+
+/// The fonts tried in turn for the name, largest first, until one fits on the display.
+const FONTS: [Font; 4] = [Font::Big, Font::Regular, Font::Medium, Font::Small];
+
+/// Scroll speed of a name too long for the display in pixels per second.
+const SCROLL_SPEED: f32 = 12.0;
+
+/// Gap between the end of a scrolling name and its start coming round again.
+const SCROLL_GAP: i32 = 12;
+
+/// How long a breath of the breathe effect takes, in seconds.
+const BREATHE_SECS: f32 = 4.0;
+
+/// How long the rainbow effect takes to cycle through every color, in seconds.
+const RAINBOW_SECS: f32 = 20.0;
+
+/// How many sparkles the sparkle effect shows at once.
+const SPARKLES: usize = 6;
+
+/// Effects shown with the name, kept subtle so it stays readable.
+#[derive(Clone, Copy, Default, EnumString)]
+#[strum(ascii_case_insensitive)]
+enum Effect {
+    /// The name on its own.
+    #[default]
+    None,
+
+    /// The name slowly fades in and out.
+    Breathe,
+
+    /// Dim sparkles twinkle behind the name.
+    Sparkle,
+
+    /// The color of the name slowly cycles through the rainbow.
+    Rainbow,
+}
+
+/// A badge parsed from its JSON.
+struct Badge {
+    name: String<NAME_SIZE>,
+
+    /// The color of the name. A `None` uses the active color.
+    color: Option<Rgb888>,
+    effect: Effect,
+}
+
+impl Badge {
+    /// Parse a badge from JSON such as `{"name": "Dom", "color": "teal", "effect": "sparkle"}`,
+    /// or a plain name.
+    fn parse(body: &str) -> Option<Self> {
+        let body = body.trim();
+        if !body.starts_with('{') {
+            return Some(Self {
+                name: String::try_from(body)
+                    .ok()
+                    .filter(|name| !name.is_empty())?,
+                color: None,
+                effect: Effect::None,
+            });
+        }
+
+        Some(Self {
+            name: String::try_from(json::extract(body, "name")?).ok()?,
+            color: match json::extract(body, "color") {
+                Some(color) => Some(Rgb888Str::from_str(color).ok()?.0),
+                None => None,
+            },
+            effect: match json::extract(body, "effect") {
+                Some(effect) => Effect::from_str(effect).ok()?,
+                None => Effect::None,
+            },
+        })
+    }
+}
+
+/// A sparkle of the sparkle effect.
+struct Sparkle {
+    x: i32,
+    y: i32,
+
+    /// When the sparkle appeared.
+    born: Instant,
+
+    /// How long the sparkle lasts, in seconds.
+    life: f32,
+}
+
+/// Name badge app. Show a name set over MQTT, for wearing at a conference.
+/// The badge is saved to flash, and if it was shown when the device was turned off it is shown
+/// again straight away at boot, so it works without a network once set.
+pub struct BadgeApp {
+    /// Reference to the display.
+    display: &'static Display<'static>,
+
+    /// Flash storage the badge is saved to.
+    storage: &'static Storage,
+
+    /// The badge set, as JSON.
+    badge: Mutex<ThreadModeRawMutex, String<JSON_SIZE>>,
+
+    /// Was the badge shown when last saved.
+    enabled: AtomicBool,
+}
+
+impl BadgeApp {
+    /// Create the static ref to badge app.
+    /// Must only be called once or will panic.
+    pub fn new(display: &'static Display<'static>, storage: &'static Storage) -> &'static Self {
+        make_static!(Self {
+            display,
+            storage,
+            badge: Mutex::new(String::new()),
+            enabled: AtomicBool::new(false),
+        })
+    }
+
+    /// Restore the badge saved in flash.
+    pub async fn restore(&self) {
+        let mut record = [0u8; Record::Badge.max_size()];
+        let len = match self.storage.read_record(Record::Badge, &mut record).await {
+            Ok(Some(len)) if len > 0 => len,
+            _ => return,
+        };
+
+        // whether the badge is shown, then its JSON
+        if let Ok(json) = core::str::from_utf8(&record[1..len]) {
+            *self.badge.lock().await = String::try_from(json).unwrap_or_default();
+            self.enabled.store(record[0] == 1, Ordering::Relaxed);
+        }
+    }
+
+    /// Check if the badge was shown when the device was turned off, so should be shown at boot.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Save the badge and whether it is shown to flash.
+    async fn save(&self, json: &str, enabled: bool) {
+        let mut record = Vec::<u8, { Record::Badge.max_size() }>::new();
+        let _ = record.push(enabled as u8);
+        let _ = record.extend_from_slice(json.as_bytes());
+
+        if self
+            .storage
+            .write_record(Record::Badge, &record)
+            .await
+            .is_err()
+        {
+            logging::warn!("Badge could not be saved");
+        }
+    }
+
+    /// Save whether the badge is shown, if it has changed.
+    async fn set_enabled(&self, enabled: bool) {
+        if self.enabled.swap(enabled, Ordering::Relaxed) != enabled {
+            let json = self.badge.lock().await.clone();
+            self.save(&json, enabled).await;
+        }
+    }
+
+    /// Set the badge from its JSON, or a plain name.
+    async fn set(&self, body: &str) {
+        let body = body.trim();
+        if Badge::parse(body).is_none() {
+            MqttMessage::enqueue_debug("Invalid badge, expected a name or name, color and effect")
+                .await;
+            return;
+        }
+
+        let Ok(json) = String::try_from(body) else {
+            MqttMessage::enqueue_debug("Badge too long").await;
+            return;
+        };
+
+        self.save(&json, self.is_enabled()).await;
+        *self.badge.lock().await = json;
+    }
+
+    /// Draw `name` in the largest font it fits in, scrolling it in the regular font if none fit.
+    fn draw_name(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, name: &str, color: Rgb888, secs: f32) {
+        let y = HEIGHT as i32 / 2;
+        if let Some(font) = FONTS
+            .iter()
+            .find(|font| font.text_width(name) <= WIDTH as u32)
+        {
+            let x = (WIDTH as i32 - font.text_width(name) as i32) / 2;
+            font.draw(gr, name, x, y, color);
+            return;
+        }
+
+        let width = Font::Regular.text_width(name) as i32 + SCROLL_GAP;
+        let offset = (secs * SCROLL_SPEED) as i32 % width;
+        Font::Regular.draw(gr, name, -offset, y, color);
+        Font::Regular.draw(gr, name, width - offset, y, color);
+    }
+}
+
+impl UnicornApp for BadgeApp {
+    async fn display(&self) {
+        let started = Instant::now();
+        let mut rng = Rng::new();
+        let mut sparkles = Vec::<Sparkle, SPARKLES>::new();
+        let mut ticker = FrameTicker::new();
+
+        loop {
+            let badge = Badge::parse(&self.badge.lock().await);
+            let secs = started.elapsed().as_millis() as f32 / 1000.0;
+
+            let mut frame = FrameBuilder::new();
+            match badge {
+                Some(badge) => {
+                    let mut color = match badge.color {
+                        Some(color) => color,
+                        None => self.display.get_color().await,
+                    };
+
+                    match badge.effect {
+                        Effect::None => {}
+                        Effect::Breathe => {
+                            let phase = secs / BREATHE_SECS * core::f32::consts::TAU;
+                            color = colors::scale(color, 0.65 + 0.35 * phase.cos());
+                        }
+                        Effect::Sparkle => {
+                            let now = Instant::now();
+                            sparkles.retain(|sparkle| {
+                                (now - sparkle.born).as_millis() as f32 / 1000.0 < sparkle.life
+                            });
+                            if !sparkles.is_full() && rng.range(0.0, 1.0) < 0.1 {
+                                let _ = sparkles.push(Sparkle {
+                                    x: rng.range(0.0, WIDTH as f32) as i32,
+                                    y: rng.range(0.0, HEIGHT as f32) as i32,
+                                    born: now,
+                                    life: rng.range(0.5, 1.5),
+                                });
+                            }
+
+                            for sparkle in sparkles.iter() {
+                                let age = (now - sparkle.born).as_millis() as f32 / 1000.0;
+                                let fade = 1.0 - (age / sparkle.life * 2.0 - 1.0).abs();
+                                let _ = Pixel(
+                                    Point::new(sparkle.x, sparkle.y),
+                                    colors::scale(color, 0.3 * fade),
+                                )
+                                .draw(&mut frame);
+                            }
+                        }
+                        Effect::Rainbow => color = colors::rotate_hue(color, secs / RAINBOW_SECS),
+                    }
+
+                    Self::draw_name(frame.graphics_mut(), &badge.name, color, secs);
+                }
+                None => {
+                    let color = self.display.get_color().await;
+                    Self::draw_name(frame.graphics_mut(), "No badge", color, secs);
+                }
+            }
+
+            frame.show(display::frame_duration()).await;
+            ticker.next().await;
+        }
+    }
+
+    async fn start(&self) {
+        self.set_enabled(true).await;
+    }
+
+    async fn stop(&self) {
+        self.set_enabled(false).await;
+    }
+
+    async fn button_press(&self, _: ButtonPress) {}
+
+    async fn process_mqtt_message(&self, message: MqttReceiveMessage) {
+        if message.topic == BADGE_SET_TOPIC {
+            self.set(&message.body).await;
+        }
+    }
+
+    async fn send_mqtt_state(&self) {
+        let json = self.badge.lock().await.clone();
+        MqttMessage::enqueue_state(BADGE_STATE_TOPIC, &json).await;
+    }
+}
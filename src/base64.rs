@@ -0,0 +1,30 @@
+//! Base64 decoding for binary data sent in MQTT payloads, which must be text.
+
+/// Decode base64 `text` into `out`, returning the number of bytes written.
+/// Returns `None` if `text` is not base64 or does not fit in `out`.
+pub fn decode(text: &str, out: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for c in text.trim().bytes().filter(|c| *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            *out.get_mut(len)? = (bits >> count) as u8;
+            len += 1;
+        }
+    }
+
+    Some(len)
+}
@@ -0,0 +1,49 @@
+//! The Unicorn board the firmware is built for, picked with the `galactic`, `cosmic` or `stellar`
+//! feature.
+//!
+//! Each board has a driver crate with the same API, so everything else takes the driver, pins and
+//! panel size from here. Apps lay themselves out from `WIDTH` and `HEIGHT`, and stack what sits
+//! side by side on the 53x11 Galactic Unicorn when the panel is the 32x32 Cosmic or 16x16 Stellar.
+
+#[cfg(not(any(feature = "galactic", feature = "cosmic", feature = "stellar")))]
+compile_error!("a board feature must be enabled: galactic, cosmic or stellar");
+
+#[cfg(any(
+    all(feature = "galactic", feature = "cosmic"),
+    all(feature = "galactic", feature = "stellar"),
+    all(feature = "cosmic", feature = "stellar"),
+))]
+compile_error!("only one board feature can be enabled, build without the default features");
+
+#[cfg(feature = "galactic")]
+pub use galactic_unicorn_embassy::{
+    buttons::UnicornButtons,
+    pins::{UnicornButtonPins, UnicornDisplayPins, UnicornSensorPins},
+    GalacticUnicorn as Unicorn, HEIGHT, WIDTH,
+};
+
+#[cfg(feature = "cosmic")]
+pub use cosmic_unicorn_embassy::{
+    buttons::UnicornButtons,
+    pins::{UnicornButtonPins, UnicornDisplayPins, UnicornSensorPins},
+    CosmicUnicorn as Unicorn, HEIGHT, WIDTH,
+};
+
+#[cfg(feature = "stellar")]
+pub use stellar_unicorn_embassy::{
+    buttons::UnicornButtons,
+    pins::{UnicornButtonPins, UnicornDisplayPins, UnicornSensorPins},
+    StellarUnicorn as Unicorn, HEIGHT, WIDTH,
+};
+
+/// Name of the board, reported to home assistant and as the USB product.
+#[cfg(feature = "galactic")]
+pub const NAME: &str = "Galactic Unicorn";
+#[cfg(feature = "cosmic")]
+pub const NAME: &str = "Cosmic Unicorn";
+#[cfg(feature = "stellar")]
+pub const NAME: &str = "Stellar Unicorn";
+
+/// Whether the panel is at least twice as wide as it is tall, so text fits beside an icon.
+/// Square panels stack the text under the icon instead.
+pub const WIDE: bool = WIDTH >= 2 * HEIGHT;
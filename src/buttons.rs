@@ -1,11 +1,13 @@
 use embassy_futures::select::{select, Either};
 use embassy_rp::{
     gpio::Input,
-    peripherals::{PIN_0, PIN_1, PIN_21, PIN_26, PIN_3},
+    peripherals::{PIN_0, PIN_1, PIN_21, PIN_26, PIN_27, PIN_3, PIN_6, PIN_7, PIN_8},
 };
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
 use embassy_time::{Duration, Timer};
-use galactic_unicorn_embassy::buttons::UnicornButtons;
+use portable_atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::{board::UnicornButtons, storage::Storage, system::factory_reset};
 
 /// Type of button press made.
 pub enum ButtonPress {
@@ -25,6 +27,12 @@ pub static BRIGHTNESS_UP_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal
 /// Signal for when the brightness down button has been pressed.
 pub static BRIGHTNESS_DOWN_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal::new();
 
+/// Signal for when the volume up button has been pressed.
+pub static VOLUME_UP_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal::new();
+
+/// Signal for when the volume down button has been pressed.
+pub static VOLUME_DOWN_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal::new();
+
 /// Signal for when the switch a button has been pressed.
 pub static SWITCH_A_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal::new();
 
@@ -34,6 +42,83 @@ pub static SWITCH_B_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal::new
 /// Signal for when the switch c button has been pressed.
 pub static SWITCH_C_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal::new();
 
+/// Signal for when the switch d button has been pressed.
+pub static SWITCH_D_PRESS: Signal<ThreadModeRawMutex, ButtonPress> = Signal::new();
+
+/// Signal for when switches are pressed together, with the `chord_bit` of each switch set.
+pub static CHORD_PRESS: Signal<ThreadModeRawMutex, u8> = Signal::new();
+
+/// Bits of the buttons held down, from `held_bit`.
+static HELD: AtomicU8 = AtomicU8::new(0);
+
+/// Chord bits of the switches pressed as part of a chord, whose own presses are not published.
+static IN_CHORD: AtomicU8 = AtomicU8::new(0);
+
+/// Bits in `HELD` of the switches that can be part of a chord.
+const CHORD_SWITCHES: u8 = 0b111;
+
+/// Chord bits of all three switches pressed together, which toggles the name badge.
+#[cfg(feature = "badge-app")]
+pub const ALL_SWITCHES_CHORD: u8 = CHORD_SWITCHES;
+
+/// Are the brightness and volume buttons captured by a game, so their presses are not published.
+static CAPTURED: AtomicBool = AtomicBool::new(false);
+
+/// Get the bit for `button` in `HELD`, or 0 for buttons that are not tracked.
+fn held_bit(button: &UnicornButtons) -> u8 {
+    match button {
+        UnicornButtons::SwitchA => 1 << 0,
+        UnicornButtons::SwitchB => 1 << 1,
+        UnicornButtons::SwitchC => 1 << 2,
+        UnicornButtons::BrightnessUp => 1 << 3,
+        UnicornButtons::BrightnessDown => 1 << 4,
+        UnicornButtons::VolumeUp => 1 << 5,
+        UnicornButtons::VolumeDown => 1 << 6,
+        _ => 0,
+    }
+}
+
+/// Get the bit for `button` in a chord, or 0 for buttons that can not be part of one.
+pub fn chord_bit(button: &UnicornButtons) -> u8 {
+    held_bit(button) & CHORD_SWITCHES
+}
+
+/// Record `button` as held down, signalling a chord if another switch is already held.
+fn hold(button: &UnicornButtons) {
+    let held = HELD.fetch_or(held_bit(button), Ordering::Relaxed) | held_bit(button);
+    let chord = held & CHORD_SWITCHES;
+    if chord_bit(button) != 0 && chord.count_ones() > 1 {
+        IN_CHORD.fetch_or(chord, Ordering::Relaxed);
+        CHORD_PRESS.signal(chord);
+    }
+}
+
+/// Record `button` as released.
+fn release(button: &UnicornButtons) {
+    HELD.fetch_and(!held_bit(button), Ordering::Relaxed);
+}
+
+/// Check if `button` is held down. Only the switches, brightness and volume buttons are tracked.
+#[cfg(feature = "pong-app")]
+pub fn is_held(button: &UnicornButtons) -> bool {
+    HELD.load(Ordering::Relaxed) & held_bit(button) != 0
+}
+
+/// Capture the brightness and volume buttons for a game, which reads them with `is_held`,
+/// so they do not change the brightness whilst it is played.
+#[cfg(feature = "pong-app")]
+pub fn capture(captured: bool) {
+    CAPTURED.store(captured, Ordering::Relaxed);
+}
+
+/// Publish the press of a switch, unless it was part of a chord.
+fn publish_switch_press(press: ButtonPress, button: &UnicornButtons) {
+    let bit = chord_bit(button);
+    if IN_CHORD.fetch_and(!bit, Ordering::Relaxed) & bit == 0 {
+        publish_to_channel(press, button);
+    }
+}
+
 /// Wait for changes async on the brightness up button being pressed.
 ///
 /// Will inform signal of button press after the full press has been completed.
@@ -45,14 +130,19 @@ pub async fn brightness_up_task(mut button: Input<'static, PIN_21>) -> ! {
     loop {
         // sit here until button is pressed down
         button.wait_for_low().await;
+        hold(&UnicornButtons::BrightnessUp);
 
-        let press: ButtonPress = button_pressed(&mut button).await;
-        publish_to_channel(press, &UnicornButtons::BrightnessUp);
+        // a game reads the button as it is held, rather than waiting for the press to finish
+        if !CAPTURED.load(Ordering::Relaxed) {
+            let press: ButtonPress = button_pressed(&mut button).await;
+            publish_to_channel(press, &UnicornButtons::BrightnessUp);
+        }
 
         // wait for button to be released
         if button.is_low() {
             button.wait_for_high().await;
         }
+        release(&UnicornButtons::BrightnessUp);
 
         // add debounce
         Timer::after(Duration::from_millis(200)).await;
@@ -70,14 +160,79 @@ pub async fn brightness_down_task(mut button: Input<'static, PIN_26>) -> ! {
     loop {
         // sit here until button is pressed down
         button.wait_for_low().await;
+        hold(&UnicornButtons::BrightnessDown);
 
-        let press: ButtonPress = button_pressed(&mut button).await;
-        publish_to_channel(press, &UnicornButtons::BrightnessDown);
+        // a game reads the button as it is held, rather than waiting for the press to finish
+        if !CAPTURED.load(Ordering::Relaxed) {
+            let press: ButtonPress = button_pressed(&mut button).await;
+            publish_to_channel(press, &UnicornButtons::BrightnessDown);
+        }
+
+        // wait for button to be released
+        if button.is_low() {
+            button.wait_for_high().await;
+        }
+        release(&UnicornButtons::BrightnessDown);
+
+        // add debounce
+        Timer::after(Duration::from_millis(200)).await;
+    }
+}
+
+/// Wait for changes async on the volume up button being pressed.
+///
+/// Will inform signal of button press after the full press has been completed.
+/// The type of press is recorded in the ButtonPress enum.
+///
+/// This task has no way of cancellation.
+#[embassy_executor::task]
+pub async fn volume_up_task(mut button: Input<'static, PIN_7>) -> ! {
+    loop {
+        // sit here until button is pressed down
+        button.wait_for_low().await;
+        hold(&UnicornButtons::VolumeUp);
+
+        // a game reads the button as it is held, rather than waiting for the press to finish
+        if !CAPTURED.load(Ordering::Relaxed) {
+            let press: ButtonPress = button_pressed(&mut button).await;
+            publish_to_channel(press, &UnicornButtons::VolumeUp);
+        }
+
+        // wait for button to be released
+        if button.is_low() {
+            button.wait_for_high().await;
+        }
+        release(&UnicornButtons::VolumeUp);
+
+        // add debounce
+        Timer::after(Duration::from_millis(200)).await;
+    }
+}
+
+/// Wait for changes async on the volume down button being pressed.
+///
+/// Will inform signal of button press after the full press has been completed.
+/// The type of press is recorded in the ButtonPress enum.
+///
+/// This task has no way of cancellation.
+#[embassy_executor::task]
+pub async fn volume_down_task(mut button: Input<'static, PIN_8>) -> ! {
+    loop {
+        // sit here until button is pressed down
+        button.wait_for_low().await;
+        hold(&UnicornButtons::VolumeDown);
+
+        // a game reads the button as it is held, rather than waiting for the press to finish
+        if !CAPTURED.load(Ordering::Relaxed) {
+            let press: ButtonPress = button_pressed(&mut button).await;
+            publish_to_channel(press, &UnicornButtons::VolumeDown);
+        }
 
         // wait for button to be released
         if button.is_low() {
             button.wait_for_high().await;
         }
+        release(&UnicornButtons::VolumeDown);
 
         // add debounce
         Timer::after(Duration::from_millis(200)).await;
@@ -95,14 +250,16 @@ pub async fn button_a_task(mut button: Input<'static, PIN_0>) -> ! {
     loop {
         // sit here until button is pressed down
         button.wait_for_low().await;
+        hold(&UnicornButtons::SwitchA);
 
         let press: ButtonPress = button_pressed(&mut button).await;
-        publish_to_channel(press, &UnicornButtons::SwitchA);
+        publish_switch_press(press, &UnicornButtons::SwitchA);
 
         // wait for button to be released
         if button.is_low() {
             button.wait_for_high().await;
         }
+        release(&UnicornButtons::SwitchA);
 
         // add debounce
         Timer::after(Duration::from_millis(200)).await;
@@ -120,14 +277,16 @@ pub async fn button_b_task(mut button: Input<'static, PIN_1>) -> ! {
     loop {
         // sit here until button is pressed down
         button.wait_for_low().await;
+        hold(&UnicornButtons::SwitchB);
 
         let press: ButtonPress = button_pressed(&mut button).await;
-        publish_to_channel(press, &UnicornButtons::SwitchB);
+        publish_switch_press(press, &UnicornButtons::SwitchB);
 
         // wait for button to be released
         if button.is_low() {
             button.wait_for_high().await;
         }
+        release(&UnicornButtons::SwitchB);
 
         // add debounce
         Timer::after(Duration::from_millis(200)).await;
@@ -145,9 +304,70 @@ pub async fn button_c_task(mut button: Input<'static, PIN_3>) -> ! {
     loop {
         // sit here until button is pressed down
         button.wait_for_low().await;
+        hold(&UnicornButtons::SwitchC);
 
         let press: ButtonPress = button_pressed(&mut button).await;
-        publish_to_channel(press, &UnicornButtons::SwitchC);
+        publish_switch_press(press, &UnicornButtons::SwitchC);
+
+        // wait for button to be released
+        if button.is_low() {
+            button.wait_for_high().await;
+        }
+        release(&UnicornButtons::SwitchC);
+
+        // add debounce
+        Timer::after(Duration::from_millis(200)).await;
+    }
+}
+
+/// Wait for changes async on the switch d button being pressed.
+///
+/// Will inform signal of button press after the full press has been completed.
+/// The type of press is recorded in the ButtonPress enum.
+///
+/// This task has no way of cancellation.
+#[embassy_executor::task]
+pub async fn button_d_task(mut button: Input<'static, PIN_6>) -> ! {
+    loop {
+        // sit here until button is pressed down
+        button.wait_for_low().await;
+
+        let press: ButtonPress = button_pressed(&mut button).await;
+        publish_to_channel(press, &UnicornButtons::SwitchD);
+
+        // wait for button to be released
+        if button.is_low() {
+            button.wait_for_high().await;
+        }
+
+        // add debounce
+        Timer::after(Duration::from_millis(200)).await;
+    }
+}
+
+/// Wait for the sleep button to be held down.
+///
+/// Will factory reset if the button is held for the whole countdown.
+///
+/// This task has no way of cancellation.
+#[embassy_executor::task]
+pub async fn sleep_button_task(mut button: Input<'static, PIN_27>, storage: &'static Storage) -> ! {
+    loop {
+        // sit here until button is pressed down
+        button.wait_for_low().await;
+
+        // ignore short presses, so only a deliberate hold shows the countdown
+        if let Either::Second(_) = select(
+            button.wait_for_high(),
+            Timer::after(Duration::from_millis(500)),
+        )
+        .await
+        {
+            factory_reset::run(storage, factory_reset::SLEEP_HOLD_COUNTDOWN_SECS, || {
+                button.is_low()
+            })
+            .await;
+        }
 
         // wait for button to be released
         if button.is_low() {
@@ -204,11 +424,11 @@ fn publish_to_channel(press: ButtonPress, button_type: &UnicornButtons) {
         UnicornButtons::SwitchA => SWITCH_A_PRESS.signal(press),
         UnicornButtons::SwitchB => SWITCH_B_PRESS.signal(press),
         UnicornButtons::SwitchC => SWITCH_C_PRESS.signal(press),
-        UnicornButtons::SwitchD => todo!(),
+        UnicornButtons::SwitchD => SWITCH_D_PRESS.signal(press),
         UnicornButtons::BrightnessUp => BRIGHTNESS_UP_PRESS.signal(press),
         UnicornButtons::BrightnessDown => BRIGHTNESS_DOWN_PRESS.signal(press),
-        UnicornButtons::VolumeUp => todo!(),
-        UnicornButtons::VolumeDown => todo!(),
+        UnicornButtons::VolumeUp => VOLUME_UP_PRESS.signal(press),
+        UnicornButtons::VolumeDown => VOLUME_DOWN_PRESS.signal(press),
         UnicornButtons::Sleep => todo!(),
     }
 }
@@ -0,0 +1,414 @@
+//! Celebrations for special dates, such as New Year or a birthday.
+//!
+//! On a celebration date the display is interrupted once with a fireworks or confetti animation
+//! and a message. Celebrations are set over MQTT as JSON and saved to flash. They are held back
+//! during quiet hours and whilst an alert is shown.
+
+use core::fmt::Write;
+
+use chrono::{Datelike, NaiveDate};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::{
+    geometry::Point,
+    pixelcolor::{Rgb888, RgbColor},
+};
+use heapless::{String, Vec};
+use micromath::F32Ext;
+use strum_macros::EnumString;
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    alert,
+    board::{HEIGHT, WIDTH},
+    display::{messages::DisplayGraphicsMessage, FrameTicker},
+    fonts::Font,
+    graphics::{colors, Rng},
+    json, logging,
+    mqtt::{topics::CELEBRATIONS_STATE_TOPIC, MqttMessage},
+    storage::{Record, Storage},
+    system::{presence, quiet_hours},
+    time::Time,
+};
+
+/// Celebrations used until others are set over MQTT.
+const DEFAULT_CELEBRATIONS: &str =
+    r#"[{"date": "01-01", "text": "Happy New Year!", "theme": "fireworks"}]"#;
+
+/// The most celebrations that can be set.
+const MAX_CELEBRATIONS: usize = 8;
+
+/// Size of the celebrations JSON saved to flash.
+const JSON_SIZE: usize = Record::Celebrations.max_size();
+
+/// How often to check if a celebration date has been reached.
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// How long each celebration is shown for.
+const CELEBRATION_SECS: u64 = 15;
+
+/// Scroll speed of long celebration text in pixels per second.
+const SCROLL_SPEED: f32 = 20.0;
+
+/// The most particles on the display at once.
+const MAX_PARTICLES: usize = 64;
+
+/// Sparks thrown out by each firework.
+const FIREWORK_SPARKS: usize = 12;
+
+/// Time between fireworks being launched.
+const FIREWORK_INTERVAL_MILLIS: u64 = 700;
+
+/// Pieces of confetti dropped each second.
+const CONFETTI_RATE: f32 = 25.0;
+
+/// Downward pull on particles in pixels per second squared.
+const GRAVITY: f32 = 8.0;
+
+/// Fastest confetti falls in pixels per second, so it flutters down rather than dropping.
+const CONFETTI_MAX_SPEED: f32 = 5.0;
+
+/// The celebrations set, as JSON.
+static CELEBRATIONS: Mutex<ThreadModeRawMutex, String<JSON_SIZE>> = Mutex::new(String::new());
+
+/// The animations a celebration can be shown with.
+#[derive(Clone, Copy, Default, EnumString)]
+#[strum(serialize_all = "snake_case")]
+enum Theme {
+    Fireworks,
+    #[default]
+    Confetti,
+}
+
+/// A date to celebrate every year.
+struct Celebration {
+    month: u32,
+    day: u32,
+
+    /// The message shown over the animation.
+    text: String<64>,
+
+    theme: Theme,
+}
+
+impl Celebration {
+    /// Parse a celebration from a JSON object such as
+    /// `{"date": "12-25", "text": "Merry Christmas", "theme": "confetti"}`.
+    fn parse(json: &str) -> Option<Self> {
+        let (month, day) = json::extract(json, "date")?.split_once('-')?;
+        let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+
+        // checked against a leap year, so birthdays on the 29th of February are allowed
+        NaiveDate::from_ymd_opt(2024, month, day)?;
+
+        let theme = match json::extract(json, "theme") {
+            Some(theme) => theme.parse().ok()?,
+            None => Theme::default(),
+        };
+
+        Some(Self {
+            month,
+            day,
+            text: String::try_from(json::extract(json, "text")?).ok()?,
+            theme,
+        })
+    }
+}
+
+/// Parse a JSON array of celebrations, failing if any of them are invalid.
+fn parse_all(json: &str) -> Option<Vec<Celebration, MAX_CELEBRATIONS>> {
+    if !json.trim_start().starts_with('[') {
+        return None;
+    }
+
+    let mut celebrations = Vec::new();
+    let mut path = String::<8>::new();
+    for index in 0.. {
+        path.clear();
+        let _ = write!(path, "[{index}]");
+
+        let Some(entry) = json::extract(json, &path) else {
+            break;
+        };
+        celebrations.push(Celebration::parse(entry)?).ok()?;
+    }
+
+    Some(celebrations)
+}
+
+/// Set the celebrations from a JSON array such as
+/// `[{"date": "03-14", "text": "Happy birthday Sam", "theme": "confetti"}]`,
+/// save them to flash and send the state over MQTT.
+pub async fn set(storage: &'static Storage, body: &str) {
+    let body = body.trim();
+    if parse_all(body).is_none() {
+        MqttMessage::enqueue_debug("Invalid celebrations, expected a JSON array of dates").await;
+        return;
+    }
+
+    let Ok(json) = String::try_from(body) else {
+        MqttMessage::enqueue_debug("Celebrations too long").await;
+        return;
+    };
+
+    if storage
+        .write_record(Record::Celebrations, json.as_bytes())
+        .await
+        .is_err()
+    {
+        logging::warn!("Celebrations could not be saved");
+    }
+
+    *CELEBRATIONS.lock().await = json;
+    send_state().await;
+}
+
+/// Send the celebrations over MQTT.
+pub async fn send_state() {
+    let json = CELEBRATIONS.lock().await.clone();
+    MqttMessage::enqueue_state(CELEBRATIONS_STATE_TOPIC, &json).await;
+}
+
+/// Load the celebrations and the day last celebrated from flash.
+async fn restore(storage: &'static Storage) -> Option<i32> {
+    let mut record = [0u8; JSON_SIZE];
+    let json = match storage.read_record(Record::Celebrations, &mut record).await {
+        Ok(Some(len)) => core::str::from_utf8(&record[..len]).unwrap_or(DEFAULT_CELEBRATIONS),
+        _ => DEFAULT_CELEBRATIONS,
+    };
+    *CELEBRATIONS.lock().await = String::try_from(json).unwrap_or_default();
+
+    let mut record = [0u8; Record::Celebrated.max_size()];
+    match storage.read_record(Record::Celebrated, &mut record).await {
+        Ok(Some(len)) if len == record.len() => Some(i32::from_le_bytes(record)),
+        _ => None,
+    }
+}
+
+/// Show each celebration on its date, once per year.
+#[embassy_executor::task]
+pub async fn celebration_task(storage: &'static Storage, time: &'static Time) {
+    // days are counted from the common era, so each occurrence has its own number
+    let mut celebrated = restore(storage).await;
+
+    loop {
+        Timer::after_secs(CHECK_INTERVAL_SECS).await;
+
+        // the date is not known until it has been synced, and nobody would see it whilst away
+        if !time.is_synced() || quiet_hours::is_quiet() || presence::is_away() || alert::is_active()
+        {
+            continue;
+        }
+
+        let today = time.now().await.date_naive();
+        let day = today.num_days_from_ce();
+        if celebrated == Some(day) {
+            continue;
+        }
+
+        let Some(celebrations) = parse_all(&CELEBRATIONS.lock().await) else {
+            continue;
+        };
+        let mut todays = celebrations
+            .iter()
+            .filter(|celebration| {
+                celebration.month == today.month() && celebration.day == today.day()
+            })
+            .peekable();
+        if todays.peek().is_none() {
+            continue;
+        }
+
+        // saved before showing, so a reboot part way through does not show it again
+        celebrated = Some(day);
+        if storage
+            .write_record(Record::Celebrated, &day.to_le_bytes())
+            .await
+            .is_err()
+        {
+            logging::warn!("Celebration could not be saved");
+        }
+
+        for celebration in todays {
+            logging::info!("Celebrating {}", celebration.text);
+            show(celebration).await;
+        }
+    }
+}
+
+/// The kinds of particle in the animations.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A firework rising, which bursts into sparks at the top of its climb.
+    Rocket,
+    Spark,
+    Confetti,
+}
+
+/// A particle in the animations.
+#[derive(Clone, Copy)]
+struct Particle {
+    kind: Kind,
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    color: Rgb888,
+
+    /// How much life is left, from 1 down to 0, which fades the particle out.
+    life: f32,
+
+    /// How much life is lost each second.
+    decay: f32,
+}
+
+impl Particle {
+    /// Move the particle on by `dt` seconds.
+    fn update(&mut self, dt: f32) {
+        self.x += self.vx * dt;
+        self.y += self.vy * dt;
+        self.vy += GRAVITY * dt;
+        self.life -= self.decay * dt;
+
+        if self.kind == Kind::Confetti {
+            self.vy = self.vy.min(CONFETTI_MAX_SPEED);
+        }
+    }
+
+    /// Check if the particle has faded out or fallen off the display.
+    fn is_gone(&self) -> bool {
+        self.life <= 0.0 || self.y >= HEIGHT as f32 || self.x < -1.0 || self.x >= WIDTH as f32 + 1.0
+    }
+}
+
+/// Launch a firework from the bottom of the display.
+fn launch_firework(particles: &mut Vec<Particle, MAX_PARTICLES>, rng: &mut Rng) {
+    let _ = particles.push(Particle {
+        kind: Kind::Rocket,
+        x: rng.range(4.0, WIDTH as f32 - 4.0),
+        y: HEIGHT as f32 - 1.0,
+        vx: rng.range(-3.0, 3.0),
+        vy: rng.range(-14.0, -10.0),
+        color: Rgb888::new(120, 120, 120),
+        life: 1.0,
+        decay: 0.0,
+    });
+}
+
+/// Burst a firework into sparks.
+fn burst(particles: &mut Vec<Particle, MAX_PARTICLES>, rng: &mut Rng, x: f32, y: f32) {
+    let color = colors::from_hsv(rng.range(0.0, 1.0), 1.0, 1.0);
+    for index in 0..FIREWORK_SPARKS {
+        let angle = index as f32 * core::f32::consts::TAU / FIREWORK_SPARKS as f32;
+        let speed = rng.range(6.0, 10.0);
+        let _ = particles.push(Particle {
+            kind: Kind::Spark,
+            x,
+            y,
+            vx: angle.cos() * speed,
+            vy: angle.sin() * speed,
+            color,
+            life: 1.0,
+            decay: rng.range(0.8, 1.2),
+        });
+    }
+}
+
+/// Drop a piece of confetti from the top of the display.
+fn drop_confetti(particles: &mut Vec<Particle, MAX_PARTICLES>, rng: &mut Rng) {
+    let _ = particles.push(Particle {
+        kind: Kind::Confetti,
+        x: rng.range(0.0, WIDTH as f32),
+        y: 0.0,
+        vx: rng.range(-2.0, 2.0),
+        vy: rng.range(2.0, CONFETTI_MAX_SPEED),
+        color: colors::from_hsv(rng.range(0.0, 1.0), 1.0, 1.0),
+        life: 1.0,
+        decay: 0.0,
+    });
+}
+
+/// Show the animation for `celebration` with its text, scrolling if it is too long.
+/// Stops early if an alert is shown.
+async fn show(celebration: &Celebration) {
+    let font = Font::default();
+    let text_width = font.text_width(&celebration.text) as i32;
+    let scrolls = text_width > WIDTH as i32;
+
+    let mut rng = Rng::new();
+    let mut particles = Vec::<Particle, MAX_PARTICLES>::new();
+    let mut next_firework = Instant::now();
+    let mut confetti_due: f32 = 0.0;
+
+    let started = Instant::now();
+    let mut offset: f32 = 0.0;
+    let mut dt: f32 = 0.0;
+    let mut ticker = FrameTicker::new();
+    let mut gr = UnicornGraphics::<WIDTH, HEIGHT>::new();
+
+    while started.elapsed() < Duration::from_secs(CELEBRATION_SECS) && !alert::is_active() {
+        match celebration.theme {
+            Theme::Fireworks => {
+                if Instant::now() >= next_firework {
+                    launch_firework(&mut particles, &mut rng);
+                    next_firework += Duration::from_millis(FIREWORK_INTERVAL_MILLIS);
+                }
+            }
+            Theme::Confetti => {
+                confetti_due += CONFETTI_RATE * dt;
+                while confetti_due >= 1.0 {
+                    drop_confetti(&mut particles, &mut rng);
+                    confetti_due -= 1.0;
+                }
+            }
+        }
+
+        let mut bursts = Vec::<(f32, f32), 4>::new();
+        for particle in particles.iter_mut() {
+            particle.update(dt);
+
+            if particle.kind == Kind::Rocket && particle.vy >= 0.0 {
+                particle.life = 0.0;
+                let _ = bursts.push((particle.x, particle.y));
+            }
+        }
+        particles.retain(|particle| !particle.is_gone());
+        for (x, y) in bursts {
+            burst(&mut particles, &mut rng, x, y);
+        }
+
+        gr.clear_all();
+        for particle in particles.iter() {
+            let point = Point::new(particle.x.floor() as i32, particle.y.floor() as i32);
+            if (0..WIDTH as i32).contains(&point.x) && (0..HEIGHT as i32).contains(&point.y) {
+                gr.set_pixel(point, colors::scale(particle.color, particle.life));
+            }
+        }
+
+        let x = if scrolls {
+            // scroll in from the right edge and off the left, then start again
+            let distance = (WIDTH as i32 + text_width) as f32;
+            if offset > distance {
+                offset = 0.0;
+            }
+            WIDTH as i32 - offset as i32
+        } else {
+            (WIDTH as i32 - text_width) / 2
+        };
+        font.draw(
+            &mut gr,
+            &celebration.text,
+            x,
+            (HEIGHT / 2) as i32,
+            Rgb888::WHITE,
+        );
+
+        DisplayGraphicsMessage::from_app(gr.get_pixels(), Duration::from_millis(200))
+            .send_and_show_now()
+            .await;
+
+        let elapsed = ticker.next().await;
+        dt = elapsed.as_micros() as f32 / 1_000_000.0;
+        offset += SCROLL_SPEED * dt;
+    }
+}
@@ -1,16 +1,13 @@
 use chrono::{Datelike, Timelike, Weekday};
 use core::{fmt::Write, str::FromStr};
 use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
-use embassy_time::Timer;
+use embassy_time::{Instant, Timer};
 use embedded_graphics::{
     geometry::{Point, Size},
-    mono_font::{iso_8859_13::FONT_5X7, MonoTextStyle},
-    pixelcolor::{Rgb888, RgbColor},
+    pixelcolor::{Rgb888, RgbColor, WebColors},
     primitives::{Primitive, PrimitiveStyleBuilder, Rectangle},
-    text::Text,
 };
 use embedded_graphics_core::Drawable;
-use galactic_unicorn_embassy::{HEIGHT, WIDTH};
 use heapless::{String, Vec};
 use micromath::F32Ext;
 use static_cell::make_static;
@@ -19,16 +16,36 @@ use unicorn_graphics::UnicornGraphics;
 
 use crate::{
     app::UnicornApp,
+    board::{HEIGHT, WIDTH},
     buttons::ButtonPress,
+    config::CLOCK_TEMPERATURE_TOPIC,
     display::{
+        self,
         messages::{DisplayGraphicsMessage, DisplayTextMessage},
-        Display,
+        Display, FrameTicker,
     },
-    fonts::DrawOntoGraphics,
-    mqtt::{topics::CLOCK_APP_STATE_TOPIC, MqttMessage},
+    fonts::{DrawOntoGraphics, Font},
+    graphics::palettes::Palette,
+    json,
+    mqtt::{
+        topics::{
+            CLOCK_APP_STATE_TOPIC, CLOCK_CHIME_HOURS_SET_TOPIC, CLOCK_CHIME_HOURS_STATE_TOPIC,
+            CLOCK_CHIME_SET_TOPIC, CLOCK_CHIME_STATE_TOPIC, CLOCK_FORMAT_SET_TOPIC,
+            CLOCK_FORMAT_STATE_TOPIC, CLOCK_LAYOUT_SET_TOPIC, CLOCK_LAYOUT_STATE_TOPIC,
+            CLOCK_TICKER_SET_TOPIC,
+        },
+        MqttMessage,
+    },
+    sensors,
+    system::{quiet_hours, reboot::parse_time},
     time::Time,
 };
 
+/// Check if `topic` is the topic the clock temperature is published to.
+pub fn is_temperature_topic(topic: &str) -> bool {
+    CLOCK_TEMPERATURE_TOPIC.is_some_and(|temperature| temperature == topic)
+}
+
 /// All the effects that can be displayed on the clock.
 #[derive(Clone, Copy, EnumString, IntoStaticStr)]
 #[strum(ascii_case_insensitive)]
@@ -40,6 +57,82 @@ pub enum ClockEffect {
     Color,
 }
 
+/// How the hours of the clock are shown.
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+pub enum HourFormat {
+    /// Hours from 00 to 23.
+    #[strum(serialize = "24h")]
+    TwentyFour,
+
+    /// Hours from 01 to 12.
+    #[strum(serialize = "12h")]
+    Twelve,
+}
+
+impl HourFormat {
+    /// Get the hour to show for `hour` from 0 to 23.
+    fn hour(self, hour: u32) -> u32 {
+        match self {
+            HourFormat::TwentyFour => hour,
+            HourFormat::Twelve => match hour % 12 {
+                0 => 12,
+                hour => hour,
+            },
+        }
+    }
+}
+
+/// How the clock is laid out on the display.
+#[derive(Clone, Copy, PartialEq, EnumString, IntoStaticStr)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum ClockLayout {
+    /// The time with seconds and the calendar box.
+    Full,
+
+    /// A compact time on the left, with the latest message or ticker text scrolling on the right.
+    Ticker,
+}
+
+/// Text scrolled beside the clock in the ticker layout.
+struct TickerText {
+    text: String<64>,
+
+    /// The color to show the text in, or the active color if `None`.
+    color: Option<Rgb888>,
+}
+
+/// The hourly chime, a sweep across the digits at the top of each hour.
+#[derive(Clone, Copy)]
+struct Chime {
+    enabled: bool,
+
+    /// Hour and minute the chime starts playing, so it only plays in waking hours.
+    start: (u32, u32),
+
+    /// Hour and minute the chime stops playing.
+    end: (u32, u32),
+}
+
+impl Chime {
+    /// Check if the chime plays at the top of `hour`. Never plays in quiet hours.
+    fn plays_at(&self, hour: u32) -> bool {
+        let minutes = hour * 60;
+        let start = self.start.0 * 60 + self.start.1;
+        let end = self.end.0 * 60 + self.end.1;
+
+        if !self.enabled || quiet_hours::is_quiet() {
+            false
+        } else if start == end {
+            true
+        } else if start < end {
+            minutes >= start && minutes < end
+        } else {
+            // runs over midnight
+            minutes >= start || minutes < end
+        }
+    }
+}
+
 /// Clock app. Display the current time and date.
 pub struct ClockApp {
     /// Reference to the display.
@@ -50,6 +143,21 @@ pub struct ClockApp {
 
     /// The current effect of the clock.
     effect: Mutex<NoopRawMutex, ClockEffect>,
+
+    /// The last temperature received and when, shown in the calendar box in turn with the date.
+    temperature: Mutex<NoopRawMutex, Option<(f32, Instant)>>,
+
+    /// The hourly chime settings.
+    chime: Mutex<NoopRawMutex, Chime>,
+
+    /// How the hours are shown.
+    format: Mutex<NoopRawMutex, HourFormat>,
+
+    /// How the clock is laid out.
+    layout: Mutex<NoopRawMutex, ClockLayout>,
+
+    /// The text scrolled beside the clock in the ticker layout, the date when `None`.
+    ticker: Mutex<NoopRawMutex, Option<TickerText>>,
 }
 
 /// Trait for defining text width constant on the clock app struct.
@@ -59,10 +167,44 @@ trait AlternateTextWidth {
 }
 
 impl AlternateTextWidth for ClockApp {
-    const TEXT_WIDTH: usize = 41;
+    const TEXT_WIDTH: usize = if FULL_LAYOUT_FITS { 41 } else { WIDTH };
 }
 
+/// Whether the panel fits the full layout made for the Galactic Unicorn, with the seconds and the
+/// calendar box beside the time. Smaller panels stack the hours above the minutes instead.
+const FULL_LAYOUT_FITS: bool = WIDTH >= 53 && HEIGHT >= 11;
+
 impl ClockApp {
+    /// How far the rainbow moves across the text each second, as a fraction of the text width.
+    const RAINBOW_SPEED: f32 = 0.2;
+
+    /// How long the calendar box shows the date, then the temperature.
+    const CALENDAR_ALTERNATE_SECS: u32 = 5;
+
+    /// How long a temperature is shown for without an update, so a stale value is not shown forever.
+    const TEMPERATURE_MAX_AGE_SECS: u64 = 60 * 60;
+
+    /// How long the hourly chime takes to sweep across the digits.
+    const CHIME_MILLIS: u64 = 1500;
+
+    /// Half the width of the band of the hourly chime.
+    const CHIME_BAND_WIDTH: f32 = 3.0;
+
+    /// Left edge of the calendar box.
+    const CALENDAR_X: i32 = 42;
+
+    /// Width of the calendar box.
+    const CALENDAR_WIDTH: u32 = 11;
+
+    /// Font of the compact time in the ticker layout.
+    const TICKER_CLOCK_FONT: Font = Font::Medium;
+
+    /// Font of the text scrolled beside the time in the ticker layout.
+    const TICKER_TEXT_FONT: Font = Font::Regular;
+
+    /// Gap between the compact time and the ticker text.
+    const TICKER_GAP: i32 = 2;
+
     /// Create the static ref to clock app.
     /// Must only be called once or will panic.
     pub fn new(display: &'static Display, time: &'static Time) -> &'static Self {
@@ -70,9 +212,112 @@ impl ClockApp {
             display,
             time,
             effect: Mutex::new(ClockEffect::Color),
+            temperature: Mutex::new(None),
+            chime: Mutex::new(Chime {
+                enabled: false,
+                start: (8, 0),
+                end: (22, 0),
+            }),
+            format: Mutex::new(HourFormat::TwentyFour),
+            layout: Mutex::new(ClockLayout::Full),
+            ticker: Mutex::new(None),
         })
     }
 
+    /// Turn the hourly chime on or off from an MQTT payload and send the state over MQTT.
+    pub async fn set_chime(&self, text: &str) {
+        match text {
+            "ON" => self.chime.lock().await.enabled = true,
+            "OFF" => self.chime.lock().await.enabled = false,
+            _ => MqttMessage::enqueue_debug("Invalid hourly chime state, expected ON or OFF").await,
+        }
+
+        self.send_mqtt_state().await;
+    }
+
+    /// Set the hours the chime plays in from an MQTT payload in the form `HH:MM-HH:MM`,
+    /// and send the state over MQTT.
+    pub async fn set_chime_hours(&self, text: &str) {
+        let hours = text
+            .split_once('-')
+            .and_then(|(start, end)| Some((parse_time(start).ok()??, parse_time(end).ok()??)));
+
+        match hours {
+            Some((start, end)) => {
+                let mut chime = self.chime.lock().await;
+                chime.start = start;
+                chime.end = end;
+            }
+            None => {
+                MqttMessage::enqueue_debug("Invalid hourly chime hours, expected HH:MM-HH:MM").await
+            }
+        }
+
+        self.send_mqtt_state().await;
+    }
+
+    /// Set how the hours are shown from an MQTT payload of `12h` or `24h`,
+    /// and send the state over MQTT.
+    pub async fn set_format(&self, text: &str) {
+        match HourFormat::from_str(text.trim()) {
+            Ok(format) => *self.format.lock().await = format,
+            Err(_) => MqttMessage::enqueue_debug("Invalid clock format, expected 12h or 24h").await,
+        }
+
+        self.send_mqtt_state().await;
+    }
+
+    /// Set the layout from an MQTT payload of `full` or `ticker`, and send the state over MQTT.
+    pub async fn set_layout(&self, text: &str) {
+        match ClockLayout::from_str(text.trim()) {
+            Ok(layout) => *self.layout.lock().await = layout,
+            Err(_) => {
+                MqttMessage::enqueue_debug("Invalid clock layout, expected full or ticker").await
+            }
+        }
+
+        self.send_mqtt_state().await;
+    }
+
+    /// Set the text scrolled beside the clock in the ticker layout, in `color` or the active color.
+    /// Empty text goes back to showing the date.
+    pub async fn set_ticker(&self, text: &str, color: Option<Rgb888>) {
+        let text = text.trim();
+        *self.ticker.lock().await = match String::try_from(text) {
+            Ok(text) if !text.is_empty() => Some(TickerText { text, color }),
+            Ok(_) => None,
+            Err(_) => {
+                MqttMessage::enqueue_debug("Ticker text too long").await;
+                return;
+            }
+        };
+    }
+
+    /// Set the temperature from a plain number, or JSON with a `temperature` field.
+    /// Anything else, such as `unavailable`, clears the temperature.
+    pub async fn set_temperature(&self, body: &str) {
+        let temperature = json::extract(body, "temperature")
+            .unwrap_or(body)
+            .trim()
+            .trim_matches('"')
+            .parse::<f32>()
+            .ok()
+            .map(|temperature| (temperature, Instant::now()));
+
+        *self.temperature.lock().await = temperature;
+    }
+
+    /// Get the temperature to show, rounded to a whole degree, unless it is too old.
+    /// Falls back to a sensor on the Qw/ST connector when no temperature has been received.
+    async fn get_temperature(&self) -> Option<i32> {
+        self.temperature
+            .lock()
+            .await
+            .filter(|(_, at)| at.elapsed().as_secs() < Self::TEMPERATURE_MAX_AGE_SECS)
+            .map(|(temperature, _)| temperature)
+            .or_else(|| sensors::latest()?.temperature)
+            .map(|temperature| temperature.round() as i32)
+    }
     /// Set the active effect.
     pub async fn set_effect(&self, effect: ClockEffect) {
         *self.effect.lock().await = effect;
@@ -127,13 +372,13 @@ impl ClockApp {
         result
     }
 
-    /// Draw a colon at `x` position.
-    fn draw_colon(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, x: u32) {
+    /// Draw a colon at `x` position in the `color`.
+    fn draw_colon(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, x: u32, color: Rgb888) {
         let x = x as i32;
-        gr.set_pixel(Point { x, y: 3 }, Rgb888::new(100, 100, 100));
-        gr.set_pixel(Point { x, y: 4 }, Rgb888::new(100, 100, 100));
-        gr.set_pixel(Point { x, y: 7 }, Rgb888::new(100, 100, 100));
-        gr.set_pixel(Point { x, y: 8 }, Rgb888::new(100, 100, 100));
+        gr.set_pixel(Point { x, y: 3 }, color);
+        gr.set_pixel(Point { x, y: 4 }, color);
+        gr.set_pixel(Point { x, y: 7 }, color);
+        gr.set_pixel(Point { x, y: 8 }, color);
     }
 
     /// Draw the `num` at the `start` position in the `color`.
@@ -149,39 +394,181 @@ impl ClockApp {
         num_str.as_str().draw(gr, start, color);
     }
 
-    /// Turn hsv color into `Rgb888`.
-    fn from_hsv(h: f32, s: f32, v: f32) -> Rgb888 {
-        let i = (h * 6.0).floor();
-        let f = h * 6.0 - i;
-        let v = v * 255.0;
-        let p = v * (1.0 - s);
-        let q = v * (1.0 - f * s);
-        let t = v * (1.0 - (1.0 - f) * s);
-
-        let i = i.round() % 6.0;
-        if i == 0.0 {
-            return Rgb888::new(v.round() as u8, t.round() as u8, p.round() as u8);
-        } else if i == 1.0 {
-            return Rgb888::new(q.round() as u8, v.round() as u8, p.round() as u8);
-        } else if i == 2.0 {
-            return Rgb888::new(p.round() as u8, v.round() as u8, t.round() as u8);
-        } else if i == 3.0 {
-            return Rgb888::new(p.round() as u8, q.round() as u8, v.round() as u8);
-        } else if i == 4.0 {
-            return Rgb888::new(t.round() as u8, p.round() as u8, v.round() as u8);
-        } else if i == 5.0 {
-            return Rgb888::new(v.round() as u8, p.round() as u8, q.round() as u8);
+    /// Get how far through the hourly chime is, from 0 to 1, or `None` if it is not playing.
+    fn chime_progress(started: Option<Instant>) -> Option<f32> {
+        started
+            .map(|started| started.elapsed().as_millis() as f32 / Self::CHIME_MILLIS as f32)
+            .filter(|progress| *progress < 1.0)
+    }
+
+    /// Brighten the digits under a band sweeping across them, `progress` from 0 to 1 over the sweep.
+    /// Pixels in `colon_color` are left alone.
+    fn draw_chime(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, progress: f32, colon_color: Rgb888) {
+        let band_x = progress * (Self::TEXT_WIDTH as f32 + 2.0 * Self::CHIME_BAND_WIDTH)
+            - Self::CHIME_BAND_WIDTH;
+
+        for x in 0..Self::TEXT_WIDTH as i32 {
+            if (x as f32 - band_x).abs() >= Self::CHIME_BAND_WIDTH {
+                continue;
+            }
+
+            for y in 0..HEIGHT as i32 {
+                let point = Point::new(x, y);
+                if !gr.is_match(point, Rgb888::BLACK) && !gr.is_match(point, colon_color) {
+                    gr.set_pixel(point, Rgb888::WHITE);
+                }
+            }
+        }
+    }
+
+    /// Draw the calendar box with a `header` color strip above `text` in `color`.
+    fn draw_calendar_box(
+        gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+        header: Rgb888,
+        text: &str,
+        color: Rgb888,
+    ) {
+        Rectangle::new(
+            Point {
+                x: Self::CALENDAR_X,
+                y: 3,
+            },
+            Size {
+                height: 8,
+                width: Self::CALENDAR_WIDTH,
+            },
+        )
+        .into_styled(
+            PrimitiveStyleBuilder::new()
+                .fill_color(Rgb888::new(100, 100, 100))
+                .build(),
+        )
+        .draw(gr)
+        .unwrap();
+
+        Rectangle::new(
+            Point {
+                x: Self::CALENDAR_X,
+                y: 0,
+            },
+            Size {
+                height: 3,
+                width: Self::CALENDAR_WIDTH,
+            },
+        )
+        .into_styled(PrimitiveStyleBuilder::new().fill_color(header).build())
+        .draw(gr)
+        .unwrap();
+
+        // values too wide for the box, such as -12, use the tiny font
+        let font = if Font::Medium.text_width(text) <= Self::CALENDAR_WIDTH {
+            Font::Medium
         } else {
-            return Rgb888::new(0, 0, 0);
+            Font::Tiny
+        };
+        let x =
+            Self::CALENDAR_X + (Self::CALENDAR_WIDTH as i32 + 1 - font.text_width(text) as i32) / 2;
+        font.draw(gr, text, x, 6, color);
+    }
+
+    /// Draw the time stacked for panels too small for the full layout, the `hour` above the
+    /// `minute` in the largest font that fits, with the `second` as a bar along the bottom row.
+    fn draw_stacked_layout(
+        gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+        hour: u32,
+        minute: u32,
+        second: u32,
+        color: Rgb888,
+        colon_color: Rgb888,
+    ) {
+        // two rows of digits with a gap between them, above the seconds bar
+        let font = Font::largest_fitting("00", WIDTH as u32, (HEIGHT as u32 - 2) / 2);
+        let height = font.height() as i32;
+        let top = (HEIGHT as i32 - 2 - 2 * height) / 2;
+
+        let mut text = String::<2>::new();
+        for (row, value) in [hour, minute].into_iter().enumerate() {
+            text.clear();
+            let _ = write!(text, "{value:02}");
+            let x = (WIDTH as i32 - font.measure(&text) as i32) / 2;
+            let y = top + row as i32 * (height + 1) + height / 2;
+            font.draw(gr, &text, x, y, color);
+        }
+
+        let width = WIDTH as u32 * second / 60;
+        for x in 0..width as i32 {
+            gr.set_pixel(Point::new(x, HEIGHT as i32 - 1), colon_color);
         }
     }
 
-    /// Generate the rainbow colors needed for the rainbow effect.
-    fn generate_rainbow_colors() -> Vec<Rgb888, { Self::TEXT_WIDTH }> {
+    /// Draw the ticker layout, a compact `hour` and `minute` on the left and the ticker text
+    /// scrolled `offset` pixels across the rest of the display, or the date when there is none.
+    /// Wraps `offset` back to the start once the text has scrolled out of view.
+    async fn draw_ticker_layout(
+        &self,
+        gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+        hour: u32,
+        minute: u32,
+        color: Rgb888,
+        colon_color: Rgb888,
+        offset: &mut f32,
+    ) {
+        let font = Self::TICKER_CLOCK_FONT;
+        let clock_width = font.text_width("00:00") as i32;
+        let ticker_x = clock_width + Self::TICKER_GAP;
+        let ticker_width = WIDTH as i32 - ticker_x;
+        let y = HEIGHT as i32 / 2;
+
+        // draw the ticker first, so the clock can be drawn over anything scrolled past its edge
+        let ticker = self
+            .ticker
+            .lock()
+            .await
+            .as_ref()
+            .map(|ticker| (ticker.text.clone(), ticker.color.unwrap_or(color)));
+        let (text, text_color) = match ticker {
+            Some(ticker) => ticker,
+            None => {
+                let date = self.get_date_str().await;
+                (String::try_from(date.trim()).unwrap_or_default(), color)
+            }
+        };
+
+        let text_font = Self::TICKER_TEXT_FONT;
+        let text_width = text_font.measure(&text) as i32;
+        let x = if text_width <= ticker_width {
+            *offset = 0.0;
+            ticker_x + (ticker_width - text_width) / 2
+        } else {
+            if *offset as i32 > ticker_width + text_width {
+                *offset = 0.0;
+            }
+            WIDTH as i32 - *offset as i32
+        };
+        text_font.draw(gr, &text, x, y, text_color);
+
+        for x in 0..ticker_x {
+            for y in 0..HEIGHT as i32 {
+                gr.set_pixel(Point::new(x, y), Rgb888::BLACK);
+            }
+        }
+
+        let mut time = String::<2>::new();
+        let _ = write!(time, "{hour:02}");
+        font.draw(gr, &time, 0, y, color);
+        let colon_x = font.text_width("00") as i32;
+        font.draw(gr, ":", colon_x, y, colon_color);
+        time.clear();
+        let _ = write!(time, "{minute:02}");
+        font.draw(gr, &time, font.text_width("00:") as i32, y, color);
+    }
+
+    /// Generate the rainbow colors needed for the rainbow effect from `palette`.
+    fn generate_rainbow_colors(palette: Palette) -> Vec<Rgb888, { Self::TEXT_WIDTH }> {
         let mut colors = Vec::<Rgb888, { Self::TEXT_WIDTH }>::new();
 
         for x in 0..Self::TEXT_WIDTH {
-            let color = Self::from_hsv(x as f32 / Self::TEXT_WIDTH as f32, 1.0, 1.0);
+            let color = palette.sample_index(x, Self::TEXT_WIDTH);
             colors.push(color).unwrap();
         }
 
@@ -192,63 +579,111 @@ impl ClockApp {
 impl UnicornApp for ClockApp {
     async fn display(&self) {
         let mut hue_offset: f32 = 0.0;
-        let colors = Self::generate_rainbow_colors();
+        let mut palette = self.display.get_palette().await;
+        let mut colors = Self::generate_rainbow_colors(palette.unwrap_or(Palette::Rainbow));
 
         let mut gr = UnicornGraphics::<WIDTH, HEIGHT>::new();
 
-        let white_style = PrimitiveStyleBuilder::new()
-            .fill_color(Rgb888::new(100, 100, 100))
-            .build();
-        let red_style = PrimitiveStyleBuilder::new().fill_color(Rgb888::RED).build();
+        let mut ticker = FrameTicker::new();
+
+        // the hour the chime was last checked for, so it only starts once at the top of the hour
+        let mut chimed_hour = None;
+        let mut chime_started = None;
+
+        // how far the ticker text has scrolled in the ticker layout
+        let mut ticker_offset: f32 = 0.0;
 
         loop {
             let effect = *self.effect.lock().await;
+            let format = *self.format.lock().await;
+            let layout = *self.layout.lock().await;
 
             let dt = self.time.now().await;
             let hour = dt.time().hour();
             let minute = dt.time().minute();
             let second = dt.time().second();
 
+            if minute == 0 && second == 0 && chimed_hour != Some(hour) {
+                chimed_hour = Some(hour);
+                if self.chime.lock().await.plays_at(hour) {
+                    chime_started = Some(Instant::now());
+                }
+            }
+
             gr.clear_all();
 
             let color = self.display.get_color().await;
 
-            Self::draw_numbers(&mut gr, hour, 0, color);
-            Self::draw_colon(&mut gr, 13);
-            Self::draw_numbers(&mut gr, minute, 14, color);
-            Self::draw_colon(&mut gr, 27);
-            Self::draw_numbers(&mut gr, second, 28, color);
-
-            Rectangle::new(
-                Point { x: 42, y: 3 },
-                Size {
-                    height: 8,
-                    width: 11,
-                },
-            )
-            .into_styled(white_style)
-            .draw(&mut gr)
-            .unwrap();
-
-            Rectangle::new(
-                Point { x: 42, y: 0 },
-                Size {
-                    height: 3,
-                    width: 11,
-                },
-            )
-            .into_styled(red_style)
-            .draw(&mut gr)
-            .unwrap();
-
-            let day = self.get_day_str().await;
-            Text::new(
-                &day,
-                Point { x: 43, y: 9 },
-                MonoTextStyle::new(&FONT_5X7, Rgb888::RED),
-            )
-            .draw(&mut gr)
-            .unwrap();
+            let new_palette = self.display.get_palette().await;
+            if new_palette != palette {
+                palette = new_palette;
+                colors = Self::generate_rainbow_colors(palette.unwrap_or(Palette::Rainbow));
+            }
+
+            // an unsynced time is marked with orange colons
+            let colon_color = if self.time.is_synced() {
+                Rgb888::new(100, 100, 100)
+            } else {
+                Rgb888::CSS_ORANGE
+            };
+
+            // the ticker needs the width of the full layout, so smaller panels show the time alone
+            if layout == ClockLayout::Ticker && FULL_LAYOUT_FITS {
+                self.draw_ticker_layout(
+                    &mut gr,
+                    format.hour(hour),
+                    minute,
+                    color,
+                    colon_color,
+                    &mut ticker_offset,
+                )
+                .await;
+
+                DisplayGraphicsMessage::from_app(gr.get_pixels(), display::frame_duration())
+                    .send_and_replace_queue()
+                    .await;
+
+                let elapsed = ticker.next().await;
+                ticker_offset +=
+                    display::scroll_speed() as f32 * elapsed.as_micros() as f32 / 1_000_000.0;
+                continue;
+            }
+
+            if FULL_LAYOUT_FITS {
+                Self::draw_numbers(&mut gr, format.hour(hour), 0, color);
+                Self::draw_colon(&mut gr, 13, colon_color);
+                Self::draw_numbers(&mut gr, minute, 14, color);
+                Self::draw_colon(&mut gr, 27, colon_color);
+                Self::draw_numbers(&mut gr, second, 28, color);
+
+                // alternate the calendar box between the date and the temperature, when there is one
+                let temperature = self.get_temperature().await;
+                match temperature {
+                    Some(temperature) if second / Self::CALENDAR_ALTERNATE_SECS % 2 == 1 => {
+                        let mut text = String::<4>::new();
+                        let _ = write!(text, "{temperature}");
+                        Self::draw_calendar_box(
+                            &mut gr,
+                            Rgb888::CSS_ORANGE,
+                            &text,
+                            Rgb888::CSS_ORANGE,
+                        );
+                    }
+                    _ => {
+                        let day = self.get_day_str().await;
+                        Self::draw_calendar_box(&mut gr, Rgb888::RED, &day, Rgb888::RED);
+                    }
+                }
+            } else {
+                Self::draw_stacked_layout(
+                    &mut gr,
+                    format.hour(hour),
+                    minute,
+                    second,
+                    color,
+                    colon_color,
+                );
+            }
 
             match effect {
                 ClockEffect::Rainbow => {
@@ -258,6 +693,7 @@ impl UnicornApp for ClockApp {
                                 let point = Point::new(x as i32, y as i32);
                                 if gr.is_match(point, Rgb888::BLACK)
                                     || gr.is_match(point, Rgb888::new(100, 100, 100))
+                                    || gr.is_match(point, colon_color)
                                 {
                                     continue;
                                 }
@@ -267,7 +703,7 @@ impl UnicornApp for ClockApp {
                                         % Self::TEXT_WIDTH as f32)
                                         .round() as usize;
 
-                                if index >= 41 {
+                                if index >= Self::TEXT_WIDTH {
                                     index = 0;
                                 }
                                 let value = colors[index];
@@ -275,16 +711,36 @@ impl UnicornApp for ClockApp {
                             }
                         }
 
-                        hue_offset += 0.01;
+                        if let Some(progress) = Self::chime_progress(chime_started) {
+                            Self::draw_chime(&mut gr, progress, colon_color);
+                        }
+
+                        DisplayGraphicsMessage::from_app(
+                            gr.get_pixels(),
+                            display::frame_duration(),
+                        )
+                        .send_and_replace_queue()
+                        .await;
 
-                        let duration = embassy_time::Duration::from_millis(50);
-                        DisplayGraphicsMessage::from_app(gr.get_pixels(), duration)
-                            .send_and_replace_queue()
-                            .await;
-                        Timer::after(duration).await;
+                        let elapsed = ticker.next().await;
+                        hue_offset +=
+                            Self::RAINBOW_SPEED * elapsed.as_micros() as f32 / 1_000_000.0;
                     }
                 }
                 ClockEffect::Color => {
+                    // animate the chime at the frame rate, then go back to redrawing each second
+                    if let Some(progress) = Self::chime_progress(chime_started) {
+                        Self::draw_chime(&mut gr, progress, colon_color);
+                        DisplayGraphicsMessage::from_app(
+                            gr.get_pixels(),
+                            display::frame_duration(),
+                        )
+                        .send_and_replace_queue()
+                        .await;
+                        ticker.next().await;
+                        continue;
+                    }
+
                     let duration = embassy_time::Duration::from_millis(250);
                     DisplayGraphicsMessage::from_app(gr.get_pixels(), duration)
                         .send_and_replace_queue()
@@ -312,7 +768,14 @@ impl UnicornApp for ClockApp {
                 .send_and_show_now()
                 .await;
             }
-            ButtonPress::Long => {}
+            ButtonPress::Long => {
+                let new = match *self.format.lock().await {
+                    HourFormat::TwentyFour => HourFormat::Twelve,
+                    HourFormat::Twelve => HourFormat::TwentyFour,
+                };
+                *self.format.lock().await = new;
+                self.send_mqtt_state().await;
+            }
             ButtonPress::Double => {
                 let current = *self.effect.lock().await;
                 let new = match current {
@@ -325,7 +788,19 @@ impl UnicornApp for ClockApp {
     }
 
     async fn process_mqtt_message(&self, message: crate::mqtt::MqttReceiveMessage) {
-        if let Ok(effect) = ClockEffect::from_str(&message.body) {
+        if is_temperature_topic(&message.topic) {
+            self.set_temperature(&message.body).await;
+        } else if message.topic == CLOCK_CHIME_SET_TOPIC {
+            self.set_chime(&message.body).await;
+        } else if message.topic == CLOCK_CHIME_HOURS_SET_TOPIC {
+            self.set_chime_hours(&message.body).await;
+        } else if message.topic == CLOCK_FORMAT_SET_TOPIC {
+            self.set_format(&message.body).await;
+        } else if message.topic == CLOCK_LAYOUT_SET_TOPIC {
+            self.set_layout(&message.body).await;
+        } else if message.topic == CLOCK_TICKER_SET_TOPIC {
+            self.set_ticker(&message.body, None).await;
+        } else if let Ok(effect) = ClockEffect::from_str(&message.body) {
             self.set_effect(effect).await;
         }
     }
@@ -334,5 +809,23 @@ impl UnicornApp for ClockApp {
         let effect = *self.effect.lock().await;
         let text = effect.into();
         MqttMessage::enqueue_state(CLOCK_APP_STATE_TOPIC, text).await;
+
+        let chime = *self.chime.lock().await;
+        let state = if chime.enabled { "ON" } else { "OFF" };
+        MqttMessage::enqueue_state(CLOCK_CHIME_STATE_TOPIC, state).await;
+
+        let mut hours = String::<12>::new();
+        let _ = write!(
+            hours,
+            "{:02}:{:02}-{:02}:{:02}",
+            chime.start.0, chime.start.1, chime.end.0, chime.end.1
+        );
+        MqttMessage::enqueue_state(CLOCK_CHIME_HOURS_STATE_TOPIC, &hours).await;
+
+        let format = (*self.format.lock().await).into();
+        MqttMessage::enqueue_state(CLOCK_FORMAT_STATE_TOPIC, format).await;
+
+        let layout = (*self.layout.lock().await).into();
+        MqttMessage::enqueue_state(CLOCK_LAYOUT_STATE_TOPIC, layout).await;
     }
 }
@@ -0,0 +1,307 @@
+//! USB serial console, for recovery and provisioning when the network is unavailable.
+//!
+//! Connect with any serial terminal. Type `help` for the list of commands.
+
+use core::fmt::Write;
+
+use embassy_executor::Spawner;
+use embassy_rp::{
+    bind_interrupts,
+    peripherals::USB,
+    usb::{Driver, InterruptHandler},
+};
+use embassy_time::Instant;
+use embassy_usb::{
+    class::cdc_acm::{CdcAcmClass, State},
+    driver::EndpointError,
+    Builder, Config, UsbDevice,
+};
+use heapless::String;
+use static_cell::make_static;
+
+use crate::{
+    board,
+    config::{USB_PRODUCT_ID, USB_VENDOR_ID},
+    display::Display,
+    logging,
+    network::NetworkState,
+    self_test, settings,
+    storage::Storage,
+    system::{reboot, SystemState},
+};
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => InterruptHandler<USB>;
+});
+
+/// Maximum packet size of the serial endpoints.
+const MAX_PACKET_SIZE: u16 = 64;
+
+/// Maximum length of a command line.
+const LINE_SIZE: usize = 128;
+
+/// Commands listed by `help`.
+const HELP: &str = "commands:\r
+  status                 show the device status\r
+  wifi <ssid> <password> save the wifi network, quote an ssid with spaces\r
+  mqtt <ip> [port]       save the mqtt broker\r
+  brightness <0-255>     set the display brightness\r
+  log level [level]      show or set the log level\r
+  selftest               run the LED self test\r
+  reboot                 restart the device\r
+";
+
+/// The USB serial class used by the console.
+type Serial = CdcAcmClass<'static, Driver<'static, USB>>;
+
+/// Everything the console commands act on.
+struct Console {
+    display: &'static Display<'static>,
+    system_state: &'static SystemState,
+    storage: &'static Storage,
+}
+
+/// Start the USB device and the console task.
+pub fn start(
+    spawner: Spawner,
+    usb: USB,
+    display: &'static Display<'static>,
+    system_state: &'static SystemState,
+    storage: &'static Storage,
+) {
+    let driver = Driver::new(usb, Irqs);
+
+    let mut config = Config::new(USB_VENDOR_ID, USB_PRODUCT_ID);
+    config.manufacturer = Some("Pimoroni");
+    config.product = Some(board::NAME);
+    config.serial_number = Some(env!("CARGO_PKG_VERSION"));
+    config.max_power = 100;
+    config.max_packet_size_0 = MAX_PACKET_SIZE as u8;
+
+    // required for windows to recognise the composite device
+    config.device_class = 0xef;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        make_static!([0; 256]),
+        make_static!([0; 256]),
+        make_static!([0; 256]),
+        &mut [],
+        make_static!([0; 64]),
+    );
+
+    let serial = CdcAcmClass::new(&mut builder, make_static!(State::new()), MAX_PACKET_SIZE);
+    let usb = builder.build();
+
+    let console = Console {
+        display,
+        system_state,
+        storage,
+    };
+
+    spawner.spawn(usb_task(usb)).unwrap();
+    spawner.spawn(console_task(serial, console)).unwrap();
+}
+
+/// Run the USB device.
+#[embassy_executor::task]
+async fn usb_task(mut usb: UsbDevice<'static, Driver<'static, USB>>) -> ! {
+    usb.run().await
+}
+
+/// Read command lines from the serial port and run them.
+#[embassy_executor::task]
+async fn console_task(mut serial: Serial, console: Console) {
+    loop {
+        serial.wait_connection().await;
+        let _ = console.session(&mut serial).await;
+    }
+}
+
+impl Console {
+    /// Handle a connected terminal until it disconnects.
+    async fn session(&self, serial: &mut Serial) -> Result<(), EndpointError> {
+        write_str(
+            serial,
+            "galactic unicorn console, type help for commands\r\n> ",
+        )
+        .await?;
+
+        let mut line = String::<LINE_SIZE>::new();
+        let mut packet = [0; MAX_PACKET_SIZE as usize];
+        let mut last = 0;
+
+        loop {
+            let len = serial.read_packet(&mut packet).await?;
+
+            for &byte in &packet[..len] {
+                let previous = core::mem::replace(&mut last, byte);
+
+                match byte {
+                    // the line feed of a carriage return and line feed pair
+                    b'\n' if previous == b'\r' => {}
+                    b'\r' | b'\n' => {
+                        write_str(serial, "\r\n").await?;
+
+                        let mut output = String::<512>::new();
+                        self.run(line.trim(), &mut output).await;
+                        write_str(serial, &output).await?;
+                        write_str(serial, "> ").await?;
+
+                        line.clear();
+                    }
+                    // backspace and delete
+                    0x08 | 0x7f => {
+                        if line.pop().is_some() {
+                            write_str(serial, "\x08 \x08").await?;
+                        }
+                    }
+                    byte if byte.is_ascii() && !byte.is_ascii_control() => {
+                        if line.push(byte as char).is_ok() {
+                            serial.write_packet(&[byte]).await?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Run a command line, writing the response into `output`.
+    async fn run(&self, line: &str, output: &mut String<512>) {
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        if command.is_empty() {
+            return;
+        }
+
+        if command == "wifi" {
+            match split_ssid(rest) {
+                Some((ssid, password)) => {
+                    let result = settings::update(
+                        self.storage,
+                        &[("wifi_network", ssid), ("wifi_password", password)],
+                    )
+                    .await;
+                    write_saved(output, result);
+                }
+                None => output
+                    .push_str("usage: wifi <ssid> <password>\r\n")
+                    .unwrap(),
+            }
+            return;
+        }
+
+        let mut args = rest.split_whitespace();
+        match (command, args.next(), args.next()) {
+            ("help", _, _) => output.push_str(HELP).unwrap(),
+            ("status", _, _) => self.status(output).await,
+            ("mqtt", Some(ip), port) => {
+                let result = match port {
+                    Some(port) => {
+                        settings::update(self.storage, &[("mqtt_broker", ip), ("mqtt_port", port)])
+                            .await
+                    }
+                    None => settings::update(self.storage, &[("mqtt_broker", ip)]).await,
+                };
+                write_saved(output, result);
+            }
+            ("brightness", Some(value), _) => match value.parse::<u8>() {
+                Ok(brightness) => {
+                    self.display.set_brightness(brightness).await;
+                    let _ = write!(output, "brightness set to {brightness}\r\n");
+                }
+                Err(_) => output.push_str("brightness must be 0-255\r\n").unwrap(),
+            },
+            ("log", Some("level"), level) => {
+                if let Some(level) = level {
+                    logging::set_level(level).await;
+                }
+                let level: &'static str = logging::level().into();
+                let _ = write!(output, "log level is {level}\r\n");
+            }
+            ("selftest", _, _) => {
+                self_test::run().await;
+                output.push_str("self test complete\r\n").unwrap();
+            }
+            ("reboot", _, _) => {
+                logging::info!("Reboot requested from the console");
+                reboot::restart().await;
+            }
+            _ => {
+                let _ = write!(
+                    output,
+                    "unknown command: {line}\r\ntype help for commands\r\n"
+                );
+            }
+        }
+    }
+
+    /// Write the device status into `output`.
+    async fn status(&self, output: &mut String<512>) {
+        let settings = settings::get();
+        let network = match self.system_state.get_network_state().await {
+            NetworkState::NotInitialised => "not initialised",
+            NetworkState::Connected => "connected",
+            NetworkState::Offline => "offline",
+            NetworkState::Error(error) => error.code(),
+        };
+        let [a1, a2, a3, a4] = settings.ip;
+        let level: &'static str = logging::level().into();
+
+        let _ = write!(
+            output,
+            "version:    {}\r\nuptime:     {}s\r\nboot stage: {}\r\nnetwork:    {network}\r\nwifi:       {}\r\nip:         {a1}.{a2}.{a3}.{a4}\r\ndevice id:  {}\r\nbase topic: {}\r\nbrightness: {}\r\nlog level:  {level}\r\n",
+            env!("CARGO_PKG_VERSION"),
+            Instant::now().as_secs(),
+            self.system_state.get_boot_stage().await.text(),
+            settings.wifi_network,
+            settings.device_id,
+            settings.base_topic,
+            self.display.get_brightness().await,
+        );
+
+        if let Some(err) = self.system_state.get_last_error().await {
+            let _ = write!(output, "last error: {err}\r\n");
+        }
+    }
+}
+
+/// Split the arguments of the wifi command into the ssid and password.
+///
+/// The ssid is the first word, or everything between double quotes when it starts with one. The
+/// password is the rest of the line, so it may contain spaces.
+fn split_ssid(args: &str) -> Option<(&str, &str)> {
+    let args = args.trim_start();
+    let (ssid, password) = match args.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => args.split_once(' ')?,
+    };
+    let password = password.trim_start();
+
+    if ssid.is_empty() || password.is_empty() {
+        return None;
+    }
+
+    Some((ssid, password))
+}
+
+/// Write the result of saving settings into `output`.
+fn write_saved(output: &mut String<512>, result: Result<(), settings::SettingsError>) {
+    let _ = match result {
+        Ok(_) => write!(output, "saved, reboot to apply\r\n"),
+        Err(err) => write!(output, "not saved: {err}\r\n"),
+    };
+}
+
+/// Write `text` to the serial port, split into packets.
+async fn write_str(serial: &mut Serial, text: &str) -> Result<(), EndpointError> {
+    for chunk in text.as_bytes().chunks(MAX_PACKET_SIZE as usize) {
+        serial.write_packet(chunk).await?;
+    }
+
+    Ok(())
+}
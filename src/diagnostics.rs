@@ -0,0 +1,164 @@
+//! Memory, queue and frame diagnostics, reported on demand to catch near overflows before they
+//! crash and to measure the cost of new effects on the device.
+//!
+//! There is no heap, and every embassy task on core 0 runs on the one main stack, so the stack
+//! high-water mark covers all of them. Core 1 only runs the display queue, on its own stack.
+//! The stack is painted at boot and the deepest overwritten word gives the most stack ever used.
+
+use core::{fmt::Write, ptr::addr_of};
+
+use embassy_time::{Duration, Instant};
+use heapless::String;
+use portable_atomic::{AtomicU32, AtomicU64, Ordering};
+
+use crate::{
+    display, logging,
+    mqtt::{self, topics::DIAG_STATE_TOPIC, MqttMessage},
+};
+
+/// Pattern written over the unused stack at boot.
+const STACK_PAINT: u32 = 0xcccc_cccc;
+
+/// Space left unpainted below the stack pointer when painting, for interrupts that fire meanwhile.
+const PAINT_MARGIN: usize = 256;
+
+extern "C" {
+    /// Start of `.data`, the first statically allocated RAM. Provided by `cortex-m-rt`.
+    static __sdata: u32;
+
+    /// End of the statically allocated RAM, which is the lowest address the stack can reach.
+    static __sheap: u32;
+
+    /// Initial stack pointer, which is the top of RAM.
+    static _stack_start: u32;
+}
+
+/// Times a log line was dropped because the log queue was full.
+pub static LOG_DROPPED: Counter = Counter::new();
+
+/// Times a display message was dropped because the display queue was full.
+pub static DISPLAY_DROPPED: Counter = Counter::new();
+
+/// Times an MQTT message had to wait for a free message in the pool.
+pub static MQTT_POOL_WAITS: Counter = Counter::new();
+
+/// Frames sent to the panel since boot. Frames the same as the last are skipped, so are not counted.
+static FRAMES: Counter = Counter::new();
+
+/// Longest a frame took to compose and send to the panel since the last report, in microseconds.
+static WORST_FRAME_MICROS: AtomicU32 = AtomicU32::new(0);
+
+/// Frames sent to the panel by the last report.
+static REPORTED_FRAMES: AtomicU32 = AtomicU32::new(0);
+
+/// Uptime in milliseconds at the last report.
+static REPORTED_AT_MILLIS: AtomicU64 = AtomicU64::new(0);
+
+/// Record a frame sent to the panel, which took `duration` to compose and send.
+pub fn record_frame(duration: Duration) {
+    FRAMES.increment();
+    WORST_FRAME_MICROS.fetch_max(duration.as_micros() as u32, Ordering::Relaxed);
+}
+
+/// Get the frames per second sent to the panel since the last report,
+/// and the longest a frame took to compose and send in microseconds, then start measuring again.
+fn take_frame_stats() -> (f32, u32) {
+    let frames = FRAMES.get();
+    let now = Instant::now().as_millis();
+
+    let frames = frames.wrapping_sub(REPORTED_FRAMES.swap(frames, Ordering::Relaxed));
+    let elapsed = now.saturating_sub(REPORTED_AT_MILLIS.swap(now, Ordering::Relaxed));
+    let fps = if elapsed > 0 {
+        frames as f32 * 1000.0 / elapsed as f32
+    } else {
+        0.0
+    };
+
+    (fps, WORST_FRAME_MICROS.swap(0, Ordering::Relaxed))
+}
+
+/// A count of saturation events, safe to increment from any task.
+pub struct Counter(AtomicU32);
+
+impl Counter {
+    /// Create a new counter at zero.
+    const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    /// Add one to the counter.
+    pub fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get the current count.
+    pub fn get(&self) -> u32 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Get the bottom of the stack, the top of the stack and the start of static RAM.
+fn memory_bounds() -> (usize, usize, usize) {
+    // SAFETY: only the addresses of the linker symbols are taken, they are never read.
+    unsafe {
+        (
+            addr_of!(__sheap) as usize,
+            addr_of!(_stack_start) as usize,
+            addr_of!(__sdata) as usize,
+        )
+    }
+}
+
+/// Paint the unused stack with `STACK_PAINT`.
+/// Must be called once, as early in `main` as possible.
+pub fn paint_stack() {
+    let (bottom, _, _) = memory_bounds();
+    let end = cortex_m::register::msp::read() as usize - PAINT_MARGIN;
+
+    let mut address = bottom;
+    while address < end {
+        // SAFETY: the region is between the end of static RAM and below the current
+        // stack pointer, so it holds nothing live.
+        unsafe { (address as *mut u32).write_volatile(STACK_PAINT) };
+        address += 4;
+    }
+}
+
+/// Get the most stack used since boot, in bytes.
+fn stack_high_water() -> usize {
+    let (bottom, top, _) = memory_bounds();
+
+    let mut address = bottom;
+    // SAFETY: every address read is word aligned RAM inside the stack region.
+    while address < top && unsafe { (address as *const u32).read_volatile() } == STACK_PAINT {
+        address += 4;
+    }
+
+    top - address
+}
+
+/// Send a diagnostics report over MQTT.
+pub async fn send_report() {
+    let (bottom, top, static_start) = memory_bounds();
+    let stack_size = top - bottom;
+    let stack_used = stack_high_water();
+    let (pool_used, pool_size, send_queued) = mqtt::send_queue_usage();
+    let (mqtt_queued, app_queued) = display::queue_lengths();
+    let (fps, worst_frame_micros) = take_frame_stats();
+
+    let mut report = String::<512>::new();
+    write!(
+        report,
+        r#"{{"static_ram":{},"stack_size":{stack_size},"stack_used":{stack_used},"stack_free":{},"mqtt_pool_used":{pool_used},"mqtt_pool_size":{pool_size},"mqtt_send_queued":{send_queued},"mqtt_pool_waits":{},"display_mqtt_queued":{mqtt_queued},"display_app_queued":{app_queued},"display_dropped":{},"display_fps":{fps:.1},"display_target_fps":{},"display_frame_us_max":{worst_frame_micros},"log_queued":{},"log_dropped":{}}}"#,
+        bottom - static_start,
+        stack_size - stack_used,
+        MQTT_POOL_WAITS.get(),
+        DISPLAY_DROPPED.get(),
+        display::target_fps(),
+        logging::queue_length(),
+        LOG_DROPPED.get(),
+    )
+    .unwrap();
+
+    MqttMessage::enqueue_state(DIAG_STATE_TOPIC, &report).await;
+}
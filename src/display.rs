@@ -1,63 +1,398 @@
-use core::{cell::RefCell, fmt::Write};
-use embassy_executor::Spawner;
+use chrono::Timelike;
+use core::{
+    cell::{Cell, RefCell},
+    fmt::Write,
+    ptr::addr_of_mut,
+    str::FromStr,
+};
+use embassy_executor::{Executor, Spawner};
 use embassy_futures::select::{select, Either};
-use embassy_rp::peripherals::{ADC, DMA_CH0, PIO0};
+use embassy_rp::{
+    multicore::{spawn_core1, Stack},
+    peripherals::{ADC, CORE1, DMA_CH0, PIO0},
+};
 use embassy_sync::{
-    blocking_mutex::raw::ThreadModeRawMutex,
+    blocking_mutex::{
+        raw::{CriticalSectionRawMutex, ThreadModeRawMutex},
+        Mutex as BlockingMutex,
+    },
     channel::Channel,
     mutex::Mutex,
     pubsub::{PubSubChannel, Subscriber},
     signal::Signal,
 };
-use embassy_time::{Instant, Timer};
-use embedded_graphics::{
-    mono_font::{ascii::FONT_6X10, MonoTextStyle},
-    pixelcolor::RgbColor,
-    text::{Alignment, Baseline, Text},
-};
-use embedded_graphics_core::{
-    geometry::Point,
-    pixelcolor::{Rgb888, WebColors},
-    Drawable,
-};
-use galactic_unicorn_embassy::{
-    pins::{UnicornDisplayPins, UnicornSensorPins},
-    GalacticUnicorn, HEIGHT, WIDTH,
-};
-use heapless::String;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::{geometry::Point, pixelcolor::RgbColor};
+use embedded_graphics_core::pixelcolor::{Rgb888, WebColors};
+use heapless::{String, Vec};
 use messages::{DisplayGraphicsMessage, DisplayMessage, DisplayTextMessage};
-use static_cell::make_static;
-use unicorn_graphics::UnicornGraphics;
+use micromath::F32Ext;
+use portable_atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use static_cell::{make_static, StaticCell};
+use unicorn_graphics::{UnicornGraphics, UnicornGraphicsPixels};
 
 use crate::{
-    buttons::{self, BRIGHTNESS_DOWN_PRESS, BRIGHTNESS_UP_PRESS},
+    alert,
+    board::{Unicorn, UnicornDisplayPins, UnicornSensorPins, HEIGHT, WIDTH},
+    buttons::{
+        self, BRIGHTNESS_DOWN_PRESS, BRIGHTNESS_UP_PRESS, VOLUME_DOWN_PRESS, VOLUME_UP_PRESS,
+    },
+    diagnostics,
+    fonts::ScrollDirection,
+    graphics::{
+        self,
+        colors::Rgb888Str,
+        layers::{Layer, LayerStack},
+        palettes::Palette,
+    },
+    image, json,
     mqtt::{
         topics::{
-            AUTO_BRIGHTNESS_SET_TOPIC, AUTO_BRIGHTNESS_STATE_TOPIC, BRIGHTNESS_SET_TOPIC,
-            BRIGHTNESS_STATE_TOPIC, RGB_SET_TOPIC, RGB_STATE_TOPIC,
+            ALERT_SET_TOPIC, AUTO_BRIGHTNESS_SET_TOPIC, AUTO_BRIGHTNESS_STATE_TOPIC,
+            BRIGHTNESS_SET_TOPIC, BRIGHTNESS_STATE_TOPIC, DISPLAY_ACK_TOPIC, FPS_SET_TOPIC,
+            FPS_STATE_TOPIC, IMAGE_URL_SET_TOPIC, LAYER_OPACITY_SET_TOPIC,
+            LAYER_OPACITY_STATE_TOPIC, LIGHT_LEVEL_STATE_TOPIC, LIGHT_SET_TOPIC, LIGHT_STATE_TOPIC,
+            PALETTE_SET_TOPIC, PALETTE_STATE_TOPIC, PENDING_INDICATOR_SET_TOPIC,
+            PENDING_INDICATOR_STATE_TOPIC, RGB_SET_TOPIC, RGB_STATE_TOPIC,
+            SCROLL_DIRECTION_SET_TOPIC, SCROLL_DIRECTION_STATE_TOPIC, SCROLL_SPEED_SET_TOPIC,
+            SCROLL_SPEED_STATE_TOPIC, STREAM_TOPIC, WARMTH_SET_TOPIC, WARMTH_STATE_TOPIC,
         },
         MqttMessage, MqttReceiveMessage,
     },
+    pending, self_test,
+    storage::Storage,
+    supervisor::{self, Task},
+    time::Time,
 };
 
+/// Size of the stack for core 1, which only runs the display queue.
+const CORE1_STACK_SIZE: usize = 16 * 1024;
+
+/// Stack for core 1.
+static mut CORE1_STACK: Stack<CORE1_STACK_SIZE> = Stack::new();
+
+/// Executor for core 1, dedicated to the display queue and frame composition
+/// so heavy work on core 0 does not stutter scrolling text.
+static CORE1_EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+// The display channels and signals below are shared between the cores,
+// so they use `CriticalSectionRawMutex` which is safe across cores on the RP2040.
+
 /// Channel for color changes to be published into.
-static CHANGE_COLOR_CHANNEL: PubSubChannel<ThreadModeRawMutex, Rgb888, 1, 2, 1> =
+static CHANGE_COLOR_CHANNEL: PubSubChannel<CriticalSectionRawMutex, Rgb888, 1, 2, 1> =
     PubSubChannel::new();
 
 /// Channel for display message that will interrupt anything on the display.
-static INTERRUPT_DISPLAY_CHANNEL: Channel<ThreadModeRawMutex, DisplayMessage, 1> = Channel::new();
+static INTERRUPT_DISPLAY_CHANNEL: Channel<CriticalSectionRawMutex, DisplayMessage, 1> =
+    Channel::new();
 
 /// Channel for messages from MQTT.
-static MQTT_DISPLAY_CHANNEL: Channel<ThreadModeRawMutex, DisplayMessage, 8> = Channel::new();
+static MQTT_DISPLAY_CHANNEL: Channel<CriticalSectionRawMutex, DisplayMessage, 8> = Channel::new();
 
 /// Channel for messages from apps.
-static APP_DISPLAY_CHANNEL: Channel<ThreadModeRawMutex, DisplayMessage, 8> = Channel::new();
+static APP_DISPLAY_CHANNEL: Channel<CriticalSectionRawMutex, DisplayMessage, 8> = Channel::new();
+
+/// An MQTT message that has been shown in full.
+struct DisplayAck {
+    /// The id given by the sender, if any.
+    id: Option<String<32>>,
+
+    /// When the message was first shown.
+    shown: Instant,
+
+    /// The number the message was kept until shown with, if any.
+    pending: Option<u32>,
+}
+
+/// How long the last stream frame stays on the display once frames stop arriving.
+const STREAM_HOLD_MILLIS: u64 = 2000;
+
+/// Channel for acknowledging shown MQTT messages, from the display queue on core 1 to core 0.
+static ACK_CHANNEL: Channel<CriticalSectionRawMutex, DisplayAck, 4> = Channel::new();
+
+/// Get the number of messages waiting in the MQTT and app display queues.
+pub fn queue_lengths() -> (usize, usize) {
+    (MQTT_DISPLAY_CHANNEL.len(), APP_DISPLAY_CHANNEL.len())
+}
+
+/// Seconds between publishing the ambient light level.
+const LIGHT_LEVEL_INTERVAL_SECS: u64 = 60;
+
+/// Highest reading of the 12-bit ambient light sensor.
+const MAX_LIGHT_LEVEL: u32 = 4095;
+
+/// Shortest time between publishing brightness changes made by auto brightness.
+const AUTO_BRIGHTNESS_STATE_INTERVAL_SECS: u64 = 30;
+
+/// Signal that auto brightness has changed the brightness, which is yet to be published.
+static AUTO_BRIGHTNESS_CHANGED: Signal<ThreadModeRawMutex, bool> = Signal::new();
 
 /// Signal for auto light feature enable/disable.
 static AUTO_LIGHT_SIGNAL: Signal<ThreadModeRawMutex, bool> = Signal::new();
 
+/// How long a level bar stays shown after the level last changed.
+const LEVEL_BAR_MILLIS: u64 = 1500;
+
+/// Height of a level bar, drawn along the bottom of the display.
+const LEVEL_BAR_HEIGHT: usize = 2;
+
+/// Color of the part of a level bar above the level.
+const LEVEL_BAR_TRACK_COLOR: Rgb888 = Rgb888::new(25, 25, 25);
+
+/// Signal to show a level bar over the display, with the level out of 255 and the color to fill it in.
+static LEVEL_BAR: Signal<ThreadModeRawMutex, (u8, Rgb888)> = Signal::new();
+
+/// Briefly show a bar over the bottom of the display filled to `level` out of 255, for feedback
+/// whilst a setting such as the brightness is adjusted with the buttons.
+pub fn show_level_bar(level: u8, color: Rgb888) {
+    LEVEL_BAR.signal((level, color));
+}
+
 /// Signal for stopping the display message, ready for the next one.
-pub static STOP_CURRENT_DISPLAY: Signal<ThreadModeRawMutex, bool> = Signal::new();
+pub static STOP_CURRENT_DISPLAY: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+
+/// Signal for clearing sticky messages from the display queue.
+static CLEAR_STICKY: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+
+/// Is the current display message sticky.
+static STICKY_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Reasons the panel is blanked, as bits from `BlankReason`.
+static BLANKED: AtomicU8 = AtomicU8::new(0);
+
+/// Reasons for blanking the panel. The panel stays blanked until every reason has cleared.
+#[derive(Clone, Copy)]
+pub enum BlankReason {
+    /// It is quiet hours.
+    QuietHours = 0b01,
+
+    /// Nobody is home.
+    Away = 0b10,
+}
+
+/// Is the current display message shown whilst the panel is blanked.
+/// Interrupts and MQTT messages are, as they are only queued when they should be seen.
+static SHOWN_WHEN_BLANKED: AtomicBool = AtomicBool::new(false);
+
+/// Is the panel forced to full brightness, such as for an alert.
+static FULL_BRIGHTNESS: AtomicBool = AtomicBool::new(false);
+
+/// Check if a sticky message is currently on the display.
+pub fn is_sticky_shown() -> bool {
+    STICKY_SHOWN.load(Ordering::Relaxed)
+}
+
+/// Clear any sticky messages, stopping the current message if it is sticky.
+pub fn clear_sticky() {
+    if STICKY_SHOWN.swap(false, Ordering::Relaxed) {
+        STOP_CURRENT_DISPLAY.signal(true);
+    }
+
+    CLEAR_STICKY.signal(true);
+}
+
+/// Default target frame rate for animations and scrolling text.
+pub const DEFAULT_FPS: u8 = 30;
+
+/// Highest target frame rate that can be set.
+pub const MAX_FPS: u8 = 120;
+
+/// Default speed of scrolling text in pixels per second.
+pub const DEFAULT_SCROLL_SPEED: u8 = 50;
+
+/// Slowest speed of scrolling text that can be set, in pixels per second.
+pub const MIN_SCROLL_SPEED: u8 = 10;
+
+/// Fastest speed of scrolling text that can be set, in pixels per second.
+pub const MAX_SCROLL_SPEED: u8 = 150;
+
+/// How much a press of a volume button changes the scroll speed, in pixels per second.
+const SCROLL_SPEED_STEP: u8 = 10;
+
+/// Color of the level bar shown whilst the scroll speed is changed with the volume buttons.
+const SCROLL_SPEED_BAR_COLOR: Rgb888 = Rgb888::CSS_DEEP_SKY_BLUE;
+
+/// Speed of scrolling text in pixels per second.
+static SCROLL_SPEED: AtomicU8 = AtomicU8::new(DEFAULT_SCROLL_SPEED);
+
+/// The target frame rate.
+static TARGET_FPS: AtomicU8 = AtomicU8::new(DEFAULT_FPS);
+
+/// The direction text scrolls in when a message does not choose one.
+static SCROLL_DIRECTION: BlockingMutex<CriticalSectionRawMutex, Cell<ScrollDirection>> =
+    BlockingMutex::new(Cell::new(ScrollDirection::RightToLeft));
+
+/// Get the direction text scrolls in when a message does not choose one.
+pub fn scroll_direction() -> ScrollDirection {
+    SCROLL_DIRECTION.lock(|direction| direction.get())
+}
+
+/// Show a pixel in the corner for each MQTT message waiting behind the one on the display.
+static PENDING_INDICATOR: AtomicBool = AtomicBool::new(true);
+
+/// The number of waiting messages the pending indicator showed when last drawn.
+static PENDING_SHOWN: AtomicU8 = AtomicU8::new(0);
+
+/// The most waiting messages the pending indicator shows.
+const MAX_PENDING_SHOWN: usize = 4;
+
+/// Color of the pending indicator, dim so it does not distract from the message.
+const PENDING_INDICATOR_COLOR: Rgb888 = Rgb888::new(40, 40, 40);
+
+/// Get the number of waiting messages the pending indicator should show.
+fn pending_to_show() -> u8 {
+    if PENDING_INDICATOR.load(Ordering::Relaxed) {
+        MQTT_DISPLAY_CHANNEL.len().min(MAX_PENDING_SHOWN) as u8
+    } else {
+        0
+    }
+}
+
+/// Seconds the display output must stay the same before the display is idle.
+const IDLE_AFTER_SECS: u32 = 60;
+
+/// Frame rate whilst the display is idle.
+const IDLE_FPS: u8 = 5;
+
+/// Minutes past midnight the display starts to warm in the evening.
+const WARMTH_EVENING_START_MINS: u32 = 17 * 60;
+
+/// Minutes past midnight the display is fully warm in the evening.
+const WARMTH_EVENING_FULL_MINS: u32 = 20 * 60;
+
+/// Minutes past midnight the display starts to cool in the morning.
+const WARMTH_MORNING_START_MINS: u32 = 5 * 60;
+
+/// Minutes past midnight the display is neutral again in the morning.
+const WARMTH_MORNING_END_MINS: u32 = 7 * 60;
+
+/// How often to update the warmth as the time of day changes.
+const WARMTH_CHECK_INTERVAL_SECS: u64 = 60;
+
+/// Strength of the evening warmth as a percentage, where 0 turns it off.
+static WARMTH: AtomicU8 = AtomicU8::new(0);
+
+/// Warmth applied to each frame now, out of 255, from the strength and the time of day.
+static WARMTH_NOW: AtomicU8 = AtomicU8::new(0);
+
+/// Signals the warmth strength has changed, so the warmth applied is updated straight away.
+static WARMTH_CHANGED: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// Longest a brightness or color transition can take.
+const MAX_TRANSITION_SECS: f32 = 60.0;
+
+/// A change of brightness or color, ramped over a duration.
+struct Transition {
+    /// The brightness to end at, or `None` to leave it.
+    brightness: Option<u8>,
+
+    /// The color to end at, or `None` to leave it.
+    color: Option<Rgb888>,
+
+    /// How long to take.
+    duration: Duration,
+}
+
+/// Signal to start a transition, replacing any transition in progress.
+static TRANSITION: Signal<ThreadModeRawMutex, Transition> = Signal::new();
+
+/// Get the transition time from a JSON light command such as `{"brightness": 40, "transition": 2}`,
+/// in seconds as sent by Home Assistant.
+fn parse_transition(body: &str) -> Option<Duration> {
+    let secs = json::extract(body, "transition")?.parse::<f32>().ok()?;
+    (secs > 0.0).then(|| Duration::from_millis((secs.min(MAX_TRANSITION_SECS) * 1000.0) as u64))
+}
+
+/// Get the color from a JSON light command, either a color string or an object of `r`, `g` and `b`.
+fn parse_light_color(body: &str) -> Option<Rgb888> {
+    let color = json::extract(body, "color")?;
+    if !color.starts_with('{') {
+        return Rgb888Str::from_str(color)
+            .ok()
+            .map(|Rgb888Str(color)| color);
+    }
+
+    let channel = |name| json::extract(color, name)?.parse::<u8>().ok();
+    Some(Rgb888::new(channel("r")?, channel("g")?, channel("b")?))
+}
+
+/// Get how warm the display should be at `minutes` past midnight, where 0.0 is neutral during
+/// the day and 1.0 is fully warm at night, easing between the two in the evening and morning.
+fn warmth_of_day(minutes: u32) -> f32 {
+    let ease = |from: u32, to: u32| (minutes - from) as f32 / (to - from) as f32;
+
+    if minutes >= WARMTH_EVENING_FULL_MINS || minutes < WARMTH_MORNING_START_MINS {
+        1.0
+    } else if minutes >= WARMTH_EVENING_START_MINS {
+        ease(WARMTH_EVENING_START_MINS, WARMTH_EVENING_FULL_MINS)
+    } else if minutes >= WARMTH_MORNING_END_MINS {
+        0.0
+    } else {
+        1.0 - ease(WARMTH_MORNING_START_MINS, WARMTH_MORNING_END_MINS)
+    }
+}
+
+/// Uptime in seconds when the display output last changed.
+static OUTPUT_CHANGED_AT: AtomicU32 = AtomicU32::new(0);
+
+/// Get the speed of scrolling text in pixels per second.
+pub fn scroll_speed() -> u8 {
+    SCROLL_SPEED.load(Ordering::Relaxed)
+}
+
+/// Get the target frame rate.
+pub fn target_fps() -> u8 {
+    TARGET_FPS.load(Ordering::Relaxed)
+}
+
+/// Check if the display output has stayed the same for `IDLE_AFTER_SECS`,
+/// such as when the display is off or showing a static frame.
+pub fn is_idle() -> bool {
+    let changed_at = OUTPUT_CHANGED_AT.load(Ordering::Relaxed);
+    (Instant::now().as_secs() as u32).saturating_sub(changed_at) >= IDLE_AFTER_SECS
+}
+
+/// Get the duration of one frame at the target frame rate,
+/// or at the lower `IDLE_FPS` whilst the display is idle to save power.
+pub fn frame_duration() -> Duration {
+    let fps = if is_idle() {
+        target_fps().min(IDLE_FPS)
+    } else {
+        target_fps()
+    };
+
+    Duration::from_hz(fps as u64)
+}
+
+/// Paces a loop to the target frame rate.
+///
+/// Every ticker waits for the same frame boundaries, so apps and the scrolling text
+/// advance together rather than each sleeping for its own interval.
+pub struct FrameTicker {
+    /// When the last frame started.
+    last_frame: Instant,
+}
+
+impl FrameTicker {
+    /// Create a new frame ticker.
+    pub fn new() -> Self {
+        Self {
+            last_frame: Instant::now(),
+        }
+    }
+
+    /// Wait for the next frame boundary.
+    /// Returns the time since the previous frame, for animating at a steady speed.
+    pub async fn next(&mut self) -> Duration {
+        let period = frame_duration().as_ticks();
+        let next_frame = Instant::from_ticks((Instant::now().as_ticks() / period + 1) * period);
+        Timer::at(next_frame).await;
+
+        let elapsed = next_frame - self.last_frame;
+        self.last_frame = next_frame;
+        elapsed
+    }
+}
 
 /// Auto brightness handler.
 struct AutoBrightness {
@@ -71,6 +406,9 @@ impl AutoBrightness {
     /// Default check interval
     const DEFAULT_DURATION: u64 = 2;
 
+    /// Check interval whilst the display is idle.
+    const IDLE_DURATION: u64 = 30;
+
     /// Create a new auto brightness.
     fn new() -> Self {
         Self {
@@ -103,25 +441,44 @@ impl AutoBrightness {
     /// Check if the minimum duration for next auto light update has passed.
     /// This can be true even if `enabled` is false.
     fn has_min_duration_passed(&self) -> bool {
-        Instant::now().duration_since(self.last_check).as_secs() > self.next_check_interval
+        let interval = if is_idle() {
+            self.next_check_interval.max(Self::IDLE_DURATION)
+        } else {
+            self.next_check_interval
+        };
+
+        Instant::now().duration_since(self.last_check).as_secs() > interval
     }
 }
 
 /// Galactic unicorn display.
 pub struct Display<'a> {
-    /// The galactic unicorn board core.
-    galactic_unicorn: Mutex<ThreadModeRawMutex, GalacticUnicorn<'a>>,
+    /// The unicorn board core, for whichever board the firmware is built for.
+    galactic_unicorn: Mutex<CriticalSectionRawMutex, Unicorn<'a>>,
 
-    /// The current graphics being displayed.
-    current_graphics: Mutex<ThreadModeRawMutex, UnicornGraphics<WIDTH, HEIGHT>>,
+    /// The layers composited into the graphics being displayed.
+    layers: Mutex<CriticalSectionRawMutex, LayerStack>,
 
     /// The current active color.
-    current_color: Mutex<ThreadModeRawMutex, Rgb888>,
+    current_color: Mutex<CriticalSectionRawMutex, Rgb888>,
+
+    /// The palette effects and apps draw colors from, or `None` for each to use its own.
+    current_palette: Mutex<CriticalSectionRawMutex, Option<Palette>>,
+
+    /// The effect reported in the home assistant light state, or `None` without the effects app.
+    light_effect: Mutex<CriticalSectionRawMutex, Option<&'static str>>,
+
+    /// The frame and brightness last sent to the board, to skip redrawing the same output.
+    last_output: Mutex<CriticalSectionRawMutex, Option<(UnicornGraphicsPixels<WIDTH, HEIGHT>, u8)>>,
 
-    /// Is auto brightness enabled.
+    /// Is auto brightness enabled. Only used from core 0.
     auto_brightness: RefCell<AutoBrightness>,
 }
 
+// SAFETY: every field used by the display queue on core 1 is behind a `CriticalSectionRawMutex`,
+// which is safe across cores. `auto_brightness` is only used by tasks on core 0.
+unsafe impl Sync for Display<'_> {}
+
 impl<'a> Display<'a> {
     /// Create the static ref to display.
     /// Must only be called once or will panic.
@@ -131,26 +488,37 @@ impl<'a> Display<'a> {
         adc: ADC,
         display_pins: UnicornDisplayPins,
         sensor_pins: UnicornSensorPins,
+        core1: CORE1,
         spawner: Spawner,
     ) -> &'static Self {
         let display = make_static!(Self {
-            galactic_unicorn: Mutex::new(GalacticUnicorn::new(
-                pio,
-                display_pins,
-                sensor_pins,
-                adc,
-                dma
-            )),
-            current_graphics: Mutex::new(UnicornGraphics::new()),
+            galactic_unicorn: Mutex::new(Unicorn::new(pio, display_pins, sensor_pins, adc, dma)),
+            layers: Mutex::new(LayerStack::new()),
             current_color: Mutex::new(Rgb888::CSS_PURPLE),
+            current_palette: Mutex::new(None),
+            light_effect: Mutex::new(None),
+            last_output: Mutex::new(None),
             auto_brightness: RefCell::new(AutoBrightness::new()),
         });
 
-        spawner.spawn(process_display_queue_task(display)).unwrap();
+        // SAFETY: the stack is only handed to core 1 here, and `new` must only be called once.
+        let core1_stack = unsafe { &mut *addr_of_mut!(CORE1_STACK) };
+        spawn_core1(core1, core1_stack, move || {
+            let executor = CORE1_EXECUTOR.init(Executor::new());
+            executor.run(|spawner| spawner.spawn(process_display_queue_task(display)).unwrap());
+        });
+
         spawner
             .spawn(process_brightness_buttons_task(display))
             .unwrap();
+        spawner.spawn(process_volume_buttons_task(display)).unwrap();
+        spawner.spawn(transition_task(display)).unwrap();
         spawner.spawn(process_light_level(display)).unwrap();
+        spawner.spawn(level_bar_task(display)).unwrap();
+        spawner.spawn(send_light_level_task(display)).unwrap();
+        spawner
+            .spawn(send_auto_brightness_changes_task(display))
+            .unwrap();
 
         display
     }
@@ -162,6 +530,20 @@ impl<'a> Display<'a> {
 
     /// Set the brightness on the display and send the state over MQTT.
     pub async fn set_brightness(&'static self, brightness: u8) {
+        self.change_brightness(brightness).await;
+        self.send_brightness_state().await;
+    }
+
+    /// Set the brightness from the ambient light level.
+    /// The state is published at most once every `AUTO_BRIGHTNESS_STATE_INTERVAL_SECS`,
+    /// so a flickering light does not flood MQTT with brightness states.
+    async fn set_auto_level_brightness(&'static self, brightness: u8) {
+        self.change_brightness(brightness).await;
+        AUTO_BRIGHTNESS_CHANGED.signal(true);
+    }
+
+    /// Set the brightness and redraw the display, without publishing the state.
+    async fn change_brightness(&'static self, brightness: u8) {
         // enable auto brightness if it was previously disabled
         if self.get_brightness().await == 0 && brightness > 0 {
             self.set_auto_brightness(true).await;
@@ -170,8 +552,6 @@ impl<'a> Display<'a> {
         self.galactic_unicorn.lock().await.brightness = brightness;
         self.redraw_graphics().await;
 
-        self.send_brightness_state().await;
-
         // disable auto brightness if the display has been turned off
         if brightness == 0 {
             self.set_auto_brightness(false).await;
@@ -186,6 +566,7 @@ impl<'a> Display<'a> {
         write!(text, "{brightness}").unwrap();
 
         MqttMessage::enqueue_state(BRIGHTNESS_STATE_TOPIC, &text).await;
+        self.send_light_state().await;
     }
 
     /// Toggle the auto brightness value and send the state over MQTT.
@@ -229,6 +610,15 @@ impl<'a> Display<'a> {
         self.galactic_unicorn.lock().await.get_light_level().await
     }
 
+    /// Send the current ambient light level over MQTT, as a percentage of the sensor range.
+    pub async fn send_light_level_state(&'static self) {
+        let level = (self.get_light_level().await as u32).min(MAX_LIGHT_LEVEL);
+        let mut text = String::<5>::new();
+        write!(text, "{}", level * 100 / MAX_LIGHT_LEVEL).unwrap();
+
+        MqttMessage::enqueue_state(LIGHT_LEVEL_STATE_TOPIC, &text).await;
+    }
+
     /// Get the current active color.
     pub async fn get_color(&'static self) -> Rgb888 {
         *self.current_color.lock().await
@@ -236,20 +626,50 @@ impl<'a> Display<'a> {
 
     /// Set the color on the display and send the state over MQTT.
     pub async fn set_color(&'static self, color: Rgb888) {
+        self.change_color(color).await;
+        self.send_color_state().await;
+    }
+
+    /// Set the brightness and color, ramping to them over `transition` when given,
+    /// and send the state over MQTT.
+    pub async fn set_light(
+        &'static self,
+        brightness: Option<u8>,
+        color: Option<Rgb888>,
+        transition: Option<Duration>,
+    ) {
+        if let Some(duration) = transition {
+            TRANSITION.signal(Transition {
+                brightness,
+                color,
+                duration,
+            });
+            return;
+        }
+
+        if let Some(brightness) = brightness {
+            self.set_brightness(brightness).await;
+        }
+        if let Some(color) = color {
+            self.set_color(color).await;
+        }
+    }
+
+    /// Set the color and recolor the content drawn in the old color, without publishing the state.
+    async fn change_color(&'static self, color: Rgb888) {
         let old_color = *self.current_color.lock().await;
         *self.current_color.lock().await = color;
 
-        self.current_graphics
+        self.layers
             .lock()
             .await
+            .get_mut(Layer::Content)
             .replace_color_with_new(old_color, color);
 
         CHANGE_COLOR_CHANNEL
             .publisher()
             .unwrap()
             .publish_immediate(color);
-
-        self.send_color_state().await;
     }
 
     /// Send the current color state over MQTT.
@@ -263,20 +683,252 @@ impl<'a> Display<'a> {
         write!(text, "{r},{g},{b}").unwrap();
 
         MqttMessage::enqueue_state(RGB_STATE_TOPIC, &text).await;
+        self.send_light_state().await;
+    }
+
+    /// Set the effect reported in the home assistant light state and send the state over MQTT.
+    #[cfg(feature = "effects-app")]
+    pub async fn set_light_effect(&'static self, effect: &'static str) {
+        *self.light_effect.lock().await = Some(effect);
+        self.send_light_state().await;
+    }
+
+    /// Send the brightness, color and effect as the home assistant JSON light state.
+    async fn send_light_state(&'static self) {
+        let brightness = self.galactic_unicorn.lock().await.brightness;
+        let color = *self.current_color.lock().await;
+        let state = if brightness > 0 { "ON" } else { "OFF" };
+
+        let mut text = String::<160>::new();
+        write!(
+            text,
+            r#"{{"state":"{state}","brightness":{brightness},"color_mode":"rgb","color":{{"r":{},"g":{},"b":{}}}"#,
+            color.r(),
+            color.g(),
+            color.b(),
+        )
+        .unwrap();
+        if let Some(effect) = *self.light_effect.lock().await {
+            write!(text, r#","effect":"{effect}""#).unwrap();
+        }
+        text.push('}').unwrap();
+
+        MqttMessage::enqueue_state(LIGHT_STATE_TOPIC, &text).await;
+    }
+
+    /// Get the current palette, or `None` if each effect and app uses its own.
+    pub async fn get_palette(&'static self) -> Option<Palette> {
+        *self.current_palette.lock().await
+    }
+
+    /// Set the current palette, or `None` for each effect and app to use its own,
+    /// and send the state over MQTT.
+    pub async fn set_palette(&'static self, palette: Option<Palette>) {
+        *self.current_palette.lock().await = palette;
+        self.send_palette_state().await;
+    }
+
+    /// Send the current palette state over MQTT.
+    pub async fn send_palette_state(&'static self) {
+        let palette: &'static str = self.get_palette().await.map_or("Default", Into::into);
+        MqttMessage::enqueue_state(PALETTE_STATE_TOPIC, palette).await;
+    }
+
+    /// Set the target frame rate and send the state over MQTT.
+    pub async fn set_target_fps(&'static self, fps: u8) {
+        TARGET_FPS.store(fps.clamp(1, MAX_FPS), Ordering::Relaxed);
+        self.send_target_fps_state().await;
+    }
+
+    /// Send the target frame rate over MQTT.
+    pub async fn send_target_fps_state(&'static self) {
+        let mut text = String::<3>::new();
+        write!(text, "{}", target_fps()).unwrap();
+
+        MqttMessage::enqueue_state(FPS_STATE_TOPIC, &text).await;
+    }
+
+    /// Set the speed of scrolling text in pixels per second and send the state over MQTT.
+    pub async fn set_scroll_speed(&'static self, speed: u8) {
+        SCROLL_SPEED.store(
+            speed.clamp(MIN_SCROLL_SPEED, MAX_SCROLL_SPEED),
+            Ordering::Relaxed,
+        );
+        self.send_scroll_speed_state().await;
+    }
+
+    /// Send the speed of scrolling text over MQTT.
+    pub async fn send_scroll_speed_state(&'static self) {
+        let mut text = String::<3>::new();
+        write!(text, "{}", scroll_speed()).unwrap();
+
+        MqttMessage::enqueue_state(SCROLL_SPEED_STATE_TOPIC, &text).await;
+    }
+
+    /// Set the strength of the evening warmth as a percentage and send the state over MQTT.
+    pub async fn set_warmth(&'static self, warmth: u8) {
+        WARMTH.store(warmth.min(100), Ordering::Relaxed);
+        WARMTH_CHANGED.signal(());
+        self.send_warmth_state().await;
+    }
+
+    /// Send the strength of the evening warmth over MQTT.
+    pub async fn send_warmth_state(&'static self) {
+        let mut text = String::<3>::new();
+        write!(text, "{}", WARMTH.load(Ordering::Relaxed)).unwrap();
+
+        MqttMessage::enqueue_state(WARMTH_STATE_TOPIC, &text).await;
+    }
+
+    /// Set the default scroll direction and send the state over MQTT.
+    pub async fn set_scroll_direction(&'static self, direction: ScrollDirection) {
+        SCROLL_DIRECTION.lock(|current| current.set(direction));
+        self.send_scroll_direction_state().await;
+    }
+
+    /// Send the default scroll direction over MQTT.
+    pub async fn send_scroll_direction_state(&'static self) {
+        let direction: &'static str = scroll_direction().into();
+        MqttMessage::enqueue_state(SCROLL_DIRECTION_STATE_TOPIC, direction).await;
+    }
+
+    /// Set whether the pending message indicator is shown and send the state over MQTT.
+    pub async fn set_pending_indicator(&'static self, state: bool) {
+        PENDING_INDICATOR.store(state, Ordering::Relaxed);
+        self.redraw_graphics().await;
+        self.send_pending_indicator_state().await;
+    }
+
+    /// Send the pending message indicator state over MQTT.
+    pub async fn send_pending_indicator_state(&'static self) {
+        let text = if PENDING_INDICATOR.load(Ordering::Relaxed) {
+            "ON"
+        } else {
+            "OFF"
+        };
+
+        MqttMessage::enqueue_state(PENDING_INDICATOR_STATE_TOPIC, text).await;
+    }
+
+    /// Redraw the display if the number of waiting messages has changed since the pending
+    /// indicator was last drawn.
+    async fn refresh_pending_indicator(&'static self) {
+        if pending_to_show() != PENDING_SHOWN.load(Ordering::Relaxed) {
+            self.redraw_graphics().await;
+        }
     }
 
-    /// Set the current graphics being displayed.
+    /// Set the current graphics being displayed on the content layer.
     pub async fn set_graphics(&'static self, graphics: &UnicornGraphics<WIDTH, HEIGHT>) {
-        self.galactic_unicorn.lock().await.set_pixels(graphics);
-        *self.current_graphics.lock().await = *graphics;
+        self.set_layer(Layer::Content, graphics).await;
+    }
+
+    /// Set the graphics of `layer` and redraw the display.
+    pub async fn set_layer(&'static self, layer: Layer, graphics: &UnicornGraphics<WIDTH, HEIGHT>) {
+        self.layers.lock().await.set(layer, graphics);
+        self.redraw_graphics().await;
+    }
+
+    /// Hide `layer` and redraw the display.
+    pub async fn clear_layer(&'static self, layer: Layer) {
+        self.layers.lock().await.clear(layer);
+        self.redraw_graphics().await;
+    }
+
+    /// Set the opacity of `layer`, where 0 is invisible and 255 is fully opaque, redraw the display
+    /// and send the state over MQTT.
+    pub async fn set_layer_opacity(&'static self, layer: Layer, opacity: u8) {
+        self.layers.lock().await.set_opacity(layer, opacity);
+        self.redraw_graphics().await;
+        self.send_layer_opacity_state().await;
+    }
+
+    /// Send the opacity of each layer over MQTT, as JSON such as `{"background":255,...}`.
+    pub async fn send_layer_opacity_state(&'static self) {
+        let mut text = String::<64>::new();
+        {
+            let layers = self.layers.lock().await;
+            for (index, layer) in Layer::ALL.into_iter().enumerate() {
+                let name: &'static str = layer.into();
+                let separator = if index == 0 { '{' } else { ',' };
+                let _ = write!(text, r#"{separator}"{name}":{}"#, layers.opacity(layer));
+            }
+        }
+        let _ = text.push('}');
+
+        MqttMessage::enqueue_state(LAYER_OPACITY_STATE_TOPIC, &text).await;
     }
 
-    /// Redraw the current graphics being displayed.
+    /// Blank the panel for `reason`, or clear `reason` and show the panel again if nothing else
+    /// blanks it. Interrupts and MQTT messages still show whilst blanked.
+    pub async fn set_blanked(&'static self, reason: BlankReason, blanked: bool) {
+        if blanked {
+            BLANKED.fetch_or(reason as u8, Ordering::Relaxed);
+        } else {
+            BLANKED.fetch_and(!(reason as u8), Ordering::Relaxed);
+        }
+        self.redraw_graphics().await;
+    }
+
+    /// Force the panel to full brightness, or go back to the set brightness.
+    /// The set brightness is left unchanged, so auto brightness and the buttons carry on as before.
+    pub async fn set_full_brightness(&'static self, full: bool) {
+        FULL_BRIGHTNESS.store(full, Ordering::Relaxed);
+        self.redraw_graphics().await;
+    }
+
+    /// Redraw the current graphics being displayed, compositing all visible layers.
+    /// Skipped if the output would be the same as the last redraw.
     pub async fn redraw_graphics(&'static self) {
-        self.galactic_unicorn
-            .lock()
-            .await
-            .set_pixels(&*self.current_graphics.lock().await);
+        let started = Instant::now();
+        let mut frame = self.layers.lock().await.compose();
+
+        let warmth = WARMTH_NOW.load(Ordering::Relaxed);
+        if warmth > 0 {
+            graphics::warm(&mut frame, warmth as f32 / 255.0);
+        }
+
+        // stack a pixel down the right edge for each message waiting
+        let pending = pending_to_show();
+        PENDING_SHOWN.store(pending, Ordering::Relaxed);
+        for index in 0..pending as i32 {
+            frame.set_pixel(
+                Point::new(WIDTH as i32 - 1, index * 2),
+                PENDING_INDICATOR_COLOR,
+            );
+        }
+
+        if BLANKED.load(Ordering::Relaxed) != 0 && !SHOWN_WHEN_BLANKED.load(Ordering::Relaxed) {
+            frame.clear_all();
+        }
+
+        let pixels = frame.get_pixels();
+
+        let mut galactic_unicorn = self.galactic_unicorn.lock().await;
+        let set_brightness = galactic_unicorn.brightness;
+        let brightness = if FULL_BRIGHTNESS.load(Ordering::Relaxed) {
+            255
+        } else {
+            set_brightness
+        };
+
+        let mut last_output = self.last_output.lock().await;
+        // every frame looks the same whilst the display is off
+        let unchanged = last_output.is_some_and(|(last_pixels, last_brightness)| {
+            last_brightness == brightness && (brightness == 0 || last_pixels == pixels)
+        });
+        if unchanged {
+            return;
+        }
+
+        *last_output = Some((pixels, brightness));
+        OUTPUT_CHANGED_AT.store(Instant::now().as_secs() as u32, Ordering::Relaxed);
+
+        galactic_unicorn.brightness = brightness;
+        galactic_unicorn.set_pixels(&frame);
+        galactic_unicorn.brightness = set_brightness;
+
+        diagnostics::record_frame(started.elapsed());
     }
 
     /// Display a graphical message. Has a minimum of 1ms on the display.
@@ -293,89 +945,208 @@ impl<'a> Display<'a> {
         Timer::after_millis(1).await;
 
         loop {
+            supervisor::check_in(Task::DisplayQueue);
+
             if message.has_min_duration_passed() || STOP_CURRENT_DISPLAY.signaled() {
                 STOP_CURRENT_DISPLAY.reset();
                 break;
+            } else if message.stream && !MQTT_DISPLAY_CHANNEL.is_empty() {
+                // a stream frame is replaced as soon as anything else is queued
+                break;
             } else {
+                self.refresh_pending_indicator().await;
                 Timer::after_millis(1).await;
             }
         }
     }
 
     /// Display a text message on the display.
-    /// Will scroll the text if it exceeds the width, otherwise will center the text.
+    /// Will scroll the text if it exceeds the width, unless the message overflow is set to cut it off,
+    /// otherwise will place the text by the message alignment.
     async fn display_text_message(
         &'static self,
         graphics: &mut UnicornGraphics<WIDTH, HEIGHT>,
         message: &mut DisplayTextMessage,
     ) {
-        let color = match message.color {
+        let mut color = match message.color {
             Some(x) => x,
             None => self.get_color().await,
         };
-        let mut style = MonoTextStyle::new(&FONT_6X10, color);
-        let width = message.text.len() * style.font.character_size.width as usize;
+        let width = message.font.measure(&message.text) as usize;
         let mut color_subscriber = CHANGE_COLOR_CHANNEL.subscriber().unwrap();
 
         message.set_first_shown();
 
-        if width > WIDTH {
-            let mut x: f32 = -(WIDTH as f32);
+        if message.scrolls() {
+            let direction = message.direction.unwrap_or_else(scroll_direction);
+            let (width, display_width) = (width as f32, WIDTH as f32);
+            let full_scroll = match direction {
+                ScrollDirection::Bounce => 2.0 * (width - display_width),
+                _ => width + display_width,
+            };
+
+            let mut travelled: f32 = 0.0;
+            let mut ticker = FrameTicker::new();
 
             loop {
+                supervisor::check_in(Task::DisplayQueue);
+
                 // if message has done a full scroll
-                if x > width as f32 {
+                if travelled > full_scroll {
                     // if message has been shown for minimum duration then break
                     if message.has_min_duration_passed() {
                         break;
                     }
 
                     // otherwise, reset scroll and go again
-                    x = -(WIDTH as f32);
+                    travelled = 0.0;
                 }
 
+                // offset of the text from its point, which increases as it moves left
+                let x = match direction {
+                    ScrollDirection::RightToLeft => travelled - display_width,
+                    ScrollDirection::LeftToRight => width - travelled,
+                    ScrollDirection::Bounce => {
+                        (full_scroll / 2.0 - (full_scroll / 2.0 - travelled).abs()).max(0.0)
+                    }
+                };
+
                 if STOP_CURRENT_DISPLAY.signaled() {
                     STOP_CURRENT_DISPLAY.reset();
                     break;
                 }
 
                 match color_subscriber.try_next_message_pure() {
-                    Some(color) => style.text_color = Some(color),
+                    Some(new_color) => color = new_color,
                     None => {}
                 }
 
-                graphics.fill(Rgb888::new(5, 5, 5));
-                let mut text = Text::new(
-                    message.text.as_str(),
-                    Point::new((message.point.x - x as i32) as i32, message.point.y),
-                    style,
-                );
-                text.text_style.baseline = Baseline::Middle;
-                text.draw(graphics).unwrap();
+                if message.smooth_scroll {
+                    // blend the text at the two nearest columns by the fractional offset
+                    let offset = x.floor();
+                    let mut current = UnicornGraphics::<WIDTH, HEIGHT>::new();
+                    let mut next = UnicornGraphics::<WIDTH, HEIGHT>::new();
+                    current.clear_all();
+                    next.clear_all();
+
+                    if message.is_visible() {
+                        for (buffer, column) in [(&mut current, 0), (&mut next, 1)] {
+                            message.font.draw_decorated(
+                                buffer,
+                                &message.text,
+                                message.point.x - offset as i32 - column,
+                                message.point.y,
+                                color,
+                                message.decoration,
+                            );
+                        }
+                    }
+
+                    *graphics = crate::graphics::blend(&current, &next, x - offset);
+                    graphics.replace_color_with_new(Rgb888::BLACK, Rgb888::new(5, 5, 5));
+                } else {
+                    graphics.fill(Rgb888::new(5, 5, 5));
+                    if message.is_visible() {
+                        message.font.draw_decorated(
+                            graphics,
+                            &message.text,
+                            message.point.x - x as i32,
+                            message.point.y,
+                            color,
+                            message.decoration,
+                        );
+                    }
+                }
                 self.set_graphics(graphics).await;
 
-                x += 0.05;
-                Timer::after_millis(1).await;
+                let elapsed = ticker.next().await;
+                travelled += scroll_speed() as f32 * elapsed.as_micros() as f32 / 1_000_000.0;
             }
-        } else {
-            graphics.fill(Rgb888::new(5, 5, 5));
-
-            let mut text = Text::new(
-                message.text.as_str(),
-                Point::new((WIDTH / 2) as i32, message.point.y),
-                style,
-            );
-            text.text_style.alignment = Alignment::Center;
-            text.text_style.baseline = Baseline::Middle;
-
-            text.draw(graphics).unwrap();
-            self.set_graphics(graphics).await;
+        } else if let Some(pages) = message.pages() {
+            let mut page = 0;
+            let mut page_started = Instant::now();
+            let mut redraw = true;
+            let mut visible = message.is_visible();
 
             loop {
-                Timer::after_millis(10).await;
+                supervisor::check_in(Task::DisplayQueue);
 
-                if message.has_min_duration_passed() || STOP_CURRENT_DISPLAY.signaled() {
-                    STOP_CURRENT_DISPLAY.reset();
+                if page_started.elapsed() >= message.page_duration {
+                    page += 1;
+                    page_started = Instant::now();
+                    redraw = true;
+
+                    // once every page has been shown, stop if the message has been shown for long enough
+                    if page == pages.len() {
+                        if message.has_min_duration_passed() {
+                            break;
+                        }
+                        page = 0;
+                    }
+                }
+
+                if redraw || message.is_visible() != visible {
+                    redraw = false;
+                    visible = message.is_visible();
+
+                    graphics.fill(Rgb888::new(5, 5, 5));
+                    if visible {
+                        let text = &pages[page];
+                        message.font.draw_decorated(
+                            graphics,
+                            text,
+                            message.align.start_x(message.font.measure(text)),
+                            message.point.y,
+                            color,
+                            message.decoration,
+                        );
+                    }
+                    self.set_graphics(graphics).await;
+                }
+
+                self.refresh_pending_indicator().await;
+                Timer::after_millis(10).await;
+
+                if STOP_CURRENT_DISPLAY.signaled() {
+                    STOP_CURRENT_DISPLAY.reset();
+                    break;
+                }
+            }
+        } else {
+            let font = message.layout_font();
+            let lines = message.placed_lines();
+
+            // start inverted so the first pass always draws
+            let mut visible = !message.is_visible();
+
+            loop {
+                supervisor::check_in(Task::DisplayQueue);
+
+                // only redraw when a blinking message changes visibility
+                if message.is_visible() != visible {
+                    visible = !visible;
+
+                    graphics.fill(Rgb888::new(5, 5, 5));
+                    if visible {
+                        for (text, point) in &lines {
+                            font.draw_decorated(
+                                graphics,
+                                text,
+                                point.x,
+                                point.y,
+                                color,
+                                message.decoration,
+                            );
+                        }
+                    }
+                    self.set_graphics(graphics).await;
+                }
+
+                self.refresh_pending_indicator().await;
+                Timer::after_millis(10).await;
+
+                if message.has_min_duration_passed() || STOP_CURRENT_DISPLAY.signaled() {
+                    STOP_CURRENT_DISPLAY.reset();
                     break;
                 }
             }
@@ -383,11 +1154,14 @@ impl<'a> Display<'a> {
     }
 }
 
-/// Process the display queues.
+/// Process the display queues. Runs on core 1.
+/// A requested self test runs before anything else.
 /// Queues are prioritised by:
 /// - Interrupt channel
 /// - MQTT channel
 /// - App channel
+///
+/// A sticky message blocks the app channel and is re-queued behind any new MQTT message until cleared.
 #[embassy_executor::task]
 async fn process_display_queue_task(display: &'static Display<'static>) {
     let mut graphics = UnicornGraphics::new();
@@ -398,33 +1172,73 @@ async fn process_display_queue_task(display: &'static Display<'static>) {
     let mut is_message_replaced = false;
 
     loop {
+        supervisor::check_in(Task::DisplayQueue);
+
+        if self_test::take_request() {
+            self_test::sweep(display).await;
+        }
+
         match INTERRUPT_DISPLAY_CHANNEL.try_receive() {
-            Ok(value) => match value {
-                DisplayMessage::Graphics(mut value) => {
-                    display
-                        .display_graphics_message(&mut graphics, &mut value)
-                        .await;
-                }
-                DisplayMessage::Text(mut value) => {
-                    display
-                        .display_text_message(&mut graphics, &mut value)
-                        .await;
+            Ok(value) => {
+                SHOWN_WHEN_BLANKED.store(true, Ordering::Relaxed);
+                match value {
+                    DisplayMessage::Graphics(mut value) => {
+                        display
+                            .display_graphics_message(&mut graphics, &mut value)
+                            .await;
+                    }
+                    DisplayMessage::Text(mut value) => {
+                        display
+                            .display_text_message(&mut graphics, &mut value)
+                            .await;
+                    }
                 }
-            },
+            }
             Err(_) => {}
         };
 
+        if CLEAR_STICKY.signaled() {
+            CLEAR_STICKY.reset();
+
+            if message.as_ref().is_some_and(DisplayMessage::is_sticky) {
+                message = None;
+            }
+
+            // drop any sticky messages waiting in the queue
+            let mut queued = Vec::<DisplayMessage, 8>::new();
+            while let Ok(value) = MQTT_DISPLAY_CHANNEL.try_receive() {
+                if !value.is_sticky() {
+                    let _ = queued.push(value);
+                }
+            }
+            for value in queued {
+                if MQTT_DISPLAY_CHANNEL.try_send(value).is_err() {
+                    diagnostics::DISPLAY_DROPPED.increment();
+                }
+            }
+        }
+
         if !is_message_replaced {
             match MQTT_DISPLAY_CHANNEL.try_receive() {
                 Ok(value) => {
                     is_message_replaced = true;
-                    message.replace(value);
+
+                    // re-queue a sticky message so it is shown again after the new message
+                    if let Some(previous) = message.replace(value) {
+                        if previous.is_sticky() && MQTT_DISPLAY_CHANNEL.try_send(previous).is_err()
+                        {
+                            diagnostics::DISPLAY_DROPPED.increment();
+                        }
+                    }
                 }
                 Err(_) => {}
             }
         }
 
-        if !is_message_replaced {
+        let is_sticky = message.as_ref().is_some_and(DisplayMessage::is_sticky);
+        STICKY_SHOWN.store(is_sticky, Ordering::Relaxed);
+
+        if !is_message_replaced && !is_sticky {
             match APP_DISPLAY_CHANNEL.try_receive() {
                 Ok(value) => {
                     is_message_replaced = true;
@@ -434,7 +1248,16 @@ async fn process_display_queue_task(display: &'static Display<'static>) {
             }
         }
 
+        SHOWN_WHEN_BLANKED.store(
+            message.as_ref().is_some_and(DisplayMessage::is_from_mqtt),
+            Ordering::Relaxed,
+        );
+
         if message.is_some() {
+            let first_pass = message
+                .as_ref()
+                .is_some_and(|value| value.first_shown().is_none());
+
             match message.as_mut().unwrap() {
                 DisplayMessage::Graphics(value) => {
                     display.display_graphics_message(&mut graphics, value).await;
@@ -452,6 +1275,23 @@ async fn process_display_queue_task(display: &'static Display<'static>) {
                 }
             }
 
+            // acknowledge MQTT messages once, when they have been shown in full the first time
+            if let Some(value) = message
+                .as_ref()
+                .filter(|value| first_pass && value.is_from_mqtt() && !value.is_stream())
+            {
+                if let Some(shown) = value.first_shown() {
+                    let ack = DisplayAck {
+                        id: value.id().cloned(),
+                        shown,
+                        pending: value.pending(),
+                    };
+                    if ACK_CHANNEL.try_send(ack).is_err() {
+                        diagnostics::DISPLAY_DROPPED.increment();
+                    }
+                }
+            }
+
             is_message_replaced = false;
         } else {
             Timer::after_millis(200).await;
@@ -459,6 +1299,56 @@ async fn process_display_queue_task(display: &'static Display<'static>) {
     }
 }
 
+/// Ramp the brightness and color to those of each transition, a step each frame,
+/// and send the state over MQTT once the transition ends.
+#[embassy_executor::task]
+async fn transition_task(display: &'static Display<'static>) {
+    let mut next = TRANSITION.wait().await;
+
+    loop {
+        let transition = next;
+        let from_brightness = display.get_brightness().await as f32;
+        let from_color = display.get_color().await;
+        let started = Instant::now();
+        let mut ticker = FrameTicker::new();
+
+        next = loop {
+            let t = (started.elapsed().as_millis() as f32
+                / transition.duration.as_millis().max(1) as f32)
+                .min(1.0);
+
+            if let Some(to) = transition.brightness {
+                let brightness =
+                    (from_brightness + (to as f32 - from_brightness) * t).round() as u8;
+                if brightness != display.get_brightness().await {
+                    display.change_brightness(brightness).await;
+                }
+            }
+            if let Some(to) = transition.color {
+                display
+                    .change_color(graphics::colors::lerp(from_color, to, t))
+                    .await;
+            }
+
+            if t >= 1.0 {
+                if transition.brightness.is_some() {
+                    display.send_brightness_state().await;
+                }
+                if transition.color.is_some() {
+                    display.send_color_state().await;
+                }
+                break TRANSITION.wait().await;
+            }
+
+            // a new transition starts from wherever this one has got to
+            match select(TRANSITION.wait(), ticker.next()).await {
+                Either::First(transition) => break transition,
+                Either::Second(_) => {}
+            }
+        };
+    }
+}
+
 /// Process any brightness button presses and update the display.
 #[embassy_executor::task]
 async fn process_brightness_buttons_task(display: &'static Display<'static>) {
@@ -510,6 +1400,8 @@ async fn process_brightness_buttons_task(display: &'static Display<'static>) {
             },
         }
 
+        show_level_bar(display.get_brightness().await, Rgb888::WHITE);
+
         if let Ok(mut ab) = display.auto_brightness.try_borrow_mut() {
             ab.disable_short();
             continue;
@@ -517,6 +1409,65 @@ async fn process_brightness_buttons_task(display: &'static Display<'static>) {
     }
 }
 
+/// Process any volume button presses and update the scroll speed, as there is no audio to adjust.
+#[embassy_executor::task]
+async fn process_volume_buttons_task(display: &'static Display<'static>) {
+    loop {
+        let current_speed = scroll_speed();
+
+        let speed = match select(VOLUME_UP_PRESS.wait(), VOLUME_DOWN_PRESS.wait()).await {
+            Either::First(press) => match press {
+                buttons::ButtonPress::Short => current_speed.saturating_add(SCROLL_SPEED_STEP),
+                buttons::ButtonPress::Long => MAX_SCROLL_SPEED,
+                buttons::ButtonPress::Double => DEFAULT_SCROLL_SPEED,
+            },
+            Either::Second(press) => match press {
+                buttons::ButtonPress::Short => current_speed.saturating_sub(SCROLL_SPEED_STEP),
+                buttons::ButtonPress::Long => MIN_SCROLL_SPEED,
+                buttons::ButtonPress::Double => DEFAULT_SCROLL_SPEED,
+            },
+        };
+        display.set_scroll_speed(speed).await;
+
+        let level = (scroll_speed() - MIN_SCROLL_SPEED) as u32 * 255
+            / (MAX_SCROLL_SPEED - MIN_SCROLL_SPEED) as u32;
+        show_level_bar(level as u8, SCROLL_SPEED_BAR_COLOR);
+    }
+}
+
+/// Draw level bars on the overlay layer, hiding them once the level stops changing.
+#[embassy_executor::task]
+async fn level_bar_task(display: &'static Display<'static>) {
+    loop {
+        let mut level_bar = LEVEL_BAR.wait().await;
+
+        loop {
+            let (level, color) = level_bar;
+            let filled = (level as usize * WIDTH).div_ceil(255);
+
+            let mut graphics = UnicornGraphics::<WIDTH, HEIGHT>::new();
+            for y in HEIGHT - LEVEL_BAR_HEIGHT..HEIGHT {
+                for x in 0..WIDTH {
+                    let pixel_color = if x < filled {
+                        color
+                    } else {
+                        LEVEL_BAR_TRACK_COLOR
+                    };
+                    graphics.set_pixel(Point::new(x as i32, y as i32), pixel_color);
+                }
+            }
+            display.set_layer(Layer::Overlay, &graphics).await;
+
+            match select(Timer::after_millis(LEVEL_BAR_MILLIS), LEVEL_BAR.wait()).await {
+                Either::First(_) => break,
+                Either::Second(next) => level_bar = next,
+            }
+        }
+
+        display.clear_layer(Layer::Overlay).await;
+    }
+}
+
 /// Process the light level and update brightness if required.
 #[embassy_executor::task]
 async fn process_light_level(display: &'static Display<'static>) {
@@ -535,7 +1486,7 @@ async fn process_light_level(display: &'static Display<'static>) {
         if light_level > brightness.saturating_add(10)
             || light_level < brightness.saturating_sub(10)
         {
-            display.set_brightness(light_level).await;
+            display.set_auto_level_brightness(light_level).await;
         }
 
         if let Ok(mut ab) = display.auto_brightness.try_borrow_mut() {
@@ -560,16 +1511,92 @@ async fn process_light_level(display: &'static Display<'static>) {
     }
 }
 
+/// Publish brightness changes made by auto brightness, coalescing any made within
+/// `AUTO_BRIGHTNESS_STATE_INTERVAL_SECS` of the last publish into one state with the latest brightness.
+#[embassy_executor::task]
+async fn send_auto_brightness_changes_task(display: &'static Display<'static>) {
+    loop {
+        AUTO_BRIGHTNESS_CHANGED.wait().await;
+        display.send_brightness_state().await;
+        Timer::after_secs(AUTO_BRIGHTNESS_STATE_INTERVAL_SECS).await;
+    }
+}
+
+/// Publish the ambient light level periodically, whether or not auto brightness is enabled.
+#[embassy_executor::task]
+async fn send_light_level_task(display: &'static Display<'static>) {
+    loop {
+        Timer::after_secs(LIGHT_LEVEL_INTERVAL_SECS).await;
+        display.send_light_level_state().await;
+    }
+}
+
+/// Publish an acknowledgement for each MQTT message shown in full, with its id and when it was
+/// first shown as a unix timestamp, and forget it from the messages waiting to be shown.
+#[embassy_executor::task]
+pub async fn send_acks_task(time: &'static Time, storage: &'static Storage) {
+    loop {
+        let ack = ACK_CHANNEL.receive().await;
+        if let Some(seq) = ack.pending {
+            pending::shown(storage, seq).await;
+        }
+
+        let shown = time.now().await.timestamp() - ack.shown.elapsed().as_secs() as i64;
+
+        let mut payload = String::<80>::new();
+        let _ = match ack.id {
+            Some(id) => write!(payload, r#"{{"id":"{id}","shown":{shown}}}"#),
+            None => write!(payload, r#"{{"id":null,"shown":{shown}}}"#),
+        };
+        MqttMessage::enqueue_state(DISPLAY_ACK_TOPIC, &payload).await;
+    }
+}
+
+/// Tint the display warm in the evening and neutral in the day, by the strength set over MQTT.
+#[embassy_executor::task]
+pub async fn warmth_task(display: &'static Display<'static>, time: &'static Time) {
+    loop {
+        // the time of day is not known until it has been synced or restored
+        let of_day = if time.is_synced() {
+            let now = time.now().await;
+            warmth_of_day(now.hour() * 60 + now.minute())
+        } else {
+            0.0
+        };
+
+        let warmth = (WARMTH.load(Ordering::Relaxed) as f32 / 100.0 * of_day * 255.0).round() as u8;
+        if WARMTH_NOW.swap(warmth, Ordering::Relaxed) != warmth {
+            display.redraw_graphics().await;
+        }
+
+        select(
+            Timer::after_secs(WARMTH_CHECK_INTERVAL_SECS),
+            WARMTH_CHANGED.wait(),
+        )
+        .await;
+    }
+}
+
 /// Process MQTT messages related to the display.
 #[embassy_executor::task]
 pub async fn process_mqtt_messages_task(
     display: &'static Display<'static>,
     mut subscriber: Subscriber<'static, ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1>,
 ) {
+    let mut stream = stream::StreamDecoder::new();
+
     loop {
         let message = subscriber.next_message_pure().await;
 
-        if message.topic == BRIGHTNESS_SET_TOPIC {
+        if message.topic == BRIGHTNESS_SET_TOPIC && message.body.trim_start().starts_with('{') {
+            match json::extract(&message.body, "brightness").and_then(|value| value.parse().ok()) {
+                Some(brightness) => {
+                    let transition = parse_transition(&message.body);
+                    display.set_light(Some(brightness), None, transition).await;
+                }
+                None => MqttMessage::enqueue_debug("Invalid brightness").await,
+            }
+        } else if message.topic == BRIGHTNESS_SET_TOPIC {
             let brightness: u8 = match message.body.parse() {
                 Ok(value) => value,
                 Err(_) => 255,
@@ -581,67 +1608,128 @@ pub async fn process_mqtt_messages_task(
             } else {
                 display.set_auto_brightness(false).await;
             }
-        } else if message.topic == RGB_SET_TOPIC {
-            let mut r = String::<3>::new();
-            let mut g = String::<3>::new();
-            let mut b = String::<3>::new();
-
-            let mut r_compl = false;
-            let mut g_compl = false;
-            let mut b_compl = false;
-            for c in message.body.chars() {
-                if !r_compl {
-                    if c == ',' {
-                        r_compl = true;
-                    } else {
-                        write!(r, "{c}").unwrap();
-                    }
-
-                    continue;
-                }
-
-                if !g_compl {
-                    if c == ',' {
-                        g_compl = true;
-                    } else {
-                        write!(g, "{c}").unwrap();
-                    }
-
-                    continue;
+        } else if message.topic == RGB_SET_TOPIC && message.body.trim_start().starts_with('{') {
+            match parse_light_color(&message.body) {
+                Some(color) => {
+                    let transition = parse_transition(&message.body);
+                    display.set_light(None, Some(color), transition).await;
                 }
-
-                if !b_compl {
-                    if c == ',' {
-                        b_compl = true;
-                    } else {
-                        write!(b, "{c}").unwrap();
-                    }
-
-                    continue;
+                None => MqttMessage::enqueue_debug("Invalid color").await,
+            }
+        } else if message.topic == LIGHT_SET_TOPIC {
+            let brightness = match json::extract(&message.body, "state") {
+                Some("OFF") => Some(0),
+                _ => match json::extract(&message.body, "brightness") {
+                    Some(value) => value.parse().ok(),
+                    // turning on without a brightness lights an off display at full brightness
+                    None if display.get_brightness().await == 0 => Some(255),
+                    None => None,
+                },
+            };
+            let color = parse_light_color(&message.body);
+            let transition = parse_transition(&message.body);
+            display.set_light(brightness, color, transition).await;
+        } else if message.topic == RGB_SET_TOPIC {
+            match Rgb888Str::from_str(&message.body) {
+                Ok(Rgb888Str(color)) => display.set_color(color).await,
+                Err(_) => MqttMessage::enqueue_debug("Invalid color").await,
+            }
+        } else if message.topic == FPS_SET_TOPIC {
+            match message.body.trim().parse::<u8>() {
+                Ok(fps) => display.set_target_fps(fps).await,
+                Err(_) => MqttMessage::enqueue_debug("Invalid frame rate").await,
+            }
+        } else if message.topic == SCROLL_SPEED_SET_TOPIC {
+            match message.body.trim().parse::<u8>() {
+                Ok(speed) => display.set_scroll_speed(speed).await,
+                Err(_) => MqttMessage::enqueue_debug("Invalid scroll speed").await,
+            }
+        } else if message.topic == WARMTH_SET_TOPIC {
+            match message.body.trim().parse::<u8>() {
+                Ok(warmth) => display.set_warmth(warmth).await,
+                Err(_) => MqttMessage::enqueue_debug("Invalid warmth, expected 0 to 100").await,
+            }
+        } else if message.topic == PENDING_INDICATOR_SET_TOPIC {
+            display.set_pending_indicator(message.body == "ON").await;
+        } else if message.topic == SCROLL_DIRECTION_SET_TOPIC {
+            match ScrollDirection::from_str(message.body.trim()) {
+                Ok(direction) => display.set_scroll_direction(direction).await,
+                Err(_) => MqttMessage::enqueue_debug("Invalid scroll direction").await,
+            }
+        } else if message.topic == LAYER_OPACITY_SET_TOPIC {
+            let layer = json::extract(&message.body, "layer").and_then(|l| Layer::from_str(l).ok());
+            let opacity = json::extract(&message.body, "opacity").and_then(|o| o.parse().ok());
+            match (layer, opacity) {
+                (Some(layer), Some(opacity)) => display.set_layer_opacity(layer, opacity).await,
+                _ => MqttMessage::enqueue_debug("Invalid layer opacity").await,
+            }
+        } else if message.topic == PALETTE_SET_TOPIC && message.body.eq_ignore_ascii_case("default")
+        {
+            display.set_palette(None).await;
+        } else if message.topic == PALETTE_SET_TOPIC {
+            match Palette::from_str(&message.body) {
+                Ok(palette) => display.set_palette(Some(palette)).await,
+                Err(_) => MqttMessage::enqueue_debug("Invalid palette").await,
+            }
+        } else if message.topic == ALERT_SET_TOPIC {
+            alert::request(&message.body).await;
+        } else if message.topic == IMAGE_URL_SET_TOPIC {
+            image::request(&message.body).await;
+        } else if message.topic == STREAM_TOPIC {
+            match stream.apply(&message.body) {
+                Ok(Some(pixels)) => DisplayGraphicsMessage::from_stream(pixels).send().await,
+                Ok(None) => {}
+                Err(err) => {
+                    let mut text = String::<64>::new();
+                    let _ = write!(text, "Invalid stream packet, {err}");
+                    MqttMessage::enqueue_debug(&text).await;
                 }
             }
-
-            let r = r.parse::<u8>().unwrap_or_default();
-            let g = g.parse::<u8>().unwrap_or_default();
-            let b = b.parse::<u8>().unwrap_or_default();
-
-            display.set_color(Rgb888::new(r, g, b)).await;
         }
     }
 }
 
 /// Message structs for sending into the display channels.
 pub mod messages {
+    use core::convert::Infallible;
+
     use embassy_time::{Duration, Instant};
-    use embedded_graphics::{geometry::Point, pixelcolor::Rgb888};
-    use galactic_unicorn_embassy::{HEIGHT, WIDTH};
-    use heapless::String;
-    use unicorn_graphics::UnicornGraphicsPixels;
+    use embedded_graphics::{
+        geometry::{Angle, OriginDimensions, Point, Size},
+        pixelcolor::{Rgb888, RgbColor},
+        primitives::{Arc, Circle, Line, Primitive, PrimitiveStyle, Rectangle},
+        Drawable,
+    };
+    use embedded_graphics_core::{draw_target::DrawTarget, Pixel};
+    use heapless::{String, Vec};
+    use unicorn_graphics::{UnicornGraphics, UnicornGraphicsPixels};
+
+    use crate::board::{HEIGHT, WIDTH};
+    use crate::diagnostics;
+    use crate::fonts::{self, Font, ScrollDirection, TextAlign, TextDecoration, TextOverflow};
 
     use super::{
         APP_DISPLAY_CHANNEL, INTERRUPT_DISPLAY_CHANNEL, MQTT_DISPLAY_CHANNEL, STOP_CURRENT_DISPLAY,
+        STREAM_HOLD_MILLIS,
     };
 
+    /// How long each page of paged text is shown for by default.
+    const DEFAULT_PAGE_DURATION_MILLIS: u64 = 2000;
+
+    /// The most pages text can be split into.
+    const MAX_PAGES: usize = 16;
+
+    /// Size of the options of a saved text message, before its font name, id and text.
+    const SAVED_HEADER_SIZE: usize = 16;
+
+    /// Longest custom font name kept when saving a text message.
+    const MAX_SAVED_FONT_NAME: usize = 16;
+
+    /// Largest size of a text message saved with `write_saved`, the options followed by the
+    /// font name, id and text, each after their length.
+    pub const SAVED_TEXT_MESSAGE_SIZE: usize =
+        SAVED_HEADER_SIZE + 1 + MAX_SAVED_FONT_NAME + 1 + 32 + 1 + 64;
+
     /// Possible display channels.
     enum DisplayChannels {
         /// MQTT display channel.
@@ -660,6 +1748,58 @@ pub mod messages {
         Text(DisplayTextMessage),
     }
 
+    impl DisplayMessage {
+        /// Check if the message should stay on the display until cleared.
+        pub(super) fn is_sticky(&self) -> bool {
+            match self {
+                DisplayMessage::Graphics(_) => false,
+                DisplayMessage::Text(value) => value.sticky,
+            }
+        }
+
+        /// Check if the message was queued into the MQTT channel.
+        pub(super) fn is_from_mqtt(&self) -> bool {
+            let channel = match self {
+                DisplayMessage::Graphics(value) => &value.channel,
+                DisplayMessage::Text(value) => &value.channel,
+            };
+
+            matches!(channel, DisplayChannels::MQTT)
+        }
+
+        /// Check if the message is a frame from the display stream.
+        pub(super) fn is_stream(&self) -> bool {
+            match self {
+                DisplayMessage::Graphics(value) => value.stream,
+                DisplayMessage::Text(_) => false,
+            }
+        }
+
+        /// When the message was first shown, or `None` if it has not been shown yet.
+        pub(super) fn first_shown(&self) -> Option<Instant> {
+            match self {
+                DisplayMessage::Graphics(value) => value.first_shown,
+                DisplayMessage::Text(value) => value.first_shown,
+            }
+        }
+
+        /// The id given by the sender, if any.
+        pub(super) fn id(&self) -> Option<&String<32>> {
+            match self {
+                DisplayMessage::Graphics(value) => value.id.as_ref(),
+                DisplayMessage::Text(value) => value.id.as_ref(),
+            }
+        }
+
+        /// The number the message is kept until shown with, if any.
+        pub(super) fn pending(&self) -> Option<u32> {
+            match self {
+                DisplayMessage::Graphics(_) => None,
+                DisplayMessage::Text(value) => value.pending,
+            }
+        }
+    }
+
     /// Show some text on the display. Has a 64 byte maximum size.
     pub struct DisplayTextMessage {
         /// The text to display.
@@ -677,6 +1817,42 @@ pub mod messages {
         /// When the message was first shown on the display.
         pub(super) first_shown: Option<Instant>,
 
+        /// Flash the text on and off whilst it is displayed.
+        pub(super) blink: bool,
+
+        /// Keep the text on the display until it is cleared.
+        pub(super) sticky: bool,
+
+        /// The font to display the text in.
+        pub(super) font: Font,
+
+        /// Outline or shadow drawn behind the text.
+        pub(super) decoration: Option<TextDecoration>,
+
+        /// Blend adjacent columns when scrolling for smoother motion.
+        pub(super) smooth_scroll: bool,
+
+        /// Direction to scroll in, or the default direction if `None`.
+        pub(super) direction: Option<ScrollDirection>,
+
+        /// Where to place text that fits on the display.
+        pub(super) align: TextAlign,
+
+        /// Show the text as two lines, split at a line break or wrapped between words.
+        pub(super) two_line: bool,
+
+        /// What to do with text too wide for the display.
+        pub(super) overflow: TextOverflow,
+
+        /// How long each page is shown for when the overflow is `Page`.
+        pub(super) page_duration: Duration,
+
+        /// Id given by the sender, published when the message has been shown.
+        pub(super) id: Option<String<32>>,
+
+        /// Number the message is kept in flash with until it has been shown, if it is.
+        pub(super) pending: Option<u32>,
+
         /// What channel to publish the message into.
         channel: DisplayChannels,
     }
@@ -706,6 +1882,18 @@ pub mod messages {
                 point,
                 duration: Duration::from_secs(3),
                 first_shown: None,
+                blink: false,
+                sticky: false,
+                font: Font::default(),
+                decoration: None,
+                smooth_scroll: false,
+                direction: None,
+                align: TextAlign::default(),
+                two_line: false,
+                overflow: TextOverflow::default(),
+                page_duration: Duration::from_millis(DEFAULT_PAGE_DURATION_MILLIS),
+                id: None,
+                pending: None,
                 channel: DisplayChannels::MQTT,
             }
         }
@@ -744,12 +1932,195 @@ pub mod messages {
                 point,
                 duration,
                 first_shown: None,
+                blink: false,
+                sticky: false,
+                font: Font::default(),
+                decoration: None,
+                smooth_scroll: false,
+                direction: None,
+                align: TextAlign::default(),
+                two_line: false,
+                overflow: TextOverflow::default(),
+                page_duration: Duration::from_millis(DEFAULT_PAGE_DURATION_MILLIS),
+                id: None,
+                pending: None,
                 channel: DisplayChannels::APP,
             }
         }
+
+        /// Flash the text on and off whilst it is displayed.
+        pub fn with_blink(mut self, blink: bool) -> Self {
+            self.blink = blink;
+            self
+        }
+
+        /// Keep the text on the display until it is cleared with `clear_sticky`.
+        pub fn with_sticky(mut self, sticky: bool) -> Self {
+            self.sticky = sticky;
+            self
+        }
+
+        /// Display the text in `font`.
+        pub fn with_font(mut self, font: Font) -> Self {
+            self.font = font;
+            self
+        }
+
+        /// Blend adjacent columns by the fractional scroll offset, for smoother slow scrolling.
+        pub fn with_smooth_scroll(mut self, smooth_scroll: bool) -> Self {
+            self.smooth_scroll = smooth_scroll;
+            self
+        }
+
+        /// Scroll the text in `direction`. A `None` uses the default scroll direction.
+        pub fn with_direction(mut self, direction: Option<ScrollDirection>) -> Self {
+            self.direction = direction;
+            self
+        }
+
+        /// Draw an outline or shadow behind the text. A `None` draws the text without one.
+        pub fn with_decoration(mut self, decoration: Option<TextDecoration>) -> Self {
+            self.decoration = decoration;
+            self
+        }
+
+        /// Place text that fits on the display to the left, center or right.
+        pub fn with_align(mut self, align: TextAlign) -> Self {
+            self.align = align;
+            self
+        }
+
+        /// Show the text as two lines instead of one. Two lines never scroll, and a font too tall
+        /// for two lines is swapped for the tiny font.
+        pub fn with_two_line(mut self, two_line: bool) -> Self {
+            self.two_line = two_line;
+            self
+        }
+
+        /// Scroll, truncate, ellipsize or page text too wide for the display.
+        pub fn with_overflow(mut self, overflow: TextOverflow) -> Self {
+            self.overflow = overflow;
+            self
+        }
+
+        /// Set how long each page is shown for when text is paged, or keep the default if `None`.
+        pub fn with_page_duration(mut self, page_duration: Option<Duration>) -> Self {
+            if let Some(page_duration) = page_duration {
+                self.page_duration = page_duration;
+            }
+            self
+        }
+
+        /// Set the id published once the message has been shown. An id that is too long is ignored.
+        pub fn with_id(mut self, id: Option<&str>) -> Self {
+            self.id = id.and_then(|id| String::try_from(id).ok());
+            self
+        }
+
+        /// Set the minimum duration to show the message for.
+        pub fn with_duration(mut self, duration: Duration) -> Self {
+            self.duration = duration;
+            self
+        }
+
+        /// Mark the message as kept in flash with the number `pending` until it has been shown.
+        pub fn with_pending(mut self, pending: Option<u32>) -> Self {
+            self.pending = pending;
+            self
+        }
     }
 
     impl DisplayTextMessage {
+        /// Write the text and options of the message, so it can be saved and read back with
+        /// `read_saved`. The position, duration and channel are not kept.
+        #[cfg(feature = "mqtt-app")]
+        pub fn write_saved(&self, out: &mut Vec<u8, SAVED_TEXT_MESSAGE_SIZE>) {
+            let rgb = |kind: u8, color: Rgb888| [kind, color.r(), color.g(), color.b()];
+            let flags = self.blink as u8
+                | (self.sticky as u8) << 1
+                | (self.smooth_scroll as u8) << 2
+                | (self.two_line as u8) << 3;
+            let decoration = match self.decoration {
+                None => [0; 4],
+                Some(TextDecoration::Outline(color)) => rgb(1, color),
+                Some(TextDecoration::Shadow(color)) => rgb(2, color),
+            };
+            let direction = self.direction.map_or(0, |direction| direction as u8 + 1);
+            let page_duration = self.page_duration.as_millis() as u32;
+
+            // a custom font with a name too long to keep is saved as the default font
+            let font = self.font.name();
+            let font = if font.len() <= MAX_SAVED_FONT_NAME {
+                font
+            } else {
+                ""
+            };
+
+            let _ = out.extend_from_slice(&self.color.map_or([0; 4], |color| rgb(1, color)));
+            let _ = out.push(flags);
+            let _ = out.extend_from_slice(&decoration);
+            let _ = out.extend_from_slice(&[self.align as u8, self.overflow as u8, direction]);
+            let _ = out.extend_from_slice(&page_duration.to_le_bytes());
+            for text in [
+                font,
+                self.id.as_deref().unwrap_or_default(),
+                self.text.as_str(),
+            ] {
+                let _ = out.push(text.len() as u8);
+                let _ = out.extend_from_slice(text.as_bytes());
+            }
+        }
+
+        /// Read a text message written with `write_saved`, as a message on the MQTT channel.
+        pub fn read_saved(bytes: &[u8]) -> Option<Self> {
+            let header = bytes.get(..SAVED_HEADER_SIZE)?;
+            let (font, rest) = split_saved_str(&bytes[SAVED_HEADER_SIZE..])?;
+            let (id, rest) = split_saved_str(rest)?;
+            let (text, _) = split_saved_str(rest)?;
+
+            let rgb = |bytes: &[u8]| Rgb888::new(bytes[1], bytes[2], bytes[3]);
+            let color = (header[0] == 1).then(|| rgb(&header[..4]));
+            let flags = header[4];
+            let decoration = match header[5] {
+                1 => Some(TextDecoration::Outline(rgb(&header[5..9]))),
+                2 => Some(TextDecoration::Shadow(rgb(&header[5..9]))),
+                _ => None,
+            };
+            let align = match header[9] {
+                0 => TextAlign::Left,
+                2 => TextAlign::Right,
+                _ => TextAlign::Center,
+            };
+            let overflow = match header[10] {
+                1 => TextOverflow::Truncate,
+                2 => TextOverflow::Ellipsis,
+                3 => TextOverflow::Page,
+                _ => TextOverflow::Scroll,
+            };
+            let direction = match header[11] {
+                1 => Some(ScrollDirection::RightToLeft),
+                2 => Some(ScrollDirection::LeftToRight),
+                3 => Some(ScrollDirection::Bounce),
+                _ => None,
+            };
+            let page_duration = u32::from_le_bytes(header[12..16].try_into().unwrap());
+
+            Some(
+                Self::from_mqtt(text, color, None)
+                    .with_blink(flags & 1 != 0)
+                    .with_sticky(flags & 2 != 0)
+                    .with_smooth_scroll(flags & 4 != 0)
+                    .with_two_line(flags & 8 != 0)
+                    .with_font(Font::from_name(font).unwrap_or_default())
+                    .with_decoration(decoration)
+                    .with_align(align)
+                    .with_overflow(overflow)
+                    .with_direction(direction)
+                    .with_page_duration(Some(Duration::from_millis(page_duration as u64)))
+                    .with_id(Some(id).filter(|id| !id.is_empty())),
+            )
+        }
+
         /// Queue a message into the end of the channel and consume itself.
         pub async fn send(self) {
             match self.channel {
@@ -800,6 +2171,138 @@ pub mod messages {
 
             self.first_shown.unwrap().elapsed() > self.duration
         }
+
+        /// Check if the text should currently be drawn. Always true unless the message blinks.
+        pub fn is_visible(&self) -> bool {
+            !self.blink || (Instant::now().as_millis() / 500) % 2 == 0
+        }
+
+        /// Check if the text is scrolled, rather than placed on the display.
+        pub(super) fn scrolls(&self) -> bool {
+            !self.two_line
+                && matches!(self.overflow, TextOverflow::Scroll)
+                && self.font.measure(&self.text) > WIDTH as u32
+        }
+
+        /// Get the font the text is placed in, which is the tiny font for two lines in a tall font.
+        pub(super) fn layout_font(&self) -> Font {
+            if self.two_line && self.font.height() * 2 > HEIGHT as u32 + 1 {
+                Font::Tiny
+            } else {
+                self.font
+            }
+        }
+
+        /// Get each line of text to place on the display, with the point to draw it from.
+        pub(super) fn placed_lines(&self) -> Vec<(String<64>, Point), 2> {
+            let font = self.layout_font();
+            let mut lines = Vec::new();
+
+            if self.two_line {
+                let (first, second) = self.split_lines(font);
+                let height = font.height() as i32;
+                let bottom = HEIGHT as i32 - height + height / 2;
+                for (text, y) in [(first, height / 2), (second, bottom)] {
+                    let text = self.fit_line(font, text);
+                    let x = self.align.start_x(font.measure(&text));
+                    let _ = lines.push((text, Point::new(x, y)));
+                }
+            } else {
+                let text = self.fit_line(font, &self.text);
+                let x = self.align.start_x(font.measure(&text));
+                let _ = lines.push((text, Point::new(x, self.point.y)));
+            }
+
+            lines
+        }
+
+        /// Split the text into two lines at a line break, or between the words that fill the first line.
+        fn split_lines(&self, font: Font) -> (&str, &str) {
+            // a line break, or an escaped one from a JSON string
+            for separator in ["\n", "\\n"] {
+                if let Some((first, second)) = self.text.split_once(separator) {
+                    return (first.trim(), second.trim());
+                }
+            }
+
+            let first = font.fit_text(&self.text, WIDTH as u32);
+            if first.len() == self.text.len() {
+                return (&self.text, "");
+            }
+
+            match first.rfind(' ') {
+                Some(index) => (&self.text[..index], self.text[index + 1..].trim_start()),
+                None => (first, &self.text[first.len()..]),
+            }
+        }
+
+        /// Split text too wide for the display into pages of whole words that each fit,
+        /// or `None` if the text is not paged.
+        pub(super) fn pages(&self) -> Option<Vec<String<64>, MAX_PAGES>> {
+            let max_width = WIDTH as u32;
+            if self.two_line
+                || !matches!(self.overflow, TextOverflow::Page)
+                || self.font.measure(&self.text) <= max_width
+            {
+                return None;
+            }
+
+            let mut pages = Vec::new();
+            let mut page = String::<64>::new();
+            for mut word in self.text.split_whitespace() {
+                while !word.is_empty() {
+                    let mut candidate = page.clone();
+                    if !candidate.is_empty() {
+                        let _ = candidate.push(' ');
+                    }
+                    if candidate.push_str(word).is_ok()
+                        && self.font.measure(&candidate) <= max_width
+                    {
+                        page = candidate;
+                        break;
+                    }
+
+                    if page.is_empty() {
+                        // a word too wide for a page of its own is split at the edge
+                        let first = word.chars().next().map_or(0, char::len_utf8);
+                        let end = self.font.fit_text(word, max_width).len().max(first);
+                        let _ = page.push_str(&word[..end]);
+                        word = &word[end..];
+                    } else {
+                        let _ = pages.push(core::mem::take(&mut page));
+                    }
+                }
+            }
+
+            if !page.is_empty() {
+                let _ = pages.push(page);
+            }
+
+            (!pages.is_empty()).then_some(pages)
+        }
+
+        /// Get `text` cut down to fit the display in `font`, ending with `...` if the overflow is `Ellipsis`.
+        fn fit_line(&self, font: Font, text: &str) -> String<64> {
+            let max_width = WIDTH as u32;
+            let mut line = String::new();
+            if font.measure(text) <= max_width {
+                let _ = line.push_str(text);
+                return line;
+            }
+
+            match self.overflow {
+                TextOverflow::Ellipsis => {
+                    let max_width = max_width.saturating_sub(font.text_width("..."));
+                    let _ = line.push_str(font.fit_text(text, max_width).trim_end());
+                    let _ = line.push_str("...");
+                }
+                _ => {
+                    let _ = line.push_str(font.fit_text(text, max_width));
+                }
+            }
+
+            line
+        }
     }
 
     /// Show a message using the pixel buffer.
@@ -813,6 +2316,12 @@ pub mod messages {
         /// When the message was first shown on the display.
         pub(super) first_shown: Option<Instant>,
 
+        /// Id given by the sender, published when the message has been shown.
+        pub(super) id: Option<String<32>>,
+
+        /// Is the message a frame from the display stream, replaced as soon as the next arrives.
+        pub(super) stream: bool,
+
         /// What channel to publish the message into.
         channel: DisplayChannels,
     }
@@ -824,9 +2333,42 @@ pub mod messages {
                 pixels,
                 duration,
                 first_shown: None,
+                id: None,
+                stream: false,
                 channel: DisplayChannels::APP,
             }
         }
+
+        /// Display the pixels on the display for the duration specified, ahead of app messages.
+        pub fn from_mqtt(pixels: UnicornGraphicsPixels<WIDTH, HEIGHT>, duration: Duration) -> Self {
+            Self {
+                pixels,
+                duration,
+                first_shown: None,
+                id: None,
+                stream: false,
+                channel: DisplayChannels::MQTT,
+            }
+        }
+
+        /// Display a frame from the display stream until the next frame arrives,
+        /// or for a short hold once the stream stops.
+        pub(crate) fn from_stream(pixels: UnicornGraphicsPixels<WIDTH, HEIGHT>) -> Self {
+            Self {
+                pixels,
+                duration: Duration::from_millis(STREAM_HOLD_MILLIS),
+                first_shown: None,
+                id: None,
+                stream: true,
+                channel: DisplayChannels::MQTT,
+            }
+        }
+
+        /// Set the id published once the message has been shown. An id that is too long is ignored.
+        pub fn with_id(mut self, id: Option<&str>) -> Self {
+            self.id = id.and_then(|id| String::try_from(id).ok());
+            self
+        }
     }
 
     impl DisplayGraphicsMessage {
@@ -851,6 +2393,15 @@ pub mod messages {
         /// Queue a message into the end of the channel and consume itself.
         pub async fn send(self) {
             match self.channel {
+                // stream frames are dropped when the queue is full, rather than holding up MQTT
+                DisplayChannels::MQTT if self.stream => {
+                    if MQTT_DISPLAY_CHANNEL
+                        .try_send(DisplayMessage::Graphics(self))
+                        .is_err()
+                    {
+                        diagnostics::DISPLAY_DROPPED.increment();
+                    }
+                }
                 DisplayChannels::MQTT => {
                     MQTT_DISPLAY_CHANNEL
                         .send(DisplayMessage::Graphics(self))
@@ -879,5 +2430,241 @@ pub mod messages {
                 }
             }
         }
+
+        /// Show the pixels immediately, skipping the display channel queue.
+        pub async fn send_and_show_now(self) {
+            STOP_CURRENT_DISPLAY.signal(true);
+            INTERRUPT_DISPLAY_CHANNEL
+                .send(DisplayMessage::Graphics(self))
+                .await;
+        }
+    }
+
+    /// Split a string saved after its length off the start of `bytes`.
+    fn split_saved_str(bytes: &[u8]) -> Option<(&str, &[u8])> {
+        let (&len, rest) = bytes.split_first()?;
+        let text = core::str::from_utf8(rest.get(..len as usize)?).ok()?;
+        Some((text, &rest[len as usize..]))
+    }
+
+    /// Build a frame from embedded graphics primitives, then turn it into a `DisplayGraphicsMessage`.
+    /// Drawing is clipped to the display, so primitives can be partly off screen.
+    ///
+    /// The builder is an embedded graphics `DrawTarget` itself, so anything drawable can be drawn
+    /// straight onto it with `drawable.draw(&mut frame)`. It draws into its own buffer, which is
+    /// copied into the message when the frame is finished, as frames are shown by the display
+    /// queue rather than drawn onto the display directly.
+    pub struct FrameBuilder {
+        /// The graphics buffer being drawn onto.
+        graphics: UnicornGraphics<WIDTH, HEIGHT>,
+    }
+
+    impl FrameBuilder {
+        /// Start a new blank frame.
+        pub fn new() -> Self {
+            let mut graphics = UnicornGraphics::new();
+            graphics.clear_all();
+
+            Self { graphics }
+        }
+
+        /// Draw any embedded graphics drawable, such as text or a styled primitive.
+        pub fn draw(mut self, drawable: impl Drawable<Color = Rgb888>) -> Self {
+            let _ = drawable.draw(&mut self);
+            self
+        }
+
+        /// Draw a line from `start` to `end`, `width` pixels wide.
+        pub fn line(self, start: Point, end: Point, color: Rgb888, width: u32) -> Self {
+            self.draw(Line::new(start, end).into_styled(PrimitiveStyle::with_stroke(color, width)))
+        }
+
+        /// Draw a rectangle with its top left corner at `top_left`.
+        pub fn rectangle(self, top_left: Point, size: Size, style: PrimitiveStyle<Rgb888>) -> Self {
+            self.draw(Rectangle::new(top_left, size).into_styled(style))
+        }
+
+        /// Draw a circle with its bounding box top left corner at `top_left`.
+        pub fn circle(self, top_left: Point, diameter: u32, style: PrimitiveStyle<Rgb888>) -> Self {
+            self.draw(Circle::new(top_left, diameter).into_styled(style))
+        }
+
+        /// Draw a filled circle with its bounding box top left corner at `top_left`.
+        pub fn filled_circle(self, top_left: Point, diameter: u32, color: Rgb888) -> Self {
+            self.circle(top_left, diameter, PrimitiveStyle::with_fill(color))
+        }
+
+        /// Draw an arc of a circle, starting at `angle_start` and sweeping `angle_sweep`.
+        pub fn arc(
+            self,
+            top_left: Point,
+            diameter: u32,
+            angle_start: Angle,
+            angle_sweep: Angle,
+            style: PrimitiveStyle<Rgb888>,
+        ) -> Self {
+            self.draw(Arc::new(top_left, diameter, angle_start, angle_sweep).into_styled(style))
+        }
+
+        /// Get the graphics buffer for drawing that the builder does not cover.
+        pub fn graphics_mut(&mut self) -> &mut UnicornGraphics<WIDTH, HEIGHT> {
+            &mut self.graphics
+        }
+
+        /// Finish the frame as a message on the app channel, shown for `duration`.
+        /// The pixels are copied out of the builder's buffer into the message.
+        pub fn into_message(self, duration: Duration) -> DisplayGraphicsMessage {
+            DisplayGraphicsMessage::from_app(self.graphics.get_pixels(), duration)
+        }
+
+        /// Finish the frame and queue it on the app channel, shown for `duration`.
+        pub async fn show(self, duration: Duration) {
+            self.into_message(duration).send().await;
+        }
+    }
+
+    impl OriginDimensions for FrameBuilder {
+        fn size(&self) -> Size {
+            Size::new(WIDTH as u32, HEIGHT as u32)
+        }
+    }
+
+    impl DrawTarget for FrameBuilder {
+        type Color = Rgb888;
+        type Error = Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                fonts::set_pixel_clipped(&mut self.graphics, point, color);
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Compact frame streaming, so animations can be sent over MQTT in packets far smaller than a
+/// raw frame.
+///
+/// Each packet is base64 encoded. Once decoded it starts with a flags byte, then the index of
+/// the pixel it starts at as a little endian `u16`, counting row by row from the top left.
+/// The rest of the packet is ops, each a tag byte:
+///
+/// - `0x00..=0x3f` skips `n + 1` pixels, leaving them as they were in the last frame.
+/// - `0x40..=0x7f` sets the next `n + 1` pixels, each from three `r, g, b` bytes that follow.
+/// - `0x80..=0xff` sets the next `n + 1` pixels to the one color in the three bytes that follow.
+///
+/// Where `n` is the tag without its top two bits, or top bit for a run.
+pub mod stream {
+    use embedded_graphics_core::pixelcolor::Rgb888;
+    use thiserror_no_std::Error;
+    use unicorn_graphics::{UnicornGraphics, UnicornGraphicsPixels};
+
+    use crate::{
+        base64,
+        board::{HEIGHT, WIDTH},
+    };
+
+    /// Flag to start from a black frame rather than the last one.
+    const FLAG_CLEAR: u8 = 1 << 0;
+
+    /// Flag to show the frame once the packet has been applied.
+    const FLAG_SHOW: u8 = 1 << 1;
+
+    /// Largest packet once decoded, from the longest message body.
+    const PACKET_SIZE: usize = 192;
+
+    /// Size of the flags and start index before the ops.
+    const HEADER_SIZE: usize = 3;
+
+    /// Error when a stream packet can not be applied.
+    #[derive(Error, Debug)]
+    pub enum StreamError {
+        #[error("invalid base64")]
+        Encoding,
+        #[error("too short")]
+        Truncated,
+        #[error("runs past the end of the frame")]
+        Overflow,
+    }
+
+    /// Decodes stream packets into the frame they build up.
+    pub struct StreamDecoder {
+        /// The frame being built, kept between frames so packets only need to send changes.
+        frame: UnicornGraphicsPixels<WIDTH, HEIGHT>,
+    }
+
+    impl StreamDecoder {
+        /// Create a decoder with a black frame.
+        pub fn new() -> Self {
+            Self {
+                frame: UnicornGraphics::<WIDTH, HEIGHT>::new().get_pixels(),
+            }
+        }
+
+        /// Apply a base64 encoded packet to the frame.
+        /// Returns the frame if the packet asks for it to be shown.
+        pub fn apply(
+            &mut self,
+            body: &str,
+        ) -> Result<Option<UnicornGraphicsPixels<WIDTH, HEIGHT>>, StreamError> {
+            let mut packet = [0u8; PACKET_SIZE];
+            let len = base64::decode(body, &mut packet).ok_or(StreamError::Encoding)?;
+            if len < HEADER_SIZE {
+                return Err(StreamError::Truncated);
+            }
+
+            let flags = packet[0];
+            let mut index = u16::from_le_bytes([packet[1], packet[2]]) as usize;
+            let mut ops = &packet[HEADER_SIZE..len];
+
+            // check the whole packet first, so a bad one leaves the frame as it was
+            let mut frame = self.frame;
+            if flags & FLAG_CLEAR != 0 {
+                frame = UnicornGraphics::<WIDTH, HEIGHT>::new().get_pixels();
+            }
+
+            let read_color = |ops: &mut &[u8]| -> Result<Rgb888, StreamError> {
+                let rgb = ops.get(..3).ok_or(StreamError::Truncated)?;
+                let color = Rgb888::new(rgb[0], rgb[1], rgb[2]);
+                *ops = &ops[3..];
+                Ok(color)
+            };
+
+            while let Some((&tag, rest)) = ops.split_first() {
+                ops = rest;
+
+                let count = match tag {
+                    0x00..=0x7f => (tag & 0x3f) as usize + 1,
+                    _ => (tag & 0x7f) as usize + 1,
+                };
+                if index + count > WIDTH * HEIGHT {
+                    return Err(StreamError::Overflow);
+                }
+
+                match tag {
+                    0x00..=0x3f => {}
+                    0x40..=0x7f => {
+                        for pixel in index..index + count {
+                            frame[pixel / WIDTH][pixel % WIDTH] = read_color(&mut ops)?;
+                        }
+                    }
+                    _ => {
+                        let color = read_color(&mut ops)?;
+                        for pixel in index..index + count {
+                            frame[pixel / WIDTH][pixel % WIDTH] = color;
+                        }
+                    }
+                }
+
+                index += count;
+            }
+
+            self.frame = frame;
+            Ok((flags & FLAG_SHOW != 0).then_some(frame))
+        }
     }
 }
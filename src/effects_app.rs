@@ -1,11 +1,33 @@
+use core::fmt::Write;
+use core::str::FromStr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use embassy_futures::select::select;
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
 use static_cell::make_static;
 
-use crate::{app::UnicornApp, buttons::ButtonPress};
+use crate::{
+    app::UnicornApp,
+    buttons::ButtonPress,
+    display::Display,
+    mqtt::{topics::EFFECT_STATE_TOPIC, MqttMessage},
+};
 
 use self::effects::{Balls, Effects};
 
+/// Effect name that leaves the effects app, listed with the effects in home assistant.
+pub const NO_EFFECT: &str = "None";
+
+/// Write the names of the effects, followed by `NO_EFFECT`, as a JSON array.
+pub fn write_effect_list(out: &mut impl Write) -> core::fmt::Result {
+    out.write_char('[')?;
+    for effect in Effects::ALL {
+        let name: &'static str = effect.into();
+        write!(out, r#""{name}","#)?;
+    }
+    write!(out, r#""{NO_EFFECT}"]"#)
+}
+
 /// Effects app. Show different effects.
 pub struct EffectsApp {
     /// The current active effect.
@@ -16,18 +38,38 @@ pub struct EffectsApp {
 
     /// Hold a reference to the `Balls` effect.
     balls: Balls,
+
+    /// Track if the app is active or not.
+    is_active: AtomicBool,
+
+    /// The display, reporting the effect in the home assistant light state.
+    display: &'static Display<'static>,
 }
 
 impl EffectsApp {
     /// Create the static ref to effects app.
     /// Must only be called once or will panic.
-    pub fn new() -> &'static Self {
+    pub fn new(display: &'static Display<'static>) -> &'static Self {
         make_static!(Self {
             active_effect: Mutex::new(Effects::Balls),
             swap_effect: Signal::new(),
-            balls: Balls::new(),
+            balls: Balls::new(display),
+            is_active: AtomicBool::new(false),
+            display,
         })
     }
+
+    /// Set the active effect from its name.
+    /// Returns `false` if there is no effect with the name.
+    pub async fn set_effect(&self, name: &str) -> bool {
+        let Ok(effect) = Effects::from_str(name) else {
+            return false;
+        };
+
+        *self.active_effect.lock().await = effect;
+        self.swap_effect.signal(true);
+        true
+    }
 }
 
 impl UnicornApp for EffectsApp {
@@ -40,9 +82,13 @@ impl UnicornApp for EffectsApp {
         }
     }
 
-    async fn start(&self) {}
+    async fn start(&self) {
+        self.is_active.store(true, Ordering::Relaxed);
+    }
 
-    async fn stop(&self) {}
+    async fn stop(&self) {
+        self.is_active.store(false, Ordering::Relaxed);
+    }
 
     async fn button_press(&self, _: ButtonPress) {
         let mut ae: embassy_sync::mutex::MutexGuard<'_, ThreadModeRawMutex, Effects> =
@@ -58,56 +104,75 @@ impl UnicornApp for EffectsApp {
 
     async fn process_mqtt_message(&self, _: crate::mqtt::MqttReceiveMessage) {}
 
-    async fn send_mqtt_state(&self) {}
+    /// Send the active effect, or `NO_EFFECT` whilst another app is shown, so the
+    /// home assistant light shows the effect only when it is on the display.
+    async fn send_mqtt_state(&self) {
+        let effect: &'static str = if self.is_active.load(Ordering::Relaxed) {
+            (*self.active_effect.lock().await).into()
+        } else {
+            NO_EFFECT
+        };
+
+        MqttMessage::enqueue_state(EFFECT_STATE_TOPIC, effect).await;
+        self.display.set_light_effect(effect).await;
+    }
 }
 
 mod effects {
-    use embassy_time::{Duration, Instant, Timer};
-    use embedded_graphics_core::{geometry::Point, pixelcolor::Rgb888};
-    use galactic_unicorn_embassy::{HEIGHT, WIDTH};
+    use embassy_time::Instant;
+    use embedded_graphics_core::geometry::Point;
+    use strum_macros::{EnumString, IntoStaticStr};
     use unicorn_graphics::UnicornGraphics;
 
-    use crate::display::messages::DisplayGraphicsMessage;
+    use crate::{
+        board::{HEIGHT, WIDTH},
+        display::{self, messages::DisplayGraphicsMessage, Display, FrameTicker},
+        graphics::palettes::Palette,
+    };
 
     /// All the effects that can be displayed.
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, EnumString, IntoStaticStr)]
+    #[strum(ascii_case_insensitive)]
     pub enum Effects {
         /// The balls effect.
         Balls,
     }
 
-    /// Balls effect.
-    pub struct Balls;
+    impl Effects {
+        /// Every effect, in the order they are listed.
+        pub const ALL: [Effects; 1] = [Effects::Balls];
+    }
+
+    /// Balls effect. Colored from the active palette, or fire if none is chosen.
+    pub struct Balls {
+        /// Reference to the display.
+        display: &'static Display<'static>,
+    }
 
     impl Balls {
         /// Create a new balls effect.
-        pub fn new() -> Self {
-            Self {}
+        pub fn new(display: &'static Display<'static>) -> Self {
+            Self { display }
         }
 
         /// Display the balls effect.
         pub async fn display(&self) {
             let mut graphics: UnicornGraphics<WIDTH, HEIGHT> = UnicornGraphics::new();
-            let mut heat: [[f32; 13]; 53] = [[0.0; 13]; 53];
+            // two extra rows below the display hold the fire seed
+            let mut heat = [[0.0f32; HEIGHT + 2]; WIDTH];
+            let mut ticker = FrameTicker::new();
 
             loop {
-                for y in 0..11 {
-                    for x in 0..53 {
+                let palette = self.display.get_palette().await.unwrap_or(Palette::Fire);
+
+                for y in 0..HEIGHT as i32 {
+                    for x in 0..WIDTH as i32 {
                         let coord = Point { x, y };
 
                         let x = x as usize;
                         let y = y as usize;
-                        if heat[x][y] > 0.5 {
-                            let color = Rgb888::new(255, 255, 180);
-                            graphics.set_pixel(coord, color);
-                        } else if heat[x][y] > 0.4 {
-                            let color = Rgb888::new(220, 160, 0);
-                            graphics.set_pixel(coord, color);
-                        } else if heat[x][y] > 0.3 {
-                            let color = Rgb888::new(180, 50, 0);
-                            graphics.set_pixel(coord, color);
-                        } else if heat[x][y] > 0.2 {
-                            let color = Rgb888::new(40, 40, 40);
+                        if heat[x][y] > 0.2 {
+                            let color = palette.sample((heat[x][y] - 0.2) / 0.3);
                             graphics.set_pixel(coord, color);
                         }
 
@@ -116,7 +181,7 @@ mod effects {
                             heat[x][y] =
                                 (heat[x][y] + heat[x][y + 2] + heat[x][y + 1] + heat[x + 1][y + 1])
                                     / 4.0;
-                        } else if x == 52 {
+                        } else if x == WIDTH - 1 {
                             heat[x][y] =
                                 (heat[x][y] + heat[x][y + 2] + heat[x][y + 1] + heat[x - 1][y + 1])
                                     / 4.0;
@@ -134,28 +199,26 @@ mod effects {
                     }
                 }
 
-                DisplayGraphicsMessage::from_app(graphics.get_pixels(), Duration::from_millis(50))
+                DisplayGraphicsMessage::from_app(graphics.get_pixels(), display::frame_duration())
                     .send()
                     .await;
 
                 // clear the bottom row and then add a new fire seed to it
-                for x in 0..53 {
-                    heat[x as usize][11] = 0.0;
+                for column in heat.iter_mut() {
+                    column[HEIGHT] = 0.0;
                 }
 
                 // add a new random heat source
                 for _ in 0..5 {
                     let ticks = Instant::now().as_ticks();
-                    let px: usize = ticks as usize % 51 + 1;
-                    heat[px][11] = 1.0;
-                    heat[px + 1][11] = 1.0;
-                    heat[px - 1][11] = 1.0;
-                    heat[px][12] = 1.0;
-                    heat[px + 1][12] = 1.0;
-                    heat[px - 1][12] = 1.0;
+                    let px: usize = ticks as usize % (WIDTH - 2) + 1;
+                    for column in &mut heat[px - 1..=px + 1] {
+                        column[HEIGHT] = 1.0;
+                        column[HEIGHT + 1] = 1.0;
+                    }
                 }
 
-                Timer::after_millis(50).await;
+                ticker.next().await;
             }
         }
     }
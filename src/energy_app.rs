@@ -0,0 +1,214 @@
+use core::fmt::Write;
+
+use chrono::Timelike;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Timer};
+use embedded_graphics::{
+    geometry::{Point, Size},
+    pixelcolor::{Rgb888, RgbColor, WebColors},
+    primitives::Rectangle,
+};
+use heapless::String;
+use static_cell::make_static;
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    app::UnicornApp,
+    board::{HEIGHT, WIDTH},
+    buttons::ButtonPress,
+    display::{messages::DisplayGraphicsMessage, Display},
+    fonts::Font,
+    graphics::{
+        colors,
+        graph::{Graph, Style},
+    },
+    mqtt::{
+        topics::{ENERGY_SET_TOPIC, ENERGY_STATE_TOPIC},
+        MqttMessage, MqttReceiveMessage,
+    },
+    time::Time,
+};
+
+/// Number of hourly values, one for each hour of the day from midnight.
+const HOURS: usize = 24;
+
+/// Width of the chart, as wide as the display fits the same width bar for each hour.
+const CHART_WIDTH: u32 = (WIDTH / HOURS * HOURS) as u32;
+
+/// Column of the first bar, so the chart is centred.
+const CHART_X: i32 = (WIDTH as i32 - CHART_WIDTH as i32) / 2;
+
+/// How many of the cheapest hours still to come are highlighted.
+const CHEAPEST_HOURS: usize = 3;
+
+/// Color of the bar for the current hour.
+const CURRENT_COLOR: Rgb888 = Rgb888::WHITE;
+
+/// Color of the bars for the cheapest hours still to come.
+const CHEAPEST_COLOR: Rgb888 = Rgb888::CSS_LIME;
+
+/// How much to dim the bars for hours that have passed.
+const PAST_DIM: f32 = 0.3;
+
+/// Energy app. Show 24 hourly values, such as the electricity price or power used,
+/// as a bar chart with the current hour and the cheapest hours still to come highlighted.
+pub struct EnergyApp {
+    /// Reference to the display.
+    display: &'static Display<'static>,
+
+    /// Reference to the time.
+    time: &'static Time,
+
+    /// The hourly values from midnight, if any have been received.
+    values: Mutex<ThreadModeRawMutex, Option<[f32; HOURS]>>,
+}
+
+impl EnergyApp {
+    /// Create the static ref to energy app.
+    /// Must only be called once or will panic.
+    pub fn new(display: &'static Display<'static>, time: &'static Time) -> &'static Self {
+        make_static!(Self {
+            display,
+            time,
+            values: Mutex::new(None),
+        })
+    }
+
+    /// Set the hourly values from a JSON array of 24 numbers, starting at midnight.
+    async fn set_values(&self, body: &str) {
+        let body = body.trim();
+        let Some(inner) = body
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        else {
+            MqttMessage::enqueue_debug("Invalid energy values, expected a JSON array").await;
+            return;
+        };
+
+        let mut values = [0.0; HOURS];
+        let mut count = 0;
+        for value in inner.split(',') {
+            let Some(slot) = values.get_mut(count) else {
+                count += 1;
+                break;
+            };
+            let Ok(value) = value.trim().parse::<f32>() else {
+                MqttMessage::enqueue_debug("Invalid energy value, expected a number").await;
+                return;
+            };
+            *slot = value;
+            count += 1;
+        }
+
+        if count != HOURS {
+            MqttMessage::enqueue_debug("Invalid energy values, expected 24 hourly values").await;
+            return;
+        }
+
+        *self.values.lock().await = Some(values);
+    }
+}
+
+/// Mark the cheapest hours after `current`.
+fn cheapest_upcoming(values: &[f32; HOURS], current: usize) -> [bool; HOURS] {
+    let mut cheapest = [false; HOURS];
+
+    for _ in 0..CHEAPEST_HOURS {
+        let lowest = (current + 1..HOURS)
+            .filter(|hour| !cheapest[*hour])
+            .min_by(|a, b| values[*a].total_cmp(&values[*b]));
+
+        match lowest {
+            Some(hour) => cheapest[hour] = true,
+            None => break,
+        }
+    }
+
+    cheapest
+}
+
+/// Draw the hourly values as bars, with the current hour and the cheapest hours to come highlighted.
+fn draw_chart(
+    gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+    values: &[f32; HOURS],
+    current: usize,
+    color: Rgb888,
+) {
+    let mut graph = Graph::<HOURS>::new(Style::Bars);
+    graph.extend(values);
+
+    let cheapest = cheapest_upcoming(values, current);
+    let region = Rectangle::new(
+        Point::new(CHART_X, 0),
+        Size::new(CHART_WIDTH, HEIGHT as u32),
+    );
+
+    graph.draw_with(gr, region, |hour, _| {
+        if hour == current {
+            CURRENT_COLOR
+        } else if cheapest[hour] {
+            CHEAPEST_COLOR
+        } else if hour < current {
+            colors::scale(color, PAST_DIM)
+        } else {
+            color
+        }
+    });
+}
+
+impl UnicornApp for EnergyApp {
+    async fn display(&self) {
+        let mut gr = UnicornGraphics::<WIDTH, HEIGHT>::new();
+
+        loop {
+            gr.clear_all();
+
+            let color = self.display.get_color().await;
+            match *self.values.lock().await {
+                Some(values) => {
+                    let current = self.time.now().await.hour() as usize;
+                    draw_chart(&mut gr, &values, current, color);
+                }
+                None => {
+                    let text = "No data";
+                    let x = (WIDTH as i32 - Font::Regular.measure(text) as i32) / 2;
+                    Font::Regular.draw(&mut gr, text, x, HEIGHT as i32 / 2, color);
+                }
+            }
+
+            let duration = Duration::from_secs(1);
+            DisplayGraphicsMessage::from_app(gr.get_pixels(), duration)
+                .send_and_replace_queue()
+                .await;
+            Timer::after(duration).await;
+        }
+    }
+
+    async fn start(&self) {}
+
+    async fn stop(&self) {}
+
+    async fn button_press(&self, _: ButtonPress) {}
+
+    async fn process_mqtt_message(&self, message: MqttReceiveMessage) {
+        if message.topic == ENERGY_SET_TOPIC {
+            self.set_values(&message.body).await;
+        }
+    }
+
+    /// Send the hourly values as a JSON array, or an empty array when none have been received.
+    async fn send_mqtt_state(&self) {
+        let mut text = String::<512>::new();
+        let _ = text.push('[');
+        if let Some(values) = *self.values.lock().await {
+            for (hour, value) in values.iter().enumerate() {
+                if hour > 0 {
+                    let _ = text.push(',');
+                }
+                let _ = write!(text, "{value}");
+            }
+        }
+        let _ = text.push(']');
+        MqttMessage::enqueue_state(ENERGY_STATE_TOPIC, &text).await;
+    }
+}
@@ -0,0 +1,68 @@
+//! Errors on the network path. These are transient, so they are reported and retried
+//! rather than halting the device.
+
+use embassy_net::{dns, tcp::ConnectError};
+use thiserror_no_std::Error;
+
+/// Crate wide error.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    #[error("wifi join failed with status {0}")]
+    WifiJoin(u32),
+    #[error("socket connect failed")]
+    Connect(ConnectError),
+    #[error("mqtt {0}")]
+    Mqtt(&'static str),
+    #[error("dns query failed")]
+    Dns(#[from] dns::Error),
+    #[error("dns returned no address")]
+    DnsEmptyResponse,
+    #[error("can not resolve address")]
+    AddressResolve,
+    #[error("udp bind failed")]
+    UdpBind,
+    #[error("udp send failed")]
+    UdpSend,
+    #[error("udp receive failed")]
+    UdpReceive,
+    #[error("ntp request failed")]
+    Ntp,
+    #[error("can not parse ntp response")]
+    BadNtpResponse,
+    #[error("invalid url")]
+    Url,
+    #[error("bad http response")]
+    Http,
+    #[error("http request timed out")]
+    HttpTimeout,
+    #[error("http response too large")]
+    HttpTooLarge,
+    #[error("http status {0}")]
+    HttpStatus(u16),
+    #[error("unsupported image")]
+    Image,
+    #[error("time out of range")]
+    TimeOutOfRange,
+    #[error("flash error")]
+    Flash,
+    #[error("topic too long")]
+    TopicTooLong,
+    #[error("message too long")]
+    MessageTooLong,
+    #[error("message is not utf-8")]
+    InvalidUtf8,
+    #[error("no publisher available")]
+    Publisher,
+}
+
+impl From<ConnectError> for Error {
+    fn from(err: ConnectError) -> Self {
+        Self::Connect(err)
+    }
+}
+
+impl From<embassy_sync::pubsub::Error> for Error {
+    fn from(_: embassy_sync::pubsub::Error) -> Self {
+        Self::Publisher
+    }
+}
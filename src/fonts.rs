@@ -1,354 +1,843 @@
-use embedded_graphics::{geometry::Point, pixelcolor::Rgb888};
-use galactic_unicorn_embassy::{HEIGHT, WIDTH};
+use core::str::FromStr;
+
+use embedded_graphics::{
+    geometry::Point,
+    mono_font::{
+        ascii::{FONT_4X6, FONT_5X7, FONT_6X10},
+        MonoFont, MonoTextStyle,
+    },
+    pixelcolor::{Rgb888, RgbColor},
+    text::{renderer::TextRenderer, Baseline, Text},
+    Drawable,
+};
+use strum_macros::{EnumString, IntoStaticStr};
 use unicorn_graphics::UnicornGraphics;
 
-/// Trait for drawing text onto a `UnicornGraphics` instance.
-pub trait DrawOntoGraphics {
-    /// Draw self onto the graphics buffer, starting from `start` and in color of `color`.
-    fn draw(&self, gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888);
+use crate::board::{HEIGHT, WIDTH};
+
+/// Width of a glyph in the big font.
+pub const GLYPH_WIDTH: u32 = 6;
+
+/// Height of a glyph in the big font. Fills the full height of the display.
+pub const GLYPH_HEIGHT: usize = 11;
+
+/// Horizontal distance between the start of two glyphs.
+pub const GLYPH_ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// A glyph is stored as one byte per row, with the leftmost pixel in bit 5.
+type Glyph = [u8; GLYPH_HEIGHT];
+
+/// The fonts text can be displayed in.
+#[derive(Clone, Copy, Default, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Font {
+    /// The 6x11 bitmap font, filling the full height of the display.
+    Big,
+
+    /// The 6x10 font.
+    #[default]
+    Regular,
+
+    /// The 5x7 font.
+    Medium,
+
+    /// The 4x6 font.
+    Small,
+
+    /// The proportional tiny font, 5 pixels high.
+    Tiny,
+
+    /// A font converted from the `fonts` directory at build time.
+    #[strum(disabled)]
+    Custom(&'static CustomFont),
 }
 
-impl DrawOntoGraphics for &str {
-    fn draw(&self, gr: &mut UnicornGraphics<WIDTH, HEIGHT>, mut start: u32, color: Rgb888) {
-        for character in self.chars() {
-            character.draw(gr, start, color);
-            start += 7;
+impl Font {
+    /// Get the font called `name`, either one of the built in fonts or a custom font.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::from_str(name)
+            .ok()
+            .or_else(|| CustomFont::get(name).map(Font::Custom))
+    }
+
+    /// Get the largest built in font that fits `text` within `max_width` by `max_height` pixels,
+    /// or the tiny font if none do.
+    #[cfg(any(
+        feature = "clock-app",
+        feature = "pong-app",
+        feature = "timer-app",
+        feature = "weather-app"
+    ))]
+    pub fn largest_fitting(text: &str, max_width: u32, max_height: u32) -> Self {
+        [Font::Big, Font::Regular, Font::Medium, Font::Small]
+            .into_iter()
+            .find(|font| font.measure(text) <= max_width && font.height() <= max_height)
+            .unwrap_or(Font::Tiny)
+    }
+
+    /// Get the name of the font, as accepted by `from_name`.
+    #[cfg(feature = "mqtt-app")]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Font::Big => "big",
+            Font::Regular => "regular",
+            Font::Medium => "medium",
+            Font::Small => "small",
+            Font::Tiny => "tiny",
+            Font::Custom(font) => font.name,
         }
     }
-}
 
-impl DrawOntoGraphics for char {
-    fn draw(&self, gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
+    /// Get the embedded graphics font for the monospaced fonts.
+    fn mono_font(&self) -> Option<&'static MonoFont<'static>> {
         match self {
-            '0' => draw_zero(gr, start, color),
-            '1' => draw_one(gr, start, color),
-            '2' => draw_two(gr, start, color),
-            '3' => draw_three(gr, start, color),
-            '4' => draw_four(gr, start, color),
-            '5' => draw_five(gr, start, color),
-            '6' => draw_six(gr, start, color),
-            '7' => draw_seven(gr, start, color),
-            '8' => draw_eight(gr, start, color),
-            '9' => draw_nine(gr, start, color),
-            _ => draw_eight(gr, start, color),
+            Font::Regular => Some(&FONT_6X10),
+            Font::Medium => Some(&FONT_5X7),
+            Font::Small => Some(&FONT_4X6),
+            Font::Big | Font::Tiny | Font::Custom(_) => None,
         }
     }
-}
 
-/// Draw the number zero.
-fn draw_zero(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start || x == end - 1 {
-                match y {
-                    1..=9 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 1 || x == end - 2 {
-                gr.set_pixel(get_point(x, y), color);
-            } else {
-                if y <= 1 || y >= 9 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
+    /// Measure the width in pixels of `text` in this font.
+    pub fn text_width(&self, text: &str) -> u32 {
+        match self {
+            Font::Big => text.chars().count() as u32 * GLYPH_ADVANCE,
+            Font::Tiny => tiny_text_width(text),
+            Font::Custom(font) => font.text_width(text),
+            _ => {
+                let font = self.mono_font().unwrap();
+                text.chars().count() as u32 * (font.character_size.width + font.character_spacing)
             }
         }
     }
-}
 
-/// Draw the number one.
-fn draw_one(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start || x == start + 1 {
-                match y {
-                    2..=3 => gr.set_pixel(get_point(x, y), color),
-                    9..=11 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 2 {
-                match y {
-                    1..=11 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 3 {
-                match y {
-                    0..=11 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else {
-                match y {
-                    9..=11 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
+    /// Measure the width in pixels that `text` covers when drawn in this font, from the left of
+    /// its first glyph to the right of its last. Unlike `text_width`, trailing spaces and the gap
+    /// after the last glyph are not counted, so this is the width to center and scroll text by.
+    pub fn measure(&self, text: &str) -> u32 {
+        let text = text.trim_end();
+        match self {
+            Font::Big => self
+                .text_width(text)
+                .saturating_sub(GLYPH_ADVANCE - GLYPH_WIDTH),
+            Font::Tiny => tiny_text_width(text),
+            Font::Custom(font) => font.measure(text),
+            _ => {
+                MonoTextStyle::new(self.mono_font().unwrap(), Rgb888::WHITE)
+                    .measure_string(text, Point::zero(), Baseline::Top)
+                    .bounding_box
+                    .size
+                    .width
             }
         }
     }
-}
 
-/// Draw the number two.
-fn draw_two(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if y == 0 {
-                if x > start && x < start + 5 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else if y == 1 {
-                gr.set_pixel(get_point(x, y), color);
-            } else if y == 2 {
-                if x < start + 2 || x > start + 3 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else if y == 3 || y == 4 {
-                if x > start + 3 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else if y == 5 {
-                if x > start + 1 && x < start + 5 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else if y == 6 {
-                if x > start && x < start + 4 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else if y == 7 {
-                if x < start + 3 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else if y == 8 {
-                if x < start + 2 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else {
-                gr.set_pixel(get_point(x, y), color);
+    /// Height of the font in pixels.
+    pub fn height(&self) -> u32 {
+        match self {
+            Font::Big => GLYPH_HEIGHT as u32,
+            Font::Tiny => TINY_GLYPH_HEIGHT,
+            Font::Custom(font) => font.height as u32,
+            _ => self.mono_font().unwrap().character_size.height,
+        }
+    }
+
+    /// Get the longest start of `text` that fits within `max_width` pixels in this font.
+    pub fn fit_text<'a>(&self, text: &'a str, max_width: u32) -> &'a str {
+        let mut end = 0;
+        for (index, character) in text.char_indices() {
+            let next = index + character.len_utf8();
+            if self.measure(&text[..next]) > max_width {
+                break;
             }
+            end = next;
         }
+
+        &text[..end]
     }
-}
 
-/// Draw the number three.
-fn draw_three(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start {
-                match y {
-                    1 | 2 | 8 | 9 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 1 {
-                match y {
-                    0 | 1 | 2 | 5 | 8 | 9 | 10 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == end - 1 {
-                if y != 0 && y != 10 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
-            } else if x == end - 2 {
-                gr.set_pixel(get_point(x, y), color);
-            } else {
-                if y <= 1 || y >= 9 {
-                    gr.set_pixel(get_point(x, y), color);
-                } else if y == 5 {
-                    gr.set_pixel(get_point(x, y), color);
-                }
+    /// Draw `text` in this font starting from `x`, vertically centered on `y`.
+    pub fn draw(
+        &self,
+        gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+        text: &str,
+        x: i32,
+        y: i32,
+        color: Rgb888,
+    ) {
+        match self {
+            Font::Big => draw_big_text(gr, text, x, y - GLYPH_HEIGHT as i32 / 2, color),
+            Font::Tiny => {
+                draw_tiny_text(gr, text, x, y - TINY_GLYPH_HEIGHT as i32 / 2, color);
+            }
+            Font::Custom(font) => font.draw(gr, text, x, y - font.height as i32 / 2, color),
+            _ => {
+                let style = MonoTextStyle::new(self.mono_font().unwrap(), color);
+                Text::with_baseline(text, Point::new(x, y), style, Baseline::Middle)
+                    .draw(gr)
+                    .unwrap();
             }
         }
     }
-}
 
-/// Draw the number four.
-fn draw_four(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start {
-                match y {
-                    4..=7 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 1 {
-                match y {
-                    3..=7 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 2 {
-                match y {
-                    2 | 3 | 6 | 7 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 3 {
-                match y {
-                    1 | 2 | 6 | 7 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else {
-                gr.set_pixel(get_point(x, y), color);
+    /// Draw `text` like `draw`, with `decoration` drawn behind it if one is set.
+    pub fn draw_decorated(
+        &self,
+        gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+        text: &str,
+        x: i32,
+        y: i32,
+        color: Rgb888,
+        decoration: Option<TextDecoration>,
+    ) {
+        if let Some(decoration) = decoration {
+            for (dx, dy) in decoration.offsets() {
+                self.draw(gr, text, x + dx, y + dy, decoration.color());
             }
         }
+
+        self.draw(gr, text, x, y, color);
     }
 }
 
-/// Draw the number five.
-fn draw_five(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start {
-                match y {
-                    0..=4 => gr.set_pixel(get_point(x, y), color),
-                    7..=9 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 1 {
-                match y {
-                    6 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else if x == start + 2 || x == start + 3 {
-                match y {
-                    0 | 1 | 4 | 5 | 9 | 10 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 4 {
-                match y {
-                    2 | 3 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else {
-                match y {
-                    2 | 3 | 4 | 10 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            }
+/// Where text that fits on the display is placed horizontally.
+#[derive(Clone, Copy, Default, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum TextAlign {
+    /// Against the left edge.
+    Left,
+
+    /// In the middle of the display.
+    #[default]
+    Center,
+
+    /// Against the right edge.
+    Right,
+}
+
+impl TextAlign {
+    /// Get the x position to start text `width` pixels wide from.
+    pub fn start_x(&self, width: u32) -> i32 {
+        match self {
+            TextAlign::Left => 0,
+            TextAlign::Center => (WIDTH as i32 - width as i32) / 2,
+            TextAlign::Right => WIDTH as i32 - width as i32,
         }
     }
 }
 
-/// Draw the number six.
-fn draw_six(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start {
-                match y {
-                    1..=9 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 1 {
-                match y {
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else if x == start + 2 || x == start + 3 {
-                match y {
-                    0 | 1 | 4 | 5 | 9 | 10 => gr.set_pixel(get_point(x, y), color),
-                    _ => {}
-                }
-            } else if x == start + 4 {
-                match y {
-                    3 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else {
-                match y {
-                    0 | 3 | 4 | 10 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            }
+/// What to do with text too wide for the display.
+#[derive(Clone, Copy, Default, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum TextOverflow {
+    /// Scroll the text across the display.
+    #[default]
+    Scroll,
+
+    /// Cut the text off at the edge of the display.
+    Truncate,
+
+    /// Cut the text off and end it with `...`.
+    Ellipsis,
+
+    /// Split the text into pages of whole words, shown one after another.
+    Page,
+}
+
+/// Direction scrolling text moves across the display.
+#[derive(Clone, Copy, Default, PartialEq, Eq, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive, serialize_all = "snake_case")]
+pub enum ScrollDirection {
+    /// Scroll in from the right edge and off the left.
+    #[default]
+    RightToLeft,
+
+    /// Scroll in from the left edge and off the right.
+    LeftToRight,
+
+    /// Scroll to the end of the text, then back to the start.
+    Bounce,
+}
+
+/// Decoration drawn behind text to keep it legible over busy backgrounds.
+#[derive(Clone, Copy)]
+pub enum TextDecoration {
+    /// A 1 pixel outline around every glyph in the given color.
+    Outline(Rgb888),
+
+    /// A drop shadow offset 1 pixel down and right in the given color.
+    Shadow(Rgb888),
+}
+
+impl TextDecoration {
+    /// Get the offsets to draw the text at in the decoration color, before drawing the text itself.
+    fn offsets(&self) -> &'static [(i32, i32)] {
+        match self {
+            TextDecoration::Outline(_) => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+            TextDecoration::Shadow(_) => &[(1, 1)],
+        }
+    }
+
+    /// Get the color of the decoration.
+    fn color(&self) -> Rgb888 {
+        match self {
+            TextDecoration::Outline(color) | TextDecoration::Shadow(color) => *color,
         }
     }
 }
 
-/// Draw the number seven.
-fn draw_seven(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        gr.set_pixel(get_point(x, 0), color);
-        gr.set_pixel(get_point(x, 1), color);
-
-        for y in 0..11 {
-            if x == start + 5 {
-                gr.set_pixel(get_point(x, 2), color);
-            } else if x == start + 4 {
-                gr.set_pixel(get_point(x, 2), color);
-                gr.set_pixel(get_point(x, 3), color);
-            } else if x == start + 3 {
-                match y {
-                    0..=2 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else if x == start + 2 {
-                match y {
-                    0..=3 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
+/// A pixel font converted from a BDF file in the `fonts` directory by the build script.
+pub struct CustomFont {
+    /// The name of the font, from the lowercase file name.
+    pub name: &'static str,
+
+    /// Height of the font in pixels.
+    pub height: usize,
+
+    /// The glyphs of the font, sorted by character.
+    pub glyphs: &'static [CustomGlyph],
+}
+
+/// A glyph in a custom font.
+pub struct CustomGlyph {
+    /// The character the glyph is for.
+    pub character: char,
+
+    /// Horizontal distance to the next glyph.
+    pub advance: u32,
+
+    /// The pixel rows of the glyph, top first, with the leftmost pixel in the highest bit.
+    pub rows: &'static [u32],
+}
+
+impl CustomFont {
+    /// Get the custom font called `name`.
+    pub fn get(name: &str) -> Option<&'static Self> {
+        FONTS
+            .iter()
+            .find(|font| font.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Get the glyph for `character`, falling back to a question mark if the font has one.
+    fn glyph(&self, character: char) -> Option<&CustomGlyph> {
+        [character, '?'].into_iter().find_map(|character| {
+            self.glyphs
+                .binary_search_by_key(&character, |glyph| glyph.character)
+                .ok()
+                .map(|index| &self.glyphs[index])
+        })
+    }
+
+    /// Measure the width in pixels of `text` in the font.
+    pub fn text_width(&self, text: &str) -> u32 {
+        text.chars()
+            .filter_map(|character| self.glyph(character))
+            .map(|glyph| glyph.advance)
+            .sum()
+    }
+
+    /// Measure the width in pixels `text` covers in the font, up to the last lit column of its
+    /// last glyph rather than its advance.
+    pub fn measure(&self, text: &str) -> u32 {
+        let mut glyphs = text
+            .chars()
+            .filter_map(|character| self.glyph(character))
+            .peekable();
+
+        let mut width = 0;
+        while let Some(glyph) = glyphs.next() {
+            if glyphs.peek().is_some() {
+                width += glyph.advance;
+                continue;
+            }
+
+            // the rightmost lit column, as the leftmost pixel is in the highest bit
+            let ink = glyph.rows.iter().fold(0, |ink, row| ink | row);
+            width += match ink {
+                0 => glyph.advance,
+                ink => 32 - ink.trailing_zeros(),
+            };
+        }
+
+        width
+    }
+
+    /// Draw `text` in the font with its top left corner at `x` and `y`.
+    pub fn draw(
+        &self,
+        gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+        text: &str,
+        mut x: i32,
+        y: i32,
+        color: Rgb888,
+    ) {
+        for glyph in text.chars().filter_map(|character| self.glyph(character)) {
+            for (row_index, row) in glyph.rows.iter().enumerate() {
+                for column in 0..32 {
+                    if row & (1 << (31 - column)) != 0 {
+                        set_pixel_clipped(
+                            gr,
+                            Point::new(x + column as i32, y + row_index as i32),
+                            color,
+                        );
+                    }
                 }
             }
+
+            x += glyph.advance as i32;
         }
     }
 }
 
-/// Draw the number eight.
-fn draw_eight(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start || x == start + 5 {
-                match y {
-                    0 | 5 | 10 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else if x == start + 1 || x == start + 4 {
-                gr.set_pixel(get_point(x, y), color);
-            } else if x == start + 2 || x == start + 3 {
-                match y {
-                    2 | 3 | 7 | 8 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
+// Generated by the build script from the BDF files in the `fonts` directory.
+include!(concat!(env!("OUT_DIR"), "/custom_fonts.rs"));
+
+/// Trait for drawing text onto a `UnicornGraphics` instance.
+pub trait DrawOntoGraphics {
+    /// Draw self onto the graphics buffer, starting from `start` and in color of `color`.
+    fn draw(&self, gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888);
+}
+
+impl DrawOntoGraphics for &str {
+    fn draw(&self, gr: &mut UnicornGraphics<WIDTH, HEIGHT>, mut start: u32, color: Rgb888) {
+        for character in self.chars() {
+            character.draw(gr, start, color);
+            start += GLYPH_ADVANCE;
+        }
+    }
+}
+
+impl DrawOntoGraphics for char {
+    fn draw(&self, gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
+        draw_big_glyph(gr, glyph(*self), Point::new(start as i32, 0), color);
+    }
+}
+
+/// Draw `text` in the big font with its top left corner at `x` and `y`.
+/// Pixels outside of the display are skipped, so text can be drawn partly off screen.
+pub fn draw_big_text(
+    gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+    text: &str,
+    mut x: i32,
+    y: i32,
+    color: Rgb888,
+) {
+    for character in text.chars() {
+        draw_big_glyph(gr, glyph(character), Point::new(x, y), color);
+        x += GLYPH_ADVANCE as i32;
+    }
+}
+
+/// Draw a big font glyph with its top left corner at `top_left`, skipping pixels outside of the display.
+fn draw_big_glyph(
+    gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+    glyph: &Glyph,
+    top_left: Point,
+    color: Rgb888,
+) {
+    for (y, row) in glyph.iter().enumerate() {
+        for column in 0..GLYPH_WIDTH {
+            if row & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                set_pixel_clipped(gr, top_left + Point::new(column as i32, y as i32), color);
             }
         }
     }
 }
 
-/// Draw the number nine.
-fn draw_nine(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, start: u32, color: Rgb888) {
-    let end = start + 6;
-    for x in start..end {
-        for y in 0..11 {
-            if x == start {
-                match y {
-                    0 | 5..=7 | 10 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else if x == start + 1 {
-                match y {
-                    6 | 7 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else if x == start + 2 || x == start + 3 {
-                match y {
-                    2 | 3 | 6..=8 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
-                }
-            } else if x == start + 4 {
-                gr.set_pixel(get_point(x, y), color);
-            } else if x == start + 5 {
-                match y {
-                    0 | 10 => {}
-                    _ => gr.set_pixel(get_point(x, y), color),
+/// Get the glyph for `character`.
+/// Lowercase letters are drawn as uppercase and unsupported characters as a question mark.
+fn glyph(character: char) -> &'static Glyph {
+    match character.to_ascii_uppercase() {
+        c @ ' '..='_' => &GLYPHS[c as usize - ' ' as usize],
+        '°' => &DEGREE,
+        _ => &GLYPHS['?' as usize - ' ' as usize],
+    }
+}
+
+/// Glyphs for the printable ASCII characters from space to underscore.
+#[rustfmt::skip]
+const GLYPHS: [Glyph; 64] = [
+    // space
+    [0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000],
+    // !
+    [0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b000000, 0b001100, 0b001100],
+    // "
+    [0b110011, 0b110011, 0b110011, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000],
+    // #
+    [0b000000, 0b010010, 0b010010, 0b111111, 0b010010, 0b010010, 0b111111, 0b010010, 0b010010, 0b000000, 0b000000],
+    // $
+    [0b001100, 0b011110, 0b111111, 0b110100, 0b111110, 0b011111, 0b001011, 0b111111, 0b011110, 0b001100, 0b000000],
+    // %
+    [0b000000, 0b000000, 0b110001, 0b110011, 0b000110, 0b001100, 0b011000, 0b110011, 0b100011, 0b000000, 0b000000],
+    // &
+    [0b011100, 0b110110, 0b110110, 0b011100, 0b111100, 0b110111, 0b110010, 0b110110, 0b111110, 0b011011, 0b000000],
+    // apostrophe
+    [0b001100, 0b001100, 0b001100, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000],
+    // (
+    [0b000110, 0b001100, 0b011000, 0b011000, 0b011000, 0b011000, 0b011000, 0b011000, 0b011000, 0b001100, 0b000110],
+    // )
+    [0b011000, 0b001100, 0b000110, 0b000110, 0b000110, 0b000110, 0b000110, 0b000110, 0b000110, 0b001100, 0b011000],
+    // *
+    [0b000000, 0b000000, 0b000000, 0b101101, 0b011110, 0b111111, 0b011110, 0b101101, 0b000000, 0b000000, 0b000000],
+    // +
+    [0b000000, 0b000000, 0b001100, 0b001100, 0b111111, 0b111111, 0b001100, 0b001100, 0b000000, 0b000000, 0b000000],
+    // ,
+    [0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b001100, 0b001100, 0b011000],
+    // -
+    [0b000000, 0b000000, 0b000000, 0b000000, 0b011110, 0b011110, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000],
+    // .
+    [0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b001100, 0b001100],
+    // /
+    [0b000011, 0b000011, 0b000110, 0b000110, 0b001100, 0b001100, 0b001100, 0b011000, 0b011000, 0b110000, 0b110000],
+    // 0
+    [0b011110, 0b111111, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b111111, 0b011110],
+    // 1
+    [0b000100, 0b001100, 0b111100, 0b111100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b111111, 0b111111],
+    // 2
+    [0b011110, 0b111111, 0b110011, 0b000011, 0b000011, 0b001110, 0b011100, 0b111000, 0b110000, 0b111111, 0b111111],
+    // 3
+    [0b011110, 0b111111, 0b110011, 0b000011, 0b000011, 0b011111, 0b000011, 0b000011, 0b110011, 0b111111, 0b011110],
+    // 4
+    [0b000011, 0b000111, 0b001111, 0b011011, 0b110011, 0b110011, 0b111111, 0b111111, 0b000011, 0b000011, 0b000011],
+    // 5
+    [0b111111, 0b111111, 0b110000, 0b110000, 0b111110, 0b011111, 0b000011, 0b110011, 0b110011, 0b111111, 0b011110],
+    // 6
+    [0b011110, 0b111111, 0b110011, 0b110000, 0b111110, 0b111111, 0b110011, 0b110011, 0b110011, 0b111111, 0b011110],
+    // 7
+    [0b111111, 0b111111, 0b000011, 0b000110, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100],
+    // 8
+    [0b011110, 0b111111, 0b110011, 0b110011, 0b111111, 0b011110, 0b111111, 0b110011, 0b110011, 0b111111, 0b011110],
+    // 9
+    [0b011110, 0b111111, 0b110011, 0b110011, 0b111111, 0b011111, 0b000011, 0b000011, 0b110011, 0b111111, 0b011110],
+    // :
+    [0b000000, 0b000000, 0b001100, 0b001100, 0b000000, 0b000000, 0b000000, 0b001100, 0b001100, 0b000000, 0b000000],
+    // ;
+    [0b000000, 0b000000, 0b001100, 0b001100, 0b000000, 0b000000, 0b000000, 0b001100, 0b001100, 0b011000, 0b000000],
+    // <
+    [0b000000, 0b000011, 0b000110, 0b001100, 0b011000, 0b110000, 0b011000, 0b001100, 0b000110, 0b000011, 0b000000],
+    // =
+    [0b000000, 0b000000, 0b000000, 0b111111, 0b111111, 0b000000, 0b111111, 0b111111, 0b000000, 0b000000, 0b000000],
+    // >
+    [0b000000, 0b110000, 0b011000, 0b001100, 0b000110, 0b000011, 0b000110, 0b001100, 0b011000, 0b110000, 0b000000],
+    // ?
+    [0b011110, 0b111111, 0b110011, 0b000011, 0b000110, 0b001100, 0b001100, 0b001100, 0b000000, 0b001100, 0b001100],
+    // @
+    [0b011110, 0b111111, 0b110011, 0b110111, 0b110111, 0b110111, 0b110110, 0b110000, 0b110000, 0b111111, 0b011110],
+    // A
+    [0b011110, 0b111111, 0b110011, 0b110011, 0b110011, 0b111111, 0b111111, 0b110011, 0b110011, 0b110011, 0b110011],
+    // B
+    [0b111110, 0b111111, 0b110011, 0b110011, 0b111110, 0b111110, 0b110011, 0b110011, 0b110011, 0b111111, 0b111110],
+    // C
+    [0b011110, 0b111111, 0b110011, 0b110000, 0b110000, 0b110000, 0b110000, 0b110000, 0b110011, 0b111111, 0b011110],
+    // D
+    [0b111100, 0b111110, 0b110111, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110111, 0b111110, 0b111100],
+    // E
+    [0b111111, 0b111111, 0b110000, 0b110000, 0b111110, 0b111110, 0b110000, 0b110000, 0b110000, 0b111111, 0b111111],
+    // F
+    [0b111111, 0b111111, 0b110000, 0b110000, 0b111110, 0b111110, 0b110000, 0b110000, 0b110000, 0b110000, 0b110000],
+    // G
+    [0b011110, 0b111111, 0b110011, 0b110000, 0b110000, 0b110111, 0b110111, 0b110011, 0b110011, 0b111111, 0b011110],
+    // H
+    [0b110011, 0b110011, 0b110011, 0b110011, 0b111111, 0b111111, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011],
+    // I
+    [0b111111, 0b111111, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b111111, 0b111111],
+    // J
+    [0b001111, 0b001111, 0b000011, 0b000011, 0b000011, 0b000011, 0b000011, 0b110011, 0b110011, 0b111111, 0b011110],
+    // K
+    [0b110011, 0b110011, 0b110110, 0b111100, 0b111000, 0b111000, 0b111100, 0b110110, 0b110011, 0b110011, 0b110011],
+    // L
+    [0b110000, 0b110000, 0b110000, 0b110000, 0b110000, 0b110000, 0b110000, 0b110000, 0b110000, 0b111111, 0b111111],
+    // M
+    [0b100001, 0b110011, 0b111111, 0b111111, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011],
+    // N
+    [0b110011, 0b111011, 0b111011, 0b111111, 0b111111, 0b110111, 0b110111, 0b110011, 0b110011, 0b110011, 0b110011],
+    // O
+    [0b011110, 0b111111, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b111111, 0b011110],
+    // P
+    [0b111110, 0b111111, 0b110011, 0b110011, 0b111111, 0b111110, 0b110000, 0b110000, 0b110000, 0b110000, 0b110000],
+    // Q
+    [0b011110, 0b111111, 0b110011, 0b110011, 0b110011, 0b110011, 0b110101, 0b110111, 0b110011, 0b111111, 0b011101],
+    // R
+    [0b111110, 0b111111, 0b110011, 0b110011, 0b111111, 0b111110, 0b111100, 0b110110, 0b110011, 0b110011, 0b110011],
+    // S
+    [0b011110, 0b111111, 0b110011, 0b110000, 0b111110, 0b011111, 0b000011, 0b000011, 0b110011, 0b111111, 0b011110],
+    // T
+    [0b111111, 0b111111, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100],
+    // U
+    [0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b111111, 0b011110],
+    // V
+    [0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b011110, 0b011110, 0b001100, 0b001100],
+    // W
+    [0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b110011, 0b111111, 0b111111, 0b110011, 0b100001],
+    // X
+    [0b110011, 0b110011, 0b011110, 0b011110, 0b001100, 0b001100, 0b011110, 0b011110, 0b110011, 0b110011, 0b110011],
+    // Y
+    [0b110011, 0b110011, 0b110011, 0b110011, 0b011110, 0b011110, 0b001100, 0b001100, 0b001100, 0b001100, 0b001100],
+    // Z
+    [0b111111, 0b111111, 0b000011, 0b000110, 0b001100, 0b001100, 0b011000, 0b110000, 0b110000, 0b111111, 0b111111],
+    // [
+    [0b011110, 0b011110, 0b011000, 0b011000, 0b011000, 0b011000, 0b011000, 0b011000, 0b011000, 0b011110, 0b011110],
+    // backslash
+    [0b110000, 0b110000, 0b011000, 0b011000, 0b001100, 0b001100, 0b001100, 0b000110, 0b000110, 0b000011, 0b000011],
+    // ]
+    [0b011110, 0b011110, 0b000110, 0b000110, 0b000110, 0b000110, 0b000110, 0b000110, 0b000110, 0b011110, 0b011110],
+    // ^
+    [0b001100, 0b011110, 0b110011, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000],
+    // _
+    [0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b111111, 0b111111],
+];
+
+/// Glyph for the degree sign.
+#[rustfmt::skip]
+const DEGREE: Glyph = [0b011000, 0b100100, 0b100100, 0b011000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000, 0b000000];
+
+/// Height of a glyph in the tiny font.
+pub const TINY_GLYPH_HEIGHT: u32 = 5;
+
+/// A glyph in the proportional tiny font.
+/// Each row is stored in the lowest `width` bits, with the leftmost pixel in the highest bit.
+#[derive(Clone, Copy)]
+pub struct TinyGlyph {
+    /// Width of the glyph in pixels.
+    pub width: u8,
+
+    /// The pixel rows of the glyph, top first.
+    rows: [u8; TINY_GLYPH_HEIGHT as usize],
+}
+
+impl TinyGlyph {
+    /// Create a new tiny glyph.
+    const fn new(width: u8, rows: [u8; TINY_GLYPH_HEIGHT as usize]) -> Self {
+        Self { width, rows }
+    }
+
+    /// Get the glyph for `character`.
+    /// Lowercase letters are drawn as uppercase and unsupported characters as a question mark.
+    pub fn get(character: char) -> &'static Self {
+        match character.to_ascii_uppercase() {
+            c @ ' '..='_' => &TINY_GLYPHS[c as usize - ' ' as usize],
+            '°' => &TINY_DEGREE,
+            _ => &TINY_GLYPHS['?' as usize - ' ' as usize],
+        }
+    }
+
+    /// Draw the glyph with its top left corner at `x` and `y`.
+    /// Pixels outside of the display are skipped, so text can be drawn partly off screen.
+    pub fn draw(&self, gr: &mut UnicornGraphics<WIDTH, HEIGHT>, x: i32, y: i32, color: Rgb888) {
+        for (row_index, row) in self.rows.iter().enumerate() {
+            for column in 0..self.width {
+                if row & (1 << (self.width - 1 - column)) == 0 {
+                    continue;
                 }
+
+                set_pixel_clipped(
+                    gr,
+                    Point::new(x + column as i32, y + row_index as i32),
+                    color,
+                );
             }
         }
     }
 }
 
-/// Get a point with casting from u32 to i32.
-fn get_point(x: u32, y: u32) -> Point {
-    Point {
-        x: x as i32,
-        y: y as i32,
+/// Measure the width in pixels of `text` in the tiny font, including the single pixel gap between glyphs.
+pub fn tiny_text_width(text: &str) -> u32 {
+    let mut width = 0;
+    for (index, character) in text.chars().enumerate() {
+        if index > 0 {
+            width += 1;
+        }
+        width += TinyGlyph::get(character).width as u32;
+    }
+
+    width
+}
+
+/// Get the longest start of `text` that fits within `max_width` pixels in the tiny font.
+pub fn fit_tiny_text(text: &str, max_width: u32) -> &str {
+    let mut width = 0;
+    for (index, character) in text.char_indices() {
+        let glyph_width = TinyGlyph::get(character).width as u32;
+        let needed = if index == 0 {
+            glyph_width
+        } else {
+            width + 1 + glyph_width
+        };
+        if needed > max_width {
+            return &text[..index];
+        }
+        width = needed;
+    }
+
+    text
+}
+
+/// Draw `text` in the tiny font with its top left corner at `x` and `y`.
+/// Returns the x position after the last glyph, which may be off screen.
+pub fn draw_tiny_text(
+    gr: &mut UnicornGraphics<WIDTH, HEIGHT>,
+    text: &str,
+    mut x: i32,
+    y: i32,
+    color: Rgb888,
+) -> i32 {
+    for character in text.chars() {
+        let glyph = TinyGlyph::get(character);
+        glyph.draw(gr, x, y, color);
+        x += glyph.width as i32 + 1;
+    }
+
+    x
+}
+
+/// Glyphs for the tiny font, for the printable ASCII characters from space to underscore.
+#[rustfmt::skip]
+const TINY_GLYPHS: [TinyGlyph; 64] = [
+    // space
+    TinyGlyph::new(2, [0b00, 0b00, 0b00, 0b00, 0b00]),
+    // !
+    TinyGlyph::new(1, [0b1, 0b1, 0b1, 0b0, 0b1]),
+    // "
+    TinyGlyph::new(3, [0b101, 0b101, 0b000, 0b000, 0b000]),
+    // #
+    TinyGlyph::new(4, [0b0101, 0b1111, 0b0101, 0b1111, 0b0101]),
+    // $
+    TinyGlyph::new(3, [0b011, 0b110, 0b010, 0b011, 0b110]),
+    // %
+    TinyGlyph::new(3, [0b101, 0b001, 0b010, 0b100, 0b101]),
+    // &
+    TinyGlyph::new(4, [0b0100, 0b1010, 0b0100, 0b1010, 0b0101]),
+    // apostrophe
+    TinyGlyph::new(1, [0b1, 0b1, 0b0, 0b0, 0b0]),
+    // (
+    TinyGlyph::new(2, [0b01, 0b10, 0b10, 0b10, 0b01]),
+    // )
+    TinyGlyph::new(2, [0b10, 0b01, 0b01, 0b01, 0b10]),
+    // *
+    TinyGlyph::new(3, [0b000, 0b101, 0b010, 0b101, 0b000]),
+    // +
+    TinyGlyph::new(3, [0b000, 0b010, 0b111, 0b010, 0b000]),
+    // ,
+    TinyGlyph::new(2, [0b00, 0b00, 0b00, 0b01, 0b10]),
+    // -
+    TinyGlyph::new(3, [0b000, 0b000, 0b111, 0b000, 0b000]),
+    // .
+    TinyGlyph::new(1, [0b0, 0b0, 0b0, 0b0, 0b1]),
+    // /
+    TinyGlyph::new(3, [0b001, 0b001, 0b010, 0b100, 0b100]),
+    // 0
+    TinyGlyph::new(3, [0b111, 0b101, 0b101, 0b101, 0b111]),
+    // 1
+    TinyGlyph::new(3, [0b010, 0b110, 0b010, 0b010, 0b111]),
+    // 2
+    TinyGlyph::new(3, [0b111, 0b001, 0b111, 0b100, 0b111]),
+    // 3
+    TinyGlyph::new(3, [0b111, 0b001, 0b011, 0b001, 0b111]),
+    // 4
+    TinyGlyph::new(3, [0b101, 0b101, 0b111, 0b001, 0b001]),
+    // 5
+    TinyGlyph::new(3, [0b111, 0b100, 0b111, 0b001, 0b111]),
+    // 6
+    TinyGlyph::new(3, [0b111, 0b100, 0b111, 0b101, 0b111]),
+    // 7
+    TinyGlyph::new(3, [0b111, 0b001, 0b001, 0b010, 0b010]),
+    // 8
+    TinyGlyph::new(3, [0b111, 0b101, 0b111, 0b101, 0b111]),
+    // 9
+    TinyGlyph::new(3, [0b111, 0b101, 0b111, 0b001, 0b111]),
+    // :
+    TinyGlyph::new(1, [0b0, 0b1, 0b0, 0b1, 0b0]),
+    // ;
+    TinyGlyph::new(2, [0b00, 0b01, 0b00, 0b01, 0b10]),
+    // <
+    TinyGlyph::new(3, [0b001, 0b010, 0b100, 0b010, 0b001]),
+    // =
+    TinyGlyph::new(3, [0b000, 0b111, 0b000, 0b111, 0b000]),
+    // >
+    TinyGlyph::new(3, [0b100, 0b010, 0b001, 0b010, 0b100]),
+    // ?
+    TinyGlyph::new(3, [0b111, 0b001, 0b011, 0b000, 0b010]),
+    // @
+    TinyGlyph::new(4, [0b0110, 0b1001, 0b1011, 0b1000, 0b0110]),
+    // A
+    TinyGlyph::new(3, [0b010, 0b101, 0b111, 0b101, 0b101]),
+    // B
+    TinyGlyph::new(3, [0b110, 0b101, 0b110, 0b101, 0b110]),
+    // C
+    TinyGlyph::new(3, [0b011, 0b100, 0b100, 0b100, 0b011]),
+    // D
+    TinyGlyph::new(3, [0b110, 0b101, 0b101, 0b101, 0b110]),
+    // E
+    TinyGlyph::new(3, [0b111, 0b100, 0b110, 0b100, 0b111]),
+    // F
+    TinyGlyph::new(3, [0b111, 0b100, 0b110, 0b100, 0b100]),
+    // G
+    TinyGlyph::new(3, [0b011, 0b100, 0b101, 0b101, 0b011]),
+    // H
+    TinyGlyph::new(3, [0b101, 0b101, 0b111, 0b101, 0b101]),
+    // I
+    TinyGlyph::new(3, [0b111, 0b010, 0b010, 0b010, 0b111]),
+    // J
+    TinyGlyph::new(3, [0b001, 0b001, 0b001, 0b101, 0b010]),
+    // K
+    TinyGlyph::new(3, [0b101, 0b101, 0b110, 0b101, 0b101]),
+    // L
+    TinyGlyph::new(3, [0b100, 0b100, 0b100, 0b100, 0b111]),
+    // M
+    TinyGlyph::new(4, [0b1001, 0b1111, 0b1111, 0b1001, 0b1001]),
+    // N
+    TinyGlyph::new(4, [0b1001, 0b1101, 0b1011, 0b1001, 0b1001]),
+    // O
+    TinyGlyph::new(3, [0b010, 0b101, 0b101, 0b101, 0b010]),
+    // P
+    TinyGlyph::new(3, [0b110, 0b101, 0b110, 0b100, 0b100]),
+    // Q
+    TinyGlyph::new(4, [0b0100, 0b1010, 0b1010, 0b1010, 0b0101]),
+    // R
+    TinyGlyph::new(3, [0b110, 0b101, 0b110, 0b101, 0b101]),
+    // S
+    TinyGlyph::new(3, [0b011, 0b100, 0b010, 0b001, 0b110]),
+    // T
+    TinyGlyph::new(3, [0b111, 0b010, 0b010, 0b010, 0b010]),
+    // U
+    TinyGlyph::new(3, [0b101, 0b101, 0b101, 0b101, 0b111]),
+    // V
+    TinyGlyph::new(3, [0b101, 0b101, 0b101, 0b101, 0b010]),
+    // W
+    TinyGlyph::new(4, [0b1001, 0b1001, 0b1111, 0b1111, 0b1001]),
+    // X
+    TinyGlyph::new(3, [0b101, 0b101, 0b010, 0b101, 0b101]),
+    // Y
+    TinyGlyph::new(3, [0b101, 0b101, 0b010, 0b010, 0b010]),
+    // Z
+    TinyGlyph::new(3, [0b111, 0b001, 0b010, 0b100, 0b111]),
+    // [
+    TinyGlyph::new(2, [0b11, 0b10, 0b10, 0b10, 0b11]),
+    // backslash
+    TinyGlyph::new(3, [0b100, 0b100, 0b010, 0b001, 0b001]),
+    // ]
+    TinyGlyph::new(2, [0b11, 0b01, 0b01, 0b01, 0b11]),
+    // ^
+    TinyGlyph::new(3, [0b010, 0b101, 0b000, 0b000, 0b000]),
+    // _
+    TinyGlyph::new(3, [0b000, 0b000, 0b000, 0b000, 0b111]),
+];
+
+/// Tiny glyph for the degree sign.
+const TINY_DEGREE: TinyGlyph = TinyGlyph::new(3, [0b010, 0b101, 0b010, 0b000, 0b000]);
+
+/// Set the pixel at `point` if it is within the display.
+pub fn set_pixel_clipped(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, point: Point, color: Rgb888) {
+    if (0..WIDTH as i32).contains(&point.x) && (0..HEIGHT as i32).contains(&point.y) {
+        gr.set_pixel(point, color);
     }
 }
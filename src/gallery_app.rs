@@ -0,0 +1,338 @@
+use core::fmt::Write;
+
+use embassy_futures::select::select;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{Duration, Timer};
+use heapless::String;
+use static_cell::make_static;
+use unicorn_graphics::UnicornGraphicsPixels;
+
+use crate::{
+    app::UnicornApp,
+    base64,
+    board::{HEIGHT, WIDTH},
+    buttons::ButtonPress,
+    display::messages::{DisplayGraphicsMessage, DisplayTextMessage},
+    image, json, logging,
+    mqtt::{
+        topics::{
+            GALLERY_DELETE_TOPIC, GALLERY_LIST_TOPIC, GALLERY_SHOW_TOPIC, GALLERY_STATE_TOPIC,
+            GALLERY_UPLOAD_TOPIC,
+        },
+        MqttMessage, MqttReceiveMessage,
+    },
+    storage::{Storage, GALLERY_SLOTS},
+};
+
+/// Size of an image, as raw RGB bytes row by row from the top left.
+const IMAGE_SIZE: usize = WIDTH * HEIGHT * 3;
+
+/// Longest name an image can have, short enough for the list of every name to fit in a message.
+const NAME_SIZE: usize = 24;
+
+/// Marks the start of a slot holding an image.
+const SLOT_MAGIC: &[u8; 4] = b"GUGI";
+
+/// Size of the slot header, the magic, the name length, three reserved bytes and the name.
+const SLOT_HEADER_SIZE: usize = SLOT_MAGIC.len() + 4 + NAME_SIZE;
+
+/// Largest chunk of image data in an upload message once decoded.
+const CHUNK_SIZE: usize = 192;
+
+/// How long each image is shown for in the slideshow.
+const SLIDE_SECS: u64 = 10;
+
+/// How long an image is shown when the show request does not give a duration.
+const DEFAULT_SHOW_SECS: u64 = 10;
+
+/// The longest an image can be shown for.
+const MAX_SHOW_SECS: u64 = 3600;
+
+/// An image being uploaded in chunks.
+struct Upload {
+    name: String<NAME_SIZE>,
+    data: [u8; IMAGE_SIZE],
+
+    /// Bytes received so far, which is the offset the next chunk must start at.
+    received: usize,
+}
+
+/// Gallery app. Show a slideshow of images uploaded over MQTT and saved to flash.
+pub struct GalleryApp {
+    /// Flash storage the images are saved to.
+    storage: &'static Storage,
+
+    /// The image being uploaded.
+    upload: Mutex<ThreadModeRawMutex, Upload>,
+
+    /// The slot of the image shown by the slideshow.
+    current: Mutex<ThreadModeRawMutex, usize>,
+
+    /// Signal to move to the next image on a button press.
+    next: Signal<ThreadModeRawMutex, bool>,
+}
+
+impl GalleryApp {
+    /// Create the static ref to gallery app.
+    /// Must only be called once or will panic.
+    pub fn new(storage: &'static Storage) -> &'static Self {
+        make_static!(Self {
+            storage,
+            upload: Mutex::new(Upload {
+                name: String::new(),
+                data: [0; IMAGE_SIZE],
+                received: 0,
+            }),
+            current: Mutex::new(0),
+            next: Signal::new(),
+        })
+    }
+
+    /// Read the name of the image in `slot`, or `None` if the slot is empty.
+    async fn read_name(&self, slot: usize) -> Option<String<NAME_SIZE>> {
+        let mut header = [0u8; SLOT_HEADER_SIZE];
+        self.storage.read_gallery(slot, 0, &mut header).await.ok()?;
+        if &header[..SLOT_MAGIC.len()] != SLOT_MAGIC {
+            return None;
+        }
+
+        let len = (header[SLOT_MAGIC.len()] as usize).min(NAME_SIZE);
+        let name = &header[SLOT_MAGIC.len() + 4..][..len];
+        String::try_from(core::str::from_utf8(name).ok()?).ok()
+    }
+
+    /// Find the slot of the image called `name`.
+    async fn find(&self, name: &str) -> Option<usize> {
+        for slot in 0..GALLERY_SLOTS {
+            if self
+                .read_name(slot)
+                .await
+                .is_some_and(|slot_name| slot_name == name)
+            {
+                return Some(slot);
+            }
+        }
+
+        None
+    }
+
+    /// Read the image in `slot`.
+    async fn read_image(&self, slot: usize) -> Option<UnicornGraphicsPixels<WIDTH, HEIGHT>> {
+        let mut data = [0u8; IMAGE_SIZE];
+        self.storage
+            .read_gallery(slot, SLOT_HEADER_SIZE as u32, &mut data)
+            .await
+            .ok()?;
+        image::decode(&data).ok()
+    }
+
+    /// Save the image `data` called `name`, replacing any image with the same name.
+    async fn save(&self, name: &str, data: &[u8]) {
+        let mut slot = self.find(name).await;
+        if slot.is_none() {
+            for free in 0..GALLERY_SLOTS {
+                if self.read_name(free).await.is_none() {
+                    slot = Some(free);
+                    break;
+                }
+            }
+        }
+
+        let Some(slot) = slot else {
+            MqttMessage::enqueue_debug("Gallery full, delete an image first").await;
+            return;
+        };
+
+        let mut header = [0u8; SLOT_HEADER_SIZE];
+        header[..SLOT_MAGIC.len()].copy_from_slice(SLOT_MAGIC);
+        header[SLOT_MAGIC.len()] = name.len() as u8;
+        header[SLOT_MAGIC.len() + 4..][..name.len()].copy_from_slice(name.as_bytes());
+
+        // the header is written last, so a torn write leaves the slot empty
+        let result = async {
+            self.storage.erase_gallery(slot).await?;
+            self.storage
+                .write_gallery(slot, SLOT_HEADER_SIZE as u32, data)
+                .await?;
+            self.storage.write_gallery(slot, 0, &header).await
+        }
+        .await;
+
+        match result {
+            Ok(_) => MqttMessage::enqueue_debug("Gallery image saved").await,
+            Err(_) => {
+                logging::warn!("Gallery image could not be saved");
+                MqttMessage::enqueue_debug("Gallery image could not be saved").await;
+            }
+        }
+    }
+
+    /// Add a chunk of an image from JSON such as `{"name": "cat", "offset": 0, "data": "..."}`,
+    /// with the data base64 encoded. Chunks must be sent in order, and the image is saved
+    /// once every byte has been received.
+    async fn receive_chunk(&self, body: &str) {
+        let (Some(name), Some(offset), Some(data)) = (
+            json::extract(body, "name"),
+            json::extract(body, "offset").and_then(|offset| offset.parse::<usize>().ok()),
+            json::extract(body, "data"),
+        ) else {
+            MqttMessage::enqueue_debug("Invalid gallery upload, expected name, offset and data")
+                .await;
+            return;
+        };
+
+        if name.is_empty() || name.len() > NAME_SIZE || name.contains(['"', '\\']) {
+            MqttMessage::enqueue_debug("Invalid gallery image name").await;
+            return;
+        }
+
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let Some(len) = base64::decode(data, &mut chunk) else {
+            MqttMessage::enqueue_debug("Invalid gallery upload data, expected base64").await;
+            return;
+        };
+
+        let mut upload = self.upload.lock().await;
+        if offset == 0 {
+            upload.name = String::try_from(name).unwrap();
+            upload.received = 0;
+        } else if upload.name != name || upload.received != offset {
+            upload.received = 0;
+            MqttMessage::enqueue_debug("Gallery upload out of order, start again from offset 0")
+                .await;
+            return;
+        }
+
+        if offset + len > IMAGE_SIZE {
+            upload.received = 0;
+            MqttMessage::enqueue_debug("Gallery upload larger than the display").await;
+            return;
+        }
+
+        upload.data[offset..offset + len].copy_from_slice(&chunk[..len]);
+        upload.received += len;
+
+        if upload.received == IMAGE_SIZE {
+            upload.received = 0;
+            self.save(name, &upload.data).await;
+            self.send_mqtt_state().await;
+        }
+    }
+
+    /// Delete the image called `name`.
+    async fn delete(&self, name: &str) {
+        let Some(slot) = self.find(name.trim()).await else {
+            MqttMessage::enqueue_debug("Gallery image not found").await;
+            return;
+        };
+
+        if self.storage.erase_gallery(slot).await.is_err() {
+            logging::warn!("Gallery image could not be deleted");
+        }
+
+        self.send_mqtt_state().await;
+    }
+
+    /// Show an image from its name, or from JSON such as `{"name": "cat", "duration": 30}`.
+    async fn show(&self, body: &str) {
+        let name = json::extract(body, "name").unwrap_or(body.trim());
+        let duration = json::extract(body, "duration")
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_SHOW_SECS)
+            .min(MAX_SHOW_SECS);
+
+        if !self.show_image(name, Duration::from_secs(duration)).await {
+            MqttMessage::enqueue_debug("Gallery image not found").await;
+        }
+    }
+
+    /// Show the image called `name` ahead of the active app for `duration`.
+    /// Returns `false` if there is no such image, or it could not be read.
+    pub async fn show_image(&self, name: &str, duration: Duration) -> bool {
+        let Some(slot) = self.find(name).await else {
+            return false;
+        };
+
+        let Some(pixels) = self.read_image(slot).await else {
+            return false;
+        };
+
+        DisplayGraphicsMessage::from_mqtt(pixels, duration)
+            .send()
+            .await;
+        true
+    }
+}
+
+impl UnicornApp for GalleryApp {
+    async fn display(&self) {
+        loop {
+            // find the next image, starting from the current slot
+            let start = *self.current.lock().await;
+            let mut shown = None;
+            for step in 0..GALLERY_SLOTS {
+                let slot = (start + step) % GALLERY_SLOTS;
+                if self.read_name(slot).await.is_none() {
+                    continue;
+                }
+
+                if let Some(pixels) = self.read_image(slot).await {
+                    shown = Some((slot, pixels));
+                    break;
+                }
+            }
+
+            let duration = Duration::from_secs(SLIDE_SECS);
+            match shown {
+                Some((slot, pixels)) => {
+                    *self.current.lock().await = (slot + 1) % GALLERY_SLOTS;
+                    DisplayGraphicsMessage::from_app(pixels, duration)
+                        .send_and_replace_queue()
+                        .await;
+                }
+                None => {
+                    DisplayTextMessage::from_app("No images", None, None, Some(duration))
+                        .send_and_replace_queue()
+                        .await;
+                }
+            }
+
+            select(Timer::after(duration), self.next.wait()).await;
+        }
+    }
+
+    async fn start(&self) {}
+
+    async fn stop(&self) {}
+
+    async fn button_press(&self, _: ButtonPress) {
+        self.next.signal(true);
+    }
+
+    async fn process_mqtt_message(&self, message: MqttReceiveMessage) {
+        match message.topic.as_str() {
+            GALLERY_UPLOAD_TOPIC => self.receive_chunk(&message.body).await,
+            GALLERY_DELETE_TOPIC => self.delete(&message.body).await,
+            GALLERY_SHOW_TOPIC => self.show(&message.body).await,
+            GALLERY_LIST_TOPIC => self.send_mqtt_state().await,
+            _ => {}
+        }
+    }
+
+    /// Send the names of the saved images as a JSON array.
+    async fn send_mqtt_state(&self) {
+        let mut text = String::<{ GALLERY_SLOTS * (NAME_SIZE + 3) + 2 }>::new();
+        let _ = text.push('[');
+        for slot in 0..GALLERY_SLOTS {
+            if let Some(name) = self.read_name(slot).await {
+                if text.len() > 1 {
+                    let _ = text.push(',');
+                }
+                let _ = write!(text, r#""{name}""#);
+            }
+        }
+        let _ = text.push(']');
+
+        MqttMessage::enqueue_state(GALLERY_STATE_TOPIC, &text).await;
+    }
+}
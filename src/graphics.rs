@@ -0,0 +1,922 @@
+//! Helpers for working with graphics on the display.
+
+use embassy_time::Instant;
+use unicorn_graphics::UnicornGraphics;
+
+use crate::board::{HEIGHT, WIDTH};
+
+/// Dim every pixel in `graphics` by `factor`, where 0.0 is off and 1.0 is unchanged.
+#[cfg(any(feature = "energy-app", feature = "timer-app"))]
+pub fn dim(graphics: &mut UnicornGraphics<WIDTH, HEIGHT>, factor: f32) {
+    let mut pixels = graphics.get_pixels();
+    for row in pixels.iter_mut() {
+        for pixel in row.iter_mut() {
+            *pixel = colors::scale(*pixel, factor);
+        }
+    }
+
+    graphics.set_pixels(pixels);
+}
+
+/// Tint every pixel in `graphics` toward warm white by `amount`, where 0.0 is unchanged.
+pub fn warm(graphics: &mut UnicornGraphics<WIDTH, HEIGHT>, amount: f32) {
+    let mut pixels = graphics.get_pixels();
+    for row in pixels.iter_mut() {
+        for pixel in row.iter_mut() {
+            *pixel = colors::warm(*pixel, amount);
+        }
+    }
+
+    graphics.set_pixels(pixels);
+}
+
+/// Blend two buffers together, where a `t` of 0.0 is all `a` and 1.0 is all `b`.
+pub fn blend(
+    a: &UnicornGraphics<WIDTH, HEIGHT>,
+    b: &UnicornGraphics<WIDTH, HEIGHT>,
+    t: f32,
+) -> UnicornGraphics<WIDTH, HEIGHT> {
+    let mut pixels = a.get_pixels();
+    for (row, b_row) in pixels.iter_mut().zip(b.get_pixels().iter()) {
+        for (pixel, b_pixel) in row.iter_mut().zip(b_row.iter()) {
+            *pixel = colors::lerp(*pixel, *b_pixel, t);
+        }
+    }
+
+    let mut result = *a;
+    result.set_pixels(pixels);
+    result
+}
+
+/// A simple xorshift random number generator, good enough for animations.
+pub struct Rng(u32);
+
+impl Rng {
+    /// Create a generator seeded from the time since boot.
+    pub fn new() -> Self {
+        Self(Instant::now().as_ticks() as u32 | 1)
+    }
+
+    /// Get a random number between `low` and `high`.
+    pub fn range(&mut self, low: f32, high: f32) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        low + (high - low) * (self.0 % 10_000) as f32 / 10_000.0
+    }
+}
+
+/// Color parsing and helpers.
+pub mod colors {
+    use core::str::FromStr;
+
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+    use micromath::F32Ext;
+    use thiserror_no_std::Error;
+
+    /// Error when a string can not be parsed into a color.
+    #[derive(Error, Debug)]
+    pub enum ColorParseError {
+        #[error("unknown color name")]
+        UnknownName,
+        #[error("invalid hex color")]
+        InvalidHex,
+        #[error("invalid r,g,b color")]
+        InvalidTriplet,
+    }
+
+    /// A color parsed from a string.
+    ///
+    /// Accepts a CSS color name such as `red` or `teal`, a hex color as `#RRGGBB` or `RRGGBB`,
+    /// or a comma separated `r,g,b` triplet.
+    pub struct Rgb888Str(pub Rgb888);
+
+    impl FromStr for Rgb888Str {
+        type Err = ColorParseError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let s = s.trim();
+
+            if s.contains(',') {
+                return parse_triplet(s).map(Self);
+            }
+
+            if let Some(hex) = s.strip_prefix('#') {
+                return parse_hex(hex).map(Self);
+            }
+
+            match from_name(s) {
+                Some(color) => Ok(Self(color)),
+                None => parse_hex(s)
+                    .map(Self)
+                    .map_err(|_| ColorParseError::UnknownName),
+            }
+        }
+    }
+
+    /// Turn hsv color into `Rgb888`.
+    /// All components are between 0.0 and 1.0.
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Rgb888 {
+        let i = (h * 6.0).floor();
+        let f = h * 6.0 - i;
+        let v = v * 255.0;
+        let p = v * (1.0 - s);
+        let q = v * (1.0 - f * s);
+        let t = v * (1.0 - (1.0 - f) * s);
+
+        let i = i.round() % 6.0;
+        if i == 0.0 {
+            return Rgb888::new(v.round() as u8, t.round() as u8, p.round() as u8);
+        } else if i == 1.0 {
+            return Rgb888::new(q.round() as u8, v.round() as u8, p.round() as u8);
+        } else if i == 2.0 {
+            return Rgb888::new(p.round() as u8, v.round() as u8, t.round() as u8);
+        } else if i == 3.0 {
+            return Rgb888::new(p.round() as u8, q.round() as u8, v.round() as u8);
+        } else if i == 4.0 {
+            return Rgb888::new(t.round() as u8, p.round() as u8, v.round() as u8);
+        } else if i == 5.0 {
+            return Rgb888::new(v.round() as u8, p.round() as u8, q.round() as u8);
+        } else {
+            return Rgb888::new(0, 0, 0);
+        }
+    }
+
+    /// Turn `Rgb888` into hsv color.
+    /// All components are between 0.0 and 1.0.
+    #[cfg(feature = "badge-app")]
+    pub fn to_hsv(color: Rgb888) -> (f32, f32, f32) {
+        let r = color.r() as f32 / 255.0;
+        let g = color.g() as f32 / 255.0;
+        let b = color.b() as f32 / 255.0;
+
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            ((g - b) / delta).rem_euclid(6.0) / 6.0
+        } else if max == g {
+            ((b - r) / delta + 2.0) / 6.0
+        } else {
+            ((r - g) / delta + 4.0) / 6.0
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+
+        (h, s, max)
+    }
+
+    /// Turn hsl color into `Rgb888`.
+    /// All components are between 0.0 and 1.0.
+    #[cfg(any(feature = "clock-app", feature = "effects-app"))]
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Rgb888 {
+        let v = l + s * l.min(1.0 - l);
+        let s = if v == 0.0 { 0.0 } else { 2.0 * (1.0 - l / v) };
+
+        from_hsv(h, s, v)
+    }
+
+    /// Scale the brightness of `color` by scaling each channel by `factor`, clamped to the channel
+    /// range. The hue is kept, so it suits fades, where 0.0 is off and 1.0 is unchanged.
+    pub fn scale(color: Rgb888, factor: f32) -> Rgb888 {
+        let scale = |channel: u8| (channel as f32 * factor).round().clamp(0.0, 255.0) as u8;
+
+        Rgb888::new(scale(color.r()), scale(color.g()), scale(color.b()))
+    }
+
+    /// Linearly interpolate between two colors, where `t` is between 0.0 and 1.0.
+    pub fn lerp(a: Rgb888, b: Rgb888, t: f32) -> Rgb888 {
+        let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+
+        Rgb888::new(lerp(a.r(), b.r()), lerp(a.g(), b.g()), lerp(a.b(), b.b()))
+    }
+
+    /// Tint `color` toward warm white by cutting the blue and some of the green,
+    /// where an `amount` of 0.0 is unchanged and 1.0 is fully warm.
+    pub fn warm(color: Rgb888, amount: f32) -> Rgb888 {
+        let cut = |channel: u8, most: f32| (channel as f32 * (1.0 - most * amount)).round() as u8;
+
+        Rgb888::new(color.r(), cut(color.g(), 0.25), cut(color.b(), 0.6))
+    }
+
+    /// Rotate the hue of `color` by `amount`, where 1.0 is a full turn.
+    #[cfg(feature = "badge-app")]
+    pub fn rotate_hue(color: Rgb888, amount: f32) -> Rgb888 {
+        let (h, s, v) = to_hsv(color);
+
+        from_hsv((h + amount).rem_euclid(1.0), s, v)
+    }
+
+    /// Parse a color from a CSS color name.
+    /// Matching ignores case, spaces, dashes and underscores, so `Rebecca Purple` also matches.
+    pub fn from_name(name: &str) -> Option<Rgb888> {
+        let name = name
+            .bytes()
+            .filter(|b| !matches!(b, b' ' | b'-' | b'_'))
+            .map(|b| b.to_ascii_lowercase());
+
+        CSS_COLORS
+            .binary_search_by(|(css_name, _)| css_name.bytes().cmp(name.clone()))
+            .ok()
+            .map(|index| CSS_COLORS[index].1)
+    }
+
+    /// The CSS color keywords, sorted by name for binary searching.
+    /// Generated from the CSS Color Module Level 4 named colors.
+    const CSS_COLORS: [(&str, Rgb888); 148] = [
+        ("aliceblue", Rgb888::new(240, 248, 255)),
+        ("antiquewhite", Rgb888::new(250, 235, 215)),
+        ("aqua", Rgb888::new(0, 255, 255)),
+        ("aquamarine", Rgb888::new(127, 255, 212)),
+        ("azure", Rgb888::new(240, 255, 255)),
+        ("beige", Rgb888::new(245, 245, 220)),
+        ("bisque", Rgb888::new(255, 228, 196)),
+        ("black", Rgb888::new(0, 0, 0)),
+        ("blanchedalmond", Rgb888::new(255, 235, 205)),
+        ("blue", Rgb888::new(0, 0, 255)),
+        ("blueviolet", Rgb888::new(138, 43, 226)),
+        ("brown", Rgb888::new(165, 42, 42)),
+        ("burlywood", Rgb888::new(222, 184, 135)),
+        ("cadetblue", Rgb888::new(95, 158, 160)),
+        ("chartreuse", Rgb888::new(127, 255, 0)),
+        ("chocolate", Rgb888::new(210, 105, 30)),
+        ("coral", Rgb888::new(255, 127, 80)),
+        ("cornflowerblue", Rgb888::new(100, 149, 237)),
+        ("cornsilk", Rgb888::new(255, 248, 220)),
+        ("crimson", Rgb888::new(220, 20, 60)),
+        ("cyan", Rgb888::new(0, 255, 255)),
+        ("darkblue", Rgb888::new(0, 0, 139)),
+        ("darkcyan", Rgb888::new(0, 139, 139)),
+        ("darkgoldenrod", Rgb888::new(184, 134, 11)),
+        ("darkgray", Rgb888::new(169, 169, 169)),
+        ("darkgreen", Rgb888::new(0, 100, 0)),
+        ("darkgrey", Rgb888::new(169, 169, 169)),
+        ("darkkhaki", Rgb888::new(189, 183, 107)),
+        ("darkmagenta", Rgb888::new(139, 0, 139)),
+        ("darkolivegreen", Rgb888::new(85, 107, 47)),
+        ("darkorange", Rgb888::new(255, 140, 0)),
+        ("darkorchid", Rgb888::new(153, 50, 204)),
+        ("darkred", Rgb888::new(139, 0, 0)),
+        ("darksalmon", Rgb888::new(233, 150, 122)),
+        ("darkseagreen", Rgb888::new(143, 188, 143)),
+        ("darkslateblue", Rgb888::new(72, 61, 139)),
+        ("darkslategray", Rgb888::new(47, 79, 79)),
+        ("darkslategrey", Rgb888::new(47, 79, 79)),
+        ("darkturquoise", Rgb888::new(0, 206, 209)),
+        ("darkviolet", Rgb888::new(148, 0, 211)),
+        ("deeppink", Rgb888::new(255, 20, 147)),
+        ("deepskyblue", Rgb888::new(0, 191, 255)),
+        ("dimgray", Rgb888::new(105, 105, 105)),
+        ("dimgrey", Rgb888::new(105, 105, 105)),
+        ("dodgerblue", Rgb888::new(30, 144, 255)),
+        ("firebrick", Rgb888::new(178, 34, 34)),
+        ("floralwhite", Rgb888::new(255, 250, 240)),
+        ("forestgreen", Rgb888::new(34, 139, 34)),
+        ("fuchsia", Rgb888::new(255, 0, 255)),
+        ("gainsboro", Rgb888::new(220, 220, 220)),
+        ("ghostwhite", Rgb888::new(248, 248, 255)),
+        ("gold", Rgb888::new(255, 215, 0)),
+        ("goldenrod", Rgb888::new(218, 165, 32)),
+        ("gray", Rgb888::new(128, 128, 128)),
+        ("green", Rgb888::new(0, 128, 0)),
+        ("greenyellow", Rgb888::new(173, 255, 47)),
+        ("grey", Rgb888::new(128, 128, 128)),
+        ("honeydew", Rgb888::new(240, 255, 240)),
+        ("hotpink", Rgb888::new(255, 105, 180)),
+        ("indianred", Rgb888::new(205, 92, 92)),
+        ("indigo", Rgb888::new(75, 0, 130)),
+        ("ivory", Rgb888::new(255, 255, 240)),
+        ("khaki", Rgb888::new(240, 230, 140)),
+        ("lavender", Rgb888::new(230, 230, 250)),
+        ("lavenderblush", Rgb888::new(255, 240, 245)),
+        ("lawngreen", Rgb888::new(124, 252, 0)),
+        ("lemonchiffon", Rgb888::new(255, 250, 205)),
+        ("lightblue", Rgb888::new(173, 216, 230)),
+        ("lightcoral", Rgb888::new(240, 128, 128)),
+        ("lightcyan", Rgb888::new(224, 255, 255)),
+        ("lightgoldenrodyellow", Rgb888::new(250, 250, 210)),
+        ("lightgray", Rgb888::new(211, 211, 211)),
+        ("lightgreen", Rgb888::new(144, 238, 144)),
+        ("lightgrey", Rgb888::new(211, 211, 211)),
+        ("lightpink", Rgb888::new(255, 182, 193)),
+        ("lightsalmon", Rgb888::new(255, 160, 122)),
+        ("lightseagreen", Rgb888::new(32, 178, 170)),
+        ("lightskyblue", Rgb888::new(135, 206, 250)),
+        ("lightslategray", Rgb888::new(119, 136, 153)),
+        ("lightslategrey", Rgb888::new(119, 136, 153)),
+        ("lightsteelblue", Rgb888::new(176, 196, 222)),
+        ("lightyellow", Rgb888::new(255, 255, 224)),
+        ("lime", Rgb888::new(0, 255, 0)),
+        ("limegreen", Rgb888::new(50, 205, 50)),
+        ("linen", Rgb888::new(250, 240, 230)),
+        ("magenta", Rgb888::new(255, 0, 255)),
+        ("maroon", Rgb888::new(128, 0, 0)),
+        ("mediumaquamarine", Rgb888::new(102, 205, 170)),
+        ("mediumblue", Rgb888::new(0, 0, 205)),
+        ("mediumorchid", Rgb888::new(186, 85, 211)),
+        ("mediumpurple", Rgb888::new(147, 112, 219)),
+        ("mediumseagreen", Rgb888::new(60, 179, 113)),
+        ("mediumslateblue", Rgb888::new(123, 104, 238)),
+        ("mediumspringgreen", Rgb888::new(0, 250, 154)),
+        ("mediumturquoise", Rgb888::new(72, 209, 204)),
+        ("mediumvioletred", Rgb888::new(199, 21, 133)),
+        ("midnightblue", Rgb888::new(25, 25, 112)),
+        ("mintcream", Rgb888::new(245, 255, 250)),
+        ("mistyrose", Rgb888::new(255, 228, 225)),
+        ("moccasin", Rgb888::new(255, 228, 181)),
+        ("navajowhite", Rgb888::new(255, 222, 173)),
+        ("navy", Rgb888::new(0, 0, 128)),
+        ("oldlace", Rgb888::new(253, 245, 230)),
+        ("olive", Rgb888::new(128, 128, 0)),
+        ("olivedrab", Rgb888::new(107, 142, 35)),
+        ("orange", Rgb888::new(255, 165, 0)),
+        ("orangered", Rgb888::new(255, 69, 0)),
+        ("orchid", Rgb888::new(218, 112, 214)),
+        ("palegoldenrod", Rgb888::new(238, 232, 170)),
+        ("palegreen", Rgb888::new(152, 251, 152)),
+        ("paleturquoise", Rgb888::new(175, 238, 238)),
+        ("palevioletred", Rgb888::new(219, 112, 147)),
+        ("papayawhip", Rgb888::new(255, 239, 213)),
+        ("peachpuff", Rgb888::new(255, 218, 185)),
+        ("peru", Rgb888::new(205, 133, 63)),
+        ("pink", Rgb888::new(255, 192, 203)),
+        ("plum", Rgb888::new(221, 160, 221)),
+        ("powderblue", Rgb888::new(176, 224, 230)),
+        ("purple", Rgb888::new(128, 0, 128)),
+        ("rebeccapurple", Rgb888::new(102, 51, 153)),
+        ("red", Rgb888::new(255, 0, 0)),
+        ("rosybrown", Rgb888::new(188, 143, 143)),
+        ("royalblue", Rgb888::new(65, 105, 225)),
+        ("saddlebrown", Rgb888::new(139, 69, 19)),
+        ("salmon", Rgb888::new(250, 128, 114)),
+        ("sandybrown", Rgb888::new(244, 164, 96)),
+        ("seagreen", Rgb888::new(46, 139, 87)),
+        ("seashell", Rgb888::new(255, 245, 238)),
+        ("sienna", Rgb888::new(160, 82, 45)),
+        ("silver", Rgb888::new(192, 192, 192)),
+        ("skyblue", Rgb888::new(135, 206, 235)),
+        ("slateblue", Rgb888::new(106, 90, 205)),
+        ("slategray", Rgb888::new(112, 128, 144)),
+        ("slategrey", Rgb888::new(112, 128, 144)),
+        ("snow", Rgb888::new(255, 250, 250)),
+        ("springgreen", Rgb888::new(0, 255, 127)),
+        ("steelblue", Rgb888::new(70, 130, 180)),
+        ("tan", Rgb888::new(210, 180, 140)),
+        ("teal", Rgb888::new(0, 128, 128)),
+        ("thistle", Rgb888::new(216, 191, 216)),
+        ("tomato", Rgb888::new(255, 99, 71)),
+        ("turquoise", Rgb888::new(64, 224, 208)),
+        ("violet", Rgb888::new(238, 130, 238)),
+        ("wheat", Rgb888::new(245, 222, 179)),
+        ("white", Rgb888::new(255, 255, 255)),
+        ("whitesmoke", Rgb888::new(245, 245, 245)),
+        ("yellow", Rgb888::new(255, 255, 0)),
+        ("yellowgreen", Rgb888::new(154, 205, 50)),
+    ];
+
+    /// Parse a color from six hex digits, `RRGGBB`.
+    fn parse_hex(hex: &str) -> Result<Rgb888, ColorParseError> {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ColorParseError::InvalidHex);
+        }
+
+        let value = u32::from_str_radix(hex, 16).map_err(|_| ColorParseError::InvalidHex)?;
+        Ok(Rgb888::new(
+            (value >> 16) as u8,
+            (value >> 8) as u8,
+            value as u8,
+        ))
+    }
+
+    /// Parse a color from a comma separated `r,g,b` triplet.
+    fn parse_triplet(triplet: &str) -> Result<Rgb888, ColorParseError> {
+        let mut parts = triplet.split(',').map(|part| part.trim().parse::<u8>());
+
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(r)), Some(Ok(g)), Some(Ok(b)), None) => Ok(Rgb888::new(r, g, b)),
+            _ => Err(ColorParseError::InvalidTriplet),
+        }
+    }
+}
+
+/// Named color palettes that can be sampled as a gradient.
+pub mod palettes {
+    use embedded_graphics::pixelcolor::Rgb888;
+    #[cfg(any(feature = "clock-app", feature = "effects-app"))]
+    use micromath::F32Ext;
+    use strum_macros::{EnumString, IntoStaticStr};
+
+    #[cfg(any(feature = "clock-app", feature = "effects-app"))]
+    use super::colors;
+
+    /// The palettes effects and apps can draw colors from.
+    /// Until one is chosen, each effect and app uses its own, such as fire for the balls effect.
+    #[derive(Clone, Copy, PartialEq, Eq, EnumString, IntoStaticStr)]
+    #[strum(ascii_case_insensitive)]
+    pub enum Palette {
+        /// Every hue at full saturation.
+        Rainbow,
+
+        /// Dark grey through red and orange to pale yellow.
+        Fire,
+
+        /// Deep navy through blue to aqua.
+        Ocean,
+
+        /// Every hue, pale.
+        Pastel,
+
+        /// Dark green through leaf green to yellow green.
+        Forest,
+    }
+
+    #[cfg(any(feature = "clock-app", feature = "effects-app"))]
+    impl Palette {
+        /// Get the colors the gradient passes through, evenly spaced.
+        /// `Rainbow` and `Pastel` are sampled from the hue instead.
+        fn stops(&self) -> &'static [Rgb888] {
+            match self {
+                Palette::Rainbow | Palette::Pastel => &[],
+                Palette::Fire => &[
+                    Rgb888::new(40, 40, 40),
+                    Rgb888::new(180, 50, 0),
+                    Rgb888::new(220, 160, 0),
+                    Rgb888::new(255, 255, 180),
+                ],
+                Palette::Ocean => &[
+                    Rgb888::new(0, 10, 60),
+                    Rgb888::new(0, 60, 180),
+                    Rgb888::new(0, 160, 220),
+                    Rgb888::new(120, 255, 230),
+                ],
+                Palette::Forest => &[
+                    Rgb888::new(0, 50, 10),
+                    Rgb888::new(20, 120, 30),
+                    Rgb888::new(90, 190, 40),
+                    Rgb888::new(200, 230, 60),
+                ],
+            }
+        }
+
+        /// Sample the palette at `fraction` along the gradient, clamped between 0.0 and 1.0.
+        pub fn sample(&self, fraction: f32) -> Rgb888 {
+            let fraction = fraction.clamp(0.0, 1.0);
+            match self {
+                Palette::Rainbow => return colors::from_hsv(fraction, 1.0, 1.0),
+                Palette::Pastel => return colors::from_hsl(fraction, 1.0, 0.8),
+                _ => {}
+            }
+
+            let stops = self.stops();
+
+            let position = fraction * (stops.len() - 1) as f32;
+            let index = (position.floor() as usize).min(stops.len() - 2);
+            let t = position - index as f32;
+
+            colors::lerp(stops[index], stops[index + 1], t)
+        }
+
+        /// Sample the palette for item `index` of `count` items spread evenly along the gradient.
+        #[cfg(feature = "clock-app")]
+        pub fn sample_index(&self, index: usize, count: usize) -> Rgb888 {
+            if count == 0 {
+                return self.sample(0.0);
+            }
+
+            self.sample(index as f32 / count as f32)
+        }
+    }
+}
+
+/// Moving, animated sprites composed over a background.
+#[cfg(feature = "weather-app")]
+pub mod sprites {
+    use embassy_time::{Duration, Instant};
+    use embedded_graphics::{geometry::Point, pixelcolor::Rgb888};
+    use heapless::Vec;
+    use unicorn_graphics::UnicornGraphics;
+
+    use crate::{
+        board::{HEIGHT, WIDTH},
+        icons::{DrawIcon, Icon},
+    };
+
+    /// A sprite drawn from a sequence of icon frames, moving at a fixed velocity.
+    pub struct Sprite {
+        /// The frames of the animation, shown in order and looped.
+        frames: &'static [Icon],
+
+        /// The color to draw the sprite in.
+        color: Rgb888,
+
+        /// The position of the top left corner, kept fractional for slow movement.
+        x: f32,
+        y: f32,
+
+        /// Movement in pixels per second.
+        velocity_x: f32,
+        velocity_y: f32,
+
+        /// How long each frame is shown for.
+        frame_duration: Duration,
+
+        /// When the sprite was created, used to pick the current frame.
+        created: Instant,
+
+        /// When the position was last updated.
+        last_update: Instant,
+    }
+
+    impl Sprite {
+        /// Create a stationary sprite at `position`, changing frame every 200ms.
+        /// `frames` must not be empty.
+        pub fn new(frames: &'static [Icon], color: Rgb888, position: Point) -> Self {
+            let now = Instant::now();
+
+            Self {
+                frames,
+                color,
+                x: position.x as f32,
+                y: position.y as f32,
+                velocity_x: 0.0,
+                velocity_y: 0.0,
+                frame_duration: Duration::from_millis(200),
+                created: now,
+                last_update: now,
+            }
+        }
+
+        /// Move the sprite by `x` and `y` pixels per second.
+        pub fn with_velocity(mut self, x: f32, y: f32) -> Self {
+            self.velocity_x = x;
+            self.velocity_y = y;
+            self
+        }
+
+        /// Show `fps` frames per second.
+        pub fn with_frame_rate(mut self, fps: u32) -> Self {
+            self.frame_duration = Duration::from_millis(1000 / fps.max(1) as u64);
+            self
+        }
+
+        /// Get the current position of the top left corner.
+        pub fn position(&self) -> Point {
+            Point::new(self.x as i32, self.y as i32)
+        }
+
+        /// Get the frame to show now.
+        fn frame(&self) -> &'static Icon {
+            let index = self.created.elapsed().as_millis() / self.frame_duration.as_millis().max(1);
+            &self.frames[index as usize % self.frames.len()]
+        }
+
+        /// Move the sprite by its velocity for the time since the last update.
+        pub fn update(&mut self) {
+            let elapsed = self.last_update.elapsed().as_micros() as f32 / 1_000_000.0;
+            self.last_update = Instant::now();
+
+            self.x += self.velocity_x * elapsed;
+            self.y += self.velocity_y * elapsed;
+        }
+
+        /// Check if any part of the sprite is on the display.
+        pub fn is_on_screen(&self) -> bool {
+            let frame = self.frame();
+            let position = self.position();
+
+            position.x + frame.width as i32 > 0
+                && position.x < WIDTH as i32
+                && position.y + frame.height() as i32 > 0
+                && position.y < HEIGHT as i32
+        }
+
+        /// Draw the current frame at the current position.
+        pub fn draw(&self, graphics: &mut UnicornGraphics<WIDTH, HEIGHT>) {
+            graphics.draw_icon(self.frame(), self.position(), self.color);
+        }
+    }
+
+    /// A group of up to `N` sprites drawn over a background each frame.
+    pub struct SpriteScene<const N: usize> {
+        /// The sprites in the scene, drawn in order.
+        sprites: Vec<Sprite, N>,
+    }
+
+    impl<const N: usize> SpriteScene<N> {
+        /// Create an empty scene.
+        pub fn new() -> Self {
+            Self {
+                sprites: Vec::new(),
+            }
+        }
+
+        /// Add a sprite to the scene. Returns the sprite back if the scene is full.
+        pub fn add(&mut self, sprite: Sprite) -> Result<(), Sprite> {
+            self.sprites.push(sprite)
+        }
+
+        /// Check if the scene has no sprites left.
+        pub fn is_empty(&self) -> bool {
+            self.sprites.is_empty()
+        }
+
+        /// Move every sprite, remove the ones that have left the display
+        /// and draw the rest over `background` into `graphics`.
+        pub fn render(
+            &mut self,
+            background: &UnicornGraphics<WIDTH, HEIGHT>,
+            graphics: &mut UnicornGraphics<WIDTH, HEIGHT>,
+        ) {
+            graphics.set_pixels(background.get_pixels());
+
+            for sprite in self.sprites.iter_mut() {
+                sprite.update();
+            }
+            self.sprites.retain(|sprite| sprite.is_on_screen());
+
+            for sprite in self.sprites.iter() {
+                sprite.draw(graphics);
+            }
+        }
+    }
+}
+
+/// Graphs of a history of values, drawn into a region of the display.
+#[cfg(feature = "energy-app")]
+pub mod graph {
+    use embedded_graphics::{geometry::Point, pixelcolor::Rgb888, primitives::Rectangle};
+    use heapless::HistoryBuffer;
+    use micromath::F32Ext;
+    use unicorn_graphics::UnicornGraphics;
+
+    use crate::board::{HEIGHT, WIDTH};
+
+    /// How a graph draws its values.
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub enum Style {
+        /// A line joining each value, scaled to the lowest and highest values.
+        Line,
+
+        /// A bar for each value, drawn up from zero or down from it for negative values.
+        Bars,
+    }
+
+    /// A graph of the last `N` values, scaled to fit the region it is drawn in.
+    /// The newest value is drawn on the right, so the graph fills from the right as values are added.
+    pub struct Graph<const N: usize> {
+        /// The values, oldest overwritten first once full.
+        values: HistoryBuffer<f32, N>,
+
+        /// How the values are drawn.
+        style: Style,
+    }
+
+    impl<const N: usize> Graph<N> {
+        /// Create an empty graph drawn in `style`.
+        pub fn new(style: Style) -> Self {
+            Self {
+                values: HistoryBuffer::new(),
+                style,
+            }
+        }
+
+        /// Add a value, replacing the oldest once the graph is full.
+        pub fn push(&mut self, value: f32) {
+            self.values.write(value);
+        }
+
+        /// Add each of `values` in order.
+        pub fn extend(&mut self, values: &[f32]) {
+            self.values.extend_from_slice(values);
+        }
+
+        /// Remove every value.
+        pub fn clear(&mut self) {
+            self.values.clear();
+        }
+
+        /// Check if the graph has no values.
+        pub fn is_empty(&self) -> bool {
+            self.values.len() == 0
+        }
+
+        /// Get the lowest and highest values, which bars always stretch to include zero.
+        fn range(&self) -> (f32, f32) {
+            let start = match self.style {
+                Style::Line => (f32::MAX, f32::MIN),
+                Style::Bars => (0.0, 0.0),
+            };
+
+            self.values
+                .oldest_ordered()
+                .fold(start, |(low, high), value| {
+                    (low.min(*value), high.max(*value))
+                })
+        }
+
+        /// Draw the graph into `region` of `graphics` in `color`.
+        pub fn draw(
+            &self,
+            graphics: &mut UnicornGraphics<WIDTH, HEIGHT>,
+            region: Rectangle,
+            color: Rgb888,
+        ) {
+            self.draw_with(graphics, region, |_, _| color);
+        }
+
+        /// Draw the graph into `region` of `graphics`, with the color of each value from `color_of`,
+        /// which is given the index of the value from the oldest and the value.
+        pub fn draw_with(
+            &self,
+            graphics: &mut UnicornGraphics<WIDTH, HEIGHT>,
+            region: Rectangle,
+            color_of: impl Fn(usize, f32) -> Rgb888,
+        ) {
+            if self.is_empty() || region.size.width == 0 || region.size.height == 0 {
+                return;
+            }
+
+            // each value is as wide as fits N across the region, with the newest on the right
+            let step = (region.size.width as usize / N).max(1) as i32;
+            let shown = self
+                .values
+                .len()
+                .min(region.size.width as usize / step as usize);
+            let skipped = self.values.len() - shown;
+            let left = region.top_left.x + region.size.width as i32 - shown as i32 * step;
+
+            let top = region.top_left.y;
+            let bottom = top + region.size.height as i32;
+            let height = region.size.height as f32;
+
+            let (low, high) = self.range();
+            let range = if high > low { high - low } else { 1.0 };
+
+            let mut set_column = |x: i32, from: i32, to: i32, color: Rgb888| {
+                for y in from.max(top)..to.min(bottom) {
+                    for dx in 0..step {
+                        graphics.set_pixel(Point::new(x + dx, y), color);
+                    }
+                }
+            };
+
+            match self.style {
+                Style::Line => {
+                    let row =
+                        |value: f32| top + ((high - value) / range * (height - 1.0)).round() as i32;
+                    let mut previous: Option<i32> = None;
+
+                    for (index, value) in self.values.oldest_ordered().enumerate().skip(skipped) {
+                        let x = left + (index - skipped) as i32 * step;
+                        let y = row(*value);
+
+                        // join to the last value so steep changes stay connected
+                        let (from, to) = match previous {
+                            Some(last) => (y.min(last + 1), y.max(last - 1) + 1),
+                            None => (y, y + 1),
+                        };
+                        set_column(x, from, to, color_of(index, *value));
+                        previous = Some(y);
+                    }
+                }
+                Style::Bars => {
+                    let row = |value: f32| top + ((high - value) / range * height).round() as i32;
+                    let zero = row(0.0);
+
+                    for (index, value) in self.values.oldest_ordered().enumerate().skip(skipped) {
+                        let x = left + (index - skipped) as i32 * step;
+
+                        // always draw at least one pixel inside the region, so every value can be
+                        // seen, even when zero is the top row as every value is zero or less
+                        let (from, to) = if *value >= 0.0 {
+                            (row(*value).min(zero - 1).max(top), zero.max(top + 1))
+                        } else {
+                            (zero, row(*value).max(zero + 1))
+                        };
+                        set_column(x, from, to, color_of(index, *value));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Layers composited into the frame sent to the display.
+pub mod layers {
+    use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+    use strum_macros::{EnumString, IntoStaticStr};
+    use unicorn_graphics::UnicornGraphics;
+
+    use crate::board::{HEIGHT, WIDTH};
+
+    /// The layers of a frame, from bottom to top.
+    #[derive(Clone, Copy, EnumString, IntoStaticStr)]
+    #[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+    pub enum Layer {
+        /// Background effects drawn behind everything else.
+        Background,
+
+        /// The content of the active app or display message.
+        Content,
+
+        /// Icons and status drawn over the content.
+        Overlay,
+    }
+
+    impl Layer {
+        /// Every layer, from bottom to top.
+        pub const ALL: [Layer; LAYER_COUNT] = [Layer::Background, Layer::Content, Layer::Overlay];
+    }
+
+    /// The number of layers.
+    const LAYER_COUNT: usize = 3;
+
+    /// A stack of layers composited bottom to top.
+    /// Black pixels are transparent, other pixels are blended using the layer opacity.
+    pub struct LayerStack {
+        /// The graphics of each layer.
+        layers: [UnicornGraphics<WIDTH, HEIGHT>; LAYER_COUNT],
+
+        /// The opacity of each layer, where 255 is fully opaque.
+        opacity: [u8; LAYER_COUNT],
+
+        /// Whether each layer is drawn.
+        visible: [bool; LAYER_COUNT],
+    }
+
+    impl LayerStack {
+        /// Create a layer stack with only the content layer visible.
+        pub fn new() -> Self {
+            Self {
+                layers: [UnicornGraphics::new(); LAYER_COUNT],
+                opacity: [u8::MAX; LAYER_COUNT],
+                visible: [false, true, false],
+            }
+        }
+
+        /// Get the graphics of `layer`.
+        pub fn get_mut(&mut self, layer: Layer) -> &mut UnicornGraphics<WIDTH, HEIGHT> {
+            &mut self.layers[layer as usize]
+        }
+
+        /// Replace the graphics of `layer` and show it.
+        pub fn set(&mut self, layer: Layer, graphics: &UnicornGraphics<WIDTH, HEIGHT>) {
+            self.layers[layer as usize] = *graphics;
+            self.visible[layer as usize] = true;
+        }
+
+        /// Hide `layer` and clear its graphics.
+        pub fn clear(&mut self, layer: Layer) {
+            self.layers[layer as usize].clear_all();
+            self.visible[layer as usize] = false;
+        }
+
+        /// Get the opacity of `layer`, where 0 is invisible and 255 is fully opaque.
+        pub fn opacity(&self, layer: Layer) -> u8 {
+            self.opacity[layer as usize]
+        }
+
+        /// Set the opacity of `layer`, where 0 is invisible and 255 is fully opaque.
+        pub fn set_opacity(&mut self, layer: Layer, opacity: u8) {
+            self.opacity[layer as usize] = opacity;
+        }
+
+        /// Composite the visible layers into a single frame.
+        pub fn compose(&self) -> UnicornGraphics<WIDTH, HEIGHT> {
+            let mut frame = UnicornGraphics::<WIDTH, HEIGHT>::new();
+            frame.clear_all();
+            let mut pixels = frame.get_pixels();
+
+            for index in 0..LAYER_COUNT {
+                if !self.visible[index] || self.opacity[index] == 0 {
+                    continue;
+                }
+
+                let opacity = self.opacity[index] as u16;
+                let layer = self.layers[index].get_pixels();
+
+                for (row, layer_row) in pixels.iter_mut().zip(layer.iter()) {
+                    for (pixel, layer_pixel) in row.iter_mut().zip(layer_row.iter()) {
+                        if *layer_pixel == Rgb888::BLACK {
+                            continue;
+                        }
+
+                        let blend = |below: u8, above: u8| {
+                            ((below as u16 * (255 - opacity) + above as u16 * opacity) / 255) as u8
+                        };
+                        *pixel = Rgb888::new(
+                            blend(pixel.r(), layer_pixel.r()),
+                            blend(pixel.g(), layer_pixel.g()),
+                            blend(pixel.b(), layer_pixel.b()),
+                        );
+                    }
+                }
+            }
+
+            frame.set_pixels(pixels);
+            frame
+        }
+    }
+}
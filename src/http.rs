@@ -0,0 +1,175 @@
+//! A small HTTP client for fetching data from the local network.
+//!
+//! Requests are plain `http` GETs. The response is read into a buffer given by the caller,
+//! which bounds how large a body can be, and chunked bodies are decoded in place.
+
+use core::fmt::Write;
+
+use embassy_net::{dns::DnsQueryType, tcp::TcpSocket, Stack};
+use embassy_time::{with_timeout, Duration};
+use heapless::String;
+
+use crate::error::Error;
+
+/// How long a request can take, from resolving the host to the end of the body.
+const REQUEST_TIMEOUT_SECS: u64 = 15;
+
+/// Fetch `url` and return the body of the response.
+///
+/// The whole response, headers included, must fit in `buf`. Anything but a `2xx` status is an error.
+pub async fn get<'a>(
+    stack: &'static Stack<cyw43::NetDriver<'static>>,
+    url: &str,
+    buf: &'a mut [u8],
+) -> Result<&'a [u8], Error> {
+    let timeout = Duration::from_secs(REQUEST_TIMEOUT_SECS);
+    let len = with_timeout(timeout, request(stack, url, buf))
+        .await
+        .map_err(|_| Error::HttpTimeout)??;
+
+    let response = &mut buf[..len];
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or(Error::Http)?;
+    let headers = core::str::from_utf8(&response[..header_end]).map_err(|_| Error::Http)?;
+
+    let status = headers
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or(Error::Http)?;
+    if !(200..300).contains(&status) {
+        return Err(Error::HttpStatus(status));
+    }
+
+    let chunked = header(headers, "transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"));
+    let content_length = header(headers, "content-length").and_then(|value| value.parse().ok());
+
+    let body = &mut response[header_end + 4..];
+    if chunked {
+        let len = decode_chunked(body)?;
+        Ok(&body[..len])
+    } else {
+        match content_length {
+            Some(len) if len > body.len() => Err(Error::Http),
+            Some(len) => Ok(&body[..len]),
+            None => Ok(body),
+        }
+    }
+}
+
+/// Send the request and read the response into `buf` until the server closes the connection.
+async fn request(
+    stack: &'static Stack<cyw43::NetDriver<'static>>,
+    url: &str,
+    buf: &mut [u8],
+) -> Result<usize, Error> {
+    let (host, port, path) = parse_url(url).ok_or(Error::Url)?;
+
+    let mut addrs = stack.dns_query(host, DnsQueryType::A).await?;
+    let addr = addrs.pop().ok_or(Error::DnsEmptyResponse)?;
+
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 256];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.connect((addr, port)).await?;
+
+    let mut request = String::<320>::new();
+    write!(
+        request,
+        "GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n"
+    )
+    .map_err(|_| Error::MessageTooLong)?;
+
+    let mut sent = 0;
+    while sent < request.len() {
+        sent += socket
+            .write(&request.as_bytes()[sent..])
+            .await
+            .map_err(|_| Error::Http)?;
+    }
+
+    let mut len = 0;
+    loop {
+        // a full buffer is only too small if the server has more to send
+        if len == buf.len() {
+            match socket.read(&mut [0; 1]).await {
+                Ok(0) => break,
+                Ok(_) => return Err(Error::HttpTooLarge),
+                Err(_) => return Err(Error::Http),
+            }
+        }
+
+        match socket.read(&mut buf[len..]).await {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(_) => return Err(Error::Http),
+        }
+    }
+    socket.close();
+
+    Ok(len)
+}
+
+/// Split `http://host[:port][/path]` into its parts.
+fn parse_url(url: &str) -> Option<(&str, u16, &str)> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (authority, 80),
+    };
+
+    if host.is_empty() {
+        return None;
+    }
+
+    Some((host, port, path))
+}
+
+/// Find the value of the header `name`, ignoring case.
+fn header<'a>(headers: &'a str, name: &str) -> Option<&'a str> {
+    headers.split("\r\n").skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+/// Decode a chunked body in place and return the decoded length.
+fn decode_chunked(body: &mut [u8]) -> Result<usize, Error> {
+    let mut read = 0;
+    let mut written = 0;
+
+    loop {
+        let line_end = body[read..]
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or(Error::Http)?;
+        let line = core::str::from_utf8(&body[read..read + line_end]).map_err(|_| Error::Http)?;
+
+        // chunk extensions follow a semicolon and are ignored
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| Error::Http)?;
+        read += line_end + 2;
+
+        if size == 0 {
+            return Ok(written);
+        }
+
+        if read + size + 2 > body.len() {
+            return Err(Error::Http);
+        }
+
+        body.copy_within(read..read + size, written);
+        written += size;
+        read += size + 2;
+    }
+}
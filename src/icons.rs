@@ -0,0 +1,131 @@
+//! Small single color sprites for use alongside text on the display.
+
+use embedded_graphics::{geometry::Point, pixelcolor::Rgb888};
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    board::{HEIGHT, WIDTH},
+    fonts::set_pixel_clipped,
+};
+
+/// A small sprite.
+/// Each row is stored in the lowest `width` bits, with the leftmost pixel in the highest bit.
+pub struct Icon {
+    /// Width of the icon in pixels.
+    pub width: u32,
+
+    /// The pixel rows of the icon, top first.
+    pub rows: &'static [u8],
+}
+
+impl Icon {
+    /// Create a new icon.
+    pub const fn new(width: u32, rows: &'static [u8]) -> Self {
+        Self { width, rows }
+    }
+
+    /// Height of the icon in pixels.
+    pub fn height(&self) -> u32 {
+        self.rows.len() as u32
+    }
+}
+
+/// Trait for drawing icons onto a graphics buffer.
+pub trait DrawIcon {
+    /// Draw `icon` with its top left corner at `top_left` in the color of `color`.
+    /// Pixels outside of the display are skipped.
+    fn draw_icon(&mut self, icon: &Icon, top_left: Point, color: Rgb888);
+}
+
+impl DrawIcon for UnicornGraphics<WIDTH, HEIGHT> {
+    fn draw_icon(&mut self, icon: &Icon, top_left: Point, color: Rgb888) {
+        for (y, row) in icon.rows.iter().enumerate() {
+            for x in 0..icon.width {
+                if row & (1 << (icon.width - 1 - x)) != 0 {
+                    set_pixel_clipped(self, top_left + Point::new(x as i32, y as i32), color);
+                }
+            }
+        }
+    }
+}
+
+/// Get the icon called `name`, ignoring case.
+pub fn from_name(name: &str) -> Option<&'static Icon> {
+    let icons: [(&str, &'static Icon); 14] = [
+        ("wifi", &WIFI),
+        ("bell", &BELL),
+        ("thermometer", &THERMOMETER),
+        ("sun", &SUN),
+        ("cloud", &CLOUD),
+        ("rain", &RAIN),
+        ("snow", &SNOW),
+        ("storm", &STORM),
+        ("fog", &FOG),
+        ("arrow_up", &ARROW_UP),
+        ("arrow_down", &ARROW_DOWN),
+        ("arrow_left", &ARROW_LEFT),
+        ("arrow_right", &ARROW_RIGHT),
+        ("heart", &HEART),
+    ];
+
+    icons
+        .into_iter()
+        .find(|(icon_name, _)| icon_name.eq_ignore_ascii_case(name))
+        .map(|(_, icon)| icon)
+}
+
+/// WiFi signal.
+#[rustfmt::skip]
+pub const WIFI: Icon = Icon::new(7, &[0b0111110, 0b1000001, 0b0011100, 0b0100010, 0b0000000, 0b0001000]);
+
+/// Notification bell.
+#[rustfmt::skip]
+pub const BELL: Icon = Icon::new(7, &[0b0001000, 0b0011100, 0b0111110, 0b0111110, 0b0111110, 0b1111111, 0b0001000]);
+
+/// Thermometer.
+#[rustfmt::skip]
+pub const THERMOMETER: Icon = Icon::new(5, &[0b00100, 0b01010, 0b01010, 0b01110, 0b11111, 0b11111, 0b01110]);
+
+/// Clear weather.
+#[rustfmt::skip]
+pub const SUN: Icon = Icon::new(7, &[0b1001001, 0b0101010, 0b0011100, 0b1111111, 0b0011100, 0b0101010, 0b1001001]);
+
+/// Cloudy weather.
+#[rustfmt::skip]
+pub const CLOUD: Icon = Icon::new(7, &[0b0000000, 0b0001100, 0b0111110, 0b1111111, 0b1111111, 0b0111110, 0b0000000]);
+
+/// Rainy weather.
+#[rustfmt::skip]
+pub const RAIN: Icon = Icon::new(7, &[0b0001100, 0b0111110, 0b1111111, 0b0111110, 0b0000000, 0b1010100, 0b0101010]);
+
+/// Snowy weather.
+#[rustfmt::skip]
+pub const SNOW: Icon = Icon::new(7, &[0b0001100, 0b0111110, 0b1111111, 0b0111110, 0b0000000, 0b1001001, 0b0010010]);
+
+/// Stormy weather.
+#[rustfmt::skip]
+pub const STORM: Icon = Icon::new(7, &[0b0001100, 0b0111110, 0b1111111, 0b0111110, 0b0001000, 0b0011000, 0b0001000]);
+
+/// Foggy weather.
+#[rustfmt::skip]
+pub const FOG: Icon = Icon::new(7, &[0b0000000, 0b1111111, 0b0000000, 0b1111111, 0b0000000, 0b1111111, 0b0000000]);
+
+/// Arrow pointing up.
+#[rustfmt::skip]
+pub const ARROW_UP: Icon = Icon::new(7, &[0b0001000, 0b0011100, 0b0101010, 0b1001001, 0b0001000, 0b0001000, 0b0001000]);
+
+/// Arrow pointing down.
+#[rustfmt::skip]
+pub const ARROW_DOWN: Icon = Icon::new(7, &[0b0001000, 0b0001000, 0b0001000, 0b1001001, 0b0101010, 0b0011100, 0b0001000]);
+
+/// Arrow pointing left.
+#[rustfmt::skip]
+pub const ARROW_LEFT: Icon = Icon::new(7, &[0b0001000, 0b0010000, 0b0100000, 0b1111111, 0b0100000, 0b0010000, 0b0001000]);
+
+/// Arrow pointing right.
+#[rustfmt::skip]
+pub const ARROW_RIGHT: Icon = Icon::new(7, &[0b0001000, 0b0000100, 0b0000010, 0b1111111, 0b0000010, 0b0000100, 0b0001000]);
+
+/// Heart.
+#[rustfmt::skip]
+pub const HEART: Icon = Icon::new(7, &[0b0110110, 0b1111111, 0b1111111, 0b0111110, 0b0011100, 0b0001000]);
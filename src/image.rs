@@ -0,0 +1,157 @@
+//! Images fetched over HTTP and shown on the display.
+//!
+//! Images must be sized for the panel, either as raw RGB bytes row by row from the top left,
+//! or as an uncompressed 24 bit BMP. Only plain `http` URLs are supported.
+
+use embassy_net::Stack;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embassy_time::Duration;
+use embedded_graphics::pixelcolor::Rgb888;
+use heapless::String;
+use unicorn_graphics::{UnicornGraphics, UnicornGraphicsPixels};
+
+use crate::{
+    board::{HEIGHT, WIDTH},
+    display::messages::DisplayGraphicsMessage,
+    error::Error,
+    http, json, logging,
+    mqtt::MqttMessage,
+};
+
+/// How long an image is shown when the request does not give a duration.
+const DEFAULT_DURATION_SECS: u64 = 10;
+
+/// The longest an image can be shown for.
+const MAX_DURATION_SECS: u64 = 3600;
+
+/// Size of a raw RGB image.
+const RAW_SIZE: usize = WIDTH * HEIGHT * 3;
+
+/// Size of a BMP row, which is padded to a multiple of four bytes.
+const BMP_ROW_SIZE: usize = (WIDTH * 3 + 3) & !3;
+
+/// Room for the BMP file and info headers, including the larger V5 header.
+const BMP_MAX_HEADER_SIZE: usize = 138;
+
+/// Room for the HTTP response headers.
+const MAX_HEADER_SIZE: usize = 1024;
+
+/// The largest response that can be decoded.
+const MAX_RESPONSE_SIZE: usize = MAX_HEADER_SIZE + BMP_MAX_HEADER_SIZE + BMP_ROW_SIZE * HEIGHT;
+
+/// An image waiting to be fetched.
+struct ImageRequest {
+    url: String<192>,
+    duration: Duration,
+    id: Option<String<32>>,
+}
+
+/// Images waiting to be fetched. Only one is fetched at a time.
+static REQUESTS: Channel<ThreadModeRawMutex, ImageRequest, 1> = Channel::new();
+
+/// Queue an image to be fetched.
+///
+/// `body` is either the URL on its own or JSON such as `{"url": "http://...", "duration": 30}`,
+/// optionally with an `id` to publish once the image has been shown.
+pub async fn request(body: &str) {
+    let url = json::extract(body, "url").unwrap_or(body.trim());
+    if !url.starts_with("http://") {
+        MqttMessage::enqueue_debug("Only http image URLs are supported").await;
+        return;
+    }
+
+    let Ok(url) = String::try_from(url) else {
+        MqttMessage::enqueue_debug("Image URL too long").await;
+        return;
+    };
+
+    let duration = json::extract(body, "duration")
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_DURATION_SECS)
+        .min(MAX_DURATION_SECS);
+
+    let request = ImageRequest {
+        url,
+        duration: Duration::from_secs(duration),
+        id: json::extract(body, "id").and_then(|id| String::try_from(id).ok()),
+    };
+    if REQUESTS.try_send(request).is_err() {
+        MqttMessage::enqueue_debug("An image is already loading").await;
+    }
+}
+
+/// Fetch requested images and show them on the display.
+#[embassy_executor::task]
+pub async fn image_task(stack: &'static Stack<cyw43::NetDriver<'static>>) {
+    loop {
+        let request = REQUESTS.receive().await;
+
+        let mut response = [0u8; MAX_RESPONSE_SIZE];
+        match http::get(stack, &request.url, &mut response)
+            .await
+            .and_then(decode)
+        {
+            Ok(pixels) => {
+                DisplayGraphicsMessage::from_mqtt(pixels, request.duration)
+                    .with_id(request.id.as_deref())
+                    .send()
+                    .await
+            }
+            Err(err) => logging::warn!("Image fetch failed: {err}"),
+        }
+    }
+}
+
+/// Decode a BMP or raw RGB image sized for the panel.
+pub fn decode(body: &[u8]) -> Result<UnicornGraphicsPixels<WIDTH, HEIGHT>, Error> {
+    let mut pixels = UnicornGraphics::<WIDTH, HEIGHT>::new().get_pixels();
+
+    if body.starts_with(b"BM") {
+        decode_bmp(body, &mut pixels)?;
+    } else if body.len() == RAW_SIZE {
+        for (index, rgb) in body.chunks_exact(3).enumerate() {
+            pixels[index / WIDTH][index % WIDTH] = Rgb888::new(rgb[0], rgb[1], rgb[2]);
+        }
+    } else {
+        return Err(Error::Image);
+    }
+
+    Ok(pixels)
+}
+
+/// Decode an uncompressed 24 bit BMP into `pixels`.
+fn decode_bmp(body: &[u8], pixels: &mut UnicornGraphicsPixels<WIDTH, HEIGHT>) -> Result<(), Error> {
+    let read_u32 = |offset: usize| -> Result<u32, Error> {
+        body.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .ok_or(Error::Image)
+    };
+
+    let data_offset = read_u32(10)? as usize;
+    let width = read_u32(18)? as i32;
+    let height = read_u32(22)? as i32;
+    let bits_per_pixel = read_u32(28)? & 0xffff;
+    let compression = read_u32(30)?;
+
+    if width != WIDTH as i32
+        || height.unsigned_abs() as usize != HEIGHT
+        || bits_per_pixel != 24
+        || compression != 0
+    {
+        return Err(Error::Image);
+    }
+
+    // rows are stored bottom up unless the height is negative
+    let top_down = height < 0;
+    for row in 0..HEIGHT {
+        let start = data_offset + row * BMP_ROW_SIZE;
+        let data = body.get(start..start + WIDTH * 3).ok_or(Error::Image)?;
+        let y = if top_down { row } else { HEIGHT - 1 - row };
+
+        for (x, bgr) in data.chunks_exact(3).enumerate() {
+            pixels[y][x] = Rgb888::new(bgr[2], bgr[1], bgr[0]);
+        }
+    }
+
+    Ok(())
+}
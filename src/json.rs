@@ -0,0 +1,146 @@
+//! Minimal JSON helpers for pulling values out of MQTT payloads without a full parser.
+
+/// Extract the value at `path` from `json`.
+///
+/// `path` is a JSONPath-lite expression made of object keys and array indexes,
+/// such as `$.temperature`, `$.sensor.values[0]` or `status`.
+/// Strings are returned without their surrounding quotes, any other value is returned as raw JSON text.
+pub fn extract<'a>(json: &'a str, path: &str) -> Option<&'a str> {
+    let path = path.trim();
+    let mut rest = path.strip_prefix('$').unwrap_or(path);
+    let mut value = json.trim();
+
+    while !rest.is_empty() {
+        if let Some(index) = rest.strip_prefix('[') {
+            let end = index.find(']')?;
+            value = array_value(value, index[..end].trim().parse().ok()?)?;
+            rest = &index[end + 1..];
+        } else {
+            let key = rest.strip_prefix('.').unwrap_or(rest);
+            let end = key
+                .find(|c: char| c == '.' || c == '[')
+                .unwrap_or(key.len());
+            value = object_value(value, &key[..end])?;
+            rest = &key[end..];
+        }
+    }
+
+    Some(unquote(value))
+}
+
+/// Remove the surrounding quotes from a JSON string value.
+/// Values that are not strings are returned as is.
+pub fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// Get the raw value of `key` from a JSON object.
+fn object_value<'a>(object: &'a str, key: &str) -> Option<&'a str> {
+    let mut rest = object.strip_prefix('{')?;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with('}') {
+            return None;
+        }
+
+        let key_len = value_len(rest)?;
+        let current_key = unquote(&rest[..key_len]);
+
+        rest = rest[key_len..].trim_start().strip_prefix(':')?.trim_start();
+        let len = value_len(rest).filter(|len| *len > 0)?;
+
+        if current_key == key {
+            return Some(&rest[..len]);
+        }
+
+        rest = rest[len..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest);
+    }
+}
+
+/// Get the raw value at `index` from a JSON array.
+fn array_value(array: &str, index: usize) -> Option<&str> {
+    let mut rest = array.strip_prefix('[')?;
+    let mut i = 0;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with(']') {
+            return None;
+        }
+
+        let len = value_len(rest).filter(|len| *len > 0)?;
+        if i == index {
+            return Some(&rest[..len]);
+        }
+
+        rest = rest[len..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest);
+        i += 1;
+    }
+}
+
+/// Get the length in bytes of the JSON value at the start of `text`.
+fn value_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+
+    match bytes.first()? {
+        b'"' => {
+            let mut escaped = false;
+            for (i, &b) in bytes.iter().enumerate().skip(1) {
+                if escaped {
+                    escaped = false;
+                } else if b == b'\\' {
+                    escaped = true;
+                } else if b == b'"' {
+                    return Some(i + 1);
+                }
+            }
+
+            None
+        }
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut in_string = false;
+            let mut escaped = false;
+            for (i, &b) in bytes.iter().enumerate() {
+                if in_string {
+                    if escaped {
+                        escaped = false;
+                    } else if b == b'\\' {
+                        escaped = true;
+                    } else if b == b'"' {
+                        in_string = false;
+                    }
+
+                    continue;
+                }
+
+                match b {
+                    b'"' => in_string = true,
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            None
+        }
+        _ => Some(
+            bytes
+                .iter()
+                .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+                .unwrap_or(bytes.len()),
+        ),
+    }
+}
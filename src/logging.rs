@@ -0,0 +1,130 @@
+//! Log lines mirrored to MQTT, so a deployed unit can be debugged without attaching a probe.
+//!
+//! Use the `error!`, `warn!` and `info!` macros from this module.
+//! Lines below the runtime level are dropped, as are lines logged whilst the queue is full.
+
+use core::{
+    fmt::{Arguments, Write},
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use heapless::String;
+use strum_macros::{EnumString, IntoStaticStr};
+
+use crate::{
+    diagnostics,
+    mqtt::{
+        topics::{LOG_LEVEL_STATE_TOPIC, LOG_TOPIC},
+        MqttMessage,
+    },
+};
+
+/// The maximum length of a log line.
+const LINE_SIZE: usize = 128;
+
+/// Log lines waiting to be published.
+static LOG_CHANNEL: Channel<ThreadModeRawMutex, String<LINE_SIZE>, 8> = Channel::new();
+
+/// The minimum level of lines to publish.
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Log levels, from most to least verbose.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumString, IntoStaticStr)]
+#[strum(ascii_case_insensitive)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+
+    /// Publish nothing.
+    Off,
+}
+
+impl LogLevel {
+    /// Get the level from its `u8` representation.
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => LogLevel::Debug,
+            1 => LogLevel::Info,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Error,
+            _ => LogLevel::Off,
+        }
+    }
+}
+
+/// Get the current log level.
+pub fn level() -> LogLevel {
+    LogLevel::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+/// Set the log level from an MQTT payload and send the state over MQTT.
+pub async fn set_level(level: &str) {
+    match LogLevel::from_str(level.trim()) {
+        Ok(level) => LEVEL.store(level as u8, Ordering::Relaxed),
+        Err(_) => MqttMessage::enqueue_debug("Invalid log level").await,
+    }
+
+    send_level_state().await;
+}
+
+/// Send the current log level over MQTT.
+pub async fn send_level_state() {
+    let level: &'static str = level().into();
+    MqttMessage::enqueue_state(LOG_LEVEL_STATE_TOPIC, level).await;
+}
+
+/// Queue a log line if `level` is at or above the current level. Never waits.
+pub fn log(level: LogLevel, args: Arguments) {
+    if level == LogLevel::Off || level < self::level() {
+        return;
+    }
+
+    let name: &'static str = level.into();
+    let mut line = String::<LINE_SIZE>::new();
+    let _ = write!(line, "[{name}] {args}");
+
+    if LOG_CHANNEL.try_send(line).is_err() {
+        diagnostics::LOG_DROPPED.increment();
+    }
+}
+
+/// Get the number of log lines waiting to be published.
+pub fn queue_length() -> usize {
+    LOG_CHANNEL.len()
+}
+
+/// Publish queued log lines to the log topic.
+#[embassy_executor::task]
+pub async fn log_transport_task() {
+    loop {
+        let line = LOG_CHANNEL.receive().await;
+        MqttMessage::enqueue_state(LOG_TOPIC, &line).await;
+    }
+}
+
+/// Log a line at the error level.
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Error, format_args!($($arg)*))
+    };
+}
+
+/// Log a line at the warn level.
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Warn, format_args!($($arg)*))
+    };
+}
+
+/// Log a line at the info level.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Info, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {error, info, warn};
@@ -4,40 +4,77 @@
 #![no_main]
 #![feature(type_alias_impl_trait)]
 
+mod alert;
 mod app;
+#[cfg(feature = "badge-app")]
+mod badge_app;
+mod base64;
+mod board;
 mod buttons;
+mod celebration;
+#[cfg(feature = "clock-app")]
 mod clock_app;
 mod config;
+mod console;
+mod diagnostics;
 mod display;
+#[cfg(feature = "effects-app")]
 mod effects_app;
+#[cfg(feature = "energy-app")]
+mod energy_app;
+mod error;
 mod fonts;
+#[cfg(feature = "gallery-app")]
+mod gallery_app;
+mod graphics;
+mod http;
+mod icons;
+mod image;
+mod json;
+mod logging;
 mod mqtt;
+#[cfg(feature = "mqtt-app")]
 mod mqtt_app;
 mod network;
+mod panic;
+mod pending;
+#[cfg(feature = "pong-app")]
+mod pong_app;
+#[cfg(feature = "quotes-app")]
+mod quotes_app;
+mod rules;
+mod self_test;
+mod sensors;
+mod sequence;
+mod settings;
+mod storage;
+mod supervisor;
+mod sync;
 mod system;
 mod system_app;
 mod time;
+#[cfg(feature = "timer-app")]
+mod timer_app;
+#[cfg(feature = "weather-app")]
+mod weather_app;
 
 use display::Display;
 use embassy_executor::Spawner;
 use embassy_rp::gpio::{Input, Pull};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_sync::pubsub::PubSubChannel;
 
-use defmt_rtt as _;
-use galactic_unicorn_embassy::pins::UnicornSensorPins;
-use panic_halt as _;
-
-use galactic_unicorn_embassy::pins::UnicornButtonPins;
-use galactic_unicorn_embassy::pins::UnicornDisplayPins;
-
+use crate::board::{UnicornButtonPins, UnicornDisplayPins, UnicornSensorPins};
 use crate::buttons::{
     brightness_down_task, brightness_up_task, button_a_task, button_b_task, button_c_task,
+    button_d_task, sleep_button_task, volume_down_task, volume_up_task,
 };
-use crate::mqtt::MqttReceiveMessage;
+use crate::mqtt::clients::MqttReceiveChannel;
+use defmt_rtt as _;
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
+    diagnostics::paint_stack();
+
     let p = embassy_rp::init(Default::default());
 
     let display_pins = UnicornDisplayPins {
@@ -67,23 +104,92 @@ async fn main(spawner: Spawner) {
         sleep: Input::new(p.PIN_27, Pull::Up),
     };
 
-    let display = Display::new(p.PIO0, p.DMA_CH0, p.ADC, display_pins, sensor_pins, spawner);
+    let display = Display::new(
+        p.PIO0,
+        p.DMA_CH0,
+        p.ADC,
+        display_pins,
+        sensor_pins,
+        p.CORE1,
+        spawner,
+    );
+    let storage = storage::Storage::new(p.FLASH);
+    settings::load(storage).await;
+
+    // factory reset when brightness up and switch d are held at power on
+    if button_pins.brightness_up.is_low() && button_pins.switch_d.is_low() {
+        system::factory_reset::run(
+            storage,
+            system::factory_reset::POWER_ON_COUNTDOWN_SECS,
+            || button_pins.brightness_up.is_low() && button_pins.switch_d.is_low(),
+        )
+        .await;
+    }
+
+    self_test::run_on_first_boot(storage).await;
 
     let app_state = system::SystemState::new();
-    let system_app = system_app::SystemApp::new();
-    let time = time::Time::new();
+
+    console::start(spawner, p.USB, display, app_state, storage);
+    sensors::start(spawner, p.I2C0, p.PIN_4, p.PIN_5);
+    let system_app = system_app::SystemApp::new(app_state);
+    let time = time::Time::new(storage);
+    time.restore().await;
+    #[cfg(feature = "clock-app")]
     let clock_app = clock_app::ClockApp::new(display, time);
-    let effects_app = effects_app::EffectsApp::new();
-    let mqtt_app = mqtt_app::MqttApp::new();
+    #[cfg(feature = "effects-app")]
+    let effects_app = effects_app::EffectsApp::new(display);
+    #[cfg(feature = "mqtt-app")]
+    let mqtt_app = mqtt_app::MqttApp::new(storage, time);
+    #[cfg(feature = "mqtt-app")]
+    mqtt_app.restore().await;
+    pending::replay(storage, time).await;
+    #[cfg(feature = "weather-app")]
+    let weather_app = weather_app::WeatherApp::new(display);
+    #[cfg(feature = "timer-app")]
+    let timer_app = timer_app::TimerApp::new(display);
+    #[cfg(feature = "gallery-app")]
+    let gallery_app = gallery_app::GalleryApp::new(storage);
+    #[cfg(feature = "pong-app")]
+    let pong_app = pong_app::PongApp::new(display);
+    #[cfg(feature = "quotes-app")]
+    let quotes_app = quotes_app::QuotesApp::new(storage);
+    #[cfg(feature = "quotes-app")]
+    quotes_app.restore().await;
+    #[cfg(feature = "badge-app")]
+    let badge_app = badge_app::BadgeApp::new(display, storage);
+    #[cfg(feature = "badge-app")]
+    badge_app.restore().await;
+    #[cfg(feature = "energy-app")]
+    let energy_app = energy_app::EnergyApp::new(display, time);
 
     let app_controller = app::AppController::new(
         system_app,
+        #[cfg(feature = "clock-app")]
         clock_app,
+        #[cfg(feature = "effects-app")]
         effects_app,
+        #[cfg(feature = "mqtt-app")]
         mqtt_app,
+        #[cfg(feature = "weather-app")]
+        weather_app,
+        #[cfg(feature = "timer-app")]
+        timer_app,
+        #[cfg(feature = "gallery-app")]
+        gallery_app,
+        #[cfg(feature = "pong-app")]
+        pong_app,
+        #[cfg(feature = "quotes-app")]
+        quotes_app,
+        #[cfg(feature = "badge-app")]
+        badge_app,
+        #[cfg(feature = "energy-app")]
+        energy_app,
         app_state,
         spawner,
     );
+    app_controller.restore_badge().await;
+    app_controller.update_clock_ticker().await;
 
     spawner
         .spawn(brightness_up_task(button_pins.brightness_up))
@@ -94,34 +200,88 @@ async fn main(spawner: Spawner) {
     spawner.spawn(button_a_task(button_pins.switch_a)).unwrap();
     spawner.spawn(button_b_task(button_pins.switch_b)).unwrap();
     spawner.spawn(button_c_task(button_pins.switch_c)).unwrap();
+    spawner.spawn(button_d_task(button_pins.switch_d)).unwrap();
+    spawner
+        .spawn(volume_up_task(button_pins.volume_up))
+        .unwrap();
+    spawner
+        .spawn(volume_down_task(button_pins.volume_down))
+        .unwrap();
+    spawner
+        .spawn(sleep_button_task(button_pins.sleep, storage))
+        .unwrap();
 
     let stack = network::create_and_join_network(
         spawner, app_state, p.PIN_23, p.PIN_24, p.PIN_25, p.PIN_29, p.PIO1, p.DMA_CH1,
     )
     .await;
 
-    static MQTT_DISPLAY_CHANNEL: PubSubChannel<ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1> =
-        PubSubChannel::new();
+    static MQTT_DISPLAY_CHANNEL: MqttReceiveChannel = PubSubChannel::new();
+
+    static MQTT_APP_CHANNEL: MqttReceiveChannel = PubSubChannel::new();
+
+    static MQTT_SYSTEM_CHANNEL: MqttReceiveChannel = PubSubChannel::new();
+
+    spawner
+        .spawn(time::ntp::ntp_worker(stack, time, app_state))
+        .unwrap();
+
+    spawner
+        .spawn(system::reboot::daily_reboot_task(time, app_state))
+        .unwrap();
 
-    static MQTT_APP_CHANNEL: PubSubChannel<ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1> =
-        PubSubChannel::new();
+    spawner.spawn(display::warmth_task(display, time)).unwrap();
+    spawner.spawn(sync::sync_task(display, time)).unwrap();
+    spawner
+        .spawn(system::presence::presence_task(display))
+        .unwrap();
+    spawner
+        .spawn(system::quiet_hours::quiet_hours_task(display, time))
+        .unwrap();
 
-    static MQTT_SYSTEM_CHANNEL: PubSubChannel<ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1> =
-        PubSubChannel::new();
+    spawner
+        .spawn(celebration::celebration_task(storage, time))
+        .unwrap();
 
-    spawner.spawn(time::ntp::ntp_worker(stack, time)).unwrap();
+    // restored before the receive client starts, so it subscribes to the topics the rules watch
+    rules::restore(storage).await;
+    spawner
+        .spawn(rules::rules_task(app_controller, display, time))
+        .unwrap();
 
     // mqtt clients
+    spawner.spawn(alert::alert_task(display)).unwrap();
     spawner
-        .spawn(mqtt::clients::mqtt_send_client(stack))
+        .spawn(display::send_acks_task(time, storage))
         .unwrap();
+    spawner.spawn(image::image_task(stack)).unwrap();
+    #[cfg(feature = "weather-app")]
+    spawner
+        .spawn(weather_app::weather_poll_task(stack, weather_app))
+        .unwrap();
+
+    spawner
+        .spawn(mqtt::clients::mqtt_send_client(stack, app_state))
+        .unwrap();
+
+    spawner.spawn(logging::log_transport_task()).unwrap();
+
+    spawner
+        .spawn(supervisor::supervisor_task(p.WATCHDOG))
+        .unwrap();
+
+    // report the panic that caused the last reset, queued until the send client connects
+    if let Some(report) = panic::take_report() {
+        mqtt::MqttMessage::enqueue_debug(&report).await;
+    }
 
     spawner
         .spawn(mqtt::clients::mqtt_receive_client(
             stack,
-            MQTT_DISPLAY_CHANNEL.publisher().unwrap(),
-            MQTT_APP_CHANNEL.publisher().unwrap(),
-            MQTT_SYSTEM_CHANNEL.publisher().unwrap(),
+            app_state,
+            &MQTT_DISPLAY_CHANNEL,
+            &MQTT_APP_CHANNEL,
+            &MQTT_SYSTEM_CHANNEL,
         ))
         .unwrap();
 
@@ -141,6 +301,7 @@ async fn main(spawner: Spawner) {
 
     spawner
         .spawn(system::process_mqtt_messages_task(
+            storage,
             MQTT_SYSTEM_CHANNEL.subscriber().unwrap(),
         ))
         .unwrap();
@@ -10,6 +10,8 @@ use heapless::String;
 use rust_mqtt::packet::v5::publish_packet::QualityOfService;
 use topics::DEBUG_TOPIC;
 
+use crate::{diagnostics, error::Error, logging, settings};
+
 /// MQTT messages channel to be sent to the broker.
 static SEND_CHANNEL: Channel<ThreadModeRawMutex, MutexGuard<ThreadModeRawMutex, MqttMessage>, 4> =
     Channel::new();
@@ -24,7 +26,7 @@ static MESSAGE_POOL: [Mutex<ThreadModeRawMutex, MqttMessage>; 4] = [
 
 /// Message to be sent to the MQTT broker.
 pub struct MqttMessage {
-    topic: &'static str,
+    topic: String<128>,
     text: String<512>,
     qos: QualityOfService,
     retain: bool,
@@ -34,7 +36,7 @@ impl MqttMessage {
     /// Create a new MQTT message.
     const fn new() -> Self {
         MqttMessage {
-            topic: "",
+            topic: String::new(),
             text: String::new(),
             qos: QualityOfService::QoS0,
             retain: false,
@@ -42,16 +44,25 @@ impl MqttMessage {
     }
 
     /// Internal reuse of a MQTT message.
-    fn reuse(&mut self, topic: &'static str, content: &str, qos: QualityOfService, retain: bool) {
-        self.topic = topic;
+    fn reuse(
+        &mut self,
+        topic: &str,
+        content: &str,
+        qos: QualityOfService,
+        retain: bool,
+    ) -> Result<(), Error> {
+        self.topic = expand_topic(topic)?;
         self.text.clear();
-        self.text.push_str(content).unwrap();
+        self.text
+            .push_str(content)
+            .map_err(|_| Error::MessageTooLong)?;
         self.qos = qos;
         self.retain = retain;
+        Ok(())
     }
 
     /// Add a state message into the send queue.
-    pub async fn enqueue_state(topic: &'static str, content: &str) {
+    pub async fn enqueue_state(topic: &str, content: &str) {
         Self::enqueue(topic, content, QualityOfService::QoS0, false).await;
     }
 
@@ -61,14 +72,18 @@ impl MqttMessage {
     }
 
     /// Add a message into the send queue.
-    pub async fn enqueue(topic: &'static str, content: &str, qos: QualityOfService, retain: bool) {
+    /// A message that does not fit is dropped and logged.
+    pub async fn enqueue(topic: &str, content: &str, qos: QualityOfService, retain: bool) {
         let mut queued = false;
         while !queued {
             for msg_mutex in &MESSAGE_POOL {
                 let msg_lock = msg_mutex.try_lock();
                 match msg_lock {
                     Ok(mut message) => {
-                        message.reuse(topic, content, qos, retain);
+                        if let Err(err) = message.reuse(topic, content, qos, retain) {
+                            logging::error!("Dropped message to {topic}: {err}");
+                            return;
+                        }
 
                         SEND_CHANNEL.send(message).await;
                         queued = true;
@@ -79,83 +94,330 @@ impl MqttMessage {
             }
 
             if !queued {
+                diagnostics::MQTT_POOL_WAITS.increment();
                 Timer::after_millis(25).await;
             }
         }
     }
 }
 
+/// Expand a topic starting with the `~` placeholder into the full topic under the base topic.
+/// Any other topic is returned as is.
+pub fn expand_topic(topic: &str) -> Result<String<128>, Error> {
+    let mut expanded = String::new();
+    match topic.strip_prefix(topics::BASE_TOPIC) {
+        Some(rest) => write!(expanded, "{}{rest}", settings::get().base_topic)
+            .map_err(|_| Error::TopicTooLong)?,
+        None => expanded.push_str(topic).map_err(|_| Error::TopicTooLong)?,
+    }
+    Ok(expanded)
+}
+
+/// Get the number of pool messages in use, the pool size and the number of messages waiting to send.
+pub fn send_queue_usage() -> (usize, usize, usize) {
+    let in_use = MESSAGE_POOL
+        .iter()
+        .filter(|message| message.try_lock().is_err())
+        .count();
+
+    (in_use, MESSAGE_POOL.len(), SEND_CHANNEL.len())
+}
+
 /// Message that is received from the MQTT broker.
 #[derive(Clone)]
 pub struct MqttReceiveMessage {
     pub topic: String<64>,
-    pub body: String<64>,
+    pub body: String<256>,
 }
 
 impl MqttReceiveMessage {
     /// Create a new message from the content received.
-    pub fn new(topic: &str, body_bytes: &[u8]) -> Self {
+    /// The body is truncated if it exceeds the maximum size.
+    /// A topic under the base topic is shortened to start with the `~` placeholder,
+    /// so it can be compared with the constants in `topics`.
+    pub fn new(topic: &str, body_bytes: &[u8]) -> Result<Self, Error> {
         let mut h_topic = heapless::String::<64>::new();
-        write!(h_topic, "{topic}").unwrap();
+        match topic
+            .strip_prefix(settings::get().base_topic.as_str())
+            .filter(|rest| rest.starts_with('/'))
+        {
+            Some(rest) => write!(h_topic, "{}{rest}", topics::BASE_TOPIC),
+            None => write!(h_topic, "{topic}"),
+        }
+        .map_err(|_| Error::TopicTooLong)?;
 
-        let body = core::str::from_utf8(body_bytes).unwrap();
-        let mut h_body = heapless::String::<64>::new();
-        write!(h_body, "{body}").unwrap();
+        let body = core::str::from_utf8(body_bytes).map_err(|_| Error::InvalidUtf8)?;
+        let mut h_body = heapless::String::<256>::new();
+        for c in body.chars() {
+            if h_body.push(c).is_err() {
+                break;
+            }
+        }
 
-        Self {
+        Ok(Self {
             topic: h_topic,
             body: h_body,
-        }
+        })
     }
 }
 
 pub mod topics {
-    use crate::config::*;
     use constcat::concat;
 
+    /// Placeholder for the base topic, which is set at runtime.
+    /// Topics are expanded when sent or subscribed to, and received topics are shortened back,
+    /// so they can be compared with the constants here.
+    pub const BASE_TOPIC: &str = "~";
+
     pub(super) const SET: &str = "set";
     pub(super) const STATE: &str = "state";
     pub(super) const STATUS: &str = "status";
 
-    pub(super) const DEBUG_TOPIC: &str = concat!(BASE_MQTT_TOPIC, "/debug");
+    pub(super) const DEBUG_TOPIC: &str = concat!(BASE_TOPIC, "/debug");
 
-    pub const BRIGHTNESS_BASE_TOPIC: &str = concat!(BASE_MQTT_TOPIC, "/display/brightness");
+    pub const BRIGHTNESS_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/brightness");
     pub const BRIGHTNESS_SET_TOPIC: &str = concat!(BRIGHTNESS_BASE_TOPIC, "/", SET);
     pub const BRIGHTNESS_STATE_TOPIC: &str = concat!(BRIGHTNESS_BASE_TOPIC, "/", STATE);
 
-    pub const AUTO_BRIGHTNESS_BASE_TOPIC: &str =
-        concat!(BASE_MQTT_TOPIC, "/display/auto_brightness");
+    pub const AUTO_BRIGHTNESS_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/auto_brightness");
     pub const AUTO_BRIGHTNESS_SET_TOPIC: &str = concat!(AUTO_BRIGHTNESS_BASE_TOPIC, "/", SET);
     pub const AUTO_BRIGHTNESS_STATE_TOPIC: &str = concat!(AUTO_BRIGHTNESS_BASE_TOPIC, "/", STATE);
 
-    pub const RGB_BASE_TOPIC: &str = concat!(BASE_MQTT_TOPIC, "/display/rgb");
+    pub const LIGHT_LEVEL_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/light_level");
+    pub const LIGHT_LEVEL_STATE_TOPIC: &str = concat!(LIGHT_LEVEL_BASE_TOPIC, "/", STATE);
+
+    pub const RGB_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/rgb");
     pub const RGB_SET_TOPIC: &str = concat!(RGB_BASE_TOPIC, "/", SET);
     pub const RGB_STATE_TOPIC: &str = concat!(RGB_BASE_TOPIC, "/", STATE);
 
-    pub const TEXT_BASE_TOPIC: &str = concat!(BASE_MQTT_TOPIC, "/app/text");
+    pub const LIGHT_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/light");
+    pub const LIGHT_SET_TOPIC: &str = concat!(LIGHT_BASE_TOPIC, "/", SET);
+    pub const LIGHT_STATE_TOPIC: &str = concat!(LIGHT_BASE_TOPIC, "/", STATE);
+
+    pub const FPS_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/fps");
+    pub const FPS_SET_TOPIC: &str = concat!(FPS_BASE_TOPIC, "/", SET);
+    pub const FPS_STATE_TOPIC: &str = concat!(FPS_BASE_TOPIC, "/", STATE);
+
+    pub const SCROLL_SPEED_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/scroll_speed");
+    pub const SCROLL_SPEED_SET_TOPIC: &str = concat!(SCROLL_SPEED_BASE_TOPIC, "/", SET);
+    pub const SCROLL_SPEED_STATE_TOPIC: &str = concat!(SCROLL_SPEED_BASE_TOPIC, "/", STATE);
+
+    pub const WARMTH_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/warmth");
+    pub const WARMTH_SET_TOPIC: &str = concat!(WARMTH_BASE_TOPIC, "/", SET);
+    pub const WARMTH_STATE_TOPIC: &str = concat!(WARMTH_BASE_TOPIC, "/", STATE);
+
+    pub const SYNC_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/sync");
+    pub const SYNC_SET_TOPIC: &str = concat!(SYNC_BASE_TOPIC, "/", SET);
+
+    pub const LAYER_OPACITY_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/layer_opacity");
+    pub const LAYER_OPACITY_SET_TOPIC: &str = concat!(LAYER_OPACITY_BASE_TOPIC, "/", SET);
+    pub const LAYER_OPACITY_STATE_TOPIC: &str = concat!(LAYER_OPACITY_BASE_TOPIC, "/", STATE);
+
+    pub const PALETTE_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/palette");
+    pub const PALETTE_SET_TOPIC: &str = concat!(PALETTE_BASE_TOPIC, "/", SET);
+    pub const PALETTE_STATE_TOPIC: &str = concat!(PALETTE_BASE_TOPIC, "/", STATE);
+
+    pub const PENDING_INDICATOR_BASE_TOPIC: &str =
+        concat!(BASE_TOPIC, "/display/pending_indicator");
+    pub const PENDING_INDICATOR_SET_TOPIC: &str = concat!(PENDING_INDICATOR_BASE_TOPIC, "/", SET);
+    pub const PENDING_INDICATOR_STATE_TOPIC: &str =
+        concat!(PENDING_INDICATOR_BASE_TOPIC, "/", STATE);
+
+    pub const SCROLL_DIRECTION_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/scroll_direction");
+    pub const SCROLL_DIRECTION_SET_TOPIC: &str = concat!(SCROLL_DIRECTION_BASE_TOPIC, "/", SET);
+    pub const SCROLL_DIRECTION_STATE_TOPIC: &str = concat!(SCROLL_DIRECTION_BASE_TOPIC, "/", STATE);
+
+    #[cfg(feature = "mqtt-app")]
+    pub const PERSISTENT_TEXT_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/persistent_text");
+    #[cfg(feature = "mqtt-app")]
+    pub const PERSISTENT_TEXT_SET_TOPIC: &str = concat!(PERSISTENT_TEXT_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "mqtt-app")]
+    pub const PERSISTENT_TEXT_STATE_TOPIC: &str = concat!(PERSISTENT_TEXT_BASE_TOPIC, "/", STATE);
+
+    pub const DISPLAY_ACK_TOPIC: &str = concat!(BASE_TOPIC, "/display/ack");
+
+    pub const ALERT_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/alert");
+    pub const ALERT_SET_TOPIC: &str = concat!(ALERT_BASE_TOPIC, "/", SET);
+
+    pub const IMAGE_URL_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/display/image_url");
+    pub const IMAGE_URL_SET_TOPIC: &str = concat!(IMAGE_URL_BASE_TOPIC, "/", SET);
+
+    pub const STREAM_TOPIC: &str = concat!(BASE_TOPIC, "/display/stream");
+
+    pub const TEXT_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/text");
+    #[cfg(feature = "mqtt-app")]
     pub const TEXT_SET_TOPIC: &str = concat!(TEXT_BASE_TOPIC, "/", SET);
+    pub const TEXT_CLEAR_TOPIC: &str = concat!(TEXT_BASE_TOPIC, "/clear");
 
-    pub const APP_BASE_TOPIC: &str = concat!(BASE_MQTT_TOPIC, "/app");
+    /// Matches the text set topic and any sub topics, which are used as the message source.
+    #[cfg(feature = "mqtt-app")]
+    pub const TEXT_SET_SOURCE_TOPIC: &str = concat!(TEXT_SET_TOPIC, "/#");
+
+    #[cfg(feature = "mqtt-app")]
+    pub const TEXT_TEMPLATE_BASE_TOPIC: &str = concat!(TEXT_BASE_TOPIC, "/template");
+    #[cfg(feature = "mqtt-app")]
+    pub const TEXT_TEMPLATE_SET_TOPIC: &str = concat!(TEXT_TEMPLATE_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "mqtt-app")]
+    pub const TEXT_TEMPLATE_STATE_TOPIC: &str = concat!(TEXT_TEMPLATE_BASE_TOPIC, "/", STATE);
+
+    pub const APP_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app");
     pub const APP_SET_TOPIC: &str = concat!(APP_BASE_TOPIC, "/", SET);
     pub const APP_STATE_TOPIC: &str = concat!(APP_BASE_TOPIC, "/", STATE);
 
-    pub const CLOCK_APP_BASE_TOPIC: &str = concat!(BASE_MQTT_TOPIC, "/app/clock");
+    #[cfg(feature = "effects-app")]
+    pub const EFFECT_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/effect");
+    #[cfg(feature = "effects-app")]
+    pub const EFFECT_SET_TOPIC: &str = concat!(EFFECT_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "effects-app")]
+    pub const EFFECT_STATE_TOPIC: &str = concat!(EFFECT_BASE_TOPIC, "/", STATE);
+
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_APP_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/clock");
+    #[cfg(feature = "clock-app")]
     pub const CLOCK_APP_SET_TOPIC: &str = concat!(CLOCK_APP_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "clock-app")]
     pub const CLOCK_APP_STATE_TOPIC: &str = concat!(CLOCK_APP_BASE_TOPIC, "/", STATE);
-
-    pub const NTP_SYNC_TOPIC: &str = concat!(BASE_MQTT_TOPIC, "/system/ntp/sync");
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_CHIME_BASE_TOPIC: &str = concat!(CLOCK_APP_BASE_TOPIC, "/chime");
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_CHIME_SET_TOPIC: &str = concat!(CLOCK_CHIME_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_CHIME_STATE_TOPIC: &str = concat!(CLOCK_CHIME_BASE_TOPIC, "/", STATE);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_CHIME_HOURS_BASE_TOPIC: &str = concat!(CLOCK_CHIME_BASE_TOPIC, "/hours");
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_CHIME_HOURS_SET_TOPIC: &str = concat!(CLOCK_CHIME_HOURS_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_CHIME_HOURS_STATE_TOPIC: &str =
+        concat!(CLOCK_CHIME_HOURS_BASE_TOPIC, "/", STATE);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_FORMAT_BASE_TOPIC: &str = concat!(CLOCK_APP_BASE_TOPIC, "/format");
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_FORMAT_SET_TOPIC: &str = concat!(CLOCK_FORMAT_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_FORMAT_STATE_TOPIC: &str = concat!(CLOCK_FORMAT_BASE_TOPIC, "/", STATE);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_LAYOUT_BASE_TOPIC: &str = concat!(CLOCK_APP_BASE_TOPIC, "/layout");
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_LAYOUT_SET_TOPIC: &str = concat!(CLOCK_LAYOUT_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_LAYOUT_STATE_TOPIC: &str = concat!(CLOCK_LAYOUT_BASE_TOPIC, "/", STATE);
+    #[cfg(feature = "clock-app")]
+    pub const CLOCK_TICKER_SET_TOPIC: &str = concat!(CLOCK_APP_BASE_TOPIC, "/ticker/", SET);
+
+    #[cfg(feature = "gallery-app")]
+    pub const GALLERY_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/gallery");
+    #[cfg(feature = "gallery-app")]
+    pub const GALLERY_UPLOAD_TOPIC: &str = concat!(GALLERY_BASE_TOPIC, "/upload");
+    #[cfg(feature = "gallery-app")]
+    pub const GALLERY_DELETE_TOPIC: &str = concat!(GALLERY_BASE_TOPIC, "/delete");
+    #[cfg(feature = "gallery-app")]
+    pub const GALLERY_SHOW_TOPIC: &str = concat!(GALLERY_BASE_TOPIC, "/show");
+    #[cfg(feature = "gallery-app")]
+    pub const GALLERY_LIST_TOPIC: &str = concat!(GALLERY_BASE_TOPIC, "/list");
+    #[cfg(feature = "gallery-app")]
+    pub const GALLERY_STATE_TOPIC: &str = concat!(GALLERY_BASE_TOPIC, "/", STATE);
+
+    #[cfg(feature = "quotes-app")]
+    pub const QUOTES_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/quotes");
+    #[cfg(feature = "quotes-app")]
+    pub const QUOTES_ADD_TOPIC: &str = concat!(QUOTES_BASE_TOPIC, "/add");
+    #[cfg(feature = "quotes-app")]
+    pub const QUOTES_REMOVE_TOPIC: &str = concat!(QUOTES_BASE_TOPIC, "/remove");
+    #[cfg(feature = "quotes-app")]
+    pub const QUOTES_STATE_TOPIC: &str = concat!(QUOTES_BASE_TOPIC, "/", STATE);
+    #[cfg(feature = "quotes-app")]
+    pub const QUOTES_INTERVAL_BASE_TOPIC: &str = concat!(QUOTES_BASE_TOPIC, "/interval");
+    #[cfg(feature = "quotes-app")]
+    pub const QUOTES_INTERVAL_SET_TOPIC: &str = concat!(QUOTES_INTERVAL_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "quotes-app")]
+    pub const QUOTES_INTERVAL_STATE_TOPIC: &str = concat!(QUOTES_INTERVAL_BASE_TOPIC, "/", STATE);
+
+    #[cfg(feature = "badge-app")]
+    pub const BADGE_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/badge");
+    #[cfg(feature = "badge-app")]
+    pub const BADGE_SET_TOPIC: &str = concat!(BADGE_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "badge-app")]
+    pub const BADGE_STATE_TOPIC: &str = concat!(BADGE_BASE_TOPIC, "/", STATE);
+
+    #[cfg(feature = "energy-app")]
+    pub const ENERGY_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/energy");
+    #[cfg(feature = "energy-app")]
+    pub const ENERGY_SET_TOPIC: &str = concat!(ENERGY_BASE_TOPIC, "/", SET);
+    #[cfg(feature = "energy-app")]
+    pub const ENERGY_STATE_TOPIC: &str = concat!(ENERGY_BASE_TOPIC, "/", STATE);
+
+    pub const SEQUENCE_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/app/sequence");
+    pub const SEQUENCE_SET_TOPIC: &str = concat!(SEQUENCE_BASE_TOPIC, "/", SET);
+    pub const SEQUENCE_STOP_TOPIC: &str = concat!(SEQUENCE_BASE_TOPIC, "/stop");
+    pub const SEQUENCE_STATE_TOPIC: &str = concat!(SEQUENCE_BASE_TOPIC, "/", STATE);
+
+    pub const NTP_SYNC_TOPIC: &str = concat!(BASE_TOPIC, "/system/ntp/sync");
+
+    pub const SENSORS_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/sensors");
+    pub const SENSORS_STATE_TOPIC: &str = concat!(SENSORS_BASE_TOPIC, "/", STATE);
+
+    pub const HEALTH_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/system/health");
+    pub const HEALTH_STATE_TOPIC: &str = concat!(HEALTH_BASE_TOPIC, "/", STATE);
+
+    pub const DIAG_TOPIC: &str = concat!(BASE_TOPIC, "/system/diag");
+    pub const DIAG_STATE_TOPIC: &str = concat!(DIAG_TOPIC, "/", STATE);
+
+    pub const REBOOT_TIME_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/system/reboot_time");
+    pub const REBOOT_TIME_SET_TOPIC: &str = concat!(REBOOT_TIME_BASE_TOPIC, "/", SET);
+    pub const REBOOT_TIME_STATE_TOPIC: &str = concat!(REBOOT_TIME_BASE_TOPIC, "/", STATE);
+
+    pub const QUIET_HOURS_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/system/quiet_hours");
+    pub const QUIET_HOURS_SET_TOPIC: &str = concat!(QUIET_HOURS_BASE_TOPIC, "/", SET);
+    pub const QUIET_HOURS_STATE_TOPIC: &str = concat!(QUIET_HOURS_BASE_TOPIC, "/", STATE);
+    pub const QUIET_HOURS_START_BASE_TOPIC: &str = concat!(QUIET_HOURS_BASE_TOPIC, "/start");
+    pub const QUIET_HOURS_START_SET_TOPIC: &str = concat!(QUIET_HOURS_START_BASE_TOPIC, "/", SET);
+    pub const QUIET_HOURS_START_STATE_TOPIC: &str =
+        concat!(QUIET_HOURS_START_BASE_TOPIC, "/", STATE);
+    pub const QUIET_HOURS_END_BASE_TOPIC: &str = concat!(QUIET_HOURS_BASE_TOPIC, "/end");
+    pub const QUIET_HOURS_END_SET_TOPIC: &str = concat!(QUIET_HOURS_END_BASE_TOPIC, "/", SET);
+    pub const QUIET_HOURS_END_STATE_TOPIC: &str = concat!(QUIET_HOURS_END_BASE_TOPIC, "/", STATE);
+    pub const QUIET_HOURS_OVERRIDE_TOPIC: &str = concat!(QUIET_HOURS_BASE_TOPIC, "/override");
+
+    pub const CELEBRATIONS_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/system/celebrations");
+    pub const CELEBRATIONS_SET_TOPIC: &str = concat!(CELEBRATIONS_BASE_TOPIC, "/", SET);
+    pub const CELEBRATIONS_STATE_TOPIC: &str = concat!(CELEBRATIONS_BASE_TOPIC, "/", STATE);
+
+    pub const RULES_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/system/rules");
+    pub const RULES_SET_TOPIC: &str = concat!(RULES_BASE_TOPIC, "/", SET);
+    pub const RULES_STATE_TOPIC: &str = concat!(RULES_BASE_TOPIC, "/", STATE);
+
+    pub const CONFIG_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/system/config");
+    pub const CONFIG_SET_TOPIC: &str = concat!(CONFIG_BASE_TOPIC, "/", SET);
+    pub const CONFIG_STATE_TOPIC: &str = concat!(CONFIG_BASE_TOPIC, "/", STATE);
+
+    pub const RESTART_TOPIC: &str = concat!(BASE_TOPIC, "/system/restart");
+
+    pub const SELF_TEST_TOPIC: &str = concat!(BASE_TOPIC, "/system/self_test");
+
+    pub const FIRMWARE_BASE_TOPIC: &str = concat!(BASE_TOPIC, "/system/firmware");
+    pub const FIRMWARE_STATE_TOPIC: &str = concat!(FIRMWARE_BASE_TOPIC, "/", STATE);
+    pub const FIRMWARE_LATEST_SET_TOPIC: &str = concat!(FIRMWARE_BASE_TOPIC, "/latest/", SET);
+
+    pub const LOG_TOPIC: &str = concat!(BASE_TOPIC, "/system/log");
+    pub const LOG_LEVEL_BASE_TOPIC: &str = concat!(LOG_TOPIC, "/level");
+    pub const LOG_LEVEL_SET_TOPIC: &str = concat!(LOG_LEVEL_BASE_TOPIC, "/", SET);
+    pub const LOG_LEVEL_STATE_TOPIC: &str = concat!(LOG_LEVEL_BASE_TOPIC, "/", STATE);
 }
 
 pub mod clients {
-    use constcat::concat;
+    use core::fmt::Write;
+
     use cortex_m::singleton;
     use embassy_futures::select::{select, Either};
     use embassy_net::{tcp::TcpSocket, Ipv4Address, Stack};
     use embassy_sync::{
-        blocking_mutex::raw::ThreadModeRawMutex, pubsub::Publisher, signal::Signal,
+        blocking_mutex::raw::ThreadModeRawMutex,
+        pubsub::{PubSubChannel, Publisher},
+        signal::Signal,
     };
     use embassy_time::Timer;
-    use heapless::Vec;
+    use heapless::{String, Vec};
     use rust_mqtt::{
         client::{
             client::MqttClient,
@@ -165,18 +427,60 @@ pub mod clients {
         utils::rng_generator::CountingRng,
     };
 
+    #[cfg(feature = "badge-app")]
+    use super::topics::BADGE_SET_TOPIC;
+    #[cfg(feature = "effects-app")]
+    use super::topics::EFFECT_SET_TOPIC;
+    #[cfg(feature = "energy-app")]
+    use super::topics::ENERGY_SET_TOPIC;
+    #[cfg(feature = "clock-app")]
+    use super::topics::{
+        CLOCK_APP_SET_TOPIC, CLOCK_CHIME_HOURS_SET_TOPIC, CLOCK_CHIME_SET_TOPIC,
+        CLOCK_FORMAT_SET_TOPIC, CLOCK_LAYOUT_SET_TOPIC, CLOCK_TICKER_SET_TOPIC,
+    };
+    #[cfg(feature = "gallery-app")]
+    use super::topics::{
+        GALLERY_DELETE_TOPIC, GALLERY_LIST_TOPIC, GALLERY_SHOW_TOPIC, GALLERY_UPLOAD_TOPIC,
+    };
+    #[cfg(feature = "mqtt-app")]
+    use super::topics::{
+        PERSISTENT_TEXT_SET_TOPIC, TEXT_SET_SOURCE_TOPIC, TEXT_TEMPLATE_SET_TOPIC,
+    };
+    #[cfg(feature = "quotes-app")]
+    use super::topics::{QUOTES_ADD_TOPIC, QUOTES_INTERVAL_SET_TOPIC, QUOTES_REMOVE_TOPIC};
     use super::{
-        homeassistant,
+        expand_topic, homeassistant,
         topics::{
-            APP_SET_TOPIC, AUTO_BRIGHTNESS_SET_TOPIC, BRIGHTNESS_SET_TOPIC, CLOCK_APP_SET_TOPIC,
-            NTP_SYNC_TOPIC, RGB_SET_TOPIC, TEXT_SET_TOPIC,
+            ALERT_SET_TOPIC, APP_SET_TOPIC, AUTO_BRIGHTNESS_SET_TOPIC, BRIGHTNESS_SET_TOPIC,
+            CELEBRATIONS_SET_TOPIC, CONFIG_SET_TOPIC, DIAG_TOPIC, FIRMWARE_LATEST_SET_TOPIC,
+            FPS_SET_TOPIC, IMAGE_URL_SET_TOPIC, LAYER_OPACITY_SET_TOPIC, LIGHT_SET_TOPIC,
+            LOG_LEVEL_SET_TOPIC, NTP_SYNC_TOPIC, PALETTE_SET_TOPIC, PENDING_INDICATOR_SET_TOPIC,
+            QUIET_HOURS_END_SET_TOPIC, QUIET_HOURS_OVERRIDE_TOPIC, QUIET_HOURS_SET_TOPIC,
+            QUIET_HOURS_START_SET_TOPIC, REBOOT_TIME_SET_TOPIC, RESTART_TOPIC, RGB_SET_TOPIC,
+            RULES_SET_TOPIC, SCROLL_DIRECTION_SET_TOPIC, SCROLL_SPEED_SET_TOPIC, SELF_TEST_TOPIC,
+            SEQUENCE_SET_TOPIC, SEQUENCE_STOP_TOPIC, STREAM_TOPIC, SYNC_SET_TOPIC,
+            TEXT_CLEAR_TOPIC, WARMTH_SET_TOPIC,
         },
         MqttMessage, MqttReceiveMessage, SEND_CHANNEL,
     };
-    use crate::config::{
-        DEVICE_ID, HASS_BASE_MQTT_TOPIC, MQTT_BROKER_A1, MQTT_BROKER_A2, MQTT_BROKER_A3,
-        MQTT_BROKER_A4, MQTT_BROKER_PORT, MQTT_PASSWORD, MQTT_USERNAME,
-    };
+    use crate::config::{HASS_BASE_MQTT_TOPIC, PRESENCE_TOPIC, SYNC_TOPIC};
+    use crate::error::Error;
+    use crate::logging;
+    use crate::rules;
+    use crate::settings;
+    use crate::supervisor::{self, Task};
+    use crate::sync;
+    use crate::system::{presence, BootStage, SystemState};
+    #[cfg(feature = "clock-app")]
+    use crate::{clock_app, config::CLOCK_TEMPERATURE_TOPIC};
+    #[cfg(feature = "timer-app")]
+    use crate::{config::HASS_TIMER_TOPIC, timer_app};
+
+    /// Channel that received MQTT messages are published in to.
+    pub type MqttReceiveChannel = PubSubChannel<ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1>;
+
+    /// Publisher for a `MqttReceiveChannel`.
+    type MqttReceivePublisher = Publisher<'static, ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1>;
 
     /// Signal for when the send client has an error.
     pub static SEND_CLIENT_ERROR: Signal<ThreadModeRawMutex, bool> = Signal::new();
@@ -184,39 +488,65 @@ pub mod clients {
     /// Signal for when the receive client has an error.
     pub static RECEIVE_CLIENT_ERROR: Signal<ThreadModeRawMutex, bool> = Signal::new();
 
+    /// Signal for the send client to reconnect immediately, skipping the backoff.
+    static RECONNECT_SEND_CLIENT: Signal<ThreadModeRawMutex, bool> = Signal::new();
+
+    /// Signal for the receive client to reconnect immediately, skipping the backoff.
+    static RECONNECT_RECEIVE_CLIENT: Signal<ThreadModeRawMutex, bool> = Signal::new();
+
+    /// Signal for the receive client to reconnect, so it subscribes to the topics watched by rules.
+    static RESUBSCRIBE: Signal<ThreadModeRawMutex, bool> = Signal::new();
+
     /// Buffer size for the embassy net socket.
     const SOCKET_BUF_SIZE: usize = 4096;
 
     /// Buffer size for the mqtt client.
     const CLIENT_BUF_SIZE: usize = 512;
 
+    /// Topics subscribed to in each subscribe packet, so each packet fits in the client buffer.
+    const SUBSCRIBE_BATCH_SIZE: usize = 6;
+
+    /// Initial seconds to wait before reconnecting to the broker.
+    const MIN_RECONNECT_SECS: u64 = 2;
+
+    /// Maximum seconds to wait before reconnecting to the broker.
+    const MAX_RECONNECT_SECS: u64 = 64;
+
+    /// Reconnect both clients to the broker now, rather than waiting for the backoff.
+    pub fn reconnect() {
+        RECONNECT_SEND_CLIENT.signal(true);
+        RECONNECT_RECEIVE_CLIENT.signal(true);
+    }
+
+    /// Reconnect the receive client, so it subscribes to the topics now watched by rules.
+    pub fn resubscribe() {
+        RESUBSCRIBE.signal(true);
+    }
+
     /// Create an MQTT client and connect it to the broker.
     async fn create_client<'a>(
         stack: &'static Stack<cyw43::NetDriver<'static>>,
-        client_type: &'static str,
+        client_id: &'a str,
         socket_rx_buffer: &'a mut [u8],
         socket_tx_buffer: &'a mut [u8],
         client_rx_buffer: &'a mut [u8],
         client_tx_buffer: &'a mut [u8],
-    ) -> MqttClient<'a, TcpSocket<'a>, 5, CountingRng> {
+    ) -> Result<MqttClient<'a, TcpSocket<'a>, 5, CountingRng>, Error> {
         let mut socket = TcpSocket::new(stack, socket_rx_buffer, socket_tx_buffer);
         socket.set_timeout(None);
-        let host_addr = Ipv4Address::new(
-            MQTT_BROKER_A1,
-            MQTT_BROKER_A2,
-            MQTT_BROKER_A3,
-            MQTT_BROKER_A4,
-        );
-        socket.connect((host_addr, MQTT_BROKER_PORT)).await.unwrap();
+        let settings = settings::get();
+        let [a1, a2, a3, a4] = settings.mqtt_broker;
+        let host_addr = Ipv4Address::new(a1, a2, a3, a4);
+        socket.connect((host_addr, settings.mqtt_port)).await?;
 
         let mut config = ClientConfig::new(MqttVersion::MQTTv5, CountingRng(20000));
-        config.max_packet_size = 100;
+        config.max_packet_size = CLIENT_BUF_SIZE as u32;
         config.add_max_subscribe_qos(QualityOfService::QoS1);
-        config.add_client_id(client_type.into());
+        config.add_client_id(client_id);
 
-        if !MQTT_USERNAME.is_empty() {
-            config.add_username(MQTT_USERNAME);
-            config.add_password(MQTT_PASSWORD);
+        if !settings.mqtt_username.is_empty() {
+            config.add_username(&settings.mqtt_username);
+            config.add_password(&settings.mqtt_password);
         }
 
         let mut client: MqttClient<'_, TcpSocket<'_>, 5, CountingRng> = MqttClient::<_, 5, _>::new(
@@ -228,147 +558,475 @@ pub mod clients {
             config,
         );
 
-        client.connect_to_broker().await.unwrap();
+        client.connect_to_broker().await?;
 
-        client
+        Ok(client)
+    }
+
+    /// Get the client id for this device, ending with `suffix`.
+    fn client_id(suffix: &str) -> Result<String<48>, Error> {
+        let mut client_id = String::new();
+        write!(client_id, "{}_{suffix}", settings::get().device_id)
+            .map_err(|_| Error::MessageTooLong)?;
+        Ok(client_id)
+    }
+
+    /// Wait for the `backoff` seconds or the `reconnect` signal, then return the next backoff.
+    async fn wait_to_reconnect(
+        backoff: u64,
+        reconnect: &'static Signal<ThreadModeRawMutex, bool>,
+        task: Task,
+    ) -> u64 {
+        supervisor::check_in(task);
+        logging::warn!("MQTT reconnecting in {backoff}s");
+        select(Timer::after_secs(backoff), reconnect.wait()).await;
+        (backoff * 2).min(MAX_RECONNECT_SECS)
     }
 
     /// Send client for MQTT messages. Polls the `SEND_CHANNEL` to know when to send a message.
+    /// Will reconnect to the broker with a backoff when an error occurs.
     #[embassy_executor::task]
-    pub async fn mqtt_send_client(stack: &'static Stack<cyw43::NetDriver<'static>>) {
+    pub async fn mqtt_send_client(
+        stack: &'static Stack<cyw43::NetDriver<'static>>,
+        app_state: &'static SystemState,
+    ) {
         let socket_rx_buffer = singleton!(: [u8; SOCKET_BUF_SIZE] = [0; SOCKET_BUF_SIZE]).unwrap();
         let socket_tx_buffer = singleton!(: [u8; SOCKET_BUF_SIZE] = [0; SOCKET_BUF_SIZE]).unwrap();
         let client_rx_buffer = singleton!(: [u8; CLIENT_BUF_SIZE] = [0; CLIENT_BUF_SIZE]).unwrap();
         let client_tx_buffer = singleton!(: [u8; CLIENT_BUF_SIZE] = [0; CLIENT_BUF_SIZE]).unwrap();
 
-        let mut client = create_client(
-            stack,
-            concat!(DEVICE_ID, "_sender"),
-            socket_rx_buffer,
-            socket_tx_buffer,
-            client_rx_buffer,
-            client_tx_buffer,
-        )
-        .await;
+        let client_id = match client_id("sender") {
+            Ok(client_id) => client_id,
+            Err(err) => {
+                app_state.report_error("MQTT send client id", err).await;
+                return;
+            }
+        };
 
         let mut was_previous_error = false;
+        let mut backoff = MIN_RECONNECT_SECS;
 
         loop {
-            let result: Result<(), ReasonCode> =
-                match select(SEND_CHANNEL.receive(), Timer::after_secs(5)).await {
-                    Either::First(message) => {
-                        let result = client
-                            .send_message(
-                                message.topic,
-                                message.text.as_bytes(),
-                                message.qos,
-                                message.retain,
-                            )
-                            .await;
-
-                        drop(message);
-                        result
-                    }
-                    Either::Second(_) => client.send_ping().await,
-                };
-
-            match result {
-                Ok(_) => {
-                    if was_previous_error {
-                        SEND_CLIENT_ERROR.signal(false);
-                        was_previous_error = false;
-                    }
-                }
-                Err(_) => {
+            let mut client = match create_client(
+                stack,
+                &client_id,
+                &mut socket_rx_buffer[..],
+                &mut socket_tx_buffer[..],
+                &mut client_rx_buffer[..],
+                &mut client_tx_buffer[..],
+            )
+            .await
+            {
+                Ok(client) => client,
+                Err(err) => {
+                    app_state
+                        .report_error("MQTT send client connect", err)
+                        .await;
                     if !was_previous_error {
                         SEND_CLIENT_ERROR.signal(true);
                         was_previous_error = true;
                     }
+
+                    backoff =
+                        wait_to_reconnect(backoff, &RECONNECT_SEND_CLIENT, Task::MqttSend).await;
+                    continue;
                 }
             };
+
+            backoff = MIN_RECONNECT_SECS;
+            RECONNECT_SEND_CLIENT.reset();
+            app_state.advance_boot_stage(BootStage::Ntp).await;
+
+            loop {
+                supervisor::check_in(Task::MqttSend);
+
+                let result: Result<(), Error> =
+                    match select(SEND_CHANNEL.receive(), Timer::after_secs(5)).await {
+                        Either::First(message) => {
+                            let result = client
+                                .send_message(
+                                    &message.topic,
+                                    message.text.as_bytes(),
+                                    message.qos,
+                                    message.retain,
+                                )
+                                .await;
+
+                            drop(message);
+                            result.map_err(Error::from)
+                        }
+                        Either::Second(_) => client.send_ping().await.map_err(Error::from),
+                    };
+
+                match result {
+                    Ok(_) => {
+                        if was_previous_error {
+                            SEND_CLIENT_ERROR.signal(false);
+                            was_previous_error = false;
+                        }
+                    }
+                    Err(err) => {
+                        app_state.report_error("MQTT send", err).await;
+                        if !was_previous_error {
+                            SEND_CLIENT_ERROR.signal(true);
+                            was_previous_error = true;
+                        }
+
+                        // drop the client and reconnect
+                        break;
+                    }
+                };
+            }
         }
     }
 
-    /// Receive client for MQTT messages. Publishes into the relevent publisher.
+    /// Receive client for MQTT messages. Publishes into the relevent channel.
+    /// Will reconnect to the broker with a backoff when an error occurs.
     #[embassy_executor::task]
     pub async fn mqtt_receive_client(
         stack: &'static Stack<cyw43::NetDriver<'static>>,
-        display_publisher: Publisher<'static, ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1>,
-        app_publisher: Publisher<'static, ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1>,
-        system_publisher: Publisher<'static, ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1>,
+        app_state: &'static SystemState,
+        display_channel: &'static MqttReceiveChannel,
+        app_channel: &'static MqttReceiveChannel,
+        system_channel: &'static MqttReceiveChannel,
     ) {
+        let (display_publisher, app_publisher, system_publisher, client_id) =
+            match receive_client_setup(display_channel, app_channel, system_channel) {
+                Ok(setup) => setup,
+                Err(err) => {
+                    app_state
+                        .report_error("MQTT receive client setup", err)
+                        .await;
+                    return;
+                }
+            };
+
         let socket_rx_buffer = singleton!(: [u8; SOCKET_BUF_SIZE] = [0; SOCKET_BUF_SIZE]).unwrap();
         let socket_tx_buffer = singleton!(: [u8; SOCKET_BUF_SIZE] = [0; SOCKET_BUF_SIZE]).unwrap();
         let client_rx_buffer = singleton!(: [u8; CLIENT_BUF_SIZE] = [0; CLIENT_BUF_SIZE]).unwrap();
         let client_tx_buffer = singleton!(: [u8; CLIENT_BUF_SIZE] = [0; CLIENT_BUF_SIZE]).unwrap();
 
-        let mut client = create_client(
-            stack,
-            concat!(DEVICE_ID, "_receiver"),
-            socket_rx_buffer,
-            socket_tx_buffer,
-            client_rx_buffer,
-            client_tx_buffer,
-        )
-        .await;
-
-        let topics: Vec<&str, 8> = Vec::from_slice(&[
+        let topics: Result<Vec<String<128>, 64>, Error> = [
             BRIGHTNESS_SET_TOPIC,
             RGB_SET_TOPIC,
-            TEXT_SET_TOPIC,
+            LIGHT_SET_TOPIC,
+            PALETTE_SET_TOPIC,
+            LAYER_OPACITY_SET_TOPIC,
+            FPS_SET_TOPIC,
+            SCROLL_DIRECTION_SET_TOPIC,
+            SCROLL_SPEED_SET_TOPIC,
+            WARMTH_SET_TOPIC,
+            SYNC_SET_TOPIC,
+            PENDING_INDICATOR_SET_TOPIC,
+            ALERT_SET_TOPIC,
+            IMAGE_URL_SET_TOPIC,
+            STREAM_TOPIC,
+            #[cfg(feature = "mqtt-app")]
+            TEXT_SET_SOURCE_TOPIC,
+            TEXT_CLEAR_TOPIC,
+            #[cfg(feature = "mqtt-app")]
+            TEXT_TEMPLATE_SET_TOPIC,
+            #[cfg(feature = "mqtt-app")]
+            PERSISTENT_TEXT_SET_TOPIC,
             APP_SET_TOPIC,
+            #[cfg(feature = "clock-app")]
             CLOCK_APP_SET_TOPIC,
+            #[cfg(feature = "clock-app")]
+            CLOCK_CHIME_SET_TOPIC,
+            #[cfg(feature = "clock-app")]
+            CLOCK_CHIME_HOURS_SET_TOPIC,
+            #[cfg(feature = "clock-app")]
+            CLOCK_FORMAT_SET_TOPIC,
+            #[cfg(feature = "clock-app")]
+            CLOCK_LAYOUT_SET_TOPIC,
+            #[cfg(feature = "clock-app")]
+            CLOCK_TICKER_SET_TOPIC,
+            #[cfg(feature = "effects-app")]
+            EFFECT_SET_TOPIC,
+            #[cfg(feature = "gallery-app")]
+            GALLERY_UPLOAD_TOPIC,
+            #[cfg(feature = "gallery-app")]
+            GALLERY_DELETE_TOPIC,
+            #[cfg(feature = "gallery-app")]
+            GALLERY_SHOW_TOPIC,
+            #[cfg(feature = "gallery-app")]
+            GALLERY_LIST_TOPIC,
+            #[cfg(feature = "quotes-app")]
+            QUOTES_ADD_TOPIC,
+            #[cfg(feature = "quotes-app")]
+            QUOTES_REMOVE_TOPIC,
+            #[cfg(feature = "quotes-app")]
+            QUOTES_INTERVAL_SET_TOPIC,
+            #[cfg(feature = "badge-app")]
+            BADGE_SET_TOPIC,
+            #[cfg(feature = "energy-app")]
+            ENERGY_SET_TOPIC,
+            SEQUENCE_SET_TOPIC,
+            SEQUENCE_STOP_TOPIC,
             AUTO_BRIGHTNESS_SET_TOPIC,
             NTP_SYNC_TOPIC,
+            LOG_LEVEL_SET_TOPIC,
+            DIAG_TOPIC,
+            REBOOT_TIME_SET_TOPIC,
+            QUIET_HOURS_SET_TOPIC,
+            QUIET_HOURS_START_SET_TOPIC,
+            QUIET_HOURS_END_SET_TOPIC,
+            QUIET_HOURS_OVERRIDE_TOPIC,
+            CELEBRATIONS_SET_TOPIC,
+            RULES_SET_TOPIC,
+            CONFIG_SET_TOPIC,
+            RESTART_TOPIC,
+            SELF_TEST_TOPIC,
+            FIRMWARE_LATEST_SET_TOPIC,
             homeassistant::HASS_STATUS_TOPIC,
-        ])
-        .unwrap();
-
-        match client.subscribe_to_topics(&topics).await {
-            Ok(_) => MqttMessage::enqueue_debug("Subscribed to topics").await,
-            Err(code) => send_reason_code(code).await,
+        ]
+        .iter()
+        .map(|topic| expand_topic(topic))
+        .collect();
+        let mut topics = match topics {
+            Ok(topics) => topics,
+            Err(err) => {
+                app_state.report_error("MQTT subscribe topics", err).await;
+                return;
+            }
         };
 
-        let mut was_previous_error = false;
+        // the mirrored timer is outside the base topic, so it is only known at runtime
+        #[cfg(feature = "timer-app")]
+        if let Some(timer) = HASS_TIMER_TOPIC {
+            let mut topic = String::new();
+            if write!(topic, "{timer}/#").is_err() || topics.push(topic).is_err() {
+                app_state
+                    .report_error("MQTT subscribe topics", Error::TopicTooLong)
+                    .await;
+                return;
+            }
+        }
 
-        loop {
-            let result: Result<(), ReasonCode> =
-                match select(client.receive_message(), Timer::after_secs(5)).await {
-                    Either::First(received_message) => match received_message {
-                        Ok(mqtt_message) => {
-                            let message = MqttReceiveMessage::new(mqtt_message.0, mqtt_message.1);
-
-                            if mqtt_message.0.contains("display") {
-                                display_publisher.publish(message).await;
-                            } else if mqtt_message.0.contains("app") {
-                                app_publisher.publish(message).await;
-                            } else if mqtt_message.0.contains("system") {
-                                system_publisher.publish(message).await;
-                            } else if mqtt_message.0.contains(HASS_BASE_MQTT_TOPIC) {
-                                homeassistant::HASS_RECIEVE_CHANNEL.send(message).await;
-                            }
+        // the clock temperature is published by another device, so is outside the base topic
+        #[cfg(feature = "clock-app")]
+        if let Some(temperature) = CLOCK_TEMPERATURE_TOPIC {
+            if String::try_from(temperature)
+                .ok()
+                .and_then(|topic| topics.push(topic).ok())
+                .is_none()
+            {
+                app_state
+                    .report_error("MQTT subscribe topics", Error::TopicTooLong)
+                    .await;
+                return;
+            }
+        }
 
-                            Ok(())
-                        }
-                        Err(code) => Err(code),
-                    },
-                    Either::Second(_) => client.send_ping().await,
-                };
+        // the row of synced devices shares a topic outside the base topic
+        if let Some(sync) = SYNC_TOPIC {
+            let mut topic = String::new();
+            if write!(topic, "{sync}/#").is_err() || topics.push(topic).is_err() {
+                app_state
+                    .report_error("MQTT subscribe topics", Error::TopicTooLong)
+                    .await;
+                return;
+            }
+        }
 
-            match result {
-                Ok(_) => {
-                    if was_previous_error {
-                        RECEIVE_CLIENT_ERROR.signal(false);
-                        was_previous_error = false;
-                    }
-                }
-                Err(_) => {
+        // whether anyone is home is published by another device, so is outside the base topic
+        if let Some(presence) = PRESENCE_TOPIC {
+            if String::try_from(presence)
+                .ok()
+                .and_then(|topic| topics.push(topic).ok())
+                .is_none()
+            {
+                app_state
+                    .report_error("MQTT subscribe topics", Error::TopicTooLong)
+                    .await;
+                return;
+            }
+        }
+
+        let topics: Vec<&str, 64> = topics.iter().map(String::as_str).collect();
+
+        let mut was_previous_error = false;
+        let mut backoff = MIN_RECONNECT_SECS;
+
+        loop {
+            let mut client = match create_client(
+                stack,
+                &client_id,
+                &mut socket_rx_buffer[..],
+                &mut socket_tx_buffer[..],
+                &mut client_rx_buffer[..],
+                &mut client_tx_buffer[..],
+            )
+            .await
+            {
+                Ok(client) => client,
+                Err(err) => {
+                    app_state
+                        .report_error("MQTT receive client connect", err)
+                        .await;
                     if !was_previous_error {
                         RECEIVE_CLIENT_ERROR.signal(true);
                         was_previous_error = true;
                     }
+
+                    backoff =
+                        wait_to_reconnect(backoff, &RECONNECT_RECEIVE_CLIENT, Task::MqttReceive)
+                            .await;
+                    continue;
+                }
+            };
+
+            backoff = MIN_RECONNECT_SECS;
+            RECONNECT_RECEIVE_CLIENT.reset();
+
+            let mut subscribed = Ok(());
+            for batch in topics.chunks(SUBSCRIBE_BATCH_SIZE) {
+                let batch: Vec<&str, SUBSCRIBE_BATCH_SIZE> = Vec::from_slice(batch).unwrap();
+                subscribed = client.subscribe_to_topics(&batch).await;
+                if subscribed.is_err() {
+                    break;
                 }
+            }
+
+            // rules can watch any topic and are changed at runtime, so are subscribed on each connect
+            RESUBSCRIBE.reset();
+            let rule_topics = rules::topics().await;
+            for topic in rule_topics.iter() {
+                if subscribed.is_err() {
+                    break;
+                }
+
+                subscribed = match expand_topic(topic) {
+                    Ok(topic) => client.subscribe_to_topic(&topic).await,
+                    Err(_) => Ok(()),
+                };
+            }
+
+            match subscribed {
+                Ok(_) => MqttMessage::enqueue_debug("Subscribed to topics").await,
+                Err(code) => MqttMessage::enqueue_debug(get_reason_code(code)).await,
             };
+
+            loop {
+                supervisor::check_in(Task::MqttReceive);
+
+                if RESUBSCRIBE.signaled() {
+                    break;
+                }
+
+                let result: Result<(), Error> =
+                    match select(client.receive_message(), Timer::after_secs(5)).await {
+                        Either::First(received_message) => match received_message {
+                            Ok((topic, body)) => {
+                                let message = match MqttReceiveMessage::new(topic, body) {
+                                    Ok(message) => message,
+                                    Err(err) => {
+                                        logging::warn!("Ignored message on {topic}: {err}");
+                                        continue;
+                                    }
+                                };
+
+                                if rule_topics.contains(&message.topic) {
+                                    rules::observe(message.clone());
+                                }
+
+                                // the mirrored timer is under the home assistant topic, but is for the timer app
+                                #[cfg(feature = "timer-app")]
+                                if timer_app::is_timer_topic(&message.topic) {
+                                    app_publisher.publish(message).await;
+                                    continue;
+                                }
+
+                                // the clock temperature can be under any topic, but is for the clock app
+                                #[cfg(feature = "clock-app")]
+                                if clock_app::is_temperature_topic(&message.topic) {
+                                    app_publisher.publish(message).await;
+                                    continue;
+                                }
+
+                                // the synced row shares a topic, and the coordinator text is for it too
+                                if sync::is_sync_topic(&message.topic) {
+                                    sync::SYNC_CHANNEL.send(message).await;
+                                    continue;
+                                }
+
+                                // whether anyone is home can be under any topic, but is for the system
+                                if presence::is_presence_topic(&message.topic) {
+                                    system_publisher.publish(message).await;
+                                    continue;
+                                }
+
+                                // shown by the MQTT app, so it goes to the apps despite being a display topic
+                                #[cfg(feature = "mqtt-app")]
+                                if message.topic == PERSISTENT_TEXT_SET_TOPIC {
+                                    app_publisher.publish(message).await;
+                                    continue;
+                                }
+
+                                if message.topic.contains("display") {
+                                    display_publisher.publish(message).await;
+                                } else if message.topic.contains("app") {
+                                    app_publisher.publish(message).await;
+                                } else if message.topic.contains("system") {
+                                    system_publisher.publish(message).await;
+                                } else if message.topic.contains(HASS_BASE_MQTT_TOPIC) {
+                                    homeassistant::HASS_RECIEVE_CHANNEL.send(message).await;
+                                }
+
+                                Ok(())
+                            }
+                            Err(code) => Err(Error::from(code)),
+                        },
+                        Either::Second(_) => client.send_ping().await.map_err(Error::from),
+                    };
+
+                match result {
+                    Ok(_) => {
+                        if was_previous_error {
+                            RECEIVE_CLIENT_ERROR.signal(false);
+                            was_previous_error = false;
+                        }
+                    }
+                    Err(err) => {
+                        app_state.report_error("MQTT receive", err).await;
+                        if !was_previous_error {
+                            RECEIVE_CLIENT_ERROR.signal(true);
+                            was_previous_error = true;
+                        }
+
+                        // drop the client and reconnect
+                        break;
+                    }
+                };
+            }
+        }
+    }
+
+    /// Create the publishers and client id for the receive client.
+    fn receive_client_setup(
+        display_channel: &'static MqttReceiveChannel,
+        app_channel: &'static MqttReceiveChannel,
+        system_channel: &'static MqttReceiveChannel,
+    ) -> Result<
+        (
+            MqttReceivePublisher,
+            MqttReceivePublisher,
+            MqttReceivePublisher,
+            String<48>,
+        ),
+        Error,
+    > {
+        Ok((
+            display_channel.publisher()?,
+            app_channel.publisher()?,
+            system_channel.publisher()?,
+            client_id("receiver")?,
+        ))
+    }
+
+    impl From<ReasonCode> for Error {
+        fn from(code: ReasonCode) -> Self {
+            Self::Mqtt(get_reason_code(code))
         }
     }
 
@@ -425,12 +1083,6 @@ pub mod clients {
             ReasonCode::NetworkError => "NetworkError",
         }
     }
-
-    /// Send the `ReasonCode` as a debug message.
-    async fn send_reason_code(code: ReasonCode) {
-        let message = get_reason_code(code);
-        MqttMessage::enqueue_debug(message).await;
-    }
 }
 
 pub mod homeassistant {
@@ -444,10 +1096,20 @@ pub mod homeassistant {
     use heapless::String;
     use rust_mqtt::packet::v5::publish_packet::QualityOfService;
 
-    use crate::app::AppController;
-    use crate::config::{DEVICE_ID, HASS_BASE_MQTT_TOPIC};
-    use crate::display::Display;
+    use crate::app::{self, AppController};
+    use crate::board::NAME as BOARD_NAME;
+    use crate::celebration;
+    use crate::config::HASS_BASE_MQTT_TOPIC;
+    use crate::display::{Display, MAX_FPS, MAX_SCROLL_SPEED, MIN_SCROLL_SPEED};
+    #[cfg(feature = "effects-app")]
+    use crate::effects_app;
+    use crate::logging;
     use crate::mqtt::MqttMessage;
+    use crate::network;
+    use crate::rules;
+    use crate::sensors;
+    use crate::settings;
+    use crate::system::{self, firmware::VERSION};
 
     use super::{topics::*, MqttReceiveMessage};
 
@@ -457,154 +1119,683 @@ pub mod homeassistant {
     pub static HASS_RECIEVE_CHANNEL: Channel<ThreadModeRawMutex, MqttReceiveMessage, 2> =
         Channel::new();
 
+    /// Get the discovery config topic for the `component` entity `object_id` on this device.
+    fn discovery_topic(component: &str, object_id: &str) -> String<128> {
+        let device_id = &settings::get().device_id;
+        let mut topic = String::new();
+        write!(
+            topic,
+            "{HASS_BASE_MQTT_TOPIC}/{component}/{device_id}/{object_id}/config"
+        )
+        .unwrap();
+        topic
+    }
+
     /// Send the home assistant discovery messages to auto configure the device.
+    /// State and command topics are given relative to the `~` base topic.
     async fn send_home_assistant_discovery() {
-        // clock effect
-        let topic = concat!(
-            HASS_BASE_MQTT_TOPIC,
-            "/select/",
-            DEVICE_ID,
-            "/clock_effect/config"
-        );
-        let mut payload = String::<512>::new();
-        write!(
-            payload,
-            r#"
+        let settings = settings::get();
+        let device_id = settings.device_id.as_str();
+        let base_topic = settings.base_topic.as_str();
+
+        // active app, only listing the apps in this build
+        let mut options = String::<{ app::APP_OPTIONS_SIZE }>::new();
+        if app::write_app_options(&mut options).is_ok() {
+            let topic = discovery_topic("select", "active_app");
+            let mut payload = String::<512>::new();
+            write!(
+                payload,
+                r#"
 {{
   "dev" : {{
-    "ids": "{DEVICE_ID}",
-    "name": "Galactic Unicorn",
+    "ids": "{device_id}",
+    "name": "{BOARD_NAME}",
     "manufacturer": "Pimoroni",
-    "model": "Galactic Unicorn"
+    "model": "{BOARD_NAME}",
+    "sw_version": "{VERSION}"
   }},
+  "~": "{base_topic}",
+  "name": "Active app",
+  "stat_t": "{APP_STATE_TOPIC}",
+  "cmd_t": "{APP_SET_TOPIC}",
+  "options": {options},
+  "uniq_id": "{device_id}_apps_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+        } else {
+            logging::error!("App options do not fit in {} bytes", app::APP_OPTIONS_SIZE);
+        }
+
+        // clock effect
+        #[cfg(feature = "clock-app")]
+        {
+            let topic = discovery_topic("select", "clock_effect");
+            let mut payload = String::<384>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
   "name": "Clock effect",
   "stat_t": "{CLOCK_APP_STATE_TOPIC}",
   "cmd_t": "{CLOCK_APP_SET_TOPIC}",
   "options": ["Rainbow", "Color"],
-  "uniq_id": "{DEVICE_ID}_clock_01"
+  "uniq_id": "{device_id}_clock_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+
+            let topic = discovery_topic("switch", "clock_chime");
+            let mut payload = String::<384>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Hourly chime",
+  "cmd_t": "{CLOCK_CHIME_SET_TOPIC}",
+  "stat_t": "{CLOCK_CHIME_STATE_TOPIC}",
+  "uniq_id": "{device_id}_clock_chime_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+
+            let topic = discovery_topic("text", "clock_chime_hours");
+            let mut payload = String::<384>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Hourly chime hours",
+  "stat_t": "{CLOCK_CHIME_HOURS_STATE_TOPIC}",
+  "cmd_t": "{CLOCK_CHIME_HOURS_SET_TOPIC}",
+  "pattern": "^([01]?[0-9]|2[0-3]):[0-5][0-9]-([01]?[0-9]|2[0-3]):[0-5][0-9]$",
+  "uniq_id": "{device_id}_clock_chime_hours_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+
+            let topic = discovery_topic("select", "clock_format");
+            let mut payload = String::<384>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Clock format",
+  "stat_t": "{CLOCK_FORMAT_STATE_TOPIC}",
+  "cmd_t": "{CLOCK_FORMAT_SET_TOPIC}",
+  "options": ["24h", "12h"],
+  "uniq_id": "{device_id}_clock_format_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+
+            let topic = discovery_topic("select", "clock_layout");
+            let mut payload = String::<384>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Clock layout",
+  "stat_t": "{CLOCK_LAYOUT_STATE_TOPIC}",
+  "cmd_t": "{CLOCK_LAYOUT_SET_TOPIC}",
+  "options": ["full", "ticker"],
+  "uniq_id": "{device_id}_clock_layout_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+        }
+
+        // MQTT text message (as a notification from home assistant)
+        #[cfg(feature = "mqtt-app")]
+        {
+            let topic = discovery_topic("notify", "mqtt_message");
+            let mut payload = String::<384>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Display text",
+  "cmd_t": "{TEXT_SET_TOPIC}",
+  "uniq_id": "{device_id}_display_text_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+        }
+
+        // persistent text, shown by the MQTT app when idle
+        #[cfg(feature = "mqtt-app")]
+        {
+            let topic = discovery_topic("text", "persistent_text");
+            let mut payload = String::<384>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Persistent text",
+  "stat_t": "{PERSISTENT_TEXT_STATE_TOPIC}",
+  "cmd_t": "{PERSISTENT_TEXT_SET_TOPIC}",
+  "max": 64,
+  "uniq_id": "{device_id}_persistent_text_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+        }
+
+        // display color and brightness, with the effects app effects when it is built
+        #[cfg(feature = "effects-app")]
+        let effects = {
+            let mut effect_list = String::<64>::new();
+            effects_app::write_effect_list(&mut effect_list).unwrap();
+            let mut effects = String::<192>::new();
+            write!(
+                effects,
+                r#"
+  "effect": true,
+  "effect_list": {effect_list},"#
+            )
+            .unwrap();
+            effects
+        };
+        #[cfg(not(feature = "effects-app"))]
+        let effects = "";
+
+        let topic = discovery_topic("light", "board");
+        let mut payload = String::<640>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Display",
+  "schema": "json",
+  "cmd_t": "{LIGHT_SET_TOPIC}",
+  "stat_t": "{LIGHT_STATE_TOPIC}",
+  "brightness": true,
+  "supported_color_modes": ["rgb"],{effects}
+  "uniq_id": "{device_id}_light_01"
 }}"#
         )
         .unwrap();
-        MqttMessage::enqueue_hass(topic, &payload).await;
-
-        // active app
-        let topic = concat!(
-            HASS_BASE_MQTT_TOPIC,
-            "/select/",
-            DEVICE_ID,
-            "/active_app/config"
-        );
-        let mut payload = String::<256>::new();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // display frame rate
+        let topic = discovery_topic("number", "fps");
+        let mut payload = String::<384>::new();
         write!(
             payload,
             r#"
 {{
   "dev" : {{
-    "ids": "{DEVICE_ID}"
+    "ids": "{device_id}"
   }},
-  "name": "Active app",
-  "stat_t": "{APP_STATE_TOPIC}",
-  "cmd_t": "{APP_SET_TOPIC}",
-  "options": ["Clock", "Effects", "Mqtt"],
-  "uniq_id": "{DEVICE_ID}_apps_01"
+  "~": "{base_topic}",
+  "name": "Frame rate",
+  "stat_t": "{FPS_STATE_TOPIC}",
+  "cmd_t": "{FPS_SET_TOPIC}",
+  "min": 1,
+  "max": {MAX_FPS},
+  "uniq_id": "{device_id}_fps_01"
 }}"#
         )
         .unwrap();
-        MqttMessage::enqueue_hass(topic, &payload).await;
+        MqttMessage::enqueue_hass(&topic, &payload).await;
 
-        // MQTT text message (as a notification from home assistant)
-        let topic = concat!(
-            HASS_BASE_MQTT_TOPIC,
-            "/notify/",
-            DEVICE_ID,
-            "/mqtt_message/config"
-        );
-        let mut payload = String::<256>::new();
+        // speed of scrolling text
+        let topic = discovery_topic("number", "scroll_speed");
+        let mut payload = String::<384>::new();
         write!(
             payload,
             r#"
 {{
   "dev" : {{
-    "ids": "{DEVICE_ID}"
+    "ids": "{device_id}"
   }},
-  "name": "Display text",
-  "cmd_t": "{TEXT_SET_TOPIC}",
-  "uniq_id": "{DEVICE_ID}_display_text_01"
+  "~": "{base_topic}",
+  "name": "Scroll speed",
+  "stat_t": "{SCROLL_SPEED_STATE_TOPIC}",
+  "cmd_t": "{SCROLL_SPEED_SET_TOPIC}",
+  "min": {MIN_SCROLL_SPEED},
+  "max": {MAX_SCROLL_SPEED},
+  "unit_of_meas": "px/s",
+  "uniq_id": "{device_id}_scroll_speed_01"
 }}"#
         )
         .unwrap();
-        MqttMessage::enqueue_hass(topic, &payload).await;
+        MqttMessage::enqueue_hass(&topic, &payload).await;
 
-        // display color and brightness
-        let topic = concat!(HASS_BASE_MQTT_TOPIC, "/light/", DEVICE_ID, "/board/config");
-        let mut payload = String::<512>::new();
+        // evening warmth
+        let topic = discovery_topic("number", "warmth");
+        let mut payload = String::<384>::new();
         write!(
             payload,
             r#"
 {{
   "dev" : {{
-    "ids": "{DEVICE_ID}"
+    "ids": "{device_id}"
   }},
-  "name": "Display",
-  "cmd_t": "{BRIGHTNESS_SET_TOPIC}",
-  "pl_off": 0,
-  "rgb_stat_t": "{RGB_STATE_TOPIC}",
-  "rgb_cmd_t": "{RGB_SET_TOPIC}",
-  "bri_stat_t": "{BRIGHTNESS_STATE_TOPIC}",
-  "bri_cmd_t": "{BRIGHTNESS_SET_TOPIC}",
-  "on_cmd_type": "brightness",
-  "uniq_id": "{DEVICE_ID}_light_01"
+  "~": "{base_topic}",
+  "name": "Evening warmth",
+  "stat_t": "{WARMTH_STATE_TOPIC}",
+  "cmd_t": "{WARMTH_SET_TOPIC}",
+  "min": 0,
+  "max": 100,
+  "unit_of_meas": "%",
+  "uniq_id": "{device_id}_warmth_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // display palette
+        let topic = discovery_topic("select", "palette");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Palette",
+  "stat_t": "{PALETTE_STATE_TOPIC}",
+  "cmd_t": "{PALETTE_SET_TOPIC}",
+  "options": ["Default", "Rainbow", "Fire", "Ocean", "Pastel", "Forest"],
+  "uniq_id": "{device_id}_palette_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // default scroll direction of text
+        let topic = discovery_topic("select", "scroll_direction");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Scroll direction",
+  "stat_t": "{SCROLL_DIRECTION_STATE_TOPIC}",
+  "cmd_t": "{SCROLL_DIRECTION_SET_TOPIC}",
+  "options": ["right_to_left", "left_to_right", "bounce"],
+  "uniq_id": "{device_id}_scroll_direction_01"
 }}"#
         )
         .unwrap();
-        MqttMessage::enqueue_hass(topic, &payload).await;
+        MqttMessage::enqueue_hass(&topic, &payload).await;
 
         // display auto brightness
-        let topic = concat!(
-            HASS_BASE_MQTT_TOPIC,
-            "/switch/",
-            DEVICE_ID,
-            "/auto_brightness/config"
-        );
-        let mut payload = String::<256>::new();
+        let topic = discovery_topic("switch", "auto_brightness");
+        let mut payload = String::<384>::new();
         write!(
             payload,
             r#"
 {{
   "dev" : {{
-    "ids": "{DEVICE_ID}"
+    "ids": "{device_id}"
   }},
+  "~": "{base_topic}",
   "name": "Auto brightness",
   "cmd_t": "{AUTO_BRIGHTNESS_SET_TOPIC}",
   "stat_t": "{AUTO_BRIGHTNESS_STATE_TOPIC}",
-  "uniq_id": "{DEVICE_ID}_auto_brightness_01"
+  "uniq_id": "{device_id}_auto_brightness_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // pending message indicator
+        let topic = discovery_topic("switch", "pending_indicator");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Pending message indicator",
+  "cmd_t": "{PENDING_INDICATOR_SET_TOPIC}",
+  "stat_t": "{PENDING_INDICATOR_STATE_TOPIC}",
+  "uniq_id": "{device_id}_pending_indicator_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // ambient light sensor
+        let topic = discovery_topic("sensor", "light_level");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Light level",
+  "stat_t": "{LIGHT_LEVEL_STATE_TOPIC}",
+  "stat_cla": "measurement",
+  "unit_of_meas": "%",
+  "uniq_id": "{device_id}_light_level_01"
 }}"#
         )
         .unwrap();
-        MqttMessage::enqueue_hass(topic, &payload).await;
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // sensors on the qw/st connector, all from the one state topic
+        let external_sensors = [
+            (
+                "temperature",
+                "Temperature",
+                r#","dev_cla":"temperature","unit_of_meas":"°C""#,
+            ),
+            (
+                "humidity",
+                "Humidity",
+                r#","dev_cla":"humidity","unit_of_meas":"%""#,
+            ),
+            (
+                "pressure",
+                "Pressure",
+                r#","dev_cla":"atmospheric_pressure","unit_of_meas":"hPa""#,
+            ),
+            (
+                "co2",
+                "Carbon dioxide",
+                r#","dev_cla":"carbon_dioxide","unit_of_meas":"ppm""#,
+            ),
+        ];
+        for (key, name, extra) in external_sensors {
+            if !sensors::measures(key) {
+                continue;
+            }
+
+            let topic = discovery_topic("sensor", key);
+            let mut payload = String::<512>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "{name}",
+  "stat_t": "{SENSORS_STATE_TOPIC}",
+  "val_tpl": "{{{{ value_json.{key} }}}}",
+  "stat_cla": "measurement"{extra},
+  "uniq_id": "{device_id}_sensor_{key}_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+        }
+
+        // network health, all from the one state topic
+        let health_sensors = [
+            ("ip", "IP address", ""),
+            (
+                "rssi",
+                "WiFi signal",
+                r#","dev_cla":"signal_strength","stat_cla":"measurement","unit_of_meas":"dBm""#,
+            ),
+            (
+                "uptime",
+                "Uptime",
+                r#","dev_cla":"duration","stat_cla":"total_increasing","unit_of_meas":"s""#,
+            ),
+        ];
+        for (key, name, extra) in health_sensors {
+            let topic = discovery_topic("sensor", key);
+            let mut payload = String::<512>::new();
+            write!(
+                payload,
+                r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "{name}",
+  "stat_t": "{HEALTH_STATE_TOPIC}",
+  "val_tpl": "{{{{ value_json.{key} }}}}",
+  "ent_cat": "diagnostic"{extra},
+  "uniq_id": "{device_id}_{key}_01"
+}}"#
+            )
+            .unwrap();
+            MqttMessage::enqueue_hass(&topic, &payload).await;
+        }
+
+        // log level
+        let topic = discovery_topic("select", "log_level");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Log level",
+  "stat_t": "{LOG_LEVEL_STATE_TOPIC}",
+  "cmd_t": "{LOG_LEVEL_SET_TOPIC}",
+  "options": ["Debug", "Info", "Warn", "Error", "Off"],
+  "uniq_id": "{device_id}_log_level_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // daily reboot time
+        let topic = discovery_topic("text", "reboot_time");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Daily reboot time",
+  "stat_t": "{REBOOT_TIME_STATE_TOPIC}",
+  "cmd_t": "{REBOOT_TIME_SET_TOPIC}",
+  "pattern": "^(off|([01]?[0-9]|2[0-3]):[0-5][0-9])$",
+  "uniq_id": "{device_id}_reboot_time_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // firmware version, updated by flashing over USB as there is no OTA
+        let topic = discovery_topic("update", "firmware");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Firmware",
+  "stat_t": "{FIRMWARE_STATE_TOPIC}",
+  "dev_cla": "firmware",
+  "ent_cat": "config",
+  "uniq_id": "{device_id}_firmware_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // quiet hours
+        let topic = discovery_topic("switch", "quiet_hours");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Quiet hours",
+  "cmd_t": "{QUIET_HOURS_SET_TOPIC}",
+  "stat_t": "{QUIET_HOURS_STATE_TOPIC}",
+  "uniq_id": "{device_id}_quiet_hours_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // quiet hours start time
+        let topic = discovery_topic("text", "quiet_hours_start");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Quiet hours start",
+  "stat_t": "{QUIET_HOURS_START_STATE_TOPIC}",
+  "cmd_t": "{QUIET_HOURS_START_SET_TOPIC}",
+  "pattern": "^([01]?[0-9]|2[0-3]):[0-5][0-9]$",
+  "uniq_id": "{device_id}_quiet_hours_start_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // quiet hours end time
+        let topic = discovery_topic("text", "quiet_hours_end");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Quiet hours end",
+  "stat_t": "{QUIET_HOURS_END_STATE_TOPIC}",
+  "cmd_t": "{QUIET_HOURS_END_SET_TOPIC}",
+  "pattern": "^([01]?[0-9]|2[0-3]):[0-5][0-9]$",
+  "uniq_id": "{device_id}_quiet_hours_end_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
 
         // force sync to NTP
-        let topic = concat!(
-            HASS_BASE_MQTT_TOPIC,
-            "/button/",
-            DEVICE_ID,
-            "/ntp_sync/config"
-        );
-        let mut payload = String::<256>::new();
+        let topic = discovery_topic("button", "ntp_sync");
+        let mut payload = String::<384>::new();
         write!(
             payload,
             r#"
 {{
   "dev" : {{
-    "ids": "{DEVICE_ID}"
+    "ids": "{device_id}"
   }},
+  "~": "{base_topic}",
   "name": "NTP Sync",
   "cmd_t": "{NTP_SYNC_TOPIC}",
-  "uniq_id": "{DEVICE_ID}_button_01"
+  "uniq_id": "{device_id}_button_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // restart, to apply saved config
+        let topic = discovery_topic("button", "restart");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "Restart",
+  "cmd_t": "{RESTART_TOPIC}",
+  "dev_cla": "restart",
+  "uniq_id": "{device_id}_restart_01"
+}}"#
+        )
+        .unwrap();
+        MqttMessage::enqueue_hass(&topic, &payload).await;
+
+        // LED self test
+        let topic = discovery_topic("button", "self_test");
+        let mut payload = String::<384>::new();
+        write!(
+            payload,
+            r#"
+{{
+  "dev" : {{
+    "ids": "{device_id}"
+  }},
+  "~": "{base_topic}",
+  "name": "LED self test",
+  "cmd_t": "{SELF_TEST_TOPIC}",
+  "ent_cat": "diagnostic",
+  "uniq_id": "{device_id}_self_test_01"
 }}"#
         )
         .unwrap();
-        MqttMessage::enqueue_hass(topic, &payload).await;
+        MqttMessage::enqueue_hass(&topic, &payload).await;
     }
 
     /// Send app states over MQTT.
@@ -614,13 +1805,29 @@ pub mod homeassistant {
     ) {
         display.send_brightness_state().await;
         display.send_color_state().await;
+        display.send_palette_state().await;
+        display.send_layer_opacity_state().await;
+        display.send_target_fps_state().await;
+        display.send_scroll_direction_state().await;
+        display.send_scroll_speed_state().await;
+        display.send_warmth_state().await;
+        display.send_pending_indicator_state().await;
         display.send_auto_brightness_state().await;
+        display.send_light_level_state().await;
+        network::request_health_state();
+        logging::send_level_state().await;
+        system::reboot::send_reboot_time_state().await;
+        system::firmware::send_state().await;
+        system::quiet_hours::send_state().await;
+        celebration::send_state().await;
+        rules::send_state().await;
+        settings::send_state(settings::get()).await;
         app_controller.send_mqtt_states().await;
     }
 
     impl MqttMessage {
         /// Add a home assistant message into the send queue.
-        async fn enqueue_hass(topic: &'static str, content: &str) {
+        async fn enqueue_hass(topic: &str, content: &str) {
             Self::enqueue(topic, content, QualityOfService::QoS0, false).await;
         }
     }
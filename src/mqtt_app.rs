@@ -1,41 +1,521 @@
+use core::fmt::Write;
+use core::str::FromStr;
 use core::sync::atomic::{AtomicBool, Ordering};
 
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
-use embassy_time::Duration;
-use heapless::String;
+use embassy_time::{Duration, Instant};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor, WebColors};
+use heapless::{String, Vec};
 use static_cell::make_static;
+use strum_macros::EnumString;
 
 use crate::{
-    app::UnicornApp, buttons::ButtonPress, display::messages::DisplayTextMessage,
-    mqtt::MqttReceiveMessage,
+    app::UnicornApp,
+    buttons::ButtonPress,
+    config::{LAST_MESSAGE_STALE_SECS, TEXT_DEDUPE_WINDOW_SECS},
+    display::messages::DisplayTextMessage,
+    fonts::{Font, ScrollDirection, TextAlign, TextDecoration, TextOverflow},
+    graphics::colors::Rgb888Str,
+    json, logging,
+    mqtt::{
+        topics::{
+            PERSISTENT_TEXT_SET_TOPIC, PERSISTENT_TEXT_STATE_TOPIC, TEXT_SET_TOPIC,
+            TEXT_TEMPLATE_SET_TOPIC, TEXT_TEMPLATE_STATE_TOPIC,
+        },
+        MqttMessage, MqttReceiveMessage,
+    },
+    pending,
+    storage::{Record, Storage},
+    system::quiet_hours,
+    time::Time,
 };
 
+/// The number of messages kept in the history.
+const HISTORY_SIZE: usize = 10;
+
+/// Number of payload bytes kept to confirm a repeat, alongside the hash.
+const DEDUPE_PREFIX_SIZE: usize = 64;
+
+/// Color of a restored message older than `LAST_MESSAGE_STALE_SECS`.
+const STALE_COLOR: Rgb888 = Rgb888::new(60, 60, 60);
+
+/// Severity of a text message. Sets the color the message is displayed in.
+#[derive(Clone, Copy, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Severity {
+    /// Informational message, displayed in the active color.
+    Info,
+
+    /// Warning message, displayed in orange.
+    #[strum(serialize = "warn", serialize = "warning")]
+    Warn,
+
+    /// Critical message, displayed in red.
+    Critical,
+}
+
+impl Severity {
+    /// Get the color to display the severity in. A `None` will use the active color.
+    pub fn color(&self) -> Option<Rgb888> {
+        match self {
+            Severity::Info => None,
+            Severity::Warn => Some(Rgb888::CSS_ORANGE),
+            Severity::Critical => Some(Rgb888::RED),
+        }
+    }
+}
+
+/// A message received on the text topic.
+#[derive(Clone)]
+pub struct TextMessage {
+    /// The abbreviated topic suffix the message was published to.
+    /// Empty if the message was published directly to the text topic.
+    pub source: String<24>,
+
+    /// The text of the message.
+    pub text: String<64>,
+
+    /// The severity of the message.
+    pub severity: Severity,
+
+    /// The color to display the message in, overriding the severity color.
+    pub color: Option<Rgb888>,
+
+    /// Flash the message on and off whilst it is displayed.
+    pub blink: bool,
+
+    /// The font to display the message in.
+    pub font: Font,
+
+    /// Outline or shadow drawn behind the message.
+    pub decoration: Option<TextDecoration>,
+
+    /// Where to place the message when it fits on the display.
+    pub align: TextAlign,
+
+    /// What to do with the message when it is too wide for the display.
+    pub overflow: TextOverflow,
+
+    /// Show the message as two lines.
+    pub two_line: bool,
+
+    /// How long each page is shown for when the message is paged.
+    pub page_duration: Option<Duration>,
+
+    /// Direction to scroll the message in, or the default direction if `None`.
+    pub direction: Option<ScrollDirection>,
+
+    /// The message was restored after a reboot and is older than `LAST_MESSAGE_STALE_SECS`.
+    pub stale: bool,
+}
+
+impl TextMessage {
+    /// Create a new text message, abbreviating `topic` to its last two levels below the text topic.
+    pub fn new(topic: &str, text: String<64>, severity: Severity, blink: bool, font: Font) -> Self {
+        let suffix = topic
+            .strip_prefix(TEXT_SET_TOPIC)
+            .unwrap_or_default()
+            .trim_matches('/');
+
+        let start = suffix
+            .rmatch_indices('/')
+            .nth(1)
+            .map(|(index, _)| index + 1)
+            .unwrap_or(0);
+
+        let mut source = String::<24>::new();
+        for c in suffix[start..].chars() {
+            if source.push(c).is_err() {
+                break;
+            }
+        }
+
+        Self {
+            source,
+            text,
+            severity,
+            color: None,
+            blink,
+            font,
+            decoration: None,
+            align: TextAlign::default(),
+            overflow: TextOverflow::default(),
+            two_line: false,
+            page_duration: None,
+            direction: None,
+            stale: false,
+        }
+    }
+
+    /// Get the color to display the message in. A `None` will use the active color.
+    /// Stale messages are greyed out.
+    pub fn color(&self) -> Option<Rgb888> {
+        if self.stale {
+            return Some(STALE_COLOR);
+        }
+
+        self.color.or(self.severity.color())
+    }
+
+    /// Get the text to display, prefixed with the source if `show_source` is true.
+    pub fn display_text(&self, show_source: bool) -> String<64> {
+        if !show_source || self.source.is_empty() {
+            return self.text.clone();
+        }
+
+        let mut result = String::<64>::new();
+        for c in self
+            .source
+            .chars()
+            .chain(": ".chars())
+            .chain(self.text.chars())
+        {
+            if result.push(c).is_err() {
+                break;
+            }
+        }
+
+        result
+    }
+}
+
 /// MQTT app. Will display the latest MQTT message.
 pub struct MqttApp {
-    /// The last message received.
-    pub last_message: Mutex<ThreadModeRawMutex, Option<String<64>>>,
+    /// The last messages received, oldest first.
+    pub messages: Mutex<ThreadModeRawMutex, Vec<TextMessage, HISTORY_SIZE>>,
+
+    /// The index into `messages` of the message being displayed.
+    pub selected: Mutex<ThreadModeRawMutex, usize>,
 
     /// Signal to update the message displayed.
     pub update_message: Signal<ThreadModeRawMutex, bool>,
 
+    /// Show the history index before the next message is displayed.
+    pub show_index: AtomicBool,
+
+    /// Show the topic the message was published to before the message.
+    pub show_source: AtomicBool,
+
+    /// JSONPath-lite template used to extract the displayed value from JSON payloads.
+    pub template: Mutex<ThreadModeRawMutex, Option<String<32>>>,
+
+    /// Text displayed when idle, in place of the last message.
+    pub persistent_text: Mutex<ThreadModeRawMutex, Option<String<64>>>,
+
+    /// The message history is being stepped through, so is shown over the persistent text.
+    pub browsing: AtomicBool,
+
+    /// A hash of the topic and payload of the last text message received, the start of the
+    /// payload, and when it was received.
+    pub last_received:
+        Mutex<ThreadModeRawMutex, Option<(u32, Vec<u8, DEDUPE_PREFIX_SIZE>, Instant)>>,
+
     /// Track if the app is active or not.
     pub is_active: AtomicBool,
+
+    /// Storage for the last message, so it is shown again after a reboot.
+    storage: &'static Storage,
+
+    /// Time used to date the saved message.
+    time: &'static Time,
 }
 
 impl MqttApp {
     /// Create the static ref to MQTT app.
     /// Must only be called once or will panic.
-    pub fn new() -> &'static Self {
+    pub fn new(storage: &'static Storage, time: &'static Time) -> &'static Self {
         make_static!(Self {
-            last_message: Mutex::new(None),
+            messages: Mutex::new(Vec::new()),
+            selected: Mutex::new(0),
             update_message: Signal::new(),
+            show_index: AtomicBool::new(false),
+            show_source: AtomicBool::new(false),
+            template: Mutex::new(None),
+            persistent_text: Mutex::new(None),
+            browsing: AtomicBool::new(false),
+            last_received: Mutex::new(None),
             is_active: AtomicBool::new(false),
+            storage,
+            time,
         })
     }
 
+    /// Restore the last message saved in flash, marking it stale if it is older than
+    /// `LAST_MESSAGE_STALE_SECS`.
+    pub async fn restore(&self) {
+        let mut record = [0u8; Record::LastMessage.max_size()];
+        let len = match self
+            .storage
+            .read_record(Record::LastMessage, &mut record)
+            .await
+        {
+            Ok(Some(len)) => len,
+            _ => return,
+        };
+
+        // the time received, then the length of the source, the source and the text
+        let record = &record[..len];
+        let Some((timestamp, source_len)) = record
+            .get(..8)
+            .zip(record.get(8))
+            .map(|(timestamp, len)| (i64::from_le_bytes(timestamp.try_into().unwrap()), *len))
+        else {
+            return;
+        };
+        let Some((source, text)) = record
+            .get(9..)
+            .filter(|rest| rest.len() >= source_len as usize)
+            .map(|rest| rest.split_at(source_len as usize))
+        else {
+            return;
+        };
+
+        let (Ok(source), Ok(text)) = (core::str::from_utf8(source), core::str::from_utf8(text))
+        else {
+            return;
+        };
+        let (Ok(source), Ok(text)) = (String::try_from(source), String::try_from(text)) else {
+            return;
+        };
+
+        let mut message = TextMessage::new("", text, Severity::Info, false, Font::default());
+        message.source = source;
+        let age = self.time.now().await.timestamp() - timestamp;
+        message.stale = age > LAST_MESSAGE_STALE_SECS as i64;
+
+        self.set_last_message(message).await;
+    }
+
+    /// Save `message` to flash with the time it was received, so it can be restored after a reboot.
+    async fn save_last_message(&self, message: &TextMessage) {
+        let mut record = Vec::<u8, { Record::LastMessage.max_size() }>::new();
+        let timestamp = self.time.now().await.timestamp();
+        let _ = record.extend_from_slice(&timestamp.to_le_bytes());
+        let _ = record.push(message.source.len() as u8);
+        let _ = record.extend_from_slice(message.source.as_bytes());
+        let _ = record.extend_from_slice(message.text.as_bytes());
+
+        if self
+            .storage
+            .write_record(Record::LastMessage, &record)
+            .await
+            .is_err()
+        {
+            logging::warn!("Last message could not be saved");
+        }
+    }
+
+    /// Handle a message received on the text topic, or a sub topic of it.
+    /// The message is queued on the display and added to the history.
+    ///
+    /// JSON payloads can set `severity` to one of `info`, `warn` or `critical`,
+    /// `color` to a color name, hex color or `r,g,b` triplet,
+    /// `blink` to `true`, `sticky` to `true` to keep the message displayed until cleared
+    /// `font` to one of `big`, `regular`, `medium`, `small`, `tiny` or the name of a custom font
+    /// `outline` or `shadow` to a color to draw behind the text,
+    /// `smooth` to `true` to blend columns when scrolling,
+    /// `align` to one of `left`, `center` or `right`,
+    /// `overflow` to one of `scroll`, `truncate`, `ellipsis` or `page`,
+    /// `page_duration` to the seconds each page is shown for,
+    /// `direction` to one of `right_to_left`, `left_to_right` or `bounce`
+    /// and `two_line` to `true` to show the text as two lines.
+    pub async fn receive_text(&self, topic: &str, payload: &str) {
+        if self.is_repeat(topic, payload).await {
+            MqttMessage::enqueue_debug("Dropped repeated text message").await;
+            return;
+        }
+
+        let text = self.apply_template(payload).await;
+        let severity = json::extract(payload, "severity")
+            .and_then(|severity| Severity::from_str(severity).ok())
+            .unwrap_or(Severity::Info);
+        let blink = json::extract(payload, "blink") == Some("true");
+        let sticky = json::extract(payload, "sticky") == Some("true");
+        let smooth = json::extract(payload, "smooth") == Some("true");
+        let font = json::extract(payload, "font")
+            .and_then(Font::from_name)
+            .unwrap_or_default();
+        let mut message = TextMessage::new(topic, text, severity, blink, font);
+        message.color = json::extract(payload, "color")
+            .and_then(|color| Rgb888Str::from_str(color).ok())
+            .map(|Rgb888Str(color)| color);
+        message.decoration = Self::parse_decoration(payload);
+        message.align = json::extract(payload, "align")
+            .and_then(|align| TextAlign::from_str(align).ok())
+            .unwrap_or_default();
+        message.overflow = json::extract(payload, "overflow")
+            .and_then(|overflow| TextOverflow::from_str(overflow).ok())
+            .unwrap_or_default();
+        message.two_line = json::extract(payload, "two_line") == Some("true");
+        message.page_duration = json::extract(payload, "page_duration")
+            .and_then(|secs| secs.parse::<f32>().ok())
+            .filter(|secs| *secs > 0.0)
+            .map(|secs| Duration::from_millis((secs * 1000.0) as u64));
+        message.direction = json::extract(payload, "direction")
+            .and_then(|direction| ScrollDirection::from_str(direction).ok());
+
+        // keep held back messages as the last message, so they can be seen after quiet hours
+        if quiet_hours::is_quiet() && !matches!(message.severity, Severity::Critical) {
+            self.save_last_message(&message).await;
+            self.set_last_message(message).await;
+            return;
+        }
+
+        // keep the message in flash until it has been shown, so a reboot does not lose it
+        let show_source = self.show_source.load(Ordering::Relaxed);
+        let text = message.display_text(show_source);
+        let display_message = DisplayTextMessage::from_mqtt(&text, message.color(), None)
+            .with_blink(message.blink)
+            .with_font(message.font)
+            .with_decoration(message.decoration)
+            .with_align(message.align)
+            .with_overflow(message.overflow)
+            .with_two_line(message.two_line)
+            .with_page_duration(message.page_duration)
+            .with_direction(message.direction)
+            .with_smooth_scroll(smooth)
+            .with_sticky(sticky)
+            .with_id(json::extract(payload, "id"));
+        let seq = pending::add(self.storage, self.time, &display_message).await;
+        display_message.with_pending(Some(seq)).send().await;
+
+        self.save_last_message(&message).await;
+        self.set_last_message(message).await;
+    }
+
+    /// Check if the same payload was received on the same topic within the dedupe window,
+    /// and remember this message for the next check.
+    /// Repeats keep extending the window, so a notification re-sent in a loop is only shown once.
+    async fn is_repeat(&self, topic: &str, payload: &str) -> bool {
+        let Some(window) = TEXT_DEDUPE_WINDOW_SECS else {
+            return false;
+        };
+
+        // FNV-1a, with a separator so the topic and payload cannot run into each other
+        let hash = topic
+            .bytes()
+            .chain(core::iter::once(0))
+            .chain(payload.bytes())
+            .fold(0x811c_9dc5_u32, |hash, byte| {
+                (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+            });
+
+        // the hash alone could match a different message, so the start of the payload must too
+        let prefix = &payload.as_bytes()[..payload.len().min(DEDUPE_PREFIX_SIZE)];
+
+        let mut last_received = self.last_received.lock().await;
+        let is_repeat = last_received
+            .as_ref()
+            .is_some_and(|(last, last_prefix, at)| {
+                *last == hash && last_prefix.as_slice() == prefix && at.elapsed().as_secs() < window
+            });
+        *last_received = Some((hash, Vec::from_slice(prefix).unwrap(), Instant::now()));
+
+        is_repeat
+    }
+
+    /// Get the outline or shadow from the `outline` or `shadow` color of a JSON payload.
+    fn parse_decoration(payload: &str) -> Option<TextDecoration> {
+        let color = |field| {
+            json::extract(payload, field)
+                .and_then(|color| Rgb888Str::from_str(color).ok())
+                .map(|Rgb888Str(color)| color)
+        };
+
+        color("outline")
+            .map(TextDecoration::Outline)
+            .or_else(|| color("shadow").map(TextDecoration::Shadow))
+    }
+
     /// Set the last message received from MQTT.
-    pub async fn set_last_message(&self, message: String<64>) {
-        self.last_message.lock().await.replace(message);
+    /// Drops the oldest message if the history is full and selects the new message.
+    pub async fn set_last_message(&self, message: TextMessage) {
+        let mut messages = self.messages.lock().await;
+        if messages.is_full() {
+            messages.remove(0);
+        }
+        let _ = messages.push(message);
+
+        *self.selected.lock().await = messages.len() - 1;
+        self.show_index.store(false, Ordering::Relaxed);
+        self.browsing.store(false, Ordering::Relaxed);
+        self.update_message.signal(true);
+    }
+
+    /// Set the template used to extract values from JSON payloads and send the state over MQTT.
+    /// An empty template will display payloads as they are received.
+    pub async fn set_template(&self, template: &str) {
+        let mut new_template = String::<32>::new();
+        if new_template.push_str(template.trim()).is_err() {
+            MqttMessage::enqueue_debug("Template is too long").await;
+            return;
+        }
+
+        *self.template.lock().await = if new_template.is_empty() {
+            None
+        } else {
+            Some(new_template)
+        };
+
+        self.send_mqtt_state().await;
+    }
+
+    /// Set the text displayed when idle and send the state over MQTT.
+    /// An empty text goes back to displaying the last message.
+    pub async fn set_persistent_text(&self, text: &str) {
+        let mut new_text = String::<64>::new();
+        if new_text.push_str(text.trim()).is_err() {
+            MqttMessage::enqueue_debug("Persistent text is too long").await;
+            return;
+        }
+
+        *self.persistent_text.lock().await = if new_text.is_empty() {
+            None
+        } else {
+            Some(new_text)
+        };
+
+        self.browsing.store(false, Ordering::Relaxed);
+        self.update_message.signal(true);
+        self.send_mqtt_state().await;
+    }
+
+    /// Get the text to display for `payload`, extracting a field with the template if one is set.
+    /// Without a template, the `text` field of a JSON payload is used.
+    /// Payloads that do not match are displayed as they are received.
+    pub async fn apply_template(&self, payload: &str) -> String<64> {
+        let text = match self.template.lock().await.as_ref() {
+            Some(template) => json::extract(payload, template),
+            None => json::extract(payload, "text"),
+        }
+        .unwrap_or(payload);
+
+        let mut result = String::<64>::new();
+        if result.push_str(text).is_err() {
+            result.push_str("Too many characters!").unwrap();
+        }
+
+        result
+    }
+
+    /// Toggle showing the source of each message before its text.
+    pub async fn toggle_source(&self) {
+        self.show_source.fetch_xor(true, Ordering::Relaxed);
+        self.update_message.signal(true);
+    }
+
+    /// Step through the message history by `step`, clamped to the oldest and newest messages.
+    async fn navigate(&self, step: isize) {
+        let len = self.messages.lock().await.len();
+        if len == 0 {
+            return;
+        }
+
+        let mut selected = self.selected.lock().await;
+        *selected = selected.saturating_add_signed(step).min(len - 1);
+
+        self.show_index.store(true, Ordering::Relaxed);
+        self.browsing.store(true, Ordering::Relaxed);
         self.update_message.signal(true);
     }
 }
@@ -43,11 +523,62 @@ impl MqttApp {
 impl UnicornApp for MqttApp {
     async fn display(&self) {
         loop {
-            match self.last_message.lock().await.as_ref() {
-                Some(val) => {
-                    DisplayTextMessage::from_app(&val, None, None, Some(Duration::from_secs(1)))
+            let persistent_text = if self.browsing.load(Ordering::Relaxed) {
+                None
+            } else {
+                self.persistent_text.lock().await.clone()
+            };
+
+            if let Some(text) = persistent_text {
+                DisplayTextMessage::from_app(&text, None, None, Some(Duration::from_secs(1)))
+                    .send_and_replace_queue()
+                    .await;
+
+                self.update_message.wait().await;
+                continue;
+            }
+
+            let selected = *self.selected.lock().await;
+            let (message, len) = {
+                let messages = self.messages.lock().await;
+                (messages.get(selected).cloned(), messages.len())
+            };
+
+            match message {
+                Some(message) => {
+                    let val = message.display_text(self.show_source.load(Ordering::Relaxed));
+                    let text_message = DisplayTextMessage::from_app(
+                        &val,
+                        message.color(),
+                        None,
+                        Some(Duration::from_secs(1)),
+                    )
+                    .with_blink(message.blink)
+                    .with_font(message.font)
+                    .with_decoration(message.decoration)
+                    .with_align(message.align)
+                    .with_overflow(message.overflow)
+                    .with_two_line(message.two_line)
+                    .with_page_duration(message.page_duration)
+                    .with_direction(message.direction);
+
+                    if self.show_index.swap(false, Ordering::Relaxed) {
+                        let mut index = String::<8>::new();
+                        write!(index, "{}/{len}", selected + 1).unwrap();
+
+                        DisplayTextMessage::from_app(
+                            &index,
+                            None,
+                            None,
+                            Some(Duration::from_millis(750)),
+                        )
                         .send_and_replace_queue()
-                        .await
+                        .await;
+
+                        text_message.send().await
+                    } else {
+                        text_message.send_and_replace_queue().await
+                    }
                 }
                 None => {
                     DisplayTextMessage::from_app(
@@ -73,9 +604,34 @@ impl UnicornApp for MqttApp {
         self.is_active.store(false, Ordering::Relaxed);
     }
 
-    async fn button_press(&self, _: ButtonPress) {}
+    async fn button_press(&self, press: ButtonPress) {
+        match press {
+            ButtonPress::Short => self.navigate(-1).await,
+            ButtonPress::Double => self.navigate(1).await,
+            ButtonPress::Long => {
+                let len = self.messages.lock().await.len();
+                self.navigate(len as isize).await;
+            }
+        }
+    }
+
+    async fn process_mqtt_message(&self, message: MqttReceiveMessage) {
+        if message.topic == TEXT_TEMPLATE_SET_TOPIC {
+            self.set_template(&message.body).await;
+        } else if message.topic == PERSISTENT_TEXT_SET_TOPIC {
+            self.set_persistent_text(&message.body).await;
+        }
+    }
 
-    async fn process_mqtt_message(&self, _: MqttReceiveMessage) {}
+    async fn send_mqtt_state(&self) {
+        match self.template.lock().await.as_ref() {
+            Some(template) => MqttMessage::enqueue_state(TEXT_TEMPLATE_STATE_TOPIC, template).await,
+            None => MqttMessage::enqueue_state(TEXT_TEMPLATE_STATE_TOPIC, "").await,
+        }
 
-    async fn send_mqtt_state(&self) {}
+        match self.persistent_text.lock().await.as_ref() {
+            Some(text) => MqttMessage::enqueue_state(PERSISTENT_TEXT_STATE_TOPIC, text).await,
+            None => MqttMessage::enqueue_state(PERSISTENT_TEXT_STATE_TOPIC, "").await,
+        }
+    }
 }
@@ -1,6 +1,7 @@
+use core::fmt::Write;
 use cyw43_pio::PioSpi;
 use embassy_executor::Spawner;
-use embassy_futures::select::{select, Either};
+use embassy_futures::select::{select, select3, Either, Either3};
 use embassy_net::{Ipv4Address, Ipv4Cidr, Stack, StackResources};
 use embassy_rp::{
     bind_interrupts,
@@ -8,22 +9,133 @@ use embassy_rp::{
     peripherals::{DMA_CH1, PIN_23, PIN_24, PIN_25, PIN_29, PIO1},
     pio::{InterruptHandler, Pio},
 };
-use embassy_time::{Duration, Timer};
-use heapless::Vec;
-use static_cell::StaticCell;
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor, WebColors};
+use heapless::{String, Vec};
+use portable_atomic::{AtomicI16, Ordering};
+use static_cell::{make_static, StaticCell};
 
 use crate::{
-    config::*,
-    mqtt::clients::{RECEIVE_CLIENT_ERROR, SEND_CLIENT_ERROR},
-    system::SystemState,
+    error::Error,
+    logging,
+    mqtt::{
+        clients::{self, RECEIVE_CLIENT_ERROR, SEND_CLIENT_ERROR},
+        topics::HEALTH_STATE_TOPIC,
+        MqttMessage,
+    },
+    settings,
+    system::{BootStage, SystemState},
+    time::ntp::SYNC_SIGNAL,
 };
 
 /// Network states.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum NetworkState {
     NotInitialised,
     Connected,
-    Error,
+
+    /// The wifi network could not be joined at boot. The device runs without a network
+    /// whilst joining is retried in the background.
+    Offline,
+
+    Error(NetworkError),
+}
+
+/// Network errors, in order of priority.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NetworkError {
+    /// The wifi link has been lost.
+    Wifi,
+
+    /// The MQTT broker can not be reached.
+    Mqtt,
+}
+
+impl NetworkError {
+    /// Get the short code to display for the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            NetworkError::Wifi => "E1 WiFi",
+            NetworkError::Mqtt => "E2 MQTT",
+        }
+    }
+
+    /// Get the color to display the error in.
+    pub fn color(&self) -> Rgb888 {
+        match self {
+            NetworkError::Wifi => Rgb888::RED,
+            NetworkError::Mqtt => Rgb888::CSS_ORANGE,
+        }
+    }
+}
+
+/// Signal to rejoin the wifi network now, rather than waiting for the backoff.
+pub static REJOIN_WIFI: Signal<ThreadModeRawMutex, bool> = Signal::new();
+
+/// Initial seconds to wait before rejoining the wifi network.
+const MIN_REJOIN_SECS: u64 = 2;
+
+/// Maximum seconds to wait before rejoining the wifi network.
+const MAX_REJOIN_SECS: u64 = 64;
+
+/// Seconds to try joining the wifi network at boot before going offline.
+const JOIN_TIMEOUT_SECS: u64 = 30;
+
+/// Seconds between sending the network health over MQTT.
+const HEALTH_INTERVAL_SECS: u64 = 60;
+
+/// Health updates between measuring the signal strength, as it needs a scan.
+const RSSI_EVERY_HEALTH_UPDATES: u32 = 5;
+
+/// Signal to send the network health now, rather than waiting for the interval.
+static SEND_HEALTH: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// Signal strength of the wifi network in dBm, or 0 if it has not been measured.
+static RSSI: AtomicI16 = AtomicI16::new(0);
+
+/// The cyw43 GPIO the Pico W onboard LED is wired to.
+const LED_GPIO: u8 = 0;
+
+/// Milliseconds the onboard LED is on, then off, for a slow blink.
+const SLOW_BLINK_MILLIS: u64 = 500;
+
+/// Milliseconds the onboard LED is on, then off, for a fast blink.
+const FAST_BLINK_MILLIS: u64 = 100;
+
+/// Milliseconds the onboard LED is on between each short dip off.
+const DIP_ON_MILLIS: u64 = 1900;
+
+/// The cyw43 control, shared between rejoining the network and driving the onboard LED.
+type SharedControl = Mutex<ThreadModeRawMutex, cyw43::Control<'static>>;
+
+/// Patterns shown on the onboard LED.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LedPattern {
+    /// Joining the wifi network, or connecting to the MQTT broker.
+    SlowBlink,
+
+    /// Connected to the MQTT broker.
+    Solid,
+
+    /// A network error.
+    FastBlink,
+
+    /// Connected, but the time can not be synced with NTP.
+    Dip,
+}
+
+impl LedPattern {
+    /// Get the pattern for the current network state, boot stage and NTP health.
+    fn from_state(network_state: NetworkState, boot_stage: BootStage, ntp_failing: bool) -> Self {
+        match network_state {
+            NetworkState::Error(_) => LedPattern::FastBlink,
+            NetworkState::Connected if ntp_failing => LedPattern::Dip,
+            NetworkState::Connected if boot_stage > BootStage::Mqtt => LedPattern::Solid,
+            _ => LedPattern::SlowBlink,
+        }
+    }
 }
 
 bind_interrupts!(struct Irqs {
@@ -48,7 +160,9 @@ async fn net_task(stack: &'static Stack<cyw43::NetDriver<'static>>) -> ! {
     stack.run().await
 }
 
-/// Create and join the wifi network. Will wait until it has successfully joined.
+/// Create and join the wifi network.
+/// Will wait until it has joined, or go offline after `JOIN_TIMEOUT_SECS` and keep retrying
+/// in the background.
 pub async fn create_and_join_network(
     spawner: Spawner,
     app_state: &'static SystemState,
@@ -86,12 +200,19 @@ pub async fn create_and_join_network(
         .set_power_management(cyw43::PowerManagementMode::PowerSave)
         .await;
 
+    let settings = settings::get();
+    let [ip_a1, ip_a2, ip_a3, ip_a4] = settings.ip;
+    let [gw_a1, gw_a2, gw_a3, gw_a4] = settings.gateway;
+
     let mut addresses: Vec<Ipv4Address, 3> = Vec::new();
     addresses.insert(0, Ipv4Address::new(1, 1, 1, 1)).unwrap();
     let config = embassy_net::Config::ipv4_static(embassy_net::StaticConfigV4 {
-        address: Ipv4Cidr::new(Ipv4Address::new(IP_A1, IP_A2, IP_A3, IP_A4), PREFIX_LENGTH),
+        address: Ipv4Cidr::new(
+            Ipv4Address::new(ip_a1, ip_a2, ip_a3, ip_a4),
+            settings.prefix_length,
+        ),
         dns_servers: addresses,
-        gateway: Some(Ipv4Address::new(GW_A1, GW_A2, GW_A3, GW_A4)),
+        gateway: Some(Ipv4Address::new(gw_a1, gw_a2, gw_a3, gw_a4)),
     });
     // Generate random seed
     let seed = 0x0123_4567_89ab_cdef; // chosen by fair dice roll. guarenteed to be random.
@@ -108,34 +229,230 @@ pub async fn create_and_join_network(
 
     spawner.spawn(net_task(stack)).unwrap();
 
+    let control: &'static SharedControl = make_static!(Mutex::new(control));
+    spawner.spawn(status_led_task(control, app_state)).unwrap();
+
+    app_state.advance_boot_stage(BootStage::Wifi).await;
+
+    let join = async {
+        loop {
+            let joined = control
+                .lock()
+                .await
+                .join_wpa2(&settings.wifi_network, &settings.wifi_password)
+                .await;
+            match joined {
+                Ok(_) => break,
+                Err(err) => {
+                    app_state
+                        .report_error("WiFi join failed", Error::WifiJoin(err.status))
+                        .await;
+                    Timer::after(Duration::from_secs(2)).await;
+                }
+            }
+        }
+    };
+
+    match with_timeout(Duration::from_secs(JOIN_TIMEOUT_SECS), join).await {
+        Ok(_) => {
+            app_state.set_network_state(NetworkState::Connected).await;
+            app_state.advance_boot_stage(BootStage::Mqtt).await;
+        }
+        Err(_) => {
+            logging::warn!("WiFi not joined after {JOIN_TIMEOUT_SECS}s, running offline");
+            app_state.set_network_state(NetworkState::Offline).await;
+        }
+    }
+
+    spawner
+        .spawn(monitor_network_task(app_state, stack))
+        .unwrap();
+    spawner
+        .spawn(rejoin_network_task(control, app_state, stack))
+        .unwrap();
+    spawner
+        .spawn(health_task(control, stack, app_state))
+        .unwrap();
+
+    stack
+}
+
+/// Rejoin the wifi network when the link is lost, with a backoff between failed attempts.
+/// The `REJOIN_WIFI` signal will force an attempt straight away.
+#[embassy_executor::task]
+async fn rejoin_network_task(
+    control: &'static SharedControl,
+    app_state: &'static SystemState,
+    stack: &'static Stack<cyw43::NetDriver<'static>>,
+) {
+    let mut backoff = MIN_REJOIN_SECS;
+
     loop {
-        match control.join_wpa2(WIFI_NETWORK, WIFI_PASSWORD).await {
-            Ok(_) => break,
-            Err(_) => {
-                Timer::after(Duration::from_secs(2)).await;
+        if let Either::First(_) = select(Timer::after_secs(backoff), REJOIN_WIFI.wait()).await {
+            if stack.is_link_up() {
+                backoff = MIN_REJOIN_SECS;
+                continue;
             }
         }
+
+        let settings = settings::get();
+        let joined = control
+            .lock()
+            .await
+            .join_wpa2(&settings.wifi_network, &settings.wifi_password)
+            .await;
+        backoff = match joined {
+            Ok(_) => {
+                // skip the backoffs of anything that failed whilst the link was down
+                clients::reconnect();
+                SYNC_SIGNAL.signal(true);
+                MIN_REJOIN_SECS
+            }
+            Err(err) => {
+                app_state
+                    .report_error("WiFi rejoin failed", Error::WifiJoin(err.status))
+                    .await;
+                (backoff * 2).min(MAX_REJOIN_SECS)
+            }
+        };
     }
+}
 
-    app_state.set_network_state(NetworkState::Connected).await;
+/// Show the network health on the Pico W onboard LED, so it can be seen when the panel is blanked.
+/// Blinks slowly whilst joining, is solid once the MQTT broker is connected and blinks fast on errors.
+/// The LED holds its level whilst a join attempt is in progress, as the control is busy.
+#[embassy_executor::task]
+async fn status_led_task(control: &'static SharedControl, app_state: &'static SystemState) {
+    let mut led_on = false;
 
-    spawner.spawn(monitor_network_task(app_state)).unwrap();
+    loop {
+        let pattern = LedPattern::from_state(
+            app_state.get_network_state().await,
+            app_state.get_boot_stage().await,
+            app_state.is_ntp_failing(),
+        );
 
-    stack
+        let next_on = pattern == LedPattern::Solid || !led_on;
+        if next_on != led_on {
+            control.lock().await.gpio_set(LED_GPIO, next_on).await;
+            led_on = next_on;
+        }
+
+        match pattern {
+            LedPattern::FastBlink => Timer::after_millis(FAST_BLINK_MILLIS).await,
+            LedPattern::Dip if led_on => Timer::after_millis(DIP_ON_MILLIS).await,
+            LedPattern::Dip => Timer::after_millis(FAST_BLINK_MILLIS).await,
+            LedPattern::SlowBlink | LedPattern::Solid => {
+                Timer::after_millis(SLOW_BLINK_MILLIS).await
+            }
+        }
+    }
+}
+
+/// Send the network health over MQTT as soon as possible.
+pub fn request_health_state() {
+    SEND_HEALTH.signal(());
+}
+
+/// Measure the signal strength of the wifi network by scanning for it.
+async fn measure_rssi(control: &'static SharedControl) {
+    let mut options = cyw43::ScanOptions::default();
+    options.ssid = Some(settings::get().wifi_network.clone());
+
+    let mut control = control.lock().await;
+    let mut scanner = control.scan(options).await;
+    let mut strongest: Option<i16> = None;
+    while let Some(bss) = scanner.next().await {
+        strongest = Some(strongest.map_or(bss.rssi, |rssi| rssi.max(bss.rssi)));
+    }
+
+    if let Some(rssi) = strongest {
+        RSSI.store(rssi, Ordering::Relaxed);
+    }
 }
 
-/// Wait for messages from MQTT clients and update network state accordingly.
-/// There is no built in detection for network errors hence the relying on MQTT net stack.
+/// Send the IP address, wifi signal strength, uptime and NTP health over MQTT every
+/// `HEALTH_INTERVAL_SECS`, for the home assistant diagnostic sensors.
 #[embassy_executor::task]
-async fn monitor_network_task(app_state: &'static SystemState) {
-    let res = match select(SEND_CLIENT_ERROR.wait(), RECEIVE_CLIENT_ERROR.wait()).await {
-        Either::First(val) => val,
-        Either::Second(val) => val,
-    };
+async fn health_task(
+    control: &'static SharedControl,
+    stack: &'static Stack<cyw43::NetDriver<'static>>,
+    app_state: &'static SystemState,
+) {
+    let mut updates: u32 = 0;
+
+    loop {
+        if stack.is_link_up() {
+            if updates % RSSI_EVERY_HEALTH_UPDATES == 0 {
+                measure_rssi(control).await;
+            }
+            updates = updates.wrapping_add(1);
+
+            let mut state = String::<128>::new();
+            state.push_str(r#"{"ip":""#).unwrap();
+            if let Some(config) = stack.config_v4() {
+                write!(state, "{}", config.address.address()).unwrap();
+            }
+
+            match RSSI.load(Ordering::Relaxed) {
+                0 => state.push_str(r#"","rssi":null"#).unwrap(),
+                rssi => write!(state, r#"","rssi":{rssi}"#).unwrap(),
+            }
+            write!(
+                state,
+                r#","uptime":{},"ntp_failing":{}}}"#,
+                Instant::now().as_secs(),
+                app_state.is_ntp_failing()
+            )
+            .unwrap();
+
+            MqttMessage::enqueue_state(HEALTH_STATE_TOPIC, &state).await;
+        }
 
-    if res {
-        app_state.set_network_state(NetworkState::Connected).await;
-    } else {
-        app_state.set_network_state(NetworkState::Error).await;
+        select(Timer::after_secs(HEALTH_INTERVAL_SECS), SEND_HEALTH.wait()).await;
+    }
+}
+
+/// Wait for messages from MQTT clients and check the wifi link, updating network state accordingly.
+/// There is no built in detection for broker errors hence the relying on MQTT net stack.
+/// Whilst offline, the wifi link being down is expected, so it is not reported as an error.
+#[embassy_executor::task]
+async fn monitor_network_task(
+    app_state: &'static SystemState,
+    stack: &'static Stack<cyw43::NetDriver<'static>>,
+) {
+    let mut send_error = false;
+    let mut receive_error = false;
+
+    loop {
+        match select3(
+            SEND_CLIENT_ERROR.wait(),
+            RECEIVE_CLIENT_ERROR.wait(),
+            Timer::after_secs(5),
+        )
+        .await
+        {
+            Either3::First(val) => send_error = val,
+            Either3::Second(val) => receive_error = val,
+            Either3::Third(_) => {}
+        };
+
+        let current_state = app_state.get_network_state().await;
+
+        let new_state = if !stack.is_link_up() {
+            if current_state == NetworkState::Offline {
+                current_state
+            } else {
+                NetworkState::Error(NetworkError::Wifi)
+            }
+        } else if send_error || receive_error {
+            NetworkState::Error(NetworkError::Mqtt)
+        } else {
+            NetworkState::Connected
+        };
+
+        if new_state != current_state {
+            app_state.set_network_state(new_state).await;
+        }
     }
 }
@@ -0,0 +1,114 @@
+//! Panic handler that keeps a report of the panic across a reboot.
+//!
+//! The report is written to RAM that is not initialised on startup, the board is reset,
+//! and the report is published to the debug topic once MQTT is running again.
+//! Other deliberate resets, such as the supervisor letting the watchdog fire, can leave a report too.
+
+use core::{
+    fmt::{Arguments, Write},
+    mem::MaybeUninit,
+    panic::PanicInfo,
+    ptr::{addr_of, addr_of_mut},
+};
+
+use cortex_m::peripheral::SCB;
+use heapless::String;
+
+/// Marks the report as written by the panic handler, rather than left over from power on.
+const REPORT_MAGIC: u32 = 0x5041_4e43;
+
+/// The maximum length of a panic report.
+pub const REPORT_SIZE: usize = 192;
+
+/// A panic report kept across a reset.
+#[repr(C)]
+struct PanicReport {
+    /// `REPORT_MAGIC` if the report is valid.
+    magic: u32,
+
+    /// The length of the message in bytes.
+    len: usize,
+
+    /// The panic message, including its location.
+    message: [u8; REPORT_SIZE],
+}
+
+/// The last panic report. Placed in `.uninit` so it is not cleared when the board restarts.
+#[link_section = ".uninit.PANIC_REPORT"]
+static mut PANIC_REPORT: MaybeUninit<PanicReport> = MaybeUninit::uninit();
+
+/// Writes formatted text into the report, truncating anything that does not fit.
+struct ReportWriter<'a> {
+    /// The report message buffer.
+    buffer: &'a mut [u8; REPORT_SIZE],
+
+    /// The number of bytes written.
+    len: usize,
+}
+
+impl Write for ReportWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &byte in s.as_bytes() {
+            if self.len == REPORT_SIZE {
+                break;
+            }
+
+            self.buffer[self.len] = byte;
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+
+    write_report(format_args!("{info}"));
+
+    SCB::sys_reset();
+}
+
+/// Write the report kept across the next reset, for a reset that is not caused by a panic.
+pub fn write_report(args: Arguments) {
+    cortex_m::interrupt::free(|_| {
+        // SAFETY: the report is only written with interrupts disabled,
+        // so nothing else on this core can access the report.
+        let report = unsafe { &mut *addr_of_mut!(PANIC_REPORT).cast::<PanicReport>() };
+
+        let mut writer = ReportWriter {
+            buffer: &mut report.message,
+            len: 0,
+        };
+        let _ = writer.write_fmt(args);
+
+        report.len = writer.len;
+        report.magic = REPORT_MAGIC;
+    });
+}
+
+/// Take the report of the panic that caused the last reset, if there was one.
+/// The report is cleared so it is only returned once.
+pub fn take_report() -> Option<String<REPORT_SIZE>> {
+    // SAFETY: only called from the main task after boot, whilst the panic handler is not running.
+    // The fields are plain integers and bytes, so any left over bit pattern is valid to read.
+    let report = unsafe { &mut *addr_of_mut!(PANIC_REPORT).cast::<PanicReport>() };
+    if unsafe { addr_of!(report.magic).read_volatile() } != REPORT_MAGIC {
+        return None;
+    }
+    report.magic = 0;
+
+    let message = &report.message[..report.len.min(REPORT_SIZE)];
+    let mut text = String::new();
+    for c in core::str::from_utf8(message)
+        .unwrap_or("panic report is not valid UTF-8")
+        .chars()
+    {
+        if text.push(c).is_err() {
+            break;
+        }
+    }
+
+    Some(text)
+}
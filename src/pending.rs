@@ -0,0 +1,158 @@
+//! Text messages waiting to be shown, kept in flash so they are not lost to a reboot.
+//!
+//! Each text message queued on the display is saved along with when it was received, and removed
+//! again once it has been shown in full. Messages still waiting at boot are queued again with the
+//! options they were sent with, unless they are older than `PENDING_MESSAGE_MAX_AGE_SECS`.
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use heapless::Vec;
+use portable_atomic::{AtomicU32, Ordering};
+
+use crate::{
+    config::PENDING_MESSAGE_MAX_AGE_SECS,
+    display::messages::{DisplayTextMessage, SAVED_TEXT_MESSAGE_SIZE},
+    logging,
+    storage::{Record, Storage},
+    time::Time,
+};
+
+/// The most messages kept, as many as the MQTT display queue holds.
+const MAX_PENDING: usize = 8;
+
+/// Size of a saved message before the message itself, the time received and the message length.
+const HEADER_SIZE: usize = 8 + 1;
+
+const _: () = assert!(
+    MAX_PENDING * (HEADER_SIZE + SAVED_TEXT_MESSAGE_SIZE) <= Record::PendingMessages.max_size()
+);
+
+/// A text message waiting to be shown.
+struct PendingMessage {
+    /// Number to match the message to its acknowledgement once shown.
+    seq: u32,
+
+    /// Unix time the message was received.
+    received: i64,
+
+    /// The text and options of the message, as saved by `DisplayTextMessage::write_saved`.
+    message: Vec<u8, SAVED_TEXT_MESSAGE_SIZE>,
+}
+
+/// The messages waiting to be shown, oldest first.
+static PENDING: Mutex<ThreadModeRawMutex, Vec<PendingMessage, MAX_PENDING>> =
+    Mutex::new(Vec::new());
+
+/// The number given to the next message.
+static NEXT_SEQ: AtomicU32 = AtomicU32::new(1);
+
+/// Save the messages waiting to be shown to flash.
+async fn save(storage: &'static Storage, pending: &[PendingMessage]) {
+    let mut record = Vec::<u8, { Record::PendingMessages.max_size() }>::new();
+    for message in pending {
+        let _ = record.extend_from_slice(&message.received.to_le_bytes());
+        let _ = record.push(message.message.len() as u8);
+        let _ = record.extend_from_slice(&message.message);
+    }
+
+    if storage
+        .write_record(Record::PendingMessages, &record)
+        .await
+        .is_err()
+    {
+        logging::warn!("Pending messages could not be saved");
+    }
+}
+
+/// Keep `message` until it has been shown, dropping the oldest message if too many are waiting.
+/// Returns the number to mark the message as shown with.
+#[cfg(feature = "mqtt-app")]
+pub async fn add(
+    storage: &'static Storage,
+    time: &'static Time,
+    message: &DisplayTextMessage,
+) -> u32 {
+    let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+    let received = time.now().await.timestamp();
+    let mut saved = Vec::new();
+    message.write_saved(&mut saved);
+
+    let mut pending = PENDING.lock().await;
+    if pending.is_full() {
+        pending.remove(0);
+    }
+    let _ = pending.push(PendingMessage {
+        seq,
+        received,
+        message: saved,
+    });
+
+    save(storage, &pending).await;
+    seq
+}
+
+/// Forget the message numbered `seq` once it has been shown.
+pub async fn shown(storage: &'static Storage, seq: u32) {
+    let mut pending = PENDING.lock().await;
+    let Some(index) = pending.iter().position(|message| message.seq == seq) else {
+        return;
+    };
+
+    pending.remove(index);
+    save(storage, &pending).await;
+}
+
+/// Queue the messages that were waiting to be shown before the reboot, unless they are too old.
+pub async fn replay(storage: &'static Storage, time: &'static Time) {
+    let mut record = [0u8; Record::PendingMessages.max_size()];
+    let len = match storage
+        .read_record(Record::PendingMessages, &mut record)
+        .await
+    {
+        Ok(Some(len)) if len > 0 => len,
+        _ => return,
+    };
+
+    let now = time.now().await.timestamp();
+    let mut pending = PENDING.lock().await;
+    let mut replayed = Vec::<DisplayTextMessage, MAX_PENDING>::new();
+
+    // each message is the time received, the message length and the message
+    let mut rest = &record[..len];
+    while rest.len() >= HEADER_SIZE {
+        let received = i64::from_le_bytes(rest[..8].try_into().unwrap());
+        let message_len = rest[8] as usize;
+        let Some(saved) = rest.get(HEADER_SIZE..HEADER_SIZE + message_len) else {
+            break;
+        };
+        rest = &rest[HEADER_SIZE + message_len..];
+
+        let age = now - received;
+        if age < 0 || age as u64 > PENDING_MESSAGE_MAX_AGE_SECS {
+            continue;
+        }
+
+        let (Some(message), Ok(saved)) = (
+            DisplayTextMessage::read_saved(saved),
+            Vec::from_slice(saved),
+        ) else {
+            continue;
+        };
+
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+        let _ = replayed.push(message.with_pending(Some(seq)));
+        let _ = pending.push(PendingMessage {
+            seq,
+            received,
+            message: saved,
+        });
+    }
+
+    // drop the messages that were too old from flash as well
+    save(storage, &pending).await;
+
+    // the display queue marks messages as shown through the lock, so it is released before sending
+    drop(pending);
+    for message in replayed {
+        message.send().await;
+    }
+}
@@ -0,0 +1,314 @@
+use core::fmt::Write;
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
+use embassy_time::{Duration, Timer};
+use embedded_graphics::{
+    geometry::Point,
+    pixelcolor::{Rgb888, RgbColor},
+    primitives::{Line, Primitive, PrimitiveStyle},
+    Drawable, Pixel,
+};
+use heapless::String;
+use micromath::F32Ext;
+use static_cell::make_static;
+
+use crate::{
+    app::UnicornApp,
+    board::{UnicornButtons, HEIGHT, WIDTH},
+    buttons::{self, ButtonPress},
+    display::{self, messages::FrameBuilder, Display, FrameTicker},
+    fonts::Font,
+    mqtt::MqttReceiveMessage,
+};
+
+/// Height of each paddle in pixels.
+const PADDLE_HEIGHT: f32 = 3.0;
+
+/// Speed a paddle moves whilst its button is held, in pixels per second.
+const PADDLE_SPEED: f32 = 14.0;
+
+/// Speed the ball is served at across the display, in pixels per second.
+const SERVE_SPEED: f32 = 18.0;
+
+/// How much faster the ball gets each time it is hit.
+const SPEED_UP: f32 = 1.08;
+
+/// Fastest the ball can go across the display, so it can not skip past a paddle in one frame.
+const MAX_SPEED: f32 = 45.0;
+
+/// Largest up or down speed given to the ball by hitting it with the end of a paddle,
+/// as a fraction of its speed across.
+const MAX_ANGLE: f32 = 0.8;
+
+/// Points needed to win a game.
+const WINNING_SCORE: u8 = 5;
+
+/// How long the score is shown between points.
+const SCORE_SECS: u64 = 2;
+
+/// How long the final score flashes for at the end of a game.
+const GAME_OVER_SECS: u64 = 4;
+
+/// The players, named by the side of the display their paddle is on.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+}
+
+/// A paddle moved up and down by a pair of buttons.
+struct Paddle {
+    /// The x position of the paddle.
+    x: i32,
+
+    /// The y position of the top of the paddle.
+    y: f32,
+
+    /// Button that moves the paddle up.
+    up: UnicornButtons,
+
+    /// Button that moves the paddle down.
+    down: UnicornButtons,
+}
+
+impl Paddle {
+    /// Create a paddle at `x` in the middle of the display.
+    fn new(x: i32, up: UnicornButtons, down: UnicornButtons) -> Self {
+        Self {
+            x,
+            y: (HEIGHT as f32 - PADDLE_HEIGHT) / 2.0,
+            up,
+            down,
+        }
+    }
+
+    /// Move the paddle on by `dt` seconds, from the buttons held.
+    fn update(&mut self, dt: f32) {
+        if buttons::is_held(&self.up) {
+            self.y -= PADDLE_SPEED * dt;
+        }
+        if buttons::is_held(&self.down) {
+            self.y += PADDLE_SPEED * dt;
+        }
+
+        self.y = self.y.clamp(0.0, HEIGHT as f32 - PADDLE_HEIGHT);
+    }
+
+    /// Check if the paddle covers the row `y`.
+    fn covers(&self, y: f32) -> bool {
+        y + 0.5 >= self.y && y - 0.5 <= self.y + PADDLE_HEIGHT
+    }
+
+    /// Draw the paddle.
+    fn draw(&self, frame: &mut FrameBuilder, color: Rgb888) {
+        let top = self.y.round() as i32;
+        let _ = Line::new(
+            Point::new(self.x, top),
+            Point::new(self.x, top + PADDLE_HEIGHT as i32 - 1),
+        )
+        .into_styled(PrimitiveStyle::with_stroke(color, 1))
+        .draw(frame);
+    }
+}
+
+/// The ball.
+struct Ball {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+}
+
+impl Ball {
+    /// Serve the ball from the middle of the display towards `side`.
+    fn serve(side: Side, serve: u32) -> Self {
+        // alternate the serve up and down, so each point starts differently
+        let vy = if serve % 2 == 0 { 0.4 } else { -0.4 } * SERVE_SPEED;
+        let vx = match side {
+            Side::Left => -SERVE_SPEED,
+            Side::Right => SERVE_SPEED,
+        };
+
+        Self {
+            x: WIDTH as f32 / 2.0,
+            y: HEIGHT as f32 / 2.0,
+            vx,
+            vy,
+        }
+    }
+
+    /// Bounce the ball back off `paddle`, angled by where on the paddle it hit.
+    fn hit(&mut self, paddle: &Paddle) {
+        let offset = (self.y - (paddle.y + PADDLE_HEIGHT / 2.0)) / (PADDLE_HEIGHT / 2.0);
+        let speed = (self.vx.abs() * SPEED_UP).min(MAX_SPEED);
+
+        self.vx = if self.vx > 0.0 { -speed } else { speed };
+        self.vy = offset.clamp(-1.0, 1.0) * MAX_ANGLE * speed;
+        self.x = if self.vx > 0.0 {
+            paddle.x as f32 + 1.0
+        } else {
+            paddle.x as f32 - 1.0
+        };
+    }
+}
+
+/// Pong app. A two player game, with the volume buttons moving the left paddle
+/// and the brightness buttons moving the right paddle.
+pub struct PongApp {
+    /// Reference to the display.
+    display: &'static Display<'static>,
+
+    /// Signal to start a new game on a button press.
+    restart: Signal<ThreadModeRawMutex, bool>,
+}
+
+impl PongApp {
+    /// Create the static ref to pong app.
+    /// Must only be called once or will panic.
+    pub fn new(display: &'static Display<'static>) -> &'static Self {
+        make_static!(Self {
+            display,
+            restart: Signal::new(),
+        })
+    }
+
+    /// Show the score in the largest font that fits, the big font on the Galactic Unicorn,
+    /// for `duration`.
+    async fn show_score(&self, left: u8, right: u8, color: Rgb888, duration: Duration) {
+        let mut text = String::<8>::new();
+        let _ = write!(text, "{left}-{right}");
+
+        let mut frame = FrameBuilder::new();
+        let font = Font::largest_fitting(&text, WIDTH as u32, HEIGHT as u32);
+        let x = (WIDTH as i32 - font.measure(&text) as i32) / 2;
+        font.draw(frame.graphics_mut(), &text, x, (HEIGHT / 2) as i32, color);
+
+        frame.show(duration).await;
+        Timer::after(duration).await;
+    }
+
+    /// Play games of pong until the app is changed.
+    async fn play(&self) {
+        let mut left = Paddle::new(0, UnicornButtons::VolumeUp, UnicornButtons::VolumeDown);
+        let mut right = Paddle::new(
+            WIDTH as i32 - 1,
+            UnicornButtons::BrightnessUp,
+            UnicornButtons::BrightnessDown,
+        );
+        let mut scores = (0u8, 0u8);
+        let mut serve = 0;
+        let mut ball = Ball::serve(Side::Left, serve);
+        let mut ticker = FrameTicker::new();
+
+        loop {
+            let dt = ticker.next().await.as_millis() as f32 / 1000.0;
+
+            if self.restart.signaled() {
+                self.restart.reset();
+                scores = (0, 0);
+                serve = 0;
+                ball = Ball::serve(Side::Left, serve);
+            }
+
+            left.update(dt);
+            right.update(dt);
+
+            ball.x += ball.vx * dt;
+            ball.y += ball.vy * dt;
+
+            // bounce off the top and bottom
+            if ball.y < 0.0 {
+                ball.y = -ball.y;
+                ball.vy = -ball.vy;
+            } else if ball.y > HEIGHT as f32 - 1.0 {
+                ball.y = 2.0 * (HEIGHT as f32 - 1.0) - ball.y;
+                ball.vy = -ball.vy;
+            }
+
+            // hit a paddle, or score a point past it
+            let mut scored = None;
+            if ball.vx < 0.0 && ball.x <= left.x as f32 + 1.0 {
+                if left.covers(ball.y) {
+                    ball.hit(&left);
+                } else if ball.x < left.x as f32 {
+                    scored = Some(Side::Right);
+                }
+            } else if ball.vx > 0.0 && ball.x >= right.x as f32 - 1.0 {
+                if right.covers(ball.y) {
+                    ball.hit(&right);
+                } else if ball.x > right.x as f32 {
+                    scored = Some(Side::Left);
+                }
+            }
+
+            let color = self.display.get_color().await;
+
+            if let Some(side) = scored {
+                match side {
+                    Side::Left => scores.0 += 1,
+                    Side::Right => scores.1 += 1,
+                }
+
+                if scores.0 >= WINNING_SCORE || scores.1 >= WINNING_SCORE {
+                    self.show_score(
+                        scores.0,
+                        scores.1,
+                        color,
+                        Duration::from_secs(GAME_OVER_SECS),
+                    )
+                    .await;
+                    scores = (0, 0);
+                } else {
+                    self.show_score(scores.0, scores.1, color, Duration::from_secs(SCORE_SECS))
+                        .await;
+                }
+
+                // the point is served towards the player who lost it
+                serve += 1;
+                ball = Ball::serve(
+                    match side {
+                        Side::Left => Side::Right,
+                        Side::Right => Side::Left,
+                    },
+                    serve,
+                );
+                ticker = FrameTicker::new();
+                continue;
+            }
+
+            let mut frame = FrameBuilder::new();
+            left.draw(&mut frame, Rgb888::WHITE);
+            right.draw(&mut frame, Rgb888::WHITE);
+            let _ = Pixel(
+                Point::new(ball.x.round() as i32, ball.y.round() as i32),
+                color,
+            )
+            .draw(&mut frame);
+
+            frame.show(display::frame_duration()).await;
+        }
+    }
+}
+
+impl UnicornApp for PongApp {
+    async fn display(&self) {
+        self.play().await;
+    }
+
+    async fn start(&self) {
+        buttons::capture(true);
+    }
+
+    async fn stop(&self) {
+        buttons::capture(false);
+    }
+
+    /// Start a new game.
+    async fn button_press(&self, _: ButtonPress) {
+        self.restart.signal(true);
+    }
+
+    async fn process_mqtt_message(&self, _: MqttReceiveMessage) {}
+
+    async fn send_mqtt_state(&self) {}
+}
@@ -0,0 +1,234 @@
+use core::fmt::Write;
+
+use embassy_futures::select::select;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{Duration, Timer};
+use heapless::{String, Vec};
+use static_cell::make_static;
+
+use crate::{
+    app::UnicornApp,
+    buttons::ButtonPress,
+    display::messages::DisplayTextMessage,
+    logging,
+    mqtt::{
+        topics::{
+            QUOTES_ADD_TOPIC, QUOTES_INTERVAL_SET_TOPIC, QUOTES_INTERVAL_STATE_TOPIC,
+            QUOTES_REMOVE_TOPIC, QUOTES_STATE_TOPIC,
+        },
+        MqttMessage, MqttReceiveMessage,
+    },
+    storage::{Record, Storage},
+};
+
+/// The most quotes that can be saved, few enough for the list of every quote to fit in a message.
+const MAX_QUOTES: usize = 8;
+
+/// Longest a quote can be.
+const QUOTE_SIZE: usize = 60;
+
+/// How often to move to the next quote when no interval has been set, in minutes.
+const DEFAULT_INTERVAL_MINS: u8 = 60;
+
+const _: () = assert!(1 + MAX_QUOTES * (QUOTE_SIZE + 1) <= Record::Quotes.max_size());
+
+/// The saved quotes and how often to rotate them.
+struct Quotes {
+    /// The quotes, in the order they were added.
+    list: Vec<String<QUOTE_SIZE>, MAX_QUOTES>,
+
+    /// How often to move to the next quote, in minutes.
+    interval_mins: u8,
+}
+
+/// Quotes app. Show a different quote from a list saved to flash every few minutes,
+/// like a message of the day board.
+pub struct QuotesApp {
+    /// Flash storage the quotes are saved to.
+    storage: &'static Storage,
+
+    /// The saved quotes.
+    quotes: Mutex<ThreadModeRawMutex, Quotes>,
+
+    /// Index of the quote shown.
+    current: Mutex<ThreadModeRawMutex, usize>,
+
+    /// Signal to show the next quote on a button press.
+    next: Signal<ThreadModeRawMutex, bool>,
+}
+
+impl QuotesApp {
+    /// Create the static ref to quotes app.
+    /// Must only be called once or will panic.
+    pub fn new(storage: &'static Storage) -> &'static Self {
+        make_static!(Self {
+            storage,
+            quotes: Mutex::new(Quotes {
+                list: Vec::new(),
+                interval_mins: DEFAULT_INTERVAL_MINS,
+            }),
+            current: Mutex::new(0),
+            next: Signal::new(),
+        })
+    }
+
+    /// Restore the quotes saved in flash.
+    pub async fn restore(&self) {
+        let mut record = [0u8; Record::Quotes.max_size()];
+        let len = match self.storage.read_record(Record::Quotes, &mut record).await {
+            Ok(Some(len)) if len > 0 => len,
+            _ => return,
+        };
+
+        // the interval, then each quote on its own line
+        let Ok(text) = core::str::from_utf8(&record[1..len]) else {
+            return;
+        };
+
+        let mut quotes = self.quotes.lock().await;
+        quotes.interval_mins = record[0].max(1);
+        quotes.list = text
+            .lines()
+            .filter_map(|quote| String::try_from(quote).ok())
+            .take(MAX_QUOTES)
+            .collect();
+    }
+
+    /// Save the quotes to flash.
+    async fn save(&self, quotes: &Quotes) {
+        let mut record = Vec::<u8, { Record::Quotes.max_size() }>::new();
+        let _ = record.push(quotes.interval_mins);
+        for (index, quote) in quotes.list.iter().enumerate() {
+            if index > 0 {
+                let _ = record.push(b'\n');
+            }
+            let _ = record.extend_from_slice(quote.as_bytes());
+        }
+
+        if self
+            .storage
+            .write_record(Record::Quotes, &record)
+            .await
+            .is_err()
+        {
+            logging::warn!("Quotes could not be saved");
+        }
+    }
+
+    /// Add a quote to the end of the list.
+    async fn add(&self, body: &str) {
+        let body = body.trim();
+        if body.is_empty() || body.contains(['"', '\\', '\n']) {
+            MqttMessage::enqueue_debug("Invalid quote").await;
+            return;
+        }
+
+        let Ok(quote) = String::try_from(body) else {
+            MqttMessage::enqueue_debug("Quote too long").await;
+            return;
+        };
+
+        let mut quotes = self.quotes.lock().await;
+        if quotes.list.push(quote).is_err() {
+            MqttMessage::enqueue_debug("Quotes full, remove a quote first").await;
+            return;
+        }
+
+        self.save(&quotes).await;
+    }
+
+    /// Remove the quote matching `body`.
+    async fn remove(&self, body: &str) {
+        let mut quotes = self.quotes.lock().await;
+        let Some(index) = quotes.list.iter().position(|quote| quote == body.trim()) else {
+            MqttMessage::enqueue_debug("Quote not found").await;
+            return;
+        };
+
+        quotes.list.remove(index);
+        self.save(&quotes).await;
+    }
+
+    /// Set how often to move to the next quote, in minutes.
+    async fn set_interval(&self, body: &str) {
+        let Some(interval_mins) = body.trim().parse::<u8>().ok().filter(|mins| *mins > 0) else {
+            MqttMessage::enqueue_debug("Invalid quote interval, expected minutes from 1 to 255")
+                .await;
+            return;
+        };
+
+        let mut quotes = self.quotes.lock().await;
+        quotes.interval_mins = interval_mins;
+        self.save(&quotes).await;
+    }
+}
+
+impl UnicornApp for QuotesApp {
+    async fn display(&self) {
+        loop {
+            let (quote, interval_mins) = {
+                let quotes = self.quotes.lock().await;
+                let mut current = self.current.lock().await;
+                if *current >= quotes.list.len() {
+                    *current = 0;
+                }
+                (quotes.list.get(*current).cloned(), quotes.interval_mins)
+            };
+
+            let text = match &quote {
+                Some(quote) => quote.as_str(),
+                None => "No quotes",
+            };
+
+            DisplayTextMessage::from_app(text, None, None, Some(Duration::from_secs(1)))
+                .send_and_replace_queue()
+                .await;
+
+            select(
+                Timer::after(Duration::from_secs(interval_mins as u64 * 60)),
+                self.next.wait(),
+            )
+            .await;
+
+            let mut current = self.current.lock().await;
+            *current = current.wrapping_add(1);
+        }
+    }
+
+    async fn start(&self) {}
+
+    async fn stop(&self) {}
+
+    async fn button_press(&self, _: ButtonPress) {
+        self.next.signal(true);
+    }
+
+    async fn process_mqtt_message(&self, message: MqttReceiveMessage) {
+        match message.topic.as_str() {
+            QUOTES_ADD_TOPIC => self.add(&message.body).await,
+            QUOTES_REMOVE_TOPIC => self.remove(&message.body).await,
+            QUOTES_INTERVAL_SET_TOPIC => self.set_interval(&message.body).await,
+            _ => {}
+        }
+    }
+
+    /// Send the quotes as a JSON array, and how often they change.
+    async fn send_mqtt_state(&self) {
+        let quotes = self.quotes.lock().await;
+
+        let mut text = String::<{ MAX_QUOTES * (QUOTE_SIZE + 3) + 2 }>::new();
+        let _ = text.push('[');
+        for (index, quote) in quotes.list.iter().enumerate() {
+            if index > 0 {
+                let _ = text.push(',');
+            }
+            let _ = write!(text, r#""{quote}""#);
+        }
+        let _ = text.push(']');
+        MqttMessage::enqueue_state(QUOTES_STATE_TOPIC, &text).await;
+
+        let mut interval = String::<4>::new();
+        let _ = write!(interval, "{}", quotes.interval_mins);
+        MqttMessage::enqueue_state(QUOTES_INTERVAL_STATE_TOPIC, &interval).await;
+    }
+}
@@ -0,0 +1,287 @@
+//! Automation rules run on the device, so basic behaviour carries on when Home Assistant is down.
+//!
+//! Each rule has a trigger, either a time of day, a value published to an MQTT topic or switches
+//! pressed together, and an action, either changing app, showing text or setting the brightness.
+//! Rules are set over MQTT as JSON and saved to flash.
+
+use core::fmt::Write;
+
+use chrono::Timelike;
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, mutex::Mutex};
+use embassy_time::Timer;
+use heapless::{String, Vec};
+
+use crate::{
+    app::AppController,
+    board::UnicornButtons,
+    buttons::{self, CHORD_PRESS},
+    display::{messages::DisplayTextMessage, Display},
+    json, logging,
+    mqtt::{self, topics::RULES_STATE_TOPIC, MqttMessage, MqttReceiveMessage},
+    storage::{Record, Storage},
+    system::reboot::parse_time,
+    time::Time,
+};
+
+/// The most rules that can be set.
+pub const MAX_RULES: usize = 8;
+
+/// Size of the rules JSON saved to flash.
+const JSON_SIZE: usize = Record::Rules.max_size();
+
+/// How often to check if the time of a rule has been reached.
+const CHECK_INTERVAL_SECS: u64 = 15;
+
+/// The rules set, as JSON.
+static RULES: Mutex<ThreadModeRawMutex, String<JSON_SIZE>> = Mutex::new(String::new());
+
+/// Messages received on topics watched by rules.
+static MESSAGES: Channel<ThreadModeRawMutex, MqttReceiveMessage, 2> = Channel::new();
+
+/// What makes a rule run.
+enum Trigger {
+    /// The hour and minute of the day.
+    At(u32, u32),
+
+    /// A message on a topic, optionally only with the given value.
+    Topic {
+        topic: String<64>,
+        equals: Option<String<32>>,
+    },
+
+    /// Switches pressed together, as their chord bits.
+    Chord(u8),
+}
+
+/// What a rule does when it runs.
+enum Action {
+    /// Change to an app.
+    App(String<16>),
+
+    /// Show text ahead of the app.
+    Text(String<64>),
+
+    /// Set the brightness.
+    Brightness(u8),
+}
+
+/// A trigger and the action it runs.
+struct Rule {
+    trigger: Trigger,
+    action: Action,
+}
+
+impl Rule {
+    /// Parse a rule from a JSON object such as `{"at": "07:00", "app": "clock"}`,
+    /// `{"topic": "~/door", "equals": "open", "text": "Door open"}` or `{"chord": "AC", "brightness": 20}`.
+    fn parse(json: &str) -> Option<Self> {
+        let trigger = if let Some(at) = json::extract(json, "at") {
+            let (hour, minute) = parse_time(at).ok()??;
+            Trigger::At(hour, minute)
+        } else if let Some(topic) = json::extract(json, "topic") {
+            Trigger::Topic {
+                topic: String::try_from(topic).ok()?,
+                equals: match json::extract(json, "equals") {
+                    Some(value) => Some(String::try_from(value).ok()?),
+                    None => None,
+                },
+            }
+        } else if let Some(chord) = json::extract(json, "chord") {
+            let mut bits = 0u8;
+            for switch in chord.chars() {
+                bits |= match switch.to_ascii_uppercase() {
+                    'A' => buttons::chord_bit(&UnicornButtons::SwitchA),
+                    'B' => buttons::chord_bit(&UnicornButtons::SwitchB),
+                    'C' => buttons::chord_bit(&UnicornButtons::SwitchC),
+                    _ => return None,
+                };
+            }
+
+            if bits.count_ones() < 2 {
+                return None;
+            }
+            Trigger::Chord(bits)
+        } else {
+            return None;
+        };
+
+        let action = if let Some(app) = json::extract(json, "app") {
+            Action::App(String::try_from(app).ok()?)
+        } else if let Some(text) = json::extract(json, "text") {
+            Action::Text(String::try_from(text).ok()?)
+        } else if let Some(brightness) = json::extract(json, "brightness") {
+            Action::Brightness(brightness.parse().ok()?)
+        } else {
+            return None;
+        };
+
+        Some(Self { trigger, action })
+    }
+}
+
+/// Parse a JSON array of rules, failing if any of them are invalid.
+fn parse_all(json: &str) -> Option<Vec<Rule, MAX_RULES>> {
+    if !json.trim_start().starts_with('[') {
+        return None;
+    }
+
+    let mut rules = Vec::new();
+    let mut path = String::<8>::new();
+    for index in 0.. {
+        path.clear();
+        let _ = write!(path, "[{index}]");
+        let Some(entry) = json::extract(json, &path) else {
+            break;
+        };
+
+        rules.push(Rule::parse(entry)?).ok()?;
+    }
+
+    Some(rules)
+}
+
+/// Set the rules from a JSON array, save them to flash and send the state over MQTT.
+pub async fn set(storage: &'static Storage, body: &str) {
+    let body = body.trim();
+    if parse_all(body).is_none() {
+        MqttMessage::enqueue_debug("Invalid rules, expected a JSON array of rules").await;
+        return;
+    }
+
+    let Ok(json) = String::try_from(body) else {
+        MqttMessage::enqueue_debug("Rules too long").await;
+        return;
+    };
+
+    if storage
+        .write_record(Record::Rules, json.as_bytes())
+        .await
+        .is_err()
+    {
+        logging::warn!("Rules could not be saved");
+    }
+
+    *RULES.lock().await = json;
+    send_state().await;
+
+    // the topics watched by the rules may have changed
+    mqtt::clients::resubscribe();
+}
+
+/// Send the rules over MQTT.
+pub async fn send_state() {
+    let json = RULES.lock().await.clone();
+    MqttMessage::enqueue_state(RULES_STATE_TOPIC, &json).await;
+}
+
+/// Load the rules from flash.
+/// Must be called before the MQTT receive client starts, so it subscribes to the topics watched.
+pub async fn restore(storage: &'static Storage) {
+    let mut record = [0u8; JSON_SIZE];
+    if let Ok(Some(len)) = storage.read_record(Record::Rules, &mut record).await {
+        if let Ok(json) = core::str::from_utf8(&record[..len]) {
+            *RULES.lock().await = String::try_from(json).unwrap_or_default();
+        }
+    }
+}
+
+/// Get the topics watched by the rules, to be subscribed to.
+pub async fn topics() -> Vec<String<64>, MAX_RULES> {
+    let Some(rules) = parse_all(&RULES.lock().await) else {
+        return Vec::new();
+    };
+
+    rules
+        .into_iter()
+        .filter_map(|rule| match rule.trigger {
+            Trigger::Topic { topic, .. } => Some(topic),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Pass a message received on a topic watched by the rules to them.
+/// The message is dropped if the rules are yet to handle the last ones.
+pub fn observe(message: MqttReceiveMessage) {
+    let _ = MESSAGES.try_send(message);
+}
+
+/// Run the action of a rule.
+async fn run(
+    action: &Action,
+    app_controller: &'static AppController,
+    display: &'static Display<'static>,
+) {
+    match action {
+        Action::App(name) => {
+            if app_controller.select_app(name).await {
+                app_controller.send_mqtt_states().await;
+            } else {
+                MqttMessage::enqueue_debug("Rule app not found").await;
+            }
+        }
+        Action::Text(text) => DisplayTextMessage::from_mqtt(text, None, None).send().await,
+        Action::Brightness(brightness) => display.set_brightness(*brightness).await,
+    }
+}
+
+/// Run the rules whose triggers have been met.
+#[embassy_executor::task]
+pub async fn rules_task(
+    app_controller: &'static AppController,
+    display: &'static Display<'static>,
+    time: &'static Time,
+) {
+    // the minute the time of day rules were last checked, so each runs once
+    let mut last_minute = None;
+
+    loop {
+        let event = select3(
+            MESSAGES.receive(),
+            CHORD_PRESS.wait(),
+            Timer::after_secs(CHECK_INTERVAL_SECS),
+        )
+        .await;
+
+        // all three switches together toggle the name badge rather than running rules
+        #[cfg(feature = "badge-app")]
+        if matches!(event, Either3::Second(buttons::ALL_SWITCHES_CHORD)) {
+            app_controller.toggle_badge().await;
+            continue;
+        }
+
+        // the time of day is not known until it has been synced
+        let mut minute = None;
+        if matches!(event, Either3::Third(_)) && time.is_synced() {
+            let now = time.now().await;
+            let current = (now.hour(), now.minute());
+            if last_minute != Some(current) {
+                minute = Some(current);
+            }
+            last_minute = Some(current);
+        }
+
+        let Some(rules) = parse_all(&RULES.lock().await) else {
+            continue;
+        };
+
+        for rule in rules.iter() {
+            let triggered = match (&rule.trigger, &event) {
+                (Trigger::Topic { topic, equals }, Either3::First(message)) => {
+                    *topic == message.topic
+                        && equals
+                            .as_ref()
+                            .map_or(true, |value| value == message.body.trim())
+                }
+                (Trigger::Chord(bits), Either3::Second(chord)) => bits == chord,
+                (Trigger::At(hour, at_minute), _) => minute == Some((*hour, *at_minute)),
+                _ => false,
+            };
+
+            if triggered {
+                run(&rule.action, app_controller, display).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! LED self test, to check the hardware after soldering or shipping.
+//!
+//! The test lights each row, then each column, then the whole display in each color channel.
+//! It runs on the display queue so nothing else is drawn over it, and runs once on first boot.
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Timer;
+use embedded_graphics::{
+    geometry::Point,
+    pixelcolor::{Rgb888, RgbColor},
+};
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    board::{HEIGHT, WIDTH},
+    display::Display,
+    logging,
+    mqtt::MqttMessage,
+    storage::{Record, Storage},
+};
+
+/// Milliseconds each row is lit for.
+const ROW_MILLIS: u64 = 60;
+
+/// Milliseconds each column is lit for.
+const COLUMN_MILLIS: u64 = 20;
+
+/// Milliseconds each color channel is shown for.
+const CHANNEL_MILLIS: u64 = 500;
+
+/// Signal for the display queue to run the self test. Shared with core 1.
+static START: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Signal from the display queue that the self test has finished. Shared with core 1.
+static DONE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Run the self test and report completion on the debug topic.
+pub async fn run() {
+    logging::info!("Self test started");
+    DONE.reset();
+    START.signal(());
+    DONE.wait().await;
+
+    MqttMessage::enqueue_debug("Self test complete").await;
+}
+
+/// Run the self test if it has never run on this device, such as after a factory reset.
+pub async fn run_on_first_boot(storage: &'static Storage) {
+    if let Ok(Some(_)) = storage.read_record(Record::SelfTest, &mut []).await {
+        return;
+    }
+
+    run().await;
+
+    if storage.write_record(Record::SelfTest, &[]).await.is_err() {
+        logging::warn!("Self test record could not be saved");
+    }
+}
+
+/// Take a pending request to run the self test. Called by the display queue on core 1.
+pub(crate) fn take_request() -> bool {
+    if START.signaled() {
+        START.reset();
+        return true;
+    }
+
+    false
+}
+
+/// Sweep the rows, columns and color channels on the display, then signal `DONE`.
+/// Called by the display queue on core 1.
+pub(crate) async fn sweep(display: &'static Display<'static>) {
+    let mut graphics = UnicornGraphics::<WIDTH, HEIGHT>::new();
+
+    for y in 0..HEIGHT as i32 {
+        graphics.clear_all();
+        for x in 0..WIDTH as i32 {
+            graphics.set_pixel(Point::new(x, y), Rgb888::WHITE);
+        }
+        display.set_graphics(&graphics).await;
+        Timer::after_millis(ROW_MILLIS).await;
+    }
+
+    for x in 0..WIDTH as i32 {
+        graphics.clear_all();
+        for y in 0..HEIGHT as i32 {
+            graphics.set_pixel(Point::new(x, y), Rgb888::WHITE);
+        }
+        display.set_graphics(&graphics).await;
+        Timer::after_millis(COLUMN_MILLIS).await;
+    }
+
+    for color in [Rgb888::RED, Rgb888::GREEN, Rgb888::BLUE, Rgb888::WHITE] {
+        graphics.fill(color);
+        display.set_graphics(&graphics).await;
+        Timer::after_millis(CHANNEL_MILLIS).await;
+    }
+
+    graphics.clear_all();
+    display.set_graphics(&graphics).await;
+
+    DONE.signal(());
+}
@@ -0,0 +1,462 @@
+//! External sensors on the Qw/ST (I2C) connector.
+//!
+//! Each sensor has a driver implementing `Sensor`. Every driver is tried at boot, and the
+//! sensors that answer are read in turn, their readings merged into one and published as JSON.
+//! Adding a sensor only needs a driver and a line in `sensors_task` to try it.
+
+use core::{cell::Cell, fmt::Write};
+
+use embassy_executor::Spawner;
+use embassy_rp::{
+    bind_interrupts,
+    i2c::{self, Async, I2c, InterruptHandler},
+    peripherals::{I2C0, PIN_4, PIN_5},
+};
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use embassy_time::{Instant, Timer};
+use heapless::String;
+use portable_atomic::{AtomicU8, Ordering};
+use thiserror_no_std::Error;
+
+use crate::{
+    logging,
+    mqtt::{topics::SENSORS_STATE_TOPIC, MqttMessage},
+};
+
+bind_interrupts!(struct Irqs {
+    I2C0_IRQ => InterruptHandler<I2C0>;
+});
+
+/// The I2C bus of the Qw/ST connector.
+pub type Bus = I2c<'static, I2C0, Async>;
+
+/// How often the sensors are read and published.
+const READ_INTERVAL_SECS: u64 = 30;
+
+/// How long a reading is used for by other features, such as the clock, without an update.
+#[cfg(feature = "clock-app")]
+const READING_MAX_AGE_SECS: u64 = 5 * 60;
+
+/// Bit in `DETECTED` for the BME280.
+const BME280: u8 = 1 << 0;
+
+/// Bit in `DETECTED` for the SCD40.
+const SCD40: u8 = 1 << 1;
+
+/// The sensors that answered at boot, as bits.
+static DETECTED: AtomicU8 = AtomicU8::new(0);
+
+/// The latest reading and when it was taken.
+static LATEST: Mutex<CriticalSectionRawMutex, Cell<Option<(Reading, Instant)>>> =
+    Mutex::new(Cell::new(None));
+
+/// Error when reading a sensor.
+#[derive(Error, Debug)]
+pub enum SensorError {
+    #[error("I2C bus error")]
+    Bus,
+    #[error("bad checksum")]
+    Checksum,
+    #[error("no data")]
+    NotReady,
+}
+
+impl From<i2c::Error> for SensorError {
+    fn from(_: i2c::Error) -> Self {
+        SensorError::Bus
+    }
+}
+
+/// Measurements from the sensors, each only set if a connected sensor measures it.
+#[derive(Clone, Copy, Default)]
+pub struct Reading {
+    /// Temperature in degrees Celsius.
+    pub temperature: Option<f32>,
+
+    /// Relative humidity in percent.
+    pub humidity: Option<f32>,
+
+    /// Air pressure in hectopascals.
+    pub pressure: Option<f32>,
+
+    /// Carbon dioxide in parts per million.
+    pub co2: Option<u16>,
+}
+
+impl Reading {
+    /// Fill in any measurements missing from this reading from `other`.
+    fn merge(&mut self, other: Reading) {
+        self.temperature = self.temperature.or(other.temperature);
+        self.humidity = self.humidity.or(other.humidity);
+        self.pressure = self.pressure.or(other.pressure);
+        self.co2 = self.co2.or(other.co2);
+    }
+}
+
+/// A driver for a sensor on the Qw/ST connector.
+pub trait Sensor {
+    /// Check the sensor is connected and start it measuring.
+    async fn start(&mut self, bus: &mut Bus) -> Result<(), SensorError>;
+
+    /// Read the latest measurements from the sensor.
+    async fn read(&mut self, bus: &mut Bus) -> Result<Reading, SensorError>;
+}
+
+/// Get the latest reading, unless it is too old to use.
+#[cfg(feature = "clock-app")]
+pub fn latest() -> Option<Reading> {
+    LATEST
+        .lock(|latest| latest.get())
+        .filter(|(_, at)| at.elapsed().as_secs() < READING_MAX_AGE_SECS)
+        .map(|(reading, _)| reading)
+}
+
+/// Check if a connected sensor measures `key`, one of the keys in the published JSON.
+pub fn measures(key: &str) -> bool {
+    let detected = DETECTED.load(Ordering::Relaxed);
+    match key {
+        "temperature" | "humidity" => detected != 0,
+        "pressure" => detected & BME280 != 0,
+        "co2" => detected & SCD40 != 0,
+        _ => false,
+    }
+}
+
+/// Set up the I2C bus on the Qw/ST connector and start reading any sensors on it.
+pub fn start(spawner: Spawner, i2c: I2C0, sda: PIN_4, scl: PIN_5) {
+    let bus = I2c::new_async(i2c, scl, sda, Irqs, i2c::Config::default());
+    spawner.spawn(sensors_task(bus)).unwrap();
+}
+
+/// Start `sensor`, returning `true` if it is connected.
+async fn try_start(bus: &mut Bus, sensor: &mut impl Sensor, name: &str) -> bool {
+    match sensor.start(bus).await {
+        Ok(()) => {
+            logging::info!("Found {name} on the Qw/ST connector");
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Read `sensor` into `reading`, logging any error.
+async fn read_into(bus: &mut Bus, sensor: &mut impl Sensor, name: &str, reading: &mut Reading) {
+    match sensor.read(bus).await {
+        Ok(sensor_reading) => reading.merge(sensor_reading),
+        Err(err) => logging::warn!("{name} read failed: {err}"),
+    }
+}
+
+/// Add `key` to the JSON object in `text` if it has a `value`.
+fn write_field(text: &mut String<96>, key: &str, value: Option<f32>, decimals: usize) {
+    if let Some(value) = value {
+        if text.len() > 1 {
+            let _ = text.push(',');
+        }
+        let _ = write!(text, r#""{key}":{value:.decimals$}"#);
+    }
+}
+
+/// Publish `reading` as JSON, leaving out anything not measured.
+async fn send_reading(reading: &Reading) {
+    let mut text = String::<96>::new();
+    let _ = text.push('{');
+    write_field(&mut text, "temperature", reading.temperature, 1);
+    write_field(&mut text, "humidity", reading.humidity, 1);
+    write_field(&mut text, "pressure", reading.pressure, 1);
+    write_field(&mut text, "co2", reading.co2.map(f32::from), 0);
+    let _ = text.push('}');
+
+    MqttMessage::enqueue_state(SENSORS_STATE_TOPIC, &text).await;
+}
+
+/// Find the sensors on the Qw/ST connector, then read and publish them.
+#[embassy_executor::task]
+async fn sensors_task(mut bus: Bus) {
+    let mut bme280 = bme280::Bme280::new();
+    let mut scd40 = scd40::Scd40::new();
+
+    let mut detected = 0;
+    if try_start(&mut bus, &mut bme280, "BME280").await {
+        detected |= BME280;
+    }
+    if try_start(&mut bus, &mut scd40, "SCD40").await {
+        detected |= SCD40;
+    }
+    DETECTED.store(detected, Ordering::Relaxed);
+
+    if detected == 0 {
+        return;
+    }
+
+    loop {
+        Timer::after_secs(READ_INTERVAL_SECS).await;
+
+        let mut reading = Reading::default();
+        if detected & BME280 != 0 {
+            read_into(&mut bus, &mut bme280, "BME280", &mut reading).await;
+        }
+        if detected & SCD40 != 0 {
+            read_into(&mut bus, &mut scd40, "SCD40", &mut reading).await;
+        }
+
+        LATEST.lock(|latest| latest.set(Some((reading, Instant::now()))));
+        send_reading(&reading).await;
+    }
+}
+
+/// Bosch BME280 temperature, humidity and pressure sensor.
+pub mod bme280 {
+    use super::{Bus, Reading, Sensor, SensorError};
+
+    /// Address of the sensor, with its address pin low as on most breakouts.
+    const ADDRESS: u16 = 0x76;
+
+    /// Register holding the chip id.
+    const REG_ID: u8 = 0xd0;
+
+    /// The chip id of a BME280, rather than the BMP280 without humidity.
+    const CHIP_ID: u8 = 0x60;
+
+    /// Registers of the temperature and pressure calibration, then the first humidity value.
+    const REG_CALIBRATION_TP: u8 = 0x88;
+
+    /// Registers of the rest of the humidity calibration.
+    const REG_CALIBRATION_H: u8 = 0xe1;
+
+    /// Registers of the humidity oversampling, measurement control and standby config.
+    const REG_CTRL_HUM: u8 = 0xf2;
+    const REG_CTRL_MEAS: u8 = 0xf4;
+    const REG_CONFIG: u8 = 0xf5;
+
+    /// Register of the first measurement byte.
+    const REG_DATA: u8 = 0xf7;
+
+    /// Factory calibration read from the sensor, used to compensate the raw measurements.
+    #[derive(Default)]
+    struct Calibration {
+        t1: f32,
+        t2: f32,
+        t3: f32,
+        p: [f32; 9],
+        h1: f32,
+        h2: f32,
+        h3: f32,
+        h4: f32,
+        h5: f32,
+        h6: f32,
+    }
+
+    /// BME280 driver.
+    pub struct Bme280 {
+        calibration: Calibration,
+    }
+
+    impl Bme280 {
+        /// Create a driver, which needs starting before it is read.
+        pub fn new() -> Self {
+            Self {
+                calibration: Calibration::default(),
+            }
+        }
+    }
+
+    impl Sensor for Bme280 {
+        async fn start(&mut self, bus: &mut Bus) -> Result<(), SensorError> {
+            let mut id = [0u8; 1];
+            bus.write_read_async(ADDRESS, [REG_ID], &mut id).await?;
+            if id[0] != CHIP_ID {
+                return Err(SensorError::NotReady);
+            }
+
+            let mut tp = [0u8; 26];
+            bus.write_read_async(ADDRESS, [REG_CALIBRATION_TP], &mut tp)
+                .await?;
+            let mut h = [0u8; 7];
+            bus.write_read_async(ADDRESS, [REG_CALIBRATION_H], &mut h)
+                .await?;
+
+            let u16_at = |bytes: &[u8], i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+            let i16_at = |bytes: &[u8], i: usize| i16::from_le_bytes([bytes[i], bytes[i + 1]]);
+
+            let mut p = [0.0; 9];
+            p[0] = u16_at(&tp, 6) as f32;
+            for (index, value) in p.iter_mut().enumerate().skip(1) {
+                *value = i16_at(&tp, 6 + index * 2) as f32;
+            }
+
+            self.calibration = Calibration {
+                t1: u16_at(&tp, 0) as f32,
+                t2: i16_at(&tp, 2) as f32,
+                t3: i16_at(&tp, 4) as f32,
+                p,
+                h1: tp[25] as f32,
+                h2: i16_at(&h, 0) as f32,
+                h3: h[2] as f32,
+                // 12 bit values split across nibbles of a shared byte
+                h4: ((h[3] as i8 as i16) << 4 | (h[4] & 0x0f) as i16) as f32,
+                h5: ((h[5] as i8 as i16) << 4 | (h[4] >> 4) as i16) as f32,
+                h6: h[6] as i8 as f32,
+            };
+
+            // humidity, temperature and pressure oversampled once, measured every second
+            bus.write_async(ADDRESS, [REG_CTRL_HUM, 0x01]).await?;
+            bus.write_async(ADDRESS, [REG_CONFIG, 0xa0]).await?;
+            bus.write_async(ADDRESS, [REG_CTRL_MEAS, 0x27]).await?;
+            Ok(())
+        }
+
+        async fn read(&mut self, bus: &mut Bus) -> Result<Reading, SensorError> {
+            let mut data = [0u8; 8];
+            bus.write_read_async(ADDRESS, [REG_DATA], &mut data).await?;
+
+            let raw_20 = |i: usize| {
+                ((data[i] as u32) << 12 | (data[i + 1] as u32) << 4 | (data[i + 2] as u32) >> 4)
+                    as f32
+            };
+            let adc_p = raw_20(0);
+            let adc_t = raw_20(3);
+            let adc_h = u16::from_be_bytes([data[6], data[7]]) as f32;
+
+            // compensation formulas from the datasheet
+            let c = &self.calibration;
+            let var1 = (adc_t / 16384.0 - c.t1 / 1024.0) * c.t2;
+            let var2 =
+                (adc_t / 131072.0 - c.t1 / 8192.0) * (adc_t / 131072.0 - c.t1 / 8192.0) * c.t3;
+            let t_fine = var1 + var2;
+            let temperature = t_fine / 5120.0;
+
+            let mut var1 = t_fine / 2.0 - 64000.0;
+            let mut var2 = var1 * var1 * c.p[5] / 32768.0;
+            var2 += var1 * c.p[4] * 2.0;
+            var2 = var2 / 4.0 + c.p[3] * 65536.0;
+            var1 = (c.p[2] * var1 * var1 / 524288.0 + c.p[1] * var1) / 524288.0;
+            var1 = (1.0 + var1 / 32768.0) * c.p[0];
+            let pressure = if var1 == 0.0 {
+                None
+            } else {
+                let mut p = 1048576.0 - adc_p;
+                p = (p - var2 / 4096.0) * 6250.0 / var1;
+                let var1 = c.p[8] * p * p / 2147483648.0;
+                let var2 = p * c.p[7] / 32768.0;
+                Some((p + (var1 + var2 + c.p[6]) / 16.0) / 100.0)
+            };
+
+            let h = t_fine - 76800.0;
+            let h = (adc_h - (c.h4 * 64.0 + c.h5 / 16384.0 * h))
+                * (c.h2 / 65536.0 * (1.0 + c.h6 / 67108864.0 * h * (1.0 + c.h3 / 67108864.0 * h)));
+            let humidity = (h * (1.0 - c.h1 * h / 524288.0)).clamp(0.0, 100.0);
+
+            Ok(Reading {
+                temperature: Some(temperature),
+                humidity: Some(humidity),
+                pressure,
+                co2: None,
+            })
+        }
+    }
+}
+
+/// Sensirion SCD40 carbon dioxide, temperature and humidity sensor.
+pub mod scd40 {
+    use embassy_time::Timer;
+
+    use super::{Bus, Reading, Sensor, SensorError};
+
+    /// Address of the sensor.
+    const ADDRESS: u16 = 0x62;
+
+    /// Commands, each sent as a big endian 16 bit number.
+    const START_PERIODIC_MEASUREMENT: u16 = 0x21b1;
+    const STOP_PERIODIC_MEASUREMENT: u16 = 0x3f86;
+    const GET_DATA_READY_STATUS: u16 = 0xe4b8;
+    const READ_MEASUREMENT: u16 = 0xec05;
+
+    /// How long the sensor takes to stop measuring.
+    const STOP_MILLIS: u64 = 500;
+
+    /// How long the sensor takes to answer a read command.
+    const COMMAND_MILLIS: u64 = 1;
+
+    /// SCD40 driver.
+    pub struct Scd40;
+
+    impl Scd40 {
+        /// Create a driver, which needs starting before it is read.
+        pub fn new() -> Self {
+            Self
+        }
+
+        /// Send `command` with no arguments.
+        async fn command(bus: &mut Bus, command: u16) -> Result<(), SensorError> {
+            bus.write_async(ADDRESS, command.to_be_bytes()).await?;
+            Ok(())
+        }
+
+        /// Send `command` and read its answer of 16 bit words into `words`,
+        /// checking the checksum after each word.
+        async fn read_words(
+            bus: &mut Bus,
+            command: u16,
+            words: &mut [u16],
+        ) -> Result<(), SensorError> {
+            Self::command(bus, command).await?;
+            Timer::after_millis(COMMAND_MILLIS).await;
+
+            let mut data = [0u8; 9];
+            let data = &mut data[..words.len() * 3];
+            bus.read_async(ADDRESS, data).await?;
+
+            for (word, chunk) in words.iter_mut().zip(data.chunks(3)) {
+                if crc(&chunk[..2]) != chunk[2] {
+                    return Err(SensorError::Checksum);
+                }
+                *word = u16::from_be_bytes([chunk[0], chunk[1]]);
+            }
+            Ok(())
+        }
+    }
+
+    /// Sensirion CRC-8 of `data`, with polynomial 0x31 and initial value 0xff.
+    fn crc(data: &[u8]) -> u8 {
+        let mut crc = 0xffu8;
+        for byte in data {
+            crc ^= byte;
+            for _ in 0..8 {
+                crc = if crc & 0x80 != 0 {
+                    (crc << 1) ^ 0x31
+                } else {
+                    crc << 1
+                };
+            }
+        }
+        crc
+    }
+
+    impl Sensor for Scd40 {
+        async fn start(&mut self, bus: &mut Bus) -> Result<(), SensorError> {
+            // it may still be measuring from before a reboot, which blocks other commands
+            Self::command(bus, STOP_PERIODIC_MEASUREMENT).await?;
+            Timer::after_millis(STOP_MILLIS).await;
+            Self::command(bus, START_PERIODIC_MEASUREMENT).await
+        }
+
+        async fn read(&mut self, bus: &mut Bus) -> Result<Reading, SensorError> {
+            let mut status = [0u16; 1];
+            Self::read_words(bus, GET_DATA_READY_STATUS, &mut status).await?;
+            if status[0] & 0x07ff == 0 {
+                return Err(SensorError::NotReady);
+            }
+
+            let mut measurement = [0u16; 3];
+            Self::read_words(bus, READ_MEASUREMENT, &mut measurement).await?;
+
+            Ok(Reading {
+                temperature: Some(-45.0 + 175.0 * measurement[1] as f32 / 65535.0),
+                humidity: Some(100.0 * measurement[2] as f32 / 65535.0),
+                pressure: None,
+                co2: Some(measurement[0]),
+            })
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Sequences of steps, such as text, apps and images, played one after another.
+//!
+//! A sequence is set over MQTT as a JSON array of steps, so presentations such as
+//! the time, then the weather, then a message can be put together without changing the firmware.
+//! Once it finishes, the app shown before the sequence started is shown again.
+
+use core::fmt::Write;
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{Duration, Timer};
+use heapless::{String, Vec};
+
+use crate::{
+    app::AppController,
+    display::messages::DisplayTextMessage,
+    json,
+    mqtt::{topics::SEQUENCE_STATE_TOPIC, MqttMessage},
+};
+
+/// The most steps a sequence can have.
+const MAX_STEPS: usize = 16;
+
+/// How long a step is shown for when it does not give a duration.
+const DEFAULT_STEP_SECS: u64 = 5;
+
+/// The longest a step can be shown for.
+const MAX_STEP_SECS: u64 = 3600;
+
+/// The steps of the sequence set over MQTT.
+static SEQUENCE: Mutex<ThreadModeRawMutex, Vec<Step, MAX_STEPS>> = Mutex::new(Vec::new());
+
+/// Signal to start the sequence from its first step when `true`, or stop it when `false`.
+static CONTROL: Signal<ThreadModeRawMutex, bool> = Signal::new();
+
+/// A step of a sequence.
+#[derive(Clone)]
+enum Step {
+    /// Show text ahead of the app.
+    Text { text: String<64>, secs: u64 },
+
+    /// Change to an app.
+    App { name: String<16>, secs: u64 },
+
+    /// Show an effect in the effects app.
+    Effect { name: String<16>, secs: u64 },
+
+    /// Show an image from the gallery.
+    Image { name: String<32>, secs: u64 },
+
+    /// Leave the display as it is.
+    Pause { secs: u64 },
+
+    /// Play the steps before this one again, the number of times given, or forever for 0.
+    Repeat { times: u32 },
+}
+
+impl Step {
+    /// Parse a step from JSON such as `{"text": "Hello", "duration": 5}` or `{"pause": 2}`.
+    fn parse(json: &str) -> Option<Self> {
+        let secs = json::extract(json, "duration")
+            .map(|value| value.parse::<u64>().ok())
+            .unwrap_or(Some(DEFAULT_STEP_SECS))?
+            .clamp(1, MAX_STEP_SECS);
+
+        if let Some(text) = json::extract(json, "text") {
+            Some(Step::Text {
+                text: String::try_from(text).ok()?,
+                secs,
+            })
+        } else if let Some(name) = json::extract(json, "app") {
+            Some(Step::App {
+                name: String::try_from(name).ok()?,
+                secs,
+            })
+        } else if let Some(name) = json::extract(json, "effect") {
+            Some(Step::Effect {
+                name: String::try_from(name).ok()?,
+                secs,
+            })
+        } else if let Some(name) = json::extract(json, "image") {
+            Some(Step::Image {
+                name: String::try_from(name).ok()?,
+                secs,
+            })
+        } else if let Some(pause) = json::extract(json, "pause") {
+            Some(Step::Pause {
+                secs: pause.parse::<u64>().ok()?.clamp(1, MAX_STEP_SECS),
+            })
+        } else if let Some(times) = json::extract(json, "repeat") {
+            Some(Step::Repeat {
+                times: times.parse().ok()?,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Parse a sequence from a JSON array of steps.
+/// Returns `None` if any step is invalid, or the sequence would repeat without showing anything.
+fn parse_all(json: &str) -> Option<Vec<Step, MAX_STEPS>> {
+    let mut steps = Vec::new();
+    let mut index = 0;
+    let mut path = String::<8>::new();
+
+    loop {
+        path.clear();
+        let _ = write!(path, "[{index}]");
+        let Some(step) = json::extract(json, &path) else {
+            break;
+        };
+
+        let step = Step::parse(step)?;
+        if index == 0 && matches!(step, Step::Repeat { .. }) {
+            return None;
+        }
+
+        steps.push(step).ok()?;
+        index += 1;
+    }
+
+    (!steps.is_empty()).then_some(steps)
+}
+
+/// Start a sequence from a JSON array of steps, replacing any sequence already playing.
+pub async fn start(body: &str) {
+    let Some(steps) = parse_all(body.trim()) else {
+        MqttMessage::enqueue_debug("Invalid sequence").await;
+        return;
+    };
+
+    *SEQUENCE.lock().await = steps;
+    CONTROL.signal(true);
+}
+
+/// Stop the sequence playing, if any.
+pub fn stop() {
+    CONTROL.signal(false);
+}
+
+/// Play the steps of a sequence once, following any repeats.
+async fn play(app_controller: &'static AppController, steps: &[Step]) {
+    let mut repeated = [0u32; MAX_STEPS];
+    let mut index = 0;
+
+    while let Some(step) = steps.get(index) {
+        index += 1;
+
+        let secs = match step {
+            Step::Text { text, secs } => {
+                DisplayTextMessage::from_mqtt(text, None, None)
+                    .with_duration(Duration::from_secs(*secs))
+                    .send()
+                    .await;
+                *secs
+            }
+            Step::App { name, secs } => {
+                if app_controller.select_app(name).await {
+                    app_controller.send_mqtt_states().await;
+                } else {
+                    MqttMessage::enqueue_debug("Sequence app not found").await;
+                }
+                *secs
+            }
+            Step::Effect { name, secs } => {
+                if app_controller.select_effect(name).await {
+                    app_controller.send_mqtt_states().await;
+                } else {
+                    MqttMessage::enqueue_debug("Sequence effect not found").await;
+                }
+                *secs
+            }
+            Step::Image { name, secs } => {
+                if !app_controller
+                    .show_gallery_image(name, Duration::from_secs(*secs))
+                    .await
+                {
+                    MqttMessage::enqueue_debug("Sequence image not found").await;
+                }
+                *secs
+            }
+            Step::Pause { secs } => *secs,
+            Step::Repeat { times } => {
+                let step = index - 1;
+                if *times == 0 || repeated[step] < *times {
+                    repeated[step] += 1;
+                    // repeats before this one play in full each time round
+                    repeated[..step].fill(0);
+                    index = 0;
+                }
+                continue;
+            }
+        };
+
+        Timer::after_secs(secs).await;
+    }
+}
+
+/// Publish whether a sequence is playing.
+async fn send_state(playing: bool) {
+    let state = if playing { "ON" } else { "OFF" };
+    MqttMessage::enqueue_state(SEQUENCE_STATE_TOPIC, state).await;
+}
+
+/// Play sequences as they are started, going back to the app shown before once they finish.
+#[embassy_executor::task]
+pub async fn sequence_task(app_controller: &'static AppController) {
+    loop {
+        if !CONTROL.wait().await {
+            continue;
+        }
+
+        let previous_app = app_controller.active_app_name().await;
+        send_state(true).await;
+
+        loop {
+            let steps = SEQUENCE.lock().await.clone();
+            match select(play(app_controller, &steps), CONTROL.wait()).await {
+                // a new sequence replaces this one
+                Either::Second(true) => continue,
+                _ => break,
+            }
+        }
+
+        app_controller.select_app(previous_app).await;
+        app_controller.send_mqtt_states().await;
+        send_state(false).await;
+    }
+}
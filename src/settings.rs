@@ -0,0 +1,311 @@
+//! Runtime configuration, so one firmware build can serve many devices.
+//!
+//! Settings start from the defaults in `config.rs` and are overridden by any values saved
+//! to flash. They are loaded once at boot, so saved changes are applied on the next restart.
+
+use core::{cell::Cell, fmt::Write};
+
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use heapless::String;
+use static_cell::make_static;
+use thiserror_no_std::Error;
+
+use crate::{
+    config::*,
+    json,
+    mqtt::{topics::CONFIG_STATE_TOPIC, MqttMessage},
+    storage::{Record, Storage},
+};
+
+/// Maximum size of the saved settings text.
+const RECORD_SIZE: usize = Record::Settings.max_size();
+
+/// The settings applied at boot.
+static SETTINGS: Mutex<CriticalSectionRawMutex, Cell<Option<&'static Settings>>> =
+    Mutex::new(Cell::new(None));
+
+/// Error when changing a setting.
+#[derive(Error, Debug)]
+pub enum SettingsError {
+    #[error("unknown key")]
+    UnknownKey,
+    #[error("invalid value")]
+    InvalidValue,
+    #[error("value too long")]
+    TooLong,
+    #[error("flash error")]
+    Flash,
+}
+
+/// Device settings.
+#[derive(Clone)]
+pub struct Settings {
+    pub wifi_network: String<32>,
+    pub wifi_password: String<64>,
+
+    /// Static IP of the device.
+    pub ip: [u8; 4],
+    pub prefix_length: u8,
+    pub gateway: [u8; 4],
+
+    pub mqtt_broker: [u8; 4],
+    pub mqtt_port: u16,
+    pub mqtt_username: String<32>,
+    pub mqtt_password: String<64>,
+
+    /// The base MQTT topic the device sends and listens to.
+    pub base_topic: String<32>,
+
+    /// The device id, used for the MQTT client ids and home assistant discovery.
+    pub device_id: String<32>,
+}
+
+impl Settings {
+    /// Every settings key, in the order they are saved.
+    pub const KEYS: [&'static str; 11] = [
+        "wifi_network",
+        "wifi_password",
+        "ip",
+        "prefix_length",
+        "gateway",
+        "mqtt_broker",
+        "mqtt_port",
+        "mqtt_username",
+        "mqtt_password",
+        "base_topic",
+        "device_id",
+    ];
+
+    /// Keys whose values are never published.
+    const SECRET_KEYS: [&'static str; 2] = ["wifi_password", "mqtt_password"];
+
+    /// Create the settings from the defaults in `config.rs`.
+    fn defaults() -> Self {
+        Self {
+            wifi_network: String::try_from(WIFI_NETWORK).unwrap(),
+            wifi_password: String::try_from(WIFI_PASSWORD).unwrap(),
+            ip: [IP_A1, IP_A2, IP_A3, IP_A4],
+            prefix_length: PREFIX_LENGTH,
+            gateway: [GW_A1, GW_A2, GW_A3, GW_A4],
+            mqtt_broker: [
+                MQTT_BROKER_A1,
+                MQTT_BROKER_A2,
+                MQTT_BROKER_A3,
+                MQTT_BROKER_A4,
+            ],
+            mqtt_port: MQTT_BROKER_PORT,
+            mqtt_username: String::try_from(MQTT_USERNAME).unwrap(),
+            mqtt_password: String::try_from(MQTT_PASSWORD).unwrap(),
+            base_topic: String::try_from(BASE_MQTT_TOPIC).unwrap(),
+            device_id: String::try_from(DEVICE_ID).unwrap(),
+        }
+    }
+
+    /// Set the setting for `key` from its text value.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), SettingsError> {
+        if value.contains('\n') {
+            return Err(SettingsError::InvalidValue);
+        }
+
+        match key {
+            "wifi_network" => set_string(&mut self.wifi_network, value),
+            "wifi_password" => set_string(&mut self.wifi_password, value),
+            "ip" => parse_ip(value).map(|ip| self.ip = ip),
+            "prefix_length" => match value.parse() {
+                Ok(prefix_length) if prefix_length <= 32 => {
+                    self.prefix_length = prefix_length;
+                    Ok(())
+                }
+                _ => Err(SettingsError::InvalidValue),
+            },
+            "gateway" => parse_ip(value).map(|ip| self.gateway = ip),
+            "mqtt_broker" => parse_ip(value).map(|ip| self.mqtt_broker = ip),
+            "mqtt_port" => value
+                .parse()
+                .map(|port| self.mqtt_port = port)
+                .map_err(|_| SettingsError::InvalidValue),
+            "mqtt_username" => set_string(&mut self.mqtt_username, value),
+            "mqtt_password" => set_string(&mut self.mqtt_password, value),
+            "base_topic" => {
+                if value.is_empty() || value.contains(['#', '+']) || value.ends_with('/') {
+                    return Err(SettingsError::InvalidValue);
+                }
+                set_string(&mut self.base_topic, value)
+            }
+            "device_id" => {
+                if value.is_empty() || value.contains(['/', '#', '+', ' ']) {
+                    return Err(SettingsError::InvalidValue);
+                }
+                set_string(&mut self.device_id, value)
+            }
+            _ => Err(SettingsError::UnknownKey),
+        }
+    }
+
+    /// Write the text value of the setting for `key`.
+    pub fn write_value(&self, key: &str, out: &mut impl Write) -> core::fmt::Result {
+        match key {
+            "wifi_network" => write!(out, "{}", self.wifi_network),
+            "wifi_password" => write!(out, "{}", self.wifi_password),
+            "ip" => write_ip(out, &self.ip),
+            "prefix_length" => write!(out, "{}", self.prefix_length),
+            "gateway" => write_ip(out, &self.gateway),
+            "mqtt_broker" => write_ip(out, &self.mqtt_broker),
+            "mqtt_port" => write!(out, "{}", self.mqtt_port),
+            "mqtt_username" => write!(out, "{}", self.mqtt_username),
+            "mqtt_password" => write!(out, "{}", self.mqtt_password),
+            "base_topic" => write!(out, "{}", self.base_topic),
+            "device_id" => write!(out, "{}", self.device_id),
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply every `key=value` line of `text`, skipping any that are invalid.
+    fn apply_lines(&mut self, text: &str) {
+        for line in text.lines() {
+            if let Some((key, value)) = line.split_once('=') {
+                let _ = self.set(key, value);
+            }
+        }
+    }
+
+    /// Read the settings saved in flash on top of the defaults.
+    async fn read(storage: &'static Storage) -> Self {
+        let mut settings = Self::defaults();
+
+        let mut record = [0u8; RECORD_SIZE];
+        if let Ok(Some(len)) = storage.read_record(Record::Settings, &mut record).await {
+            if let Ok(text) = core::str::from_utf8(&record[..len]) {
+                settings.apply_lines(text);
+            }
+        }
+
+        settings
+    }
+
+    /// Save every setting to flash.
+    async fn save(&self, storage: &'static Storage) -> Result<(), SettingsError> {
+        let mut text = String::<RECORD_SIZE>::new();
+        for key in Self::KEYS {
+            write!(text, "{key}=").map_err(|_| SettingsError::TooLong)?;
+            self.write_value(key, &mut text)
+                .map_err(|_| SettingsError::TooLong)?;
+            text.push('\n').map_err(|_| SettingsError::TooLong)?;
+        }
+
+        storage
+            .write_record(Record::Settings, text.as_bytes())
+            .await
+            .map_err(|_| SettingsError::Flash)
+    }
+
+    /// Write the settings as a JSON object, with secrets hidden.
+    fn write_json(&self, out: &mut impl Write) -> core::fmt::Result {
+        out.write_char('{')?;
+        for (i, key) in Self::KEYS.iter().enumerate() {
+            if i > 0 {
+                out.write_char(',')?;
+            }
+
+            if Self::SECRET_KEYS.contains(key) {
+                write!(out, r#""{key}":"***""#)?;
+            } else {
+                write!(out, r#""{key}":""#)?;
+                self.write_value(key, out)?;
+                out.write_char('"')?;
+            }
+        }
+        out.write_char('}')
+    }
+}
+
+/// Replace `target` with `value`.
+fn set_string<const N: usize>(target: &mut String<N>, value: &str) -> Result<(), SettingsError> {
+    *target = String::try_from(value).map_err(|_| SettingsError::TooLong)?;
+    Ok(())
+}
+
+/// Parse an IPv4 address in the form `a.b.c.d`.
+fn parse_ip(value: &str) -> Result<[u8; 4], SettingsError> {
+    let mut ip = [0u8; 4];
+    let mut parts = value.trim().split('.');
+    for octet in ip.iter_mut() {
+        *octet = parts
+            .next()
+            .and_then(|part| part.parse().ok())
+            .ok_or(SettingsError::InvalidValue)?;
+    }
+
+    if parts.next().is_some() {
+        return Err(SettingsError::InvalidValue);
+    }
+
+    Ok(ip)
+}
+
+/// Write an IPv4 address in the form `a.b.c.d`.
+fn write_ip(out: &mut impl Write, ip: &[u8; 4]) -> core::fmt::Result {
+    write!(out, "{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3])
+}
+
+/// Load the settings from flash. Must be called once at boot, before `get`.
+pub async fn load(storage: &'static Storage) -> &'static Settings {
+    let settings = Settings::read(storage).await;
+    let settings: &'static Settings = make_static!(settings);
+    SETTINGS.lock(|cell| cell.set(Some(settings)));
+    settings
+}
+
+/// Get the settings applied at boot.
+/// Panics if called before `load`.
+pub fn get() -> &'static Settings {
+    SETTINGS
+        .lock(|cell| cell.get())
+        .expect("settings are not loaded")
+}
+
+/// Update the saved settings with each `(key, value)` in `changes`.
+/// Nothing is saved if any change is invalid. Changes are applied on the next restart.
+pub async fn update(
+    storage: &'static Storage,
+    changes: &[(&str, &str)],
+) -> Result<(), SettingsError> {
+    let mut settings = Settings::read(storage).await;
+    for (key, value) in changes {
+        settings.set(key, value)?;
+    }
+
+    settings.save(storage).await
+}
+
+/// Update the saved settings from a JSON object of keys and text values, then send the state.
+/// Changes are applied on the next restart.
+pub async fn update_from_json(storage: &'static Storage, body: &str) {
+    let mut settings = Settings::read(storage).await;
+
+    for key in Settings::KEYS {
+        if let Some(value) = json::extract(body, key) {
+            if let Err(err) = settings.set(key, value) {
+                let mut text = String::<64>::new();
+                let _ = write!(text, "Invalid config {key}: {err}");
+                MqttMessage::enqueue_debug(&text).await;
+                return;
+            }
+        }
+    }
+
+    match settings.save(storage).await {
+        Ok(_) => MqttMessage::enqueue_debug("Config saved, restart to apply").await,
+        Err(_) => MqttMessage::enqueue_debug("Config could not be saved").await,
+    }
+
+    send_state(&settings).await;
+}
+
+/// Send the saved settings over MQTT, with secrets hidden.
+pub async fn send_state(settings: &Settings) {
+    let mut text = String::<512>::new();
+    if settings.write_json(&mut text).is_ok() {
+        MqttMessage::enqueue_state(CONFIG_STATE_TOPIC, &text).await;
+    }
+}
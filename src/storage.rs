@@ -0,0 +1,519 @@
+//! Flash storage reserved for settings and gallery images.
+//!
+//! The last `SETTINGS_SIZE` bytes of flash are kept out of the firmware image by `memory.x`,
+//! so settings survive a firmware update and can be erased without touching the program.
+//! The `GALLERY_SIZE` bytes below them are kept out in the same way, and split into slots
+//! of one page each for gallery images.
+//!
+//! Records are appended to a journal rather than rewriting a page on every change. Each record
+//! carries a CRC, so a record torn by power loss is ignored and the previous copy is used.
+//! When the active page is full, the latest copy of every record is moved to the next page,
+//! and the pages are used in turn to spread the wear across the whole region.
+
+use embassy_rp::{
+    flash::{Blocking, Error, Flash, ERASE_SIZE},
+    peripherals::FLASH,
+};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use static_cell::make_static;
+
+/// Total size of the flash chip.
+pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+/// Size of the region reserved for settings. Must match the reservation in `memory.x`.
+pub const SETTINGS_SIZE: usize = 64 * 1024;
+
+/// Offset of the settings region from the start of flash.
+pub const SETTINGS_OFFSET: u32 = (FLASH_SIZE - SETTINGS_SIZE) as u32;
+
+/// Size of a flash page, the smallest area that can be erased.
+pub const PAGE_SIZE: usize = ERASE_SIZE;
+
+/// Size of the region reserved for gallery images, just below the settings region.
+/// Must match the reservation in `memory.x`.
+pub const GALLERY_SIZE: usize = 64 * 1024;
+
+/// Offset of the gallery region from the start of flash.
+#[cfg(feature = "gallery-app")]
+pub const GALLERY_OFFSET: u32 = SETTINGS_OFFSET - GALLERY_SIZE as u32;
+
+/// Number of gallery slots, each a page.
+#[cfg(feature = "gallery-app")]
+pub const GALLERY_SLOTS: usize = GALLERY_SIZE / PAGE_SIZE;
+
+/// Maximum size of the data in a record, the largest `Record::max_size`.
+pub const MAX_RECORD_SIZE: usize = 1120;
+
+/// Number of pages the journal rotates through.
+const PAGE_COUNT: u32 = (SETTINGS_SIZE / PAGE_SIZE) as u32;
+
+/// Marks the start of a journal page.
+const PAGE_MAGIC: &[u8; 4] = b"GUJL";
+
+/// Size of the page header, the magic followed by the page sequence number.
+const PAGE_HEADER_SIZE: u32 = 8;
+
+/// Size of the record header, the key, a reserved byte, the data length and the CRC.
+const RECORD_HEADER_SIZE: u32 = 8;
+
+/// Value of erased flash.
+const ERASED: u8 = 0xff;
+
+const _: () = assert!(SETTINGS_SIZE % PAGE_SIZE == 0);
+const _: () = assert!(GALLERY_SIZE % PAGE_SIZE == 0);
+const _: () = assert!(PAGE_HEADER_SIZE as usize + Record::journal_size() <= PAGE_SIZE);
+const _: () = {
+    let mut index = 0;
+    while index < Record::ALL.len() {
+        assert!(Record::ALL[index].max_size() <= MAX_RECORD_SIZE);
+        index += 1;
+    }
+};
+
+type SettingsFlash = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+
+/// Records kept in the journal.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Record {
+    /// The saved settings.
+    Settings = 1,
+
+    /// The last time synced with NTP.
+    Time = 2,
+
+    /// Marks that the self test has run on this device.
+    SelfTest = 3,
+
+    /// The last text message received over MQTT.
+    LastMessage = 4,
+
+    /// The celebrations set over MQTT.
+    Celebrations = 5,
+
+    /// The last day a celebration was shown.
+    Celebrated = 6,
+
+    /// The automation rules set over MQTT.
+    Rules = 7,
+
+    /// The quotes shown by the quotes app.
+    Quotes = 8,
+
+    /// The name badge and whether it is shown.
+    Badge = 9,
+
+    /// Text messages waiting to be shown.
+    PendingMessages = 10,
+}
+
+impl Record {
+    /// Every record, which are all moved to the next page when the journal rotates.
+    const ALL: [Record; 10] = [
+        Record::Settings,
+        Record::Time,
+        Record::SelfTest,
+        Record::LastMessage,
+        Record::Celebrations,
+        Record::Celebrated,
+        Record::Rules,
+        Record::Quotes,
+        Record::Badge,
+        Record::PendingMessages,
+    ];
+
+    /// The largest data the record can hold.
+    pub const fn max_size(self) -> usize {
+        match self {
+            Record::Settings => 1024,
+            Record::Time => 8,
+            Record::SelfTest => 0,
+            Record::LastMessage => 128,
+            Record::Celebrations => 256,
+            Record::Celebrated => 4,
+            Record::Rules => 256,
+            Record::Quotes => 512,
+            Record::Badge => 128,
+            Record::PendingMessages => 1120,
+        }
+    }
+
+    /// Space taken by the largest copy of every record, which must fit in a page when rotating.
+    const fn journal_size() -> usize {
+        let mut size = 0;
+        let mut index = 0;
+        while index < Self::ALL.len() {
+            size += RECORD_HEADER_SIZE as usize + Self::ALL[index].max_size().next_multiple_of(4);
+            index += 1;
+        }
+        size
+    }
+}
+
+/// Position of the journal in flash.
+struct Journal {
+    /// The active page, or `None` if nothing has been written.
+    page: Option<u32>,
+
+    /// Sequence number of the active page. The valid page with the highest sequence is active.
+    sequence: u32,
+
+    /// Offset of the erased space in the active page, or `None` if it can not be appended to.
+    end: Option<u32>,
+}
+
+/// Header of a record in the journal.
+#[derive(Clone, Copy)]
+struct RecordHeader {
+    key: u8,
+    len: u16,
+    crc: u32,
+}
+
+impl RecordHeader {
+    /// Create the header for a record of `data`.
+    fn new(key: u8, data: &[u8]) -> Self {
+        let len = data.len() as u16;
+        Self {
+            key,
+            len,
+            crc: crc32(&[&[key], &len.to_le_bytes(), data]),
+        }
+    }
+
+    fn from_bytes(bytes: [u8; RECORD_HEADER_SIZE as usize]) -> Self {
+        Self {
+            key: bytes[0],
+            len: u16::from_le_bytes([bytes[2], bytes[3]]),
+            crc: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        }
+    }
+
+    fn to_bytes(self) -> [u8; RECORD_HEADER_SIZE as usize] {
+        let mut bytes = [0u8; RECORD_HEADER_SIZE as usize];
+        bytes[0] = self.key;
+        bytes[2..4].copy_from_slice(&self.len.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.crc.to_le_bytes());
+        bytes
+    }
+
+    /// Size of the record in flash, padded so the next record is word aligned.
+    fn size(&self) -> u32 {
+        RECORD_HEADER_SIZE + (self.len as u32).next_multiple_of(4)
+    }
+
+    /// Check the record of `data` was fully written.
+    fn is_intact(&self, data: &[u8]) -> bool {
+        self.crc == crc32(&[&[self.key], &self.len.to_le_bytes(), data])
+    }
+}
+
+/// Calculate the CRC-32 of `parts`, one after another.
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for part in parts {
+        for &byte in *part {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+    }
+    !crc
+}
+
+/// Get the offset of `page` from the start of flash.
+fn page_offset(page: u32) -> u32 {
+    SETTINGS_OFFSET + page * PAGE_SIZE as u32
+}
+
+/// Read the header of the record at `offset` in `page`.
+/// Returns `None` at the end of the records, which is erased space or a record that can not be valid.
+fn read_header(
+    flash: &mut SettingsFlash,
+    page: u32,
+    offset: u32,
+) -> Result<Option<RecordHeader>, Error> {
+    if offset + RECORD_HEADER_SIZE > PAGE_SIZE as u32 {
+        return Ok(None);
+    }
+
+    let mut bytes = [0u8; RECORD_HEADER_SIZE as usize];
+    flash.blocking_read(page_offset(page) + offset, &mut bytes)?;
+    if bytes == [ERASED; RECORD_HEADER_SIZE as usize] {
+        return Ok(None);
+    }
+
+    let header = RecordHeader::from_bytes(bytes);
+    if header.len as usize > MAX_RECORD_SIZE || offset + header.size() > PAGE_SIZE as u32 {
+        return Ok(None);
+    }
+
+    Ok(Some(header))
+}
+
+/// Find the offset of the erased space after the records in `page`.
+/// Returns `None` if the page is full or ends in a record that was never fully written.
+fn records_end(flash: &mut SettingsFlash, page: u32) -> Result<Option<u32>, Error> {
+    let mut offset = PAGE_HEADER_SIZE;
+    while let Some(header) = read_header(flash, page, offset)? {
+        offset += header.size();
+    }
+
+    if offset + RECORD_HEADER_SIZE > PAGE_SIZE as u32 {
+        return Ok(None);
+    }
+
+    let mut bytes = [0u8; RECORD_HEADER_SIZE as usize];
+    flash.blocking_read(page_offset(page) + offset, &mut bytes)?;
+    Ok((bytes == [ERASED; RECORD_HEADER_SIZE as usize]).then_some(offset))
+}
+
+/// Read the latest intact copy of the `key` record in `page` into `buffer`.
+/// Returns the length of the record, or `None` if there is no intact copy that fits in `buffer`.
+fn find(
+    flash: &mut SettingsFlash,
+    page: u32,
+    key: u8,
+    buffer: &mut [u8],
+) -> Result<Option<usize>, Error> {
+    let mut latest = None;
+    let mut offset = PAGE_HEADER_SIZE;
+    while let Some(header) = read_header(flash, page, offset)? {
+        let len = header.len as usize;
+        if header.key == key && len <= buffer.len() {
+            let data = &mut buffer[..len];
+            flash.blocking_read(page_offset(page) + offset + RECORD_HEADER_SIZE, data)?;
+            if header.is_intact(data) {
+                latest = Some(offset);
+            }
+        }
+
+        offset += header.size();
+    }
+
+    let Some(offset) = latest else {
+        return Ok(None);
+    };
+
+    // a later torn copy may have been read over the intact one
+    let header = read_header(flash, page, offset)?.unwrap();
+    let len = header.len as usize;
+    flash.blocking_read(
+        page_offset(page) + offset + RECORD_HEADER_SIZE,
+        &mut buffer[..len],
+    )?;
+    Ok(Some(len))
+}
+
+/// Write a `key` record of `data` at `offset` in `page`, returning the offset after it.
+fn append(
+    flash: &mut SettingsFlash,
+    page: u32,
+    offset: u32,
+    key: u8,
+    data: &[u8],
+) -> Result<u32, Error> {
+    let header = RecordHeader::new(key, data);
+    if offset + header.size() > PAGE_SIZE as u32 {
+        return Err(Error::OutOfBounds);
+    }
+
+    // the data is written after the header, so a torn write always fails the CRC
+    flash.blocking_write(page_offset(page) + offset, &header.to_bytes())?;
+    if !data.is_empty() {
+        flash.blocking_write(page_offset(page) + offset + RECORD_HEADER_SIZE, data)?;
+    }
+
+    Ok(offset + header.size())
+}
+
+/// Find the active page of the journal.
+fn mount(flash: &mut SettingsFlash) -> Result<Journal, Error> {
+    let mut latest: Option<(u32, u32)> = None;
+    for page in 0..PAGE_COUNT {
+        let mut header = [0u8; PAGE_HEADER_SIZE as usize];
+        flash.blocking_read(page_offset(page), &mut header)?;
+        if &header[..PAGE_MAGIC.len()] != PAGE_MAGIC {
+            continue;
+        }
+
+        let sequence = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if latest.map_or(true, |(_, latest)| sequence > latest) {
+            latest = Some((page, sequence));
+        }
+    }
+
+    match latest {
+        Some((page, sequence)) => Ok(Journal {
+            page: Some(page),
+            sequence,
+            end: records_end(flash, page)?,
+        }),
+        None => Ok(Journal {
+            page: None,
+            sequence: 0,
+            end: None,
+        }),
+    }
+}
+
+/// Start the next page with the latest copy of every other record, followed by the `record` of `data`.
+/// The page header is written last, so the old page stays active until the new one is complete.
+fn rotate(
+    flash: &mut SettingsFlash,
+    journal: &mut Journal,
+    record: Record,
+    data: &[u8],
+) -> Result<(), Error> {
+    let next = journal.page.map_or(0, |page| (page + 1) % PAGE_COUNT);
+    flash.blocking_erase(page_offset(next), page_offset(next) + PAGE_SIZE as u32)?;
+
+    let mut end = PAGE_HEADER_SIZE;
+    if let Some(page) = journal.page {
+        let mut buffer = [0u8; MAX_RECORD_SIZE];
+        for other in Record::ALL {
+            if other == record {
+                continue;
+            }
+
+            if let Some(len) = find(flash, page, other as u8, &mut buffer)? {
+                end = append(flash, next, end, other as u8, &buffer[..len])?;
+            }
+        }
+    }
+    end = append(flash, next, end, record as u8, data)?;
+
+    let sequence = journal.sequence.wrapping_add(1);
+    let mut header = [0u8; PAGE_HEADER_SIZE as usize];
+    header[..PAGE_MAGIC.len()].copy_from_slice(PAGE_MAGIC);
+    header[PAGE_MAGIC.len()..].copy_from_slice(&sequence.to_le_bytes());
+    flash.blocking_write(page_offset(next), &header)?;
+
+    *journal = Journal {
+        page: Some(next),
+        sequence,
+        end: Some(end),
+    };
+    Ok(())
+}
+
+/// Access to the settings region of flash.
+pub struct Storage {
+    flash: Mutex<ThreadModeRawMutex, SettingsFlash>,
+
+    /// The journal position, found on first use.
+    journal: Mutex<ThreadModeRawMutex, Option<Journal>>,
+}
+
+impl Storage {
+    /// Create the static ref to storage.
+    /// Must only be called once or will panic.
+    pub fn new(flash: FLASH) -> &'static Self {
+        make_static!(Self {
+            flash: Mutex::new(Flash::new_blocking(flash)),
+            journal: Mutex::new(None),
+        })
+    }
+
+    /// Read the latest copy of `record` into `buffer`.
+    /// Returns the length of the record, or `None` if it has never been written.
+    pub async fn read_record(
+        &'static self,
+        record: Record,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, Error> {
+        let mut journal = self.journal.lock().await;
+        let mut flash = self.flash.lock().await;
+        if journal.is_none() {
+            *journal = Some(mount(&mut flash)?);
+        }
+
+        match journal.as_ref().and_then(|journal| journal.page) {
+            Some(page) => find(&mut flash, page, record as u8, buffer),
+            None => Ok(None),
+        }
+    }
+
+    /// Append a new copy of `record` holding `data` to the journal.
+    pub async fn write_record(&'static self, record: Record, data: &[u8]) -> Result<(), Error> {
+        if data.len() > record.max_size() {
+            return Err(Error::OutOfBounds);
+        }
+
+        let mut journal = self.journal.lock().await;
+        let mut flash = self.flash.lock().await;
+        if journal.is_none() {
+            *journal = Some(mount(&mut flash)?);
+        }
+        let journal = journal.as_mut().unwrap();
+
+        if let (Some(page), Some(end)) = (journal.page, journal.end) {
+            let size = RECORD_HEADER_SIZE + (data.len() as u32).next_multiple_of(4);
+            if end + size <= PAGE_SIZE as u32 {
+                let result = append(&mut flash, page, end, record as u8, data);
+                journal.end = result.as_ref().ok().copied();
+                return result.map(|_| ());
+            }
+        }
+
+        rotate(&mut flash, journal, record, data)
+    }
+
+    /// Erase every page of the settings region.
+    pub async fn erase_settings(&'static self) -> Result<(), Error> {
+        let mut journal = self.journal.lock().await;
+        let mut flash = self.flash.lock().await;
+        *journal = None;
+        flash.blocking_erase(SETTINGS_OFFSET, SETTINGS_OFFSET + SETTINGS_SIZE as u32)
+    }
+
+    /// Get the offset from the start of flash of `offset` within gallery `slot`,
+    /// checking `len` bytes fit in the slot.
+    #[cfg(feature = "gallery-app")]
+    fn gallery_offset(slot: usize, offset: u32, len: usize) -> Result<u32, Error> {
+        if slot >= GALLERY_SLOTS || offset as usize + len > PAGE_SIZE {
+            return Err(Error::OutOfBounds);
+        }
+
+        Ok(GALLERY_OFFSET + (slot * PAGE_SIZE) as u32 + offset)
+    }
+
+    /// Read `buffer.len()` bytes from `offset` within gallery `slot`.
+    #[cfg(feature = "gallery-app")]
+    pub async fn read_gallery(
+        &'static self,
+        slot: usize,
+        offset: u32,
+        buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        let offset = Self::gallery_offset(slot, offset, buffer.len())?;
+        let mut flash = self.flash.lock().await;
+        flash.blocking_read(offset, buffer)
+    }
+
+    /// Write `data` at `offset` within gallery `slot`, which must have been erased.
+    #[cfg(feature = "gallery-app")]
+    pub async fn write_gallery(
+        &'static self,
+        slot: usize,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let offset = Self::gallery_offset(slot, offset, data.len())?;
+        let mut flash = self.flash.lock().await;
+        flash.blocking_write(offset, data)
+    }
+
+    /// Erase gallery `slot`.
+    #[cfg(feature = "gallery-app")]
+    pub async fn erase_gallery(&'static self, slot: usize) -> Result<(), Error> {
+        let offset = Self::gallery_offset(slot, 0, PAGE_SIZE)?;
+        let mut flash = self.flash.lock().await;
+        flash.blocking_erase(offset, offset + PAGE_SIZE as u32)
+    }
+}
@@ -0,0 +1,132 @@
+//! Liveness checks for the long running tasks.
+//!
+//! Each supervised task checks in as it runs. The supervisor warns when a task has not checked in
+//! within its timeout and, if `SUPERVISOR_WATCHDOG` is enabled, stops feeding the hardware
+//! watchdog so the board resets. The watchdog also resets the board if the supervisor itself stops.
+
+use embassy_rp::{peripherals::WATCHDOG, watchdog::Watchdog};
+use embassy_time::{Duration, Instant, Timer};
+use portable_atomic::{AtomicU32, Ordering};
+
+use crate::{config::SUPERVISOR_WATCHDOG, logging, panic, time::ntp};
+
+/// Seconds between supervisor checks.
+const CHECK_INTERVAL_SECS: u64 = 2;
+
+/// Period of the hardware watchdog. The RP2040 supports a little over 8 seconds.
+const WATCHDOG_PERIOD: Duration = Duration::from_secs(8);
+
+/// Tasks that are supervised.
+#[derive(Clone, Copy)]
+pub enum Task {
+    /// The display queue on core 1.
+    DisplayQueue,
+
+    /// The MQTT send client.
+    MqttSend,
+
+    /// The MQTT receive client.
+    MqttReceive,
+
+    /// The NTP worker.
+    Ntp,
+}
+
+impl Task {
+    /// Every supervised task.
+    const ALL: [Task; 4] = [
+        Task::DisplayQueue,
+        Task::MqttSend,
+        Task::MqttReceive,
+        Task::Ntp,
+    ];
+
+    /// Get the name of the task.
+    fn name(&self) -> &'static str {
+        match self {
+            Task::DisplayQueue => "display queue",
+            Task::MqttSend => "MQTT send",
+            Task::MqttReceive => "MQTT receive",
+            Task::Ntp => "NTP",
+        }
+    }
+
+    /// Get the most seconds the task can go without checking in.
+    fn timeout_secs(&self) -> u32 {
+        match self {
+            Task::DisplayQueue => 10,
+            // allows for the longest reconnect backoff
+            Task::MqttSend | Task::MqttReceive => 120,
+            // the worker sleeps between syncs
+            Task::Ntp => ntp::SYNC_INTERVAL_SECS as u32 + 120,
+        }
+    }
+}
+
+/// Uptime in seconds of the last check in of each task, or 0 if it has not checked in yet.
+static LAST_CHECK_IN: [AtomicU32; Task::ALL.len()] = [
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+    AtomicU32::new(0),
+];
+
+/// Uptime in seconds, never 0 so it can be told apart from not checked in.
+fn uptime_secs() -> u32 {
+    (Instant::now().as_secs() as u32).max(1)
+}
+
+/// Record that `task` is still running. Safe to call from either core, and cheap enough
+/// to call every frame.
+pub fn check_in(task: Task) {
+    LAST_CHECK_IN[task as usize].store(uptime_secs(), Ordering::Relaxed);
+}
+
+/// Check every task has checked in within its timeout, warning about any that have stalled.
+#[embassy_executor::task]
+pub async fn supervisor_task(watchdog: WATCHDOG) {
+    let mut watchdog = Watchdog::new(watchdog);
+    if SUPERVISOR_WATCHDOG {
+        watchdog.start(WATCHDOG_PERIOD);
+    }
+
+    let mut stalled = [false; Task::ALL.len()];
+
+    loop {
+        Timer::after_secs(CHECK_INTERVAL_SECS).await;
+
+        let now = uptime_secs();
+        let mut any_stalled = false;
+
+        for task in Task::ALL {
+            let last = LAST_CHECK_IN[task as usize].load(Ordering::Relaxed);
+            let is_stalled = last != 0 && now.saturating_sub(last) > task.timeout_secs();
+
+            // only warn when the task first stalls
+            if is_stalled && !stalled[task as usize] {
+                logging::warn!(
+                    "{} task stalled for {}s",
+                    task.name(),
+                    now.saturating_sub(last)
+                );
+
+                if SUPERVISOR_WATCHDOG {
+                    panic::write_report(format_args!(
+                        "watchdog reset, {} task stalled",
+                        task.name()
+                    ));
+                }
+            } else if !is_stalled && stalled[task as usize] {
+                logging::info!("{} task recovered", task.name());
+            }
+
+            stalled[task as usize] = is_stalled;
+            any_stalled |= is_stalled;
+        }
+
+        // let the watchdog reset the board whilst any task is stalled
+        if SUPERVISOR_WATCHDOG && !any_stalled {
+            watchdog.feed();
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Synchronised displays, so several devices in a row show one long virtual display.
+//!
+//! Every device in the row subscribes to the shared `SYNC_TOPIC` and knows the column its panel
+//! starts at in the long display. Frames are sent as segments, each a display stream packet for
+//! the panel starting at one column, and are shown at a set unix time so the whole row changes
+//! together. Text is sent once with the time it starts, and each device scrolls its own slice of it
+//! from the NTP time, so no frames need to be sent at all.
+
+use core::{fmt::Write, str::FromStr};
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use embedded_graphics_core::pixelcolor::Rgb888;
+use heapless::String;
+use unicorn_graphics::{UnicornGraphics, UnicornGraphicsPixels};
+
+use crate::{
+    board::{HEIGHT, WIDTH},
+    config::{SYNC_COLUMN, SYNC_COORDINATOR, SYNC_TOPIC, SYNC_WIDTH},
+    display::{
+        self, messages::DisplayGraphicsMessage, stream::StreamDecoder, Display, FrameTicker,
+    },
+    fonts::Font,
+    graphics::colors::Rgb888Str,
+    json, logging,
+    mqtt::{topics::SYNC_SET_TOPIC, MqttMessage, MqttReceiveMessage},
+    time::Time,
+};
+
+/// How far ahead of now the coordinator starts synced text, so every device has received it.
+const TEXT_LEAD_MILLIS: i64 = 1000;
+
+/// Furthest ahead a frame is held for before being shown, so a clock out of step can not stall it.
+const MAX_FRAME_WAIT_MILLIS: i64 = 2000;
+
+/// Longest text that can be synced.
+const TEXT_SIZE: usize = 64;
+
+/// Messages on the sync topics and the coordinator set topic, waiting to be handled.
+pub static SYNC_CHANNEL: Channel<ThreadModeRawMutex, MqttReceiveMessage, 2> = Channel::new();
+
+/// Get the part of `topic` after the shared sync topic, such as `text` or `frame`.
+fn sync_subtopic(topic: &str) -> Option<&str> {
+    topic
+        .strip_prefix(SYNC_TOPIC?)
+        .and_then(|rest| rest.strip_prefix('/'))
+}
+
+/// Check if `topic` is handled by the sync task.
+pub fn is_sync_topic(topic: &str) -> bool {
+    topic == SYNC_SET_TOPIC || sync_subtopic(topic).is_some()
+}
+
+/// Text scrolling across the whole row.
+struct SyncText {
+    text: String<TEXT_SIZE>,
+    color: Option<Rgb888>,
+
+    /// Unix time in milliseconds the text enters from the right of the row.
+    start: i64,
+}
+
+impl SyncText {
+    /// Parse synced text from JSON such as `{"text": "Hello", "start": 1700000000000, "color": "teal"}`.
+    fn parse(body: &str) -> Option<Self> {
+        Some(Self {
+            text: String::try_from(json::extract(body, "text")?).ok()?,
+            color: match json::extract(body, "color") {
+                Some(color) => Some(Rgb888Str::from_str(color).ok()?.0),
+                None => None,
+            },
+            start: json::extract(body, "start")?.parse().ok()?,
+        })
+    }
+
+    /// Draw this panel's slice of the text at unix time `now` in milliseconds.
+    /// Returns `false` once the text has scrolled off the left of the row.
+    fn draw(&self, graphics: &mut UnicornGraphics<WIDTH, HEIGHT>, now: i64, color: Rgb888) -> bool {
+        let travelled = (now - self.start).max(0) * display::scroll_speed() as i64 / 1000;
+        let x = SYNC_WIDTH as i64 - travelled - SYNC_COLUMN as i64;
+
+        let width = Font::Regular.measure(&self.text) as i64;
+        if x + width + (SYNC_COLUMN as i64) < 0 {
+            return false;
+        }
+
+        Font::Regular.draw(graphics, &self.text, x as i32, HEIGHT as i32 / 2, color);
+        true
+    }
+}
+
+/// Parse a frame segment in the form `<column>@<show at>:<packet>`, where `column` is the first
+/// column of the panel it is for, `show at` is the unix time in milliseconds to show it at, or 0 to
+/// show it straight away, and `packet` is a display stream packet.
+fn parse_segment(body: &str) -> Option<(u16, i64, &str)> {
+    let (column, rest) = body.trim().split_once('@')?;
+    let (show_at, packet) = rest.split_once(':')?;
+
+    Some((column.parse().ok()?, show_at.parse().ok()?, packet))
+}
+
+/// Publish text to scroll across the whole row, starting shortly so every device has it.
+async fn coordinate(time: &'static Time, body: &str) {
+    let Some(topic) = SYNC_TOPIC else {
+        return;
+    };
+
+    let text = json::extract(body, "text").unwrap_or(body.trim());
+    if text.is_empty() || text.contains(['"', '\\']) {
+        MqttMessage::enqueue_debug("Invalid sync text").await;
+        return;
+    }
+
+    let start = time.now().await.timestamp_millis() + TEXT_LEAD_MILLIS;
+
+    let mut payload = String::<{ TEXT_SIZE + 96 }>::new();
+    let written = match json::extract(body, "color") {
+        Some(color) => write!(
+            payload,
+            r#"{{"text":"{text}","color":"{color}","start":{start}}}"#
+        ),
+        None => write!(payload, r#"{{"text":"{text}","start":{start}}}"#),
+    };
+
+    let mut text_topic = String::<128>::new();
+    if written.is_err() || write!(text_topic, "{topic}/text").is_err() {
+        MqttMessage::enqueue_debug("Sync text too long").await;
+        return;
+    }
+
+    MqttMessage::enqueue_state(&text_topic, &payload).await;
+}
+
+/// Show this panel's slice of the row, from frame segments and synced text.
+#[embassy_executor::task]
+pub async fn sync_task(display: &'static Display<'static>, time: &'static Time) {
+    let mut stream = StreamDecoder::new();
+    let mut text: Option<SyncText> = None;
+    let mut frame: Option<(UnicornGraphicsPixels<WIDTH, HEIGHT>, i64)> = None;
+    let mut ticker = FrameTicker::new();
+
+    loop {
+        // only wake for frames whilst there is something to show
+        let message = if text.is_some() || frame.is_some() {
+            match select(SYNC_CHANNEL.receive(), ticker.next()).await {
+                Either::First(message) => Some(message),
+                Either::Second(_) => None,
+            }
+        } else {
+            Some(SYNC_CHANNEL.receive().await)
+        };
+
+        if let Some(message) = message {
+            match sync_subtopic(&message.topic) {
+                // the row is only in step once the time has been synced
+                _ if !time.is_synced() => logging::warn!("Sync ignored until the time is synced"),
+                _ if message.topic == SYNC_SET_TOPIC => {
+                    if SYNC_COORDINATOR {
+                        coordinate(time, &message.body).await;
+                    } else {
+                        MqttMessage::enqueue_debug("This device is not the sync coordinator").await;
+                    }
+                }
+                Some("text") => match SyncText::parse(&message.body) {
+                    Some(synced) => text = Some(synced),
+                    None => MqttMessage::enqueue_debug("Invalid sync text").await,
+                },
+                Some("frame") => match parse_segment(&message.body) {
+                    Some((column, _, _)) if column != SYNC_COLUMN => {}
+                    Some((_, show_at, packet)) => match stream.apply(packet) {
+                        Ok(Some(pixels)) => frame = Some((pixels, show_at)),
+                        Ok(None) => {}
+                        Err(err) => {
+                            let mut error = String::<64>::new();
+                            let _ = write!(error, "Invalid sync packet, {err}");
+                            MqttMessage::enqueue_debug(&error).await;
+                        }
+                    },
+                    None => MqttMessage::enqueue_debug("Invalid sync frame").await,
+                },
+                _ => {}
+            }
+        }
+
+        let now = time.now().await.timestamp_millis();
+
+        if let Some((pixels, show_at)) = frame {
+            if show_at <= now || show_at - now > MAX_FRAME_WAIT_MILLIS {
+                frame = None;
+                DisplayGraphicsMessage::from_stream(pixels).send().await;
+            }
+        }
+
+        if let Some(synced) = &text {
+            if synced.start > now {
+                continue;
+            }
+
+            let color = match synced.color {
+                Some(color) => color,
+                None => display.get_color().await,
+            };
+
+            let mut graphics = UnicornGraphics::<WIDTH, HEIGHT>::new();
+            if synced.draw(&mut graphics, now, color) {
+                DisplayGraphicsMessage::from_stream(graphics.get_pixels())
+                    .send()
+                    .await;
+            } else {
+                text = None;
+            }
+        }
+    }
+}
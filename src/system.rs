@@ -1,11 +1,25 @@
 use embassy_sync::{
     blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, pubsub::Subscriber, signal::Signal,
 };
+use portable_atomic::{AtomicBool, Ordering};
 use static_cell::make_static;
 
 use crate::{
-    mqtt::{topics::NTP_SYNC_TOPIC, MqttReceiveMessage},
+    celebration, diagnostics,
+    error::Error,
+    logging,
+    mqtt::{
+        topics::{
+            CELEBRATIONS_SET_TOPIC, CONFIG_SET_TOPIC, DIAG_TOPIC, FIRMWARE_LATEST_SET_TOPIC,
+            LOG_LEVEL_SET_TOPIC, NTP_SYNC_TOPIC, QUIET_HOURS_END_SET_TOPIC,
+            QUIET_HOURS_OVERRIDE_TOPIC, QUIET_HOURS_SET_TOPIC, QUIET_HOURS_START_SET_TOPIC,
+            REBOOT_TIME_SET_TOPIC, RESTART_TOPIC, RULES_SET_TOPIC, SELF_TEST_TOPIC,
+        },
+        MqttReceiveMessage,
+    },
     network::NetworkState,
+    rules, self_test, settings,
+    storage::Storage,
     time::ntp::SYNC_SIGNAL,
 };
 
@@ -15,11 +29,47 @@ pub static STATE_CHANGED: Signal<ThreadModeRawMutex, StateUpdates> = Signal::new
 /// Possible states than can update.
 pub enum StateUpdates {
     Network,
+    Boot,
+}
+
+/// Stages of the boot process, in the order they are completed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BootStage {
+    /// The device has just started.
+    Starting,
+
+    /// Joining the wifi network.
+    Wifi,
+
+    /// Connecting to the MQTT broker.
+    Mqtt,
+
+    /// Syncing the time with NTP.
+    Ntp,
+
+    /// Boot has completed.
+    Complete,
+}
+
+impl BootStage {
+    /// Get the text to display for the stage.
+    pub fn text(&self) -> &'static str {
+        match self {
+            BootStage::Starting => "Boot..",
+            BootStage::Wifi => "WiFi..",
+            BootStage::Mqtt => "MQTT..",
+            BootStage::Ntp => "NTP..",
+            BootStage::Complete => "Ready",
+        }
+    }
 }
 
 /// App state. Encapsulates all needed states in the system.
 pub struct SystemState {
     network_state: Mutex<ThreadModeRawMutex, NetworkState>,
+    boot_stage: Mutex<ThreadModeRawMutex, BootStage>,
+    last_error: Mutex<ThreadModeRawMutex, Option<Error>>,
+    ntp_failing: AtomicBool,
 }
 
 impl SystemState {
@@ -28,6 +78,9 @@ impl SystemState {
     pub fn new() -> &'static Self {
         make_static!(Self {
             network_state: Mutex::new(NetworkState::NotInitialised),
+            boot_stage: Mutex::new(BootStage::Starting),
+            last_error: Mutex::new(None),
+            ntp_failing: AtomicBool::new(false),
         })
     }
 
@@ -41,11 +94,50 @@ impl SystemState {
         *self.network_state.lock().await = state;
         STATE_CHANGED.signal(StateUpdates::Network);
     }
+
+    /// Get the current boot stage.
+    pub async fn get_boot_stage(&'static self) -> BootStage {
+        *self.boot_stage.lock().await
+    }
+
+    /// Move the boot on to `stage` and update the `STATE_CHANGED` signal.
+    /// Stages can only move forward, so reaching an earlier stage is ignored.
+    pub async fn advance_boot_stage(&'static self, stage: BootStage) {
+        let mut boot_stage = self.boot_stage.lock().await;
+        if stage > *boot_stage {
+            *boot_stage = stage;
+            STATE_CHANGED.signal(StateUpdates::Boot);
+        }
+    }
+
+    /// Check if syncing the time with NTP has failed several times in a row.
+    pub fn is_ntp_failing(&'static self) -> bool {
+        self.ntp_failing.load(Ordering::Relaxed)
+    }
+
+    /// Set whether syncing the time with NTP is failing.
+    /// This is not a network state, as the network is still usable and the clock keeps running.
+    pub fn set_ntp_failing(&'static self, failing: bool) {
+        self.ntp_failing.store(failing, Ordering::Relaxed);
+    }
+
+    /// Get the last error reported on the network path.
+    pub async fn get_last_error(&'static self) -> Option<Error> {
+        *self.last_error.lock().await
+    }
+
+    /// Report an error on the network path, keeping it as the last error and logging it.
+    /// Never waits on MQTT, so it is safe to call while the broker is unreachable.
+    pub async fn report_error(&'static self, context: &str, err: Error) {
+        logging::error!("{context}: {err}");
+        *self.last_error.lock().await = Some(err);
+    }
 }
 
 /// Process MQTT messages that apply to the system.
 #[embassy_executor::task]
 pub async fn process_mqtt_messages_task(
+    storage: &'static Storage,
     mut subscriber: Subscriber<'static, ThreadModeRawMutex, MqttReceiveMessage, 8, 1, 1>,
 ) {
     loop {
@@ -53,6 +145,540 @@ pub async fn process_mqtt_messages_task(
 
         if message.topic == NTP_SYNC_TOPIC {
             SYNC_SIGNAL.signal(true);
+        } else if message.topic == LOG_LEVEL_SET_TOPIC {
+            logging::set_level(&message.body).await;
+        } else if message.topic == DIAG_TOPIC {
+            diagnostics::send_report().await;
+        } else if message.topic == REBOOT_TIME_SET_TOPIC {
+            reboot::set_reboot_time(&message.body).await;
+        } else if message.topic == QUIET_HOURS_SET_TOPIC {
+            quiet_hours::set_enabled(&message.body).await;
+        } else if message.topic == QUIET_HOURS_START_SET_TOPIC {
+            quiet_hours::set_start(&message.body).await;
+        } else if message.topic == QUIET_HOURS_END_SET_TOPIC {
+            quiet_hours::set_end(&message.body).await;
+        } else if message.topic == QUIET_HOURS_OVERRIDE_TOPIC {
+            quiet_hours::set_override(&message.body).await;
+        } else if message.topic == CELEBRATIONS_SET_TOPIC {
+            celebration::set(storage, &message.body).await;
+        } else if message.topic == RULES_SET_TOPIC {
+            rules::set(storage, &message.body).await;
+        } else if message.topic == CONFIG_SET_TOPIC {
+            settings::update_from_json(storage, &message.body).await;
+        } else if message.topic == RESTART_TOPIC {
+            logging::info!("Restart requested");
+            reboot::restart().await;
+        } else if message.topic == SELF_TEST_TOPIC {
+            self_test::run().await;
+        } else if message.topic == FIRMWARE_LATEST_SET_TOPIC {
+            firmware::set_latest(&message.body).await;
+        } else if presence::is_presence_topic(&message.topic) {
+            presence::set(&message.body);
+        }
+    }
+}
+
+pub mod reboot {
+    use core::fmt::Write;
+
+    use chrono::Timelike;
+    use cortex_m::peripheral::SCB;
+    use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+    use embassy_time::{Duration, Instant, Timer};
+    use heapless::String;
+
+    use super::{BootStage, SystemState};
+    use crate::{
+        config::DAILY_REBOOT_TIME,
+        display::messages::DisplayTextMessage,
+        logging,
+        mqtt::{self, topics::REBOOT_TIME_STATE_TOPIC, MqttMessage},
+        time::Time,
+    };
+
+    /// How often to check if the reboot time has been reached.
+    const CHECK_INTERVAL_SECS: u64 = 20;
+
+    /// Minimum uptime before rebooting, so a quick boot cannot reboot twice in the same minute.
+    const MIN_UPTIME_SECS: u64 = 300;
+
+    /// How long to wait for queued MQTT messages to be sent before rebooting.
+    const FLUSH_TIMEOUT_SECS: u64 = 5;
+
+    /// The hour and minute of the day to reboot at, if enabled.
+    static REBOOT_TIME: Mutex<ThreadModeRawMutex, Option<(u32, u32)>> =
+        Mutex::new(DAILY_REBOOT_TIME);
+
+    /// Parse a reboot time in the form `HH:MM`. `off` or an empty payload disables the reboot.
+    pub(crate) fn parse_time(text: &str) -> Result<Option<(u32, u32)>, ()> {
+        let text = text.trim();
+        if text.is_empty() || text.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+
+        let (hour, minute) = text.split_once(':').ok_or(())?;
+        let hour = hour.parse::<u32>().map_err(|_| ())?;
+        let minute = minute.parse::<u32>().map_err(|_| ())?;
+
+        if hour > 23 || minute > 59 {
+            return Err(());
+        }
+
+        Ok(Some((hour, minute)))
+    }
+
+    /// Set the reboot time from an MQTT payload and send the state over MQTT.
+    pub async fn set_reboot_time(text: &str) {
+        match parse_time(text) {
+            Ok(time) => *REBOOT_TIME.lock().await = time,
+            Err(_) => {
+                MqttMessage::enqueue_debug("Invalid reboot time, expected HH:MM or off").await
+            }
+        }
+
+        send_reboot_time_state().await;
+    }
+
+    /// Send the reboot time over MQTT.
+    pub async fn send_reboot_time_state() {
+        let mut state = String::<8>::new();
+        match *REBOOT_TIME.lock().await {
+            Some((hour, minute)) => write!(state, "{hour:02}:{minute:02}").unwrap(),
+            None => state.push_str("off").unwrap(),
+        }
+
+        MqttMessage::enqueue_state(REBOOT_TIME_STATE_TOPIC, &state).await;
+    }
+
+    /// Wait for queued MQTT messages to be sent, giving up after `FLUSH_TIMEOUT_SECS`.
+    async fn flush_mqtt() {
+        let deadline = Instant::now() + Duration::from_secs(FLUSH_TIMEOUT_SECS);
+        while Instant::now() < deadline {
+            // give tasks that are about to enqueue, such as logging, a chance to run first
+            Timer::after_millis(100).await;
+
+            let (pool_used, _, send_queued) = mqtt::send_queue_usage();
+            if pool_used == 0 && send_queued == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Reboot the device once a day at the reboot time, after showing a brief message.
+    #[embassy_executor::task]
+    pub async fn daily_reboot_task(time: &'static Time, system_state: &'static SystemState) {
+        loop {
+            Timer::after_secs(CHECK_INTERVAL_SECS).await;
+
+            // the time is not known until NTP has synced
+            if system_state.get_boot_stage().await != BootStage::Complete
+                || Instant::now().as_secs() < MIN_UPTIME_SECS
+            {
+                continue;
+            }
+
+            let Some((hour, minute)) = *REBOOT_TIME.lock().await else {
+                continue;
+            };
+
+            let now = time.now().await;
+            if now.hour() != hour || now.minute() != minute {
+                continue;
+            }
+
+            logging::info!("Scheduled reboot");
+            restart().await;
+        }
+    }
+
+    /// Cleanly restart the device, sending queued MQTT messages and showing a brief message first.
+    pub async fn restart() -> ! {
+        flush_mqtt().await;
+
+        DisplayTextMessage::from_app("Rebooting", None, None, None)
+            .send_and_show_now()
+            .await;
+        Timer::after_secs(3).await;
+
+        SCB::sys_reset();
+    }
+}
+
+pub mod quiet_hours {
+    use core::fmt::Write;
+
+    use chrono::Timelike;
+    use embassy_futures::select::select;
+    use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+    use embassy_time::Timer;
+    use heapless::String;
+    use portable_atomic::{AtomicBool, Ordering};
+
+    use super::reboot::parse_time;
+    use crate::{
+        config::{QUIET_HOURS_ENABLED, QUIET_HOURS_END, QUIET_HOURS_START},
+        display::{BlankReason, Display},
+        logging,
+        mqtt::{
+            topics::{
+                QUIET_HOURS_END_STATE_TOPIC, QUIET_HOURS_START_STATE_TOPIC, QUIET_HOURS_STATE_TOPIC,
+            },
+            MqttMessage,
+        },
+        time::Time,
+    };
+
+    /// How often to check if quiet hours have started or ended.
+    const CHECK_INTERVAL_SECS: u64 = 10;
+
+    /// The quiet hours schedule.
+    struct Schedule {
+        enabled: bool,
+
+        /// Hour and minute quiet hours start.
+        start: (u32, u32),
+
+        /// Hour and minute quiet hours end.
+        end: (u32, u32),
+
+        /// Was the schedule quiet at the last check.
+        scheduled: bool,
+
+        /// Quiet state forced over MQTT, along with the scheduled state when it was forced.
+        /// Cleared once the schedule changes state, so the device always wakes at the end time.
+        forced: Option<(bool, bool)>,
+    }
+
+    impl Schedule {
+        /// Check if the schedule is quiet at `minutes` past midnight.
+        fn is_scheduled(&self, minutes: u32) -> bool {
+            let start = self.start.0 * 60 + self.start.1;
+            let end = self.end.0 * 60 + self.end.1;
+
+            if !self.enabled || start == end {
+                false
+            } else if start < end {
+                minutes >= start && minutes < end
+            } else {
+                // runs over midnight
+                minutes >= start || minutes < end
+            }
+        }
+    }
+
+    static SCHEDULE: Mutex<ThreadModeRawMutex, Schedule> = Mutex::new(Schedule {
+        enabled: QUIET_HOURS_ENABLED,
+        start: QUIET_HOURS_START,
+        end: QUIET_HOURS_END,
+        scheduled: false,
+        forced: None,
+    });
+
+    /// Signals the schedule has changed, so it is checked straight away.
+    static CHANGED: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+    /// Is it currently quiet hours.
+    static QUIET: AtomicBool = AtomicBool::new(false);
+
+    /// Check if it is currently quiet hours, so non-critical notifications should be held back.
+    pub fn is_quiet() -> bool {
+        QUIET.load(Ordering::Relaxed)
+    }
+
+    /// Turn the schedule on or off from an MQTT payload and send the state over MQTT.
+    pub async fn set_enabled(text: &str) {
+        match text {
+            "ON" => SCHEDULE.lock().await.enabled = true,
+            "OFF" => SCHEDULE.lock().await.enabled = false,
+            _ => MqttMessage::enqueue_debug("Invalid quiet hours state, expected ON or OFF").await,
+        }
+
+        CHANGED.signal(());
+        send_state().await;
+    }
+
+    /// Parse a time in the form `HH:MM`.
+    fn parse(text: &str) -> Option<(u32, u32)> {
+        parse_time(text).ok().flatten()
+    }
+
+    /// Set the start time from an MQTT payload and send the state over MQTT.
+    pub async fn set_start(text: &str) {
+        match parse(text) {
+            Some(time) => SCHEDULE.lock().await.start = time,
+            None => MqttMessage::enqueue_debug("Invalid quiet hours start, expected HH:MM").await,
+        }
+
+        CHANGED.signal(());
+        send_state().await;
+    }
+
+    /// Set the end time from an MQTT payload and send the state over MQTT.
+    pub async fn set_end(text: &str) {
+        match parse(text) {
+            Some(time) => SCHEDULE.lock().await.end = time,
+            None => MqttMessage::enqueue_debug("Invalid quiet hours end, expected HH:MM").await,
+        }
+
+        CHANGED.signal(());
+        send_state().await;
+    }
+
+    /// Force quiet hours on with `sleep` or off with `wake` until the next scheduled change,
+    /// or go back to the schedule with `auto`.
+    pub async fn set_override(text: &str) {
+        let forced = match text {
+            "sleep" => Some(true),
+            "wake" => Some(false),
+            "auto" => None,
+            _ => {
+                MqttMessage::enqueue_debug(
+                    "Invalid quiet hours override, expected sleep, wake or auto",
+                )
+                .await;
+                return;
+            }
+        };
+
+        let mut schedule = SCHEDULE.lock().await;
+        schedule.forced = forced.map(|forced| (forced, schedule.scheduled));
+        CHANGED.signal(());
+    }
+
+    /// Send the schedule over MQTT.
+    pub async fn send_state() {
+        let (enabled, start, end) = {
+            let schedule = SCHEDULE.lock().await;
+            (schedule.enabled, schedule.start, schedule.end)
+        };
+
+        let text = if enabled { "ON" } else { "OFF" };
+        MqttMessage::enqueue_state(QUIET_HOURS_STATE_TOPIC, text).await;
+
+        let mut state = String::<8>::new();
+        write!(state, "{:02}:{:02}", start.0, start.1).unwrap();
+        MqttMessage::enqueue_state(QUIET_HOURS_START_STATE_TOPIC, &state).await;
+
+        state.clear();
+        write!(state, "{:02}:{:02}", end.0, end.1).unwrap();
+        MqttMessage::enqueue_state(QUIET_HOURS_END_STATE_TOPIC, &state).await;
+    }
+
+    /// Blank the display and hold back notifications during quiet hours.
+    #[embassy_executor::task]
+    pub async fn quiet_hours_task(display: &'static Display<'static>, time: &'static Time) {
+        loop {
+            let quiet = {
+                let now = time.now().await;
+                let mut schedule = SCHEDULE.lock().await;
+
+                // the time is not known until it has been synced or restored
+                let scheduled =
+                    time.is_synced() && schedule.is_scheduled(now.hour() * 60 + now.minute());
+
+                schedule.scheduled = scheduled;
+                match schedule.forced {
+                    Some((forced, scheduled_when_set)) if scheduled == scheduled_when_set => forced,
+                    _ => {
+                        schedule.forced = None;
+                        scheduled
+                    }
+                }
+            };
+
+            if QUIET.swap(quiet, Ordering::Relaxed) != quiet {
+                if quiet {
+                    logging::info!("Quiet hours started");
+                } else {
+                    logging::info!("Quiet hours ended");
+                }
+
+                display.set_blanked(BlankReason::QuietHours, quiet).await;
+            }
+
+            select(Timer::after_secs(CHECK_INTERVAL_SECS), CHANGED.wait()).await;
         }
     }
 }
+
+pub mod presence {
+    use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
+    use portable_atomic::{AtomicBool, Ordering};
+
+    use crate::{
+        config::PRESENCE_TOPIC,
+        display::{BlankReason, Display},
+        logging,
+    };
+
+    /// Is nobody home.
+    static AWAY: AtomicBool = AtomicBool::new(false);
+
+    /// Signals whether nobody is home, when it changes.
+    static CHANGED: Signal<ThreadModeRawMutex, bool> = Signal::new();
+
+    /// Check if `topic` is the topic publishing whether anyone is home.
+    pub fn is_presence_topic(topic: &str) -> bool {
+        PRESENCE_TOPIC.is_some_and(|presence| presence == topic)
+    }
+
+    /// Check if nobody is home.
+    pub fn is_away() -> bool {
+        AWAY.load(Ordering::Relaxed)
+    }
+
+    /// Set whether anyone is home from the state of a person, device tracker or binary sensor,
+    /// or a count of people home. `home`, `on` or a count above zero is home, `unknown` and
+    /// `unavailable` are ignored, and anything else, such as `not_home` or another zone, is away.
+    pub fn set(body: &str) {
+        let state = body.trim().trim_matches('"');
+        if state.eq_ignore_ascii_case("unknown") || state.eq_ignore_ascii_case("unavailable") {
+            return;
+        }
+
+        let home = match state.parse::<f32>() {
+            Ok(count) => count > 0.0,
+            Err(_) => ["home", "on", "true"]
+                .iter()
+                .any(|home| state.eq_ignore_ascii_case(home)),
+        };
+
+        if AWAY.swap(!home, Ordering::Relaxed) == home {
+            CHANGED.signal(!home);
+        }
+    }
+
+    /// Blank the display whilst nobody is home.
+    #[embassy_executor::task]
+    pub async fn presence_task(display: &'static Display<'static>) {
+        loop {
+            let away = CHANGED.wait().await;
+            if away {
+                logging::info!("Nobody home, blanking the display");
+            } else {
+                logging::info!("Somebody home, showing the display");
+            }
+
+            display.set_blanked(BlankReason::Away, away).await;
+        }
+    }
+}
+
+pub mod factory_reset {
+    use core::fmt::Write;
+
+    use cortex_m::peripheral::SCB;
+    use embassy_time::{Duration, Instant, Timer};
+    use heapless::String;
+
+    use crate::{display::messages::DisplayTextMessage, logging, storage::Storage};
+
+    /// Seconds the power-on chord must be held for, once the countdown is shown.
+    pub const POWER_ON_COUNTDOWN_SECS: u8 = 3;
+
+    /// Seconds the sleep button must be held for.
+    pub const SLEEP_HOLD_COUNTDOWN_SECS: u8 = 10;
+
+    /// Show `text` on the display straight away.
+    async fn show(text: &str, duration: Duration) {
+        DisplayTextMessage::from_app(text, None, None, Some(duration))
+            .send_and_show_now()
+            .await;
+    }
+
+    /// Count down on the display whilst `held` returns true.
+    /// Returns `true` if the buttons were held for the whole countdown.
+    async fn countdown(secs: u8, held: impl Fn() -> bool) -> bool {
+        for remaining in (1..=secs).rev() {
+            let mut text = String::<16>::new();
+            write!(text, "Reset {remaining}").unwrap();
+            show(&text, Duration::from_secs(1)).await;
+
+            let end = Instant::now() + Duration::from_secs(1);
+            while Instant::now() < end {
+                if !held() {
+                    show("Cancel", Duration::from_secs(1)).await;
+                    return false;
+                }
+
+                Timer::after_millis(50).await;
+            }
+        }
+
+        true
+    }
+
+    /// Count down whilst `held` returns true, then erase all stored settings and reboot.
+    /// With no stored settings the device starts from the defaults in `config.rs`.
+    ///
+    /// Returns if the buttons are released before the countdown ends.
+    pub async fn run(storage: &'static Storage, secs: u8, held: impl Fn() -> bool) {
+        if !countdown(secs, held).await {
+            return;
+        }
+
+        show("Erasing", Duration::from_secs(3)).await;
+        if storage.erase_settings().await.is_err() {
+            logging::error!("Factory reset failed to erase settings");
+            show("Failed", Duration::from_secs(3)).await;
+            Timer::after_secs(3).await;
+            return;
+        }
+
+        Timer::after_secs(1).await;
+        SCB::sys_reset();
+    }
+}
+
+pub mod firmware {
+    use core::fmt::Write;
+
+    use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+    use heapless::String;
+
+    use crate::mqtt::{topics::FIRMWARE_STATE_TOPIC, MqttMessage};
+
+    /// The version of the firmware running on the device.
+    pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+    /// Longest version that can be given as the latest.
+    const VERSION_SIZE: usize = 24;
+
+    /// The latest version of the firmware available, once it has been published.
+    static LATEST: Mutex<ThreadModeRawMutex, Option<String<VERSION_SIZE>>> = Mutex::new(None);
+
+    /// Set the latest version available from an MQTT payload, or clear it with an empty payload,
+    /// and send the state over MQTT.
+    pub async fn set_latest(text: &str) {
+        let text = text.trim().trim_start_matches('v');
+        if text.contains(['"', '\\']) {
+            MqttMessage::enqueue_debug("Invalid firmware version").await;
+            return;
+        }
+
+        match String::try_from(text) {
+            Ok(version) if version.is_empty() => *LATEST.lock().await = None,
+            Ok(version) => *LATEST.lock().await = Some(version),
+            Err(_) => {
+                MqttMessage::enqueue_debug("Firmware version too long").await;
+                return;
+            }
+        }
+
+        send_state().await;
+    }
+
+    /// Send the installed and latest versions over MQTT, with the latest the installed version
+    /// until another has been published.
+    pub async fn send_state() {
+        let latest = LATEST.lock().await.clone();
+        let latest = latest.as_deref().unwrap_or(VERSION);
+
+        let mut state = String::<{ 2 * VERSION_SIZE + 48 }>::new();
+        let _ = write!(
+            state,
+            r#"{{"installed_version":"{VERSION}","latest_version":"{latest}"}}"#
+        );
+        MqttMessage::enqueue_state(FIRMWARE_STATE_TOPIC, &state).await;
+    }
+}
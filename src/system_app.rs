@@ -1,28 +1,54 @@
 use embassy_time::{Duration, Instant, Timer};
 use embedded_graphics::{
-    geometry::Point,
+    geometry::{Point, Size},
+    mono_font::{ascii::FONT_5X8, MonoTextStyle},
     pixelcolor::{Rgb888, WebColors},
-    primitives::{Circle, Primitive, PrimitiveStyleBuilder},
+    primitives::{Primitive, PrimitiveStyleBuilder, Rectangle},
+    text::{Baseline, Text},
 };
 use embedded_graphics_core::Drawable;
-use galactic_unicorn_embassy::{HEIGHT, WIDTH};
 use static_cell::make_static;
 use unicorn_graphics::UnicornGraphics;
 
 use crate::{
-    app::UnicornApp, buttons::ButtonPress, display::messages::DisplayGraphicsMessage,
-    mqtt::MqttReceiveMessage,
+    app::UnicornApp,
+    board::{HEIGHT, WIDE, WIDTH},
+    buttons::ButtonPress,
+    display::{
+        self,
+        messages::{DisplayTextMessage, FrameBuilder},
+        FrameTicker,
+    },
+    fonts,
+    icons::{self, DrawIcon},
+    mqtt::{clients, MqttReceiveMessage},
+    network::{NetworkError, NetworkState, REJOIN_WIFI},
+    system::SystemState,
 };
 
 use micromath::F32Ext;
 
-pub struct SystemApp;
+/// Diameter of the circles of the loading animation.
+const CIRCLE_DIAMETER: u32 = 5;
+
+/// Columns given to each circle of the loading animation. The circles sit beside the boot stage on
+/// a wide panel, and share the width between them on a square one.
+const CIRCLE_SPACING: i32 = if WIDE { 7 } else { WIDTH as i32 / 3 };
+
+/// Width and height of the warning box of the error screen.
+const ERROR_BOX_SIZE: u32 = 11;
+
+/// System app. Shows a loading animation and the current boot stage.
+pub struct SystemApp {
+    /// System state.
+    system_state: &'static SystemState,
+}
 
 impl SystemApp {
     /// Create the static ref to system app.
     /// Must only be called once or will panic.
-    pub fn new() -> &'static Self {
-        make_static!(Self {})
+    pub fn new(system_state: &'static SystemState) -> &'static Self {
+        make_static!(Self { system_state })
     }
 
     /// Linear interpolation function.
@@ -42,6 +68,52 @@ impl SystemApp {
         t * t * t
     }
 
+    /// Draw the error screen for `error`: a flashing warning box followed by the error code.
+    /// A WiFi error shows the WiFi icon in the box instead of an exclamation mark.
+    fn draw_error(graphics: &mut UnicornGraphics<WIDTH, HEIGHT>, error: NetworkError) {
+        let color = error.color();
+
+        if (Instant::now().as_millis() / 500) % 2 == 0 {
+            Rectangle::new(Point::new(0, 0), Size::new(ERROR_BOX_SIZE, ERROR_BOX_SIZE))
+                .into_styled(
+                    PrimitiveStyleBuilder::new()
+                        .stroke_color(color)
+                        .stroke_width(1)
+                        .build(),
+                )
+                .draw(graphics)
+                .unwrap();
+
+            match error {
+                NetworkError::Wifi => graphics.draw_icon(&icons::WIFI, Point::new(2, 3), color),
+                _ => {
+                    Text::with_baseline(
+                        "!",
+                        Point::new(3, (ERROR_BOX_SIZE / 2) as i32),
+                        MonoTextStyle::new(&FONT_5X8, color),
+                        Baseline::Middle,
+                    )
+                    .draw(graphics)
+                    .unwrap();
+                }
+            }
+        }
+
+        // the code goes beside the box, or under it on square panels
+        let (x, top) = if WIDE {
+            (ERROR_BOX_SIZE + 3, 0)
+        } else {
+            (0, ERROR_BOX_SIZE)
+        };
+        fonts::draw_tiny_text(
+            graphics,
+            fonts::fit_tiny_text(error.code(), WIDTH as u32 - x),
+            x as i32,
+            (top + (HEIGHT as u32 - top).saturating_sub(fonts::TINY_GLYPH_HEIGHT) / 2) as i32,
+            color,
+        );
+    }
+
     /// Ease out cubic function.
     ///
     /// Calculates 1 minus (1 minus t) squared, which provides a gradual decrease in easing from 1 to 0.
@@ -52,13 +124,9 @@ impl SystemApp {
 
 impl UnicornApp for SystemApp {
     async fn display(&self) {
-        const MAX_POSITION: f32 = (HEIGHT as i32 - 5) as f32;
-
-        let mut graphics = UnicornGraphics::<WIDTH, HEIGHT>::new();
+        const MAX_POSITION: f32 = (HEIGHT as i32 - CIRCLE_DIAMETER as i32) as f32;
 
-        let style = PrimitiveStyleBuilder::new()
-            .fill_color(Rgb888::CSS_PURPLE)
-            .build();
+        let text_style = MonoTextStyle::new(&FONT_5X8, Rgb888::CSS_PURPLE);
 
         const ANIMATION_DURATION: f32 = 600.0;
         let mut start_time = Instant::now();
@@ -66,8 +134,22 @@ impl UnicornApp for SystemApp {
         let mut min_value = 0.0;
         let mut max_value = MAX_POSITION;
 
+        let mut ticker = FrameTicker::new();
+
         loop {
-            graphics.clear_all();
+            let mut frame = FrameBuilder::new();
+
+            if let NetworkState::Error(error) = self.system_state.get_network_state().await {
+                Self::draw_error(frame.graphics_mut(), error);
+
+                frame
+                    .into_message(Duration::from_millis(100))
+                    .send_and_replace_queue()
+                    .await;
+
+                Timer::after_millis(100).await;
+                continue;
+            }
 
             let elapsed_millis = start_time.elapsed().as_millis() as f32;
 
@@ -75,32 +157,47 @@ impl UnicornApp for SystemApp {
 
             // left circle
             let eased_progress = Self::ease_in(progress);
-            let animated_value = Self::lerp(min_value, max_value, eased_progress);
-            Circle::new(Point::new(10, animated_value.floor() as i32), 5)
-                .into_styled(style)
-                .draw(&mut graphics)
-                .unwrap();
+            let left_value = Self::lerp(min_value, max_value, eased_progress);
 
             // center circle
-            let animated_value = Self::lerp(min_value, max_value, progress);
-            Circle::new(Point::new(24, animated_value.floor() as i32), 5)
-                .into_styled(style)
-                .draw(&mut graphics)
-                .unwrap();
+            let center_value = Self::lerp(min_value, max_value, progress);
 
             // right circle
             let eased_progress = Self::ease_out(progress);
-            let animated_value = Self::lerp(min_value, max_value, eased_progress);
-            Circle::new(Point::new(38, animated_value.floor() as i32), 5)
-                .into_styled(style)
-                .draw(&mut graphics)
-                .unwrap();
+            let right_value = Self::lerp(min_value, max_value, eased_progress);
+
+            // boot stage
+            let stage = self.system_state.get_boot_stage().await;
+
+            for (index, value) in [left_value, center_value, right_value]
+                .into_iter()
+                .enumerate()
+            {
+                let x =
+                    index as i32 * CIRCLE_SPACING + (CIRCLE_SPACING - CIRCLE_DIAMETER as i32) / 2;
+                frame = frame.filled_circle(
+                    Point::new(x, value.floor() as i32),
+                    CIRCLE_DIAMETER,
+                    Rgb888::CSS_PURPLE,
+                );
+            }
+
+            // the boot stage only fits beside the circles on a wide panel
+            if WIDE {
+                frame = frame.draw(Text::with_baseline(
+                    stage.text(),
+                    Point::new(3 * CIRCLE_SPACING + 2, (HEIGHT / 2) as i32),
+                    text_style,
+                    Baseline::Middle,
+                ));
+            }
 
-            DisplayGraphicsMessage::from_app(graphics.get_pixels(), Duration::from_millis(10))
+            frame
+                .into_message(display::frame_duration())
                 .send_and_replace_queue()
                 .await;
 
-            Timer::after_millis(10).await;
+            ticker.next().await;
 
             if elapsed_millis >= ANIMATION_DURATION {
                 Timer::after_millis(25).await;
@@ -122,7 +219,24 @@ impl UnicornApp for SystemApp {
 
     async fn stop(&self) {}
 
-    async fn button_press(&self, _: ButtonPress) {}
+    async fn button_press(&self, _: ButtonPress) {
+        // retry the failing connection now, rather than waiting for the backoff
+        if let NetworkState::Error(error) = self.system_state.get_network_state().await {
+            match error {
+                NetworkError::Wifi => REJOIN_WIFI.signal(true),
+                NetworkError::Mqtt => clients::reconnect(),
+            }
+
+            DisplayTextMessage::from_app(
+                "Retrying",
+                Some(error.color()),
+                None,
+                Some(Duration::from_secs(1)),
+            )
+            .send_and_show_now()
+            .await;
+        }
+    }
 
     async fn process_mqtt_message(&self, _: MqttReceiveMessage) {}
 
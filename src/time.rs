@@ -2,30 +2,76 @@ use chrono::{DateTime, Duration};
 use chrono_tz::{Tz, GB};
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
 use embassy_time::Instant;
+use portable_atomic::{AtomicBool, Ordering};
 use static_cell::make_static;
 
+use crate::{
+    error::Error,
+    storage::{Record, Storage},
+};
+
 /// Hold a reference to the time state that can be updated via an NTP task.
 pub struct Time {
     /// The time last pulled from NTP.
     sys_start: Mutex<CriticalSectionRawMutex, DateTime<Tz>>,
+
+    /// Whether the time has been synced with NTP since boot.
+    synced: AtomicBool,
+
+    /// Storage for the last synced time.
+    storage: &'static Storage,
 }
 
 impl Time {
     /// Create the static ref to time state.
     /// Must only be called once or will panic.
-    pub fn new() -> &'static Self {
+    pub fn new(storage: &'static Storage) -> &'static Self {
         make_static!(Self {
             sys_start: Mutex::new(DateTime::UNIX_EPOCH.with_timezone(&GB)),
+            synced: AtomicBool::new(false),
+            storage,
         })
     }
 
+    /// Set the time to the last synced time saved in flash, if there is one.
+    /// The time stays unsynced until NTP syncs it.
+    pub async fn restore(&self) {
+        let mut record = [0u8; 8];
+        let timestamp = match self.storage.read_record(Record::Time, &mut record).await {
+            Ok(Some(len)) if len == record.len() => i64::from_le_bytes(record),
+            _ => return,
+        };
+
+        if let Some(now) = DateTime::from_timestamp(timestamp, 0) {
+            let _ = self.set_time(now.with_timezone(&GB)).await;
+        }
+    }
+
+    /// Save the current time to flash, so it can be restored when booting without a network.
+    pub async fn save(&self) -> Result<(), Error> {
+        let timestamp = self.now().await.timestamp();
+
+        self.storage
+            .write_record(Record::Time, &timestamp.to_le_bytes())
+            .await
+            .map_err(|_| Error::Flash)
+    }
+
+    /// Whether the time has been synced with NTP since boot.
+    /// An unsynced time is either the restored time or the epoch.
+    pub fn is_synced(&self) -> bool {
+        self.synced.load(Ordering::Relaxed)
+    }
+
     /// Set the current time.
-    pub async fn set_time(&self, now: DateTime<Tz>) {
+    /// Fails if `now` is earlier than the time since boot allows.
+    pub async fn set_time(&self, now: DateTime<Tz>) -> Result<(), Error> {
         let mut sys_start = self.sys_start.lock().await;
         let elapsed = Instant::now().as_millis();
         *sys_start = now
             .checked_sub_signed(Duration::milliseconds(elapsed as i64))
-            .expect("sys_start greater as current_ts");
+            .ok_or(Error::TimeOutOfRange)?;
+        Ok(())
     }
 
     /// Get the current time.
@@ -52,45 +98,40 @@ pub mod ntp {
         async_impl::{get_time, NtpUdpSocket},
         NtpContext, NtpTimestampGenerator,
     };
-    use thiserror_no_std::Error;
 
     use super::Time;
+    use crate::{
+        error::Error,
+        supervisor::{self, Task},
+        system::{BootStage, SystemState},
+    };
 
     const POOL_NTP_ADDR: &str = "pool.ntp.org";
 
+    /// Number of failed requests in a row before NTP is reported as failing.
+    const MAX_FAILURES: u8 = 3;
+
+    /// Seconds between syncs once the time has been synced.
+    pub const SYNC_INTERVAL_SECS: u64 = 3600;
+
     /// Signal for request to sync system with NTP.
     pub static SYNC_SIGNAL: Signal<ThreadModeRawMutex, bool> = Signal::new();
 
-    /// Error enum for NTP request.
-    #[derive(Error, Debug)]
-    pub enum SntpcError {
-        #[error("to_socket_addrs")]
-        ToSocketAddrs,
-        #[error("no addr")]
-        NoAddr,
-        #[error("udp send")]
-        UdpSend,
-        #[error("dns query error")]
-        DnsQuery(#[from] embassy_net::dns::Error),
-        #[error("dns query error")]
-        DnsEmptyResponse,
-        #[error("sntc")]
-        Sntc(#[from] sntpc::Error),
-        #[error("can not parse ntp response")]
-        BadNtpResponse,
-    }
-
-    impl From<SntpcError> for sntpc::Error {
-        fn from(err: SntpcError) -> Self {
+    impl From<Error> for sntpc::Error {
+        fn from(err: Error) -> Self {
             match err {
-                SntpcError::ToSocketAddrs => Self::AddressResolve,
-                SntpcError::NoAddr => Self::AddressResolve,
-                SntpcError::UdpSend => Self::Network,
-                _ => todo!(),
+                Error::AddressResolve => Self::AddressResolve,
+                _ => Self::Network,
             }
         }
     }
 
+    impl From<sntpc::Error> for Error {
+        fn from(_: sntpc::Error) -> Self {
+            Self::Ntp
+        }
+    }
+
     /// UdpSocket wrapper for NTP.
     struct NtpSocket<'a> {
         sock: UdpSocket<'a>,
@@ -103,15 +144,13 @@ pub mod ntp {
             buf: &[u8],
             addr: T,
         ) -> sntpc::Result<usize> {
-            let mut addr_iter = addr
-                .to_socket_addrs()
-                .map_err(|_| SntpcError::ToSocketAddrs)?;
-            let addr = addr_iter.next().ok_or(SntpcError::NoAddr)?;
+            let mut addr_iter = addr.to_socket_addrs().map_err(|_| Error::AddressResolve)?;
+            let addr = addr_iter.next().ok_or(Error::AddressResolve)?;
+            let endpoint = sock_addr_to_emb_endpoint(addr).ok_or(Error::AddressResolve)?;
             self.sock
-                .send_to(buf, sock_addr_to_emb_endpoint(addr))
+                .send_to(buf, endpoint)
                 .await
-                .map_err(|_| SntpcError::UdpSend)
-                .unwrap();
+                .map_err(|_| Error::UdpSend)?;
             Ok(buf.len())
         }
 
@@ -119,7 +158,7 @@ pub mod ntp {
         async fn recv_from(&self, buf: &mut [u8]) -> sntpc::Result<(usize, SocketAddr)> {
             match self.sock.recv_from(buf).await {
                 Ok((size, ip_endpoint)) => Ok((size, emb_endpoint_to_sock_addr(ip_endpoint))),
-                Err(_) => panic!("not exp"),
+                Err(_) => Err(Error::UdpReceive.into()),
             }
         }
     }
@@ -147,16 +186,17 @@ pub mod ntp {
     }
 
     /// Convert `SocketAddr` into embassy `IpEndpoint`.
-    fn sock_addr_to_emb_endpoint(sock_addr: SocketAddr) -> IpEndpoint {
+    /// Only IPv4 is supported, so `None` is returned for IPv6.
+    fn sock_addr_to_emb_endpoint(sock_addr: SocketAddr) -> Option<IpEndpoint> {
         let port = sock_addr.port();
         let addr = match sock_addr {
             SocketAddr::V4(addr) => {
                 let octets = addr.ip().octets();
                 embassy_net::IpAddress::v4(octets[0], octets[1], octets[2], octets[3])
             }
-            _ => todo!(),
+            SocketAddr::V6(_) => return None,
         };
-        IpEndpoint::new(addr, port)
+        Some(IpEndpoint::new(addr, port))
     }
 
     /// Timestamp generator.
@@ -190,11 +230,38 @@ pub mod ntp {
 
     /// NTP task for syncing to NTP.
     #[embassy_executor::task]
-    pub async fn ntp_worker(stack: &'static Stack<cyw43::NetDriver<'static>>, time: &'static Time) {
+    pub async fn ntp_worker(
+        stack: &'static Stack<cyw43::NetDriver<'static>>,
+        time: &'static Time,
+        app_state: &'static SystemState,
+    ) {
+        let mut failures: u8 = 0;
+
         loop {
+            supervisor::check_in(Task::Ntp);
+
             let sleep_sec = match ntp_request(stack, time).await {
-                Err(_) => 10,
-                Ok(_) => 3600,
+                Err(err) => {
+                    failures = failures.saturating_add(1);
+                    app_state.report_error("NTP request failed", err).await;
+
+                    // the clock keeps running from the last sync, so the app is left as it is
+                    if failures >= MAX_FAILURES {
+                        app_state.set_ntp_failing(true);
+                    }
+
+                    10
+                }
+                Ok(_) => {
+                    failures = 0;
+                    if let Err(err) = time.save().await {
+                        app_state.report_error("Time save failed", err).await;
+                    }
+
+                    app_state.set_ntp_failing(false);
+                    app_state.advance_boot_stage(BootStage::Complete).await;
+                    SYNC_INTERVAL_SECS
+                }
             };
 
             select(Timer::after_secs(sleep_sec), SYNC_SIGNAL.wait()).await;
@@ -206,9 +273,9 @@ pub mod ntp {
     async fn ntp_request(
         stack: &'static Stack<cyw43::NetDriver<'static>>,
         time: &'static Time,
-    ) -> Result<(), SntpcError> {
+    ) -> Result<(), Error> {
         let mut addrs = stack.dns_query(POOL_NTP_ADDR, DnsQueryType::A).await?;
-        let addr = addrs.pop().ok_or(SntpcError::DnsEmptyResponse)?;
+        let addr = addrs.pop().ok_or(Error::DnsEmptyResponse)?;
 
         let octets = addr.as_bytes();
         let ipv4_addr = no_std_net::Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]);
@@ -226,16 +293,17 @@ pub mod ntp {
             &mut tx_meta,
             &mut tx_buffer,
         );
-        socket.bind(1234).unwrap();
+        socket.bind(1234).map_err(|_| Error::UdpBind)?;
 
         let ntp_socket = NtpSocket { sock: socket };
         let ntp_context = NtpContext::new(TimestampGen::new(time).await);
 
         let ntp_result = get_time(sock_addr, ntp_socket, ntp_context).await?;
-        let now = DateTime::from_timestamp(ntp_result.seconds as i64, 0)
-            .ok_or(SntpcError::BadNtpResponse)?;
+        let now =
+            DateTime::from_timestamp(ntp_result.seconds as i64, 0).ok_or(Error::BadNtpResponse)?;
         let now = now.with_timezone(&GB);
-        time.set_time(now).await;
+        time.set_time(now).await?;
+        time.synced.store(true, Ordering::Relaxed);
 
         Ok(())
     }
@@ -0,0 +1,311 @@
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::{
+    geometry::{Point, Size},
+    mono_font::{ascii::FONT_5X8, MonoTextStyle},
+    pixelcolor::{Rgb888, RgbColor},
+    primitives::{Primitive, PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+use embedded_graphics_core::Drawable;
+use heapless::String;
+use static_cell::make_static;
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    app::UnicornApp,
+    board::{HEIGHT, WIDTH},
+    buttons::ButtonPress,
+    config::HASS_TIMER_TOPIC,
+    display::{
+        messages::{DisplayGraphicsMessage, DisplayTextMessage},
+        Display,
+    },
+    fonts::Font,
+    graphics,
+    mqtt::MqttReceiveMessage,
+};
+
+/// How close to zero the countdown must be when the timer goes idle for it to count as finished,
+/// rather than cancelled.
+const FINISH_TOLERANCE_SECS: u64 = 2;
+
+/// How long the finished timer flashes for, unless dismissed with a button press.
+const FLASH_SECS: u64 = 30;
+
+/// How much to dim a paused timer.
+const PAUSED_DIM: f32 = 0.3;
+
+/// Get the font to draw `text` in on panels too narrow for it in the 5x8 font, the largest that
+/// fits in `max_height` rows, or `None` to use the 5x8 font.
+fn narrow_font(text: &str, max_height: u32) -> Option<Font> {
+    let width = text.len() as u32 * FONT_5X8.character_size.width;
+    (width + 1 > WIDTH as u32).then(|| Font::largest_fitting(text, WIDTH as u32, max_height))
+}
+
+/// Check if `topic` is under the home assistant timer mirrored by the timer app.
+pub fn is_timer_topic(topic: &str) -> bool {
+    HASS_TIMER_TOPIC.is_some_and(|timer| {
+        topic
+            .strip_prefix(timer)
+            .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Parse a home assistant duration such as `0:05:00` into seconds.
+/// Values published by MQTT statestream are JSON encoded, so surrounding quotes are ignored.
+fn parse_duration(value: &str) -> Option<u64> {
+    let mut secs = 0;
+    for part in value.trim().trim_matches('"').split(':') {
+        // drop any fraction of a second
+        let part = part.split('.').next()?;
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+
+    Some(secs)
+}
+
+/// The states of a home assistant timer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TimerState {
+    Idle,
+    Active,
+    Paused,
+}
+
+/// The last known state of the home assistant timer.
+struct HassTimer {
+    /// The timer state.
+    state: TimerState,
+
+    /// The full duration of the timer.
+    duration_secs: u64,
+
+    /// The time left when `since` was taken.
+    remaining_secs: u64,
+
+    /// When the remaining time was last set.
+    since: Instant,
+
+    /// When the timer finished, whilst it is still being flashed.
+    finished_at: Option<Instant>,
+}
+
+impl HassTimer {
+    /// The time left on the timer.
+    fn remaining_secs(&self) -> u64 {
+        match self.state {
+            TimerState::Idle => 0,
+            TimerState::Active => self
+                .remaining_secs
+                .saturating_sub(self.since.elapsed().as_secs()),
+            TimerState::Paused => self.remaining_secs,
+        }
+    }
+}
+
+/// Timer app. Mirror a home assistant timer with its remaining time and a progress bar.
+pub struct TimerApp {
+    /// Reference to the display.
+    display: &'static Display<'static>,
+
+    /// The mirrored timer.
+    timer: Mutex<ThreadModeRawMutex, HassTimer>,
+
+    /// Track if the app is active or not.
+    is_active: AtomicBool,
+}
+
+impl TimerApp {
+    /// Create the static ref to timer app.
+    /// Must only be called once or will panic.
+    pub fn new(display: &'static Display<'static>) -> &'static Self {
+        make_static!(Self {
+            display,
+            timer: Mutex::new(HassTimer {
+                state: TimerState::Idle,
+                duration_secs: 0,
+                remaining_secs: 0,
+                since: Instant::now(),
+                finished_at: None,
+            }),
+            is_active: AtomicBool::new(false),
+        })
+    }
+
+    /// Draw the remaining time and progress bar for the timer.
+    fn draw_countdown(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, timer: &HassTimer, color: Rgb888) {
+        let remaining = timer.remaining_secs();
+        let (hours, minutes, seconds) = (remaining / 3600, remaining / 60 % 60, remaining % 60);
+
+        let mut text = String::<12>::new();
+        if hours > 0 {
+            let _ = write!(text, "{hours}:{minutes:02}:{seconds:02}");
+        } else {
+            let _ = write!(text, "{minutes}:{seconds:02}");
+        }
+
+        // the rows above the progress bar
+        let rows = HEIGHT as u32 - 3;
+        match narrow_font(&text, rows) {
+            Some(font) => font.draw(gr, &text, 0, rows as i32 / 2, color),
+            None => {
+                Text::with_baseline(
+                    &text,
+                    Point::new(1, 0),
+                    MonoTextStyle::new(&FONT_5X8, color),
+                    Baseline::Top,
+                )
+                .draw(gr)
+                .unwrap();
+            }
+        }
+
+        if timer.duration_secs > 0 {
+            let width = (WIDTH as u64 * remaining / timer.duration_secs).min(WIDTH as u64) as u32;
+            Rectangle::new(Point::new(0, HEIGHT as i32 - 2), Size::new(width, 2))
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(gr)
+                .unwrap();
+        }
+
+        // a paused timer is drawn dimmed
+        if timer.state == TimerState::Paused {
+            graphics::dim(gr, PAUSED_DIM);
+        }
+    }
+
+    /// Draw the finished timer, flashing on and off.
+    fn draw_finished(gr: &mut UnicornGraphics<WIDTH, HEIGHT>, finished_at: Instant, color: Rgb888) {
+        if finished_at.elapsed().as_millis() / 500 % 2 == 0 {
+            Rectangle::new(Point::zero(), Size::new(WIDTH as u32, HEIGHT as u32))
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(gr)
+                .unwrap();
+            match narrow_font("DONE", HEIGHT as u32) {
+                Some(font) => font.draw(gr, "DONE", 0, (HEIGHT / 2) as i32, Rgb888::BLACK),
+                None => {
+                    Text::with_baseline(
+                        "DONE",
+                        Point::new(1, (HEIGHT / 2) as i32),
+                        MonoTextStyle::new(&FONT_5X8, Rgb888::BLACK),
+                        Baseline::Middle,
+                    )
+                    .draw(gr)
+                    .unwrap();
+                }
+            }
+        }
+    }
+}
+
+impl UnicornApp for TimerApp {
+    async fn display(&self) {
+        let mut gr = UnicornGraphics::<WIDTH, HEIGHT>::new();
+
+        loop {
+            gr.clear_all();
+
+            let color = self.display.get_color().await;
+            {
+                let mut timer = self.timer.lock().await;
+                if timer
+                    .finished_at
+                    .is_some_and(|at| at.elapsed().as_secs() >= FLASH_SECS)
+                {
+                    timer.finished_at = None;
+                }
+
+                match timer.finished_at {
+                    Some(finished_at) => Self::draw_finished(&mut gr, finished_at, color),
+                    None => Self::draw_countdown(&mut gr, &timer, color),
+                }
+            }
+
+            let duration = Duration::from_millis(250);
+            DisplayGraphicsMessage::from_app(gr.get_pixels(), duration)
+                .send_and_replace_queue()
+                .await;
+            Timer::after(duration).await;
+        }
+    }
+
+    async fn start(&self) {
+        self.is_active.store(true, Ordering::Relaxed);
+    }
+
+    async fn stop(&self) {
+        self.is_active.store(false, Ordering::Relaxed);
+    }
+
+    async fn button_press(&self, _: ButtonPress) {
+        // any press dismisses the finished timer
+        self.timer.lock().await.finished_at = None;
+    }
+
+    async fn process_mqtt_message(&self, message: MqttReceiveMessage) {
+        let Some(attribute) = HASS_TIMER_TOPIC
+            .and_then(|timer| message.topic.strip_prefix(timer))
+            .and_then(|rest| rest.strip_prefix('/'))
+        else {
+            return;
+        };
+
+        let mut timer = self.timer.lock().await;
+        match attribute {
+            "state" => {
+                let state = match message.body.trim_matches('"') {
+                    "active" => TimerState::Active,
+                    "paused" => TimerState::Paused,
+                    _ => TimerState::Idle,
+                };
+
+                match state {
+                    TimerState::Active if timer.state != TimerState::Active => {
+                        timer.since = Instant::now();
+                        timer.finished_at = None;
+                    }
+                    TimerState::Paused => timer.remaining_secs = timer.remaining_secs(),
+                    TimerState::Idle
+                        if timer.state == TimerState::Active
+                            && timer.remaining_secs() <= FINISH_TOLERANCE_SECS =>
+                    {
+                        timer.finished_at = Some(Instant::now());
+
+                        // make sure a finished timer is noticed from any app
+                        if !self.is_active.load(Ordering::Relaxed) {
+                            DisplayTextMessage::from_app(
+                                "Timer done",
+                                None,
+                                None,
+                                Some(Duration::from_secs(5)),
+                            )
+                            .send_and_show_now()
+                            .await;
+                        }
+                    }
+                    _ => {}
+                }
+
+                timer.state = state;
+            }
+            "remaining" => {
+                if let Some(secs) = parse_duration(&message.body) {
+                    timer.remaining_secs = secs;
+                    timer.since = Instant::now();
+                }
+            }
+            "duration" => {
+                if let Some(secs) = parse_duration(&message.body) {
+                    timer.duration_secs = secs;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn send_mqtt_state(&self) {}
+}
@@ -0,0 +1,308 @@
+use core::fmt::Write;
+
+use embassy_net::Stack;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_graphics::{
+    geometry::Point,
+    pixelcolor::{Rgb888, RgbColor, WebColors},
+};
+use heapless::String;
+use micromath::F32Ext;
+use static_cell::make_static;
+use unicorn_graphics::UnicornGraphics;
+
+use crate::{
+    app::UnicornApp,
+    board::{HEIGHT, WIDE, WIDTH},
+    buttons::ButtonPress,
+    config::{WEATHER_POLL_INTERVAL_SECS, WEATHER_URL},
+    display::{self, messages::DisplayGraphicsMessage, Display, FrameTicker},
+    fonts::{draw_big_text, Font},
+    graphics::{
+        sprites::{Sprite, SpriteScene},
+        Rng,
+    },
+    http,
+    icons::{self, DrawIcon, Icon},
+    json, logging,
+    mqtt::MqttReceiveMessage,
+};
+
+/// Size of the buffer for the weather response, which must hold the headers and body.
+const RESPONSE_BUF_SIZE: usize = 2048;
+
+/// Where the weather icon is drawn.
+const ICON_POSITION: Point = Point::new(1, 2);
+
+/// Where the temperature starts, to the right of the icon.
+const TEXT_X: i32 = 11;
+
+/// Rows taken by the icon, which the temperature is drawn under on square panels.
+const ICON_ROWS: i32 = 11;
+
+/// Where the cloud is drawn for rain and snow, higher up to leave room for the drops to fall.
+const CLOUD_POSITION: Point = Point::new(1, -1);
+
+/// Row the drops of rain and snow start falling from, just under the cloud.
+const DROP_Y: i32 = 5;
+
+/// Leftmost column under the cloud a drop falls from.
+const DROP_MIN_X: f32 = 2.0;
+
+/// Rightmost column under the cloud a drop falls from.
+const DROP_MAX_X: f32 = 7.0;
+
+/// The most drops falling at once.
+const MAX_DROPS: usize = 8;
+
+/// Speed raindrops fall at, in pixels per second.
+const RAIN_SPEED: f32 = 9.0;
+
+/// Milliseconds between each raindrop.
+const RAIN_INTERVAL_MILLIS: u64 = 150;
+
+/// Speed snowflakes fall at, in pixels per second.
+const SNOW_SPEED: f32 = 3.0;
+
+/// Milliseconds between each snowflake.
+const SNOW_INTERVAL_MILLIS: u64 = 500;
+
+/// Most a snowflake drifts sideways, in pixels per second.
+const SNOW_DRIFT: f32 = 1.0;
+
+/// How many times a second snowflakes twinkle between their frames.
+const SNOW_TWINKLE_FPS: u32 = 3;
+
+/// Color of the falling raindrops.
+const RAIN_COLOR: Rgb888 = Rgb888::CSS_DEEP_SKY_BLUE;
+
+/// A raindrop.
+static RAINDROP: [Icon; 1] = [Icon::new(1, &[0b1, 0b1])];
+
+/// A snowflake, twinkling between a plus and a cross.
+static SNOWFLAKE: [Icon; 2] = [
+    Icon::new(3, &[0b010, 0b111, 0b010]),
+    Icon::new(3, &[0b101, 0b010, 0b101]),
+];
+
+/// Weather conditions, grouped to match the weather icons.
+#[derive(Clone, Copy)]
+enum Condition {
+    Clear,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Storm,
+}
+
+impl Condition {
+    /// Get the condition from a WMO weather code, as used by Open-Meteo.
+    fn from_wmo_code(code: u32) -> Self {
+        match code {
+            0 | 1 => Self::Clear,
+            45 | 48 => Self::Fog,
+            51..=67 | 80..=82 => Self::Rain,
+            71..=77 | 85 | 86 => Self::Snow,
+            95..=99 => Self::Storm,
+            _ => Self::Cloudy,
+        }
+    }
+
+    /// Get the condition from an OpenWeatherMap condition id.
+    fn from_owm_id(id: u32) -> Self {
+        match id {
+            200..=299 => Self::Storm,
+            300..=599 => Self::Rain,
+            600..=699 => Self::Snow,
+            700..=799 => Self::Fog,
+            800 => Self::Clear,
+            _ => Self::Cloudy,
+        }
+    }
+
+    /// The icon and its color for the condition.
+    /// Rain and snow are a cloud, with the drops falling from it drawn as sprites.
+    fn icon(&self) -> (&'static Icon, Rgb888) {
+        match self {
+            Self::Clear => (&icons::SUN, Rgb888::YELLOW),
+            Self::Cloudy | Self::Rain | Self::Snow => (&icons::CLOUD, Rgb888::new(100, 100, 100)),
+            Self::Fog => (&icons::FOG, Rgb888::new(100, 100, 100)),
+            Self::Storm => (&icons::STORM, Rgb888::CSS_ORANGE),
+        }
+    }
+
+    /// How often a drop falls from the cloud, or `None` if nothing falls.
+    fn drop_interval(&self) -> Option<Duration> {
+        match self {
+            Self::Rain => Some(Duration::from_millis(RAIN_INTERVAL_MILLIS)),
+            Self::Snow => Some(Duration::from_millis(SNOW_INTERVAL_MILLIS)),
+            _ => None,
+        }
+    }
+
+    /// Create a drop falling from column `x`, drifting sideways by `drift` pixels per second
+    /// if it is snow.
+    fn drop(&self, x: i32, drift: f32) -> Sprite {
+        let position = Point::new(x, DROP_Y);
+        match self {
+            Self::Snow => Sprite::new(&SNOWFLAKE, Rgb888::WHITE, position)
+                .with_velocity(drift, SNOW_SPEED)
+                .with_frame_rate(SNOW_TWINKLE_FPS),
+            _ => Sprite::new(&RAINDROP, RAIN_COLOR, position).with_velocity(0.0, RAIN_SPEED),
+        }
+    }
+}
+
+/// The current weather.
+#[derive(Clone, Copy)]
+struct Conditions {
+    /// Temperature in the units asked for in the request URL.
+    temperature: i32,
+
+    /// The weather condition.
+    condition: Condition,
+}
+
+impl Conditions {
+    /// Parse the current weather from an Open-Meteo or OpenWeatherMap response.
+    fn parse(body: &str) -> Option<Self> {
+        let (temperature, condition) =
+            if let Some(code) = json::extract(body, "$.current.weather_code") {
+                let temperature = json::extract(body, "$.current.temperature_2m")?;
+                (temperature, Condition::from_wmo_code(code.parse().ok()?))
+            } else {
+                let temperature = json::extract(body, "$.main.temp")?;
+                let id = json::extract(body, "$.weather[0].id")?;
+                (temperature, Condition::from_owm_id(id.parse().ok()?))
+            };
+
+        Some(Self {
+            temperature: temperature.parse::<f32>().ok()?.round() as i32,
+            condition,
+        })
+    }
+}
+
+/// Weather app. Show the current weather polled from a weather service.
+pub struct WeatherApp {
+    /// Reference to the display.
+    display: &'static Display<'static>,
+
+    /// The last weather received, if any.
+    conditions: Mutex<ThreadModeRawMutex, Option<Conditions>>,
+}
+
+impl WeatherApp {
+    /// Create the static ref to weather app.
+    /// Must only be called once or will panic.
+    pub fn new(display: &'static Display<'static>) -> &'static Self {
+        make_static!(Self {
+            display,
+            conditions: Mutex::new(None),
+        })
+    }
+}
+
+impl UnicornApp for WeatherApp {
+    async fn display(&self) {
+        let mut background = UnicornGraphics::<WIDTH, HEIGHT>::new();
+        let mut gr = UnicornGraphics::<WIDTH, HEIGHT>::new();
+        let mut drops = SpriteScene::<MAX_DROPS>::new();
+        let mut rng = Rng::new();
+        let mut last_drop = Instant::now();
+        let mut ticker = FrameTicker::new();
+
+        loop {
+            background.clear_all();
+
+            let color = self.display.get_color().await;
+            let mut text = String::<8>::new();
+            match *self.conditions.lock().await {
+                Some(conditions) => {
+                    let condition = conditions.condition;
+                    let (icon, icon_color) = condition.icon();
+                    let _ = write!(text, "{}C", conditions.temperature);
+
+                    match condition.drop_interval() {
+                        Some(interval) => {
+                            background.draw_icon(icon, CLOUD_POSITION, icon_color);
+
+                            if last_drop.elapsed() >= interval {
+                                last_drop = Instant::now();
+                                let x = rng.range(DROP_MIN_X, DROP_MAX_X).round() as i32;
+                                let drift = rng.range(-SNOW_DRIFT, SNOW_DRIFT);
+                                // the drop is skipped if too many are already falling
+                                let _ = drops.add(condition.drop(x, drift));
+                            }
+                        }
+                        None => background.draw_icon(icon, ICON_POSITION, icon_color),
+                    }
+                }
+                None => {
+                    let _ = write!(text, "--");
+                }
+            }
+            if WIDE {
+                draw_big_text(&mut background, &text, TEXT_X, 0, color);
+            } else {
+                let height = HEIGHT as i32 - ICON_ROWS;
+                let font = Font::largest_fitting(&text, WIDTH as u32, height as u32);
+                let x = (WIDTH as i32 - font.measure(&text) as i32) / 2;
+                font.draw(&mut background, &text, x, ICON_ROWS + height / 2, color);
+            }
+
+            drops.render(&background, &mut gr);
+
+            // the weather only needs drawing once a second whilst nothing is falling
+            if drops.is_empty() {
+                let duration = Duration::from_secs(1);
+                DisplayGraphicsMessage::from_app(gr.get_pixels(), duration)
+                    .send_and_replace_queue()
+                    .await;
+                Timer::after(duration).await;
+            } else {
+                DisplayGraphicsMessage::from_app(gr.get_pixels(), display::frame_duration())
+                    .send_and_replace_queue()
+                    .await;
+                ticker.next().await;
+            }
+        }
+    }
+
+    async fn start(&self) {}
+
+    async fn stop(&self) {}
+
+    async fn button_press(&self, _: ButtonPress) {}
+
+    async fn process_mqtt_message(&self, _: MqttReceiveMessage) {}
+
+    async fn send_mqtt_state(&self) {}
+}
+
+/// Poll the weather service at the configured interval.
+/// Does nothing when no weather URL is configured.
+#[embassy_executor::task]
+pub async fn weather_poll_task(
+    stack: &'static Stack<cyw43::NetDriver<'static>>,
+    app: &'static WeatherApp,
+) {
+    let Some(url) = WEATHER_URL else {
+        return;
+    };
+
+    loop {
+        let mut buf = [0u8; RESPONSE_BUF_SIZE];
+        match http::get(stack, url, &mut buf).await {
+            Ok(body) => match core::str::from_utf8(body).ok().and_then(Conditions::parse) {
+                Some(conditions) => *app.conditions.lock().await = Some(conditions),
+                None => logging::warn!("Weather response not understood"),
+            },
+            Err(err) => logging::warn!("Weather fetch failed: {err}"),
+        }
+
+        Timer::after_secs(WEATHER_POLL_INTERVAL_SECS).await;
+    }
+}